@@ -151,6 +151,52 @@ std::function<std::optional<T>(juiz::CapsuleMap)> bind_process(std::vector<Argum
     };
 }
 
+template<typename T>
+std::function<std::optional<T>(juiz::CapsuleMap)> bind_process(std::vector<ArgumentManifest>::iterator iter, std::function<std::optional<T>(const juiz::Bytes& arg)> f) {
+    return [=](juiz::CapsuleMap cm) -> std::optional<T> {
+        auto arg_name = (*iter).name_;
+        auto v = cm.get_bytes(arg_name);
+        return f(v); 
+    };
+}
+
+template<typename T, typename... R>
+std::function<std::optional<T>(juiz::CapsuleMap)> bind_process(std::vector<ArgumentManifest>::iterator iter, std::function<std::optional<T>(const juiz::Bytes& arg, R... arg2)> f) {
+    return [iter, f](juiz::CapsuleMap cm) -> std::optional<T> {
+        auto arg_name = (*iter).name_;
+        const auto v = cm.get_bytes(arg_name);
+        auto i = iter;
+        ++i;
+        auto binded = [v, f](R... rem) {
+            return f(v, rem...);
+        };
+        return bind_process<T>(i, std::function(binded))(cm); 
+    };
+}
+
+template<typename T, typename NV>
+std::function<std::optional<T>(juiz::CapsuleMap)> bind_process(std::vector<ArgumentManifest>::iterator iter, std::function<std::optional<T>(const juiz::NumericArray<NV>& arg)> f) {
+    return [=](juiz::CapsuleMap cm) -> std::optional<T> {
+        auto arg_name = (*iter).name_;
+        auto v = cm.template get_numeric_array<NV>(arg_name);
+        return f(v); 
+    };
+}
+
+template<typename T, typename NV, typename... R>
+std::function<std::optional<T>(juiz::CapsuleMap)> bind_process(std::vector<ArgumentManifest>::iterator iter, std::function<std::optional<T>(const juiz::NumericArray<NV>& arg, R... arg2)> f) {
+    return [iter, f](juiz::CapsuleMap cm) -> std::optional<T> {
+        auto arg_name = (*iter).name_;
+        const auto v = cm.template get_numeric_array<NV>(arg_name);
+        auto i = iter;
+        ++i;
+        auto binded = [v, f](R... rem) {
+            return f(v, rem...);
+        };
+        return bind_process<T>(i, std::function(binded))(cm); 
+    };
+}
+
 ////////
 
 
@@ -281,3 +327,49 @@ std::function<std::optional<T>(U*, juiz::CapsuleMap)> bind_container_process(std
     };
 }
 
+
+template<typename T, typename U>
+std::function<std::optional<T>(U*, juiz::CapsuleMap)> bind_container_process(std::vector<ArgumentManifest>::iterator iter, std::function<std::optional<T>(U*, const juiz::Bytes& arg)> f) {
+    return [=](U* u, juiz::CapsuleMap cm) -> std::optional<T> {
+        auto arg_name = (*iter).name_;
+        auto v = cm.get_bytes(arg_name);
+        return f(u, v); 
+    };
+}
+
+template<typename T, typename U, typename... R>
+std::function<std::optional<T>(U*, juiz::CapsuleMap)> bind_container_process(std::vector<ArgumentManifest>::iterator iter, std::function<std::optional<T>(U*, const juiz::Bytes& arg, R... arg2)> f) {
+    return [iter, f](U* u, juiz::CapsuleMap cm) -> std::optional<T> {
+        auto arg_name = (*iter).name_;
+        const auto v = cm.get_bytes(arg_name);
+        auto i = iter;
+        ++i;
+        auto binded = [v, f](R... rem) {
+            return f(v, rem...);
+        };
+        return bind_container_process<T>(i, std::function(binded))(u, cm); 
+    };
+}
+
+template<typename T, typename U, typename NV>
+std::function<std::optional<T>(U*, juiz::CapsuleMap)> bind_container_process(std::vector<ArgumentManifest>::iterator iter, std::function<std::optional<T>(U*, const juiz::NumericArray<NV>& arg)> f) {
+    return [=](U* u, juiz::CapsuleMap cm) -> std::optional<T> {
+        auto arg_name = (*iter).name_;
+        auto v = cm.template get_numeric_array<NV>(arg_name);
+        return f(u, v); 
+    };
+}
+
+template<typename T, typename U, typename NV, typename... R>
+std::function<std::optional<T>(U*, juiz::CapsuleMap)> bind_container_process(std::vector<ArgumentManifest>::iterator iter, std::function<std::optional<T>(U*, const juiz::NumericArray<NV>& arg, R... arg2)> f) {
+    return [iter, f](U* u, juiz::CapsuleMap cm) -> std::optional<T> {
+        auto arg_name = (*iter).name_;
+        const auto v = cm.template get_numeric_array<NV>(arg_name);
+        auto i = iter;
+        ++i;
+        auto binded = [v, f](R... rem) {
+            return f(v, rem...);
+        };
+        return bind_container_process<T>(i, std::function(binded))(u, cm); 
+    };
+}
@@ -2,6 +2,7 @@
 
 #include "core.h"
 #include "value.h"
+#include "numeric_array.h"
 #include <utility>
 #include <functional>
 //#ifdef __cplusplus
@@ -110,6 +111,40 @@ namespace juiz {
             }
             return val;
         }
+
+        juiz::Bytes get_bytes(const std::string& name) const {
+            capsule_ptr* ptr = NULL;
+            if (capsule_map_get_capsule(this->_pmap, name.c_str(), &ptr) != JUIZ_OK) {
+                throw ValueNotFoundError();
+            }
+            const uint8_t* data;
+            size_t len;
+            if (capsule_ptr_get_bytes(ptr, &data, &len) != JUIZ_OK) {
+                throw ValueNotFoundError();
+            }
+            return juiz::Bytes(data, data + len);
+        }
+
+        template<typename T>
+        juiz::NumericArray<T> get_numeric_array(const std::string& name) const {
+            capsule_ptr* ptr = NULL;
+            if (capsule_map_get_capsule(this->_pmap, name.c_str(), &ptr) != JUIZ_OK) {
+                throw ValueNotFoundError();
+            }
+            int64_t numeric_type;
+            const void* data;
+            size_t len;
+            const size_t* shape;
+            size_t ndim;
+            if (capsule_ptr_get_numeric_array(ptr, &numeric_type, &data, &len, &shape, &ndim) != JUIZ_OK) {
+                throw ValueNotFoundError();
+            }
+            if (numeric_type != numeric_type_code<T>::value) {
+                throw ValueNotFoundError();
+            }
+            const T* elements = static_cast<const T*>(data);
+            return juiz::NumericArray<T>(std::vector<size_t>(shape, shape + ndim), std::vector<T>(elements, elements + len));
+        }
     };
 
     int64_t __set_value_obj_value(const Value* src_v, value* val);
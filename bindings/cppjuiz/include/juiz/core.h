@@ -4,6 +4,7 @@
 
 #ifdef __cplusplus
 #include <cstdint>
+#include <cstddef>
 #include <vector>
 #include "value.h"
 
@@ -11,6 +12,7 @@ extern "C" {
 #else
 
 #include <stdint.h>
+#include <stddef.h>
 
 #endif
 
@@ -49,6 +51,9 @@ int capsule_ptr_get_object(capsule_ptr* cp, std::map<std::string, juiz::Value>&
 
 int capsule_ptr_lock_as_value_with_arg(capsule_ptr* cp, int64_t callback(void*, value*), void*);
 
+int capsule_ptr_get_bytes(capsule_ptr* cp, const uint8_t** val, size_t* len);
+int capsule_ptr_get_numeric_array(capsule_ptr* cp, int64_t* numeric_type, const void** data, size_t* len, const size_t** shape, size_t* ndim);
+
 
 int value_is_int(value* v);
 int value_get_int(value* v, int64_t* int_value);
@@ -100,6 +105,12 @@ int capsule_get_bool(capsule* cp, int64_t* val);
 int capsule_is_string(capsule* cp);
 int capsule_set_string(capsule* cp, const char* val);
 int capsule_get_string(capsule* cp, char** val);
+int capsule_is_bytes(capsule* cp);
+int capsule_get_bytes(capsule* cp, const uint8_t** val, size_t* len);
+int capsule_set_bytes(capsule* cp, const uint8_t* val, size_t len);
+int capsule_is_numeric_array(capsule* cp);
+int capsule_get_numeric_array(capsule* cp, int64_t* numeric_type, const void** data, size_t* len, const size_t** shape, size_t* ndim);
+int capsule_set_numeric_array(capsule* cp, int64_t numeric_type, const void* data, size_t len, const size_t* shape, size_t ndim);
 
 
 #ifdef __cplusplus
@@ -5,6 +5,7 @@
 #include "core.h"
 #include "value.h"
 #include "debug.h"
+#include "numeric_array.h"
 #include "capsule_map.h"
 #include "manifest.h"
 #include "process_manifest.h"
@@ -94,6 +94,15 @@ int64_t serialize(capsule* cp, const std::string&& retval) {
     return capsule_set_string(cp, retval.c_str());
 }
 
+int64_t serialize(capsule* cp, const juiz::Bytes& retval) {
+    return capsule_set_bytes(cp, retval.data(), retval.size());
+}
+
+template<typename T>
+int64_t serialize(capsule* cp, const juiz::NumericArray<T>& retval) {
+    return capsule_set_numeric_array(cp, juiz::numeric_type_code<T>::value, retval.data().data(), retval.data().size(), retval.shape().data(), retval.shape().size());
+}
+
 
 #define PROCESS_FACTORY(manifest_function, process_function) \
 JUIZ_API int64_t manifest_entry_point(capsule_ptr* ptr) { \
@@ -0,0 +1,38 @@
+#pragma once
+
+#include <cstdint>
+#include <cstddef>
+#include <vector>
+
+
+namespace juiz {
+
+    /// バイト列
+    using Bytes = std::vector<uint8_t>;
+
+    /// 要素の型の番号。Rust側のNumericType::code()と同じ
+    template<typename T> struct numeric_type_code;
+    template<> struct numeric_type_code<float> { static const int64_t value = 0; };
+    template<> struct numeric_type_code<double> { static const int64_t value = 1; };
+    template<> struct numeric_type_code<int32_t> { static const int64_t value = 2; };
+    template<> struct numeric_type_code<uint8_t> { static const int64_t value = 3; };
+
+    /// 型と形を持つ数値の配列。要素は行優先(最後の次元が連続する)で並べる
+    template<typename T>
+    class NumericArray {
+    public:
+        NumericArray() {}
+        NumericArray(const std::vector<size_t>& shape, const std::vector<T>& data) : shape_(shape), data_(data) {}
+        NumericArray(const std::vector<T>& data) : shape_({data.size()}), data_(data) {}
+
+    public:
+        const std::vector<size_t>& shape() const { return shape_; }
+        const std::vector<T>& data() const { return data_; }
+        std::vector<T>& data() { return data_; }
+
+    private:
+        std::vector<size_t> shape_;
+        std::vector<T> data_;
+    };
+
+}// namespace juiz
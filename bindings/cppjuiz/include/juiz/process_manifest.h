@@ -93,6 +93,16 @@ public:
     return *this;
   }
 
+  ProcessManifest add_bytes_arg(const std::string& name, const std::string& description) {
+    arguments_.push_back(ArgumentManifest("bytes", name, description, juiz::Value()));
+    return *this;
+  }
+
+  ProcessManifest add_numeric_array_arg(const std::string& name, const std::string& description) {
+    arguments_.push_back(ArgumentManifest("numeric_array", name, description, juiz::Value()));
+    return *this;
+  }
+
   ProcessManifest use_memo(const bool use_memo) {
     use_memo_ = use_memo;
     return *this;
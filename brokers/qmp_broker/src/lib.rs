@@ -1,16 +1,13 @@
 use std::sync::{Arc, Mutex};
 
-use juiz_core::{anyhow::{self, anyhow}, {create_broker_factory_impl, create_broker_proxy_factory_impl, CRUDBroker, CRUDBrokerHolder}, prelude::*};
+use juiz_core::{{create_broker_factory_impl, create_broker_proxy_factory_impl, CRUDBroker, CRUDBrokerHolder, PayloadCodec}, prelude::*};
 
 mod qmp_broker;
 mod qmp_broker_proxy;
 
-pub(crate) fn value_to_vecu8(val: &Value) -> anyhow::Result<Vec<u8>> {
-    rmp_serde::to_vec(val).or_else(|e| { Err(anyhow!(e)) })
-}
-
-pub(crate) fn vecu8_to_value(mut vecu8: Vec<u8>) -> anyhow::Result<Value> {
-    rmp_serde::from_slice::<serde_json::Value>(&mut vecu8.as_mut_slice()[..] ).or_else(|e|{Err(anyhow!(e))})
+/// マニフェストの"codec"で選ぶ符号化。書かれていなければMessagePack。ブローカーとプロキシで揃える
+pub(crate) fn codec_from_manifest(manifest: &Value) -> JuizResult<PayloadCodec> {
+    PayloadCodec::from_manifest(manifest, PayloadCodec::MessagePack)
 }
 
 pub(crate) fn value_to_request_value(class_name: &str, function_name: &str, method_name: &str, payload: Value, mut param: std::collections::HashMap<String, String>) -> Value {
@@ -71,6 +68,7 @@ pub unsafe extern "Rust" fn broker_factory(core_broker: CoreBrokerPtr) -> JuizRe
 
     create_broker_factory_impl(core_broker, manifest, create_broker_function)
 }
+export_abi_descriptor!(broker_factory_abi);
 
 #[no_mangle]
 pub unsafe extern "Rust" fn broker_proxy_factory() -> JuizResult<Arc<Mutex<dyn BrokerProxyFactory>>> {
@@ -78,4 +76,5 @@ pub unsafe extern "Rust" fn broker_proxy_factory() -> JuizResult<Arc<Mutex<dyn B
         "type_name": "qmp"
     });
     create_broker_proxy_factory_impl(manifest, qmp_broker_proxy::create_broker_proxy_function)
-}
\ No newline at end of file
+}
+export_abi_descriptor!(broker_proxy_factory_abi);
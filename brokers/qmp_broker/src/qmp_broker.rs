@@ -1,10 +1,21 @@
-
+//! QUICで要求を受けるブローカー
+//!
+//! 要求も応答もマニフェストの"codec" (既定はMessagePack) で符号化した一つの値で、ストリームごとに一往復する。
+//! QUICのストリームにはHTTPのヘッダのような場所が無いので、応答の値にオプション (時刻・通し番号・作成元など) が
+//! 付いているときは本体で包んで送る。
+//!
+//! ```json
+//! {"__value__": 3, "__option__": {"timestamp": "...", "seq": "1", "producer": "..."}}
+//! ```
+//!
+//! オプションが無い値 (プロセスの一覧など) はこれまでどおり包まずに送る。
+//! プロキシはどちらも`CapsulePtr`に戻すが、包んだ応答を読めないこれより前のプロキシとはプロセスの呼び出しが噛み合わない。
 
 use std::sync::Mutex;
 use std::time::Duration;
 use std::{net::SocketAddr, sync::Arc};
 use juiz_core::anyhow::Context;
-use juiz_core::{prelude::*, tokio, anyhow::{self, anyhow}, CRUDBroker};
+use juiz_core::{prelude::*, tokio, anyhow::{self, anyhow}, CRUDBroker, PayloadCodec};
 
 use quinn::{Connection, Endpoint, Incoming};
 use rustls::pki_types::CertificateDer;
@@ -12,7 +23,7 @@ use rustls::crypto::ring::default_provider;
 use quinn::ServerConfig;
 use rustls::pki_types::PrivatePkcs8KeyDer;
 
-use crate::{value_to_vecu8, vecu8_to_value};
+use crate::codec_from_manifest;
 
 
 #[allow(unused)]
@@ -97,8 +108,8 @@ pub const ALPN_QUIC_HTTP: &[&[u8]] = &[b"hq-29"];
 //     return retval;
 // }
 
-fn callback(request: Vec<u8>, crud_broker: Arc<Mutex<CRUDBroker>>, remote_addr: SocketAddr) -> anyhow::Result<Vec<u8>> {
-    let val = vecu8_to_value(request)?;
+fn callback(request: Vec<u8>, crud_broker: Arc<Mutex<CRUDBroker>>, remote_addr: SocketAddr, codec: PayloadCodec) -> anyhow::Result<Vec<u8>> {
+    let val = codec.decode(&request)?;
     let capsule_ptr= juiz_lock(&crud_broker)?.on_value_request(val, Some(remote_addr))?;
     // let cp = to_request(val)?;
     // let class_name = cp.get_param("class_name").unwrap().clone();
@@ -116,12 +127,18 @@ fn callback(request: Vec<u8>, crud_broker: Arc<Mutex<CRUDBroker>>, remote_addr:
     //         Err(anyhow!(JuizError::InvalidValueError{message: format!("qmp_broker received invalid value. Its method name is unknown ({})", method_name)}))
     //     }
     // }?;
-    if capsule_ptr.is_value()? {
+    if capsule_ptr.is_value()? && !capsule_ptr.get_options()?.is_empty() {
+        // オプション (時刻・通し番号・作成元など) も送るため、__value__と__option__に包む
+        codec.encode(&capsule_to_value(capsule_ptr)?)
+    } else if capsule_ptr.is_value()? {
         capsule_ptr.lock_as_value(|v| {
-            value_to_vecu8(v)
+            codec.encode(v)
         })?
+    } else if capsule_ptr.is_bytes()? || capsule_ptr.is_numeric_array()? {
+        // バイト列と数値の配列はBase64で包んだJSONにして、オプションとともに送る
+        codec.encode(&capsule_to_value(capsule_ptr)?)
     } else if capsule_ptr.is_empty()? {
-        Ok(value_to_vecu8(&jvalue!({}))?)
+        Ok(codec.encode(&jvalue!({}))?)
     // } else if capsule_ptr.is_mat()? {
     //     todo!()
     } else {
@@ -138,8 +155,15 @@ pub async fn on_start(broker_manifest: Value, crud_broker: Arc<Mutex<CRUDBroker>
     let host = obj_get_str(&broker_manifest, "host").or::<&str>(Ok("0.0.0.0") ).unwrap();
     let port  = obj_get_i64(&broker_manifest, "port").or::<i64>( Ok(8080)).unwrap();
     let idle_timeout = obj_get_i64( &broker_manifest, "idle_timeout").and_then(|v|{ Ok(Some(v as u64)) }).or::<Option<u64>>(Ok(None)).unwrap();
+    let codec = match codec_from_manifest(&broker_manifest) {
+        Ok(codec) => codec,
+        Err(e) => {
+            log::error!("qmp_broker::on_start() failed. Invalid codec. Error({e:?})");
+            return;
+        }
+    };
     let address = format!("{:}:{:}", host, port);
-    run_server(address.parse().unwrap(), idle_timeout,  Arc::new(move |req, conn: &Connection| {callback(req, crud_broker.clone(), conn.remote_address())} )).await;
+    run_server(address.parse().unwrap(), idle_timeout,  Arc::new(move |req, conn: &Connection| {callback(req, crud_broker.clone(), conn.remote_address(), codec)} )).await;
 }
    
 
@@ -210,14 +234,12 @@ async fn handle_connection<F>(conn: Connection, callback: Arc<F>) -> anyhow::Res
 
 
 async fn handle_stream<F>(send: &mut quinn::SendStream, recv: &mut quinn::RecvStream, callback: Arc<F>, connection: &Connection) -> anyhow::Result<()> where F: Fn(Vec<u8>, &Connection)-> anyhow::Result<Vec<u8>>  + Send + Sync{
-    let mut req = recv.read_to_end(64 * 1024).await.or_else(|e| {
+    let req = recv.read_to_end(64 * 1024).await.or_else(|e| {
         log::error!("RecvStream::read_to_end() failed. {e:?}");
         return Err(anyhow!(e))
     })?;
 
-    let req_msg = rmp_serde::from_slice::<serde_json::Value>(&mut &req.as_mut_slice()[..] )?;
-
-    log::trace!("request : {:?}", req_msg);
+    log::trace!("request : {} bytes", req.len());
     
     let response = callback(req, connection)?;
 
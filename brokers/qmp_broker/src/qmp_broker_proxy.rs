@@ -1,15 +1,19 @@
 use std::{net::SocketAddr, sync::{Arc, Mutex}, time::Duration};
 
-use juiz_core::{anyhow::{self, anyhow}, futures, prelude::*, tokio::{self}, CRUDBrokerProxy, CRUDBrokerProxyHolder};
+use juiz_core::{anyhow::{self, anyhow}, futures, prelude::*, tokio::{self}, CRUDBrokerProxy, CRUDBrokerProxyHolder, PayloadCodec};
 use quinn::{crypto::rustls::QuicClientConfig, ClientConfig, Connection, Endpoint, TransportConfig};
 use rustls::{crypto::ring::default_provider, pki_types::{CertificateDer, ServerName, UnixTime}};
 
-use crate::{payload_to_request_value, to_request_value, value_to_request_value, value_to_vecu8, vecu8_to_value};
+use crate::{payload_to_request_value, to_request_value, value_to_request_value, codec_from_manifest};
 
 pub(crate) fn create_broker_proxy_function(_core_broker: &CoreWorker, manifest: Value) -> JuizResult<Arc<Mutex<dyn BrokerProxy>>> {
     log::trace!("create_broker_proxy_function({manifest:}) called");
     let name = obj_get_str(&manifest, "name")?;
-    Ok(CRUDBrokerProxyHolder::new("QuicBrokerProxy", "qmp", name, Box::new(QuicBrokerProxy::new(&manifest)?))?)
+    let holder = CRUDBrokerProxyHolder::new("QuicBrokerProxy", "qmp", name, Box::new(QuicBrokerProxy::new(&manifest)?))?;
+    // マニフェストの"timeout" [sec] はタイムアウトの指定が無い呼び出しに使う
+    let default_timeout = obj_get_f64(&manifest, TIMEOUT_PARAM_KEY).ok().map(|sec| parse_timeout(&sec.to_string())).transpose()?;
+    juiz_lock(&holder)?.set_default_timeout(default_timeout);
+    Ok(holder)
 }
 
 //use juiz_core::futures;
@@ -20,6 +24,7 @@ struct QuicBrokerProxy {
     rt:  Arc<tokio::runtime::Runtime>,
     endpoint: Endpoint, 
     connection: Connection,
+    codec: PayloadCodec,
 }
 
 fn manifest_to_host_and_port(manifest: &Value) -> JuizResult<(&str, &str, i64)> {
@@ -68,6 +73,7 @@ impl QuicBrokerProxy {
             Err(e)
         })?;
         let idle_timeout = obj_get_i64( &manifest, "idle_timeout").and_then(|v|{ Ok(Some(v as u64)) }).or::<Option<u64>>(Ok(None)).unwrap();
+        let codec = codec_from_manifest(manifest)?;
     
         let address = format!("{:}:{:}", host, port);
         log::trace!(" - address='{address}'");
@@ -100,6 +106,7 @@ impl QuicBrokerProxy {
             rt: rt.clone(),
             endpoint,
             connection,
+            codec,
         })
     }
 }
@@ -135,13 +142,13 @@ async fn write_and_then<T, F: Fn(Vec<u8>)->anyhow::Result<T>>(connection: &Conne
 
 
 fn response_to_capsule_ptr(response: Value) -> JuizResult<CapsulePtr> {
-    Ok(response.into())
+    CapsulePtr::from_json_value(response)
 }
 
 
-async fn write_and_then_value<T, F: Fn(serde_json::Value)->anyhow::Result<T>>(connection: &Connection, request: serde_json::Value, callback: F) -> anyhow::Result<T> {
-    write_and_then(connection, value_to_vecu8(&request)?, |response| {
-        callback(vecu8_to_value(response)?)
+async fn write_and_then_value<T, F: Fn(serde_json::Value)->anyhow::Result<T>>(connection: &Connection, codec: PayloadCodec, request: serde_json::Value, callback: F) -> anyhow::Result<T> {
+    write_and_then(connection, codec.encode(&request)?, |response| {
+        callback(codec.decode(&response)?)
     }).await
 }
 
@@ -150,7 +157,7 @@ impl CRUDBrokerProxy for QuicBrokerProxy {
     fn create(&self, class_name: &str, function_name: &str, payload: Value, param: std::collections::HashMap<String, String>) -> JuizResult<CapsulePtr> {
         log::trace!("QuicBrokerProxy::create({class_name},{function_name},{payload:?},{param:?}) called");
         let request = value_to_request_value(class_name, function_name, "create", payload, param);
-        futures::executor::block_on(write_and_then_value(&self.connection, request, |response| {
+        futures::executor::block_on(write_and_then_value(&self.connection, self.codec, request, |response| {
             response_to_capsule_ptr(response)
         }))
     }
@@ -158,7 +165,7 @@ impl CRUDBrokerProxy for QuicBrokerProxy {
     fn delete(&self, class_name: &str, function_name: &str, param: std::collections::HashMap<String, String>) -> JuizResult<CapsulePtr> {
         log::trace!("QuicBrokerProxy::delete({class_name},{function_name},{param:?}) called");
         let request = to_request_value(class_name, function_name, "delete", param);
-        futures::executor::block_on(write_and_then_value(&self.connection, request, |response| {
+        futures::executor::block_on(write_and_then_value(&self.connection, self.codec, request, |response| {
             response_to_capsule_ptr(response)
         }))
     }
@@ -166,17 +173,29 @@ impl CRUDBrokerProxy for QuicBrokerProxy {
     fn read(&self, class_name: &str, function_name: &str, param: std::collections::HashMap<String, String>) -> JuizResult<CapsulePtr> {
         log::trace!("QuicBrokerProxy::read({class_name},{function_name},{param:?}) called");
         let request = to_request_value(class_name, function_name, "read", param);
-        futures::executor::block_on(write_and_then_value(&self.connection, request, |response| {
+        futures::executor::block_on(write_and_then_value(&self.connection, self.codec, request, |response| {
             response_to_capsule_ptr(response)
         }))
     }
 
     fn update(&self, class_name: &str, function_name: &str, payload: CapsuleMap, param: std::collections::HashMap<String, String>) -> JuizResult<CapsulePtr> {
         log::trace!("QuicBrokerProxy::update({class_name},{function_name},{payload:?},{param:?}) called");
+        let timeout = param.get(TIMEOUT_PARAM_KEY).map(|v| parse_timeout(v)).transpose()?;
         let request = payload_to_request_value(class_name, function_name, "update", payload, param);
-        futures::executor::block_on(write_and_then_value(&self.connection, request, |response| {
+        let future = write_and_then_value(&self.connection, self.codec, request, |response| {
             response_to_capsule_ptr(response)
-        }))
+        });
+        match timeout {
+            None => futures::executor::block_on(future),
+            Some(timeout) => {
+                // tokio::time::timeoutはランタイムのタイマーを使う
+                let _guard = self.rt.enter();
+                futures::executor::block_on(tokio::time::timeout(timeout, future)).unwrap_or_else(|_| {
+                    log::error!("QuicBrokerProxy::update({class_name},{function_name}) timed out after {timeout:?}");
+                    Err(anyhow!(JuizError::RemoteCallTimeoutError{target: self.connection.remote_address().to_string(), timeout_sec: timeout.as_secs_f64()}))
+                })
+            }
+        }
     }
 }
 
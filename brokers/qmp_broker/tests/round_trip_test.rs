@@ -0,0 +1,62 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use juiz_core::{prelude::*, tokio, CRUDBroker, SystemStore, SystemStorePtr};
+
+extern crate qmp_broker;
+extern crate juiz_core;
+
+fn increment_function(args: CapsuleMap) -> JuizResult<Capsule> {
+    let i = args.get("arg1")?.lock_as_value(|v| v.as_i64().unwrap())?;
+    Ok(jvalue!(i + 1).into())
+}
+
+/// 空いているUDPのポート
+fn free_port() -> JuizResult<i64> {
+    Ok(std::net::UdpSocket::bind("127.0.0.1:0")?.local_addr()?.port() as i64)
+}
+
+/// increment0のプロセスを持つCoreBrokerと、それを受けるQMPのブローカーを立てる
+fn setup_broker(port: i64, codec: &str) -> JuizResult<(CoreBrokerPtr, Identifier)> {
+    let core = CoreBrokerPtr::new(CoreBroker::new(jvalue!({}), SystemStorePtr::new(SystemStore::new()))?);
+    let manifest = jvalue!({
+        "type_name": "increment",
+        "arguments": [{"name": "arg1", "type": "int", "description": "test_argument", "default": 1}],
+    });
+    let pf = process_factory_create(manifest.try_into()?, increment_function)?;
+    let id = core.lock_mut().and_then(|mut cb| {
+        cb.worker_mut().store_mut().processes.register_factory("increment", pf)?;
+        let p = cb.worker_mut().create_process_ref(jvalue!({"name": "increment0", "type_name": "increment"}).try_into()?)?;
+        Ok(p.identifier().clone())
+    })?;
+
+    let broker_manifest = jvalue!({"type_name": "qmp", "name": format!("127.0.0.1:{port}"), "host": "127.0.0.1", "port": port, "codec": codec});
+    let crud = Arc::new(Mutex::new(CRUDBroker::new(core.clone(), broker_manifest.clone())?));
+    std::thread::spawn(move || {
+        tokio::runtime::Runtime::new().unwrap().block_on(qmp_broker::on_start(broker_manifest, crud));
+    });
+    std::thread::sleep(Duration::from_millis(300));
+    Ok((core, id))
+}
+
+#[test]
+fn qmp_round_trip_test() -> JuizResult<()> {
+    for codec in ["msgpack", "json"] {
+        let port = free_port()?;
+        let (core, id) = setup_broker(port, codec)?;
+        let factory = unsafe { qmp_broker::broker_proxy_factory() }?;
+        let proxy = juiz_lock(&factory)?.create_broker_proxy(core.lock()?.worker(), jvalue!({"type_name": "qmp", "name": format!("127.0.0.1:{port}"), "codec": codec}))?;
+
+        // プロセスの出力は__value__と__option__に包んで送り、オプションも戻る
+        let mut args = CapsuleMap::new();
+        args.insert("arg1".to_owned(), jvalue!(2).into());
+        let output = juiz_lock(&proxy)?.process_call(&id, args)?;
+        assert_eq!(output.producer()?, Some(id.clone()), "codec={codec}");
+        assert!(output.timestamp()?.is_some(), "codec={codec}");
+        assert_eq!(output.extract_value()?, jvalue!(3), "codec={codec}");
+
+        // オプションの無い値は包まない
+        let list = juiz_lock(&proxy)?.process_list(false)?;
+        assert_eq!(list.as_array().unwrap().len(), 1, "codec={codec}");
+    }
+    Ok(())
+}
@@ -51,7 +51,7 @@ impl ExecutionContext for MainLoopEC {
             log::trace!("MainLoopEC().execute_func() called");
             match juiz_lock(&c) {
                 Err(e) => return Err(e),
-                Ok(cc) => {
+                Ok(mut cc) => {
                     match cc.get_state() {
                         ExecutionContextState::STARTED => cc.svc().and(Ok(())),
                         _ => Ok(())
@@ -60,7 +60,11 @@ impl ExecutionContext for MainLoopEC {
             }
         });
         system.set_spin_callback(func);
-        system.set_spin_sleeptime(Duration::from_micros((1000000.0 / self.rate) as u64));
+        let period = Duration::from_micros((1000000.0 / self.rate) as u64);
+        if let Ok(mut cc) = juiz_lock(&core) {
+            cc.set_period(Some(period));
+        }
+        system.set_spin_sleeptime(period);
     }
 
     fn is_periodic(&self) -> bool {
@@ -91,3 +95,4 @@ pub unsafe extern "Rust" fn execution_context_factory() -> JuizResult<Arc<Mutex<
     env_logger::init();
     Ok(Arc::new(Mutex::new(MainLoopECFactory{})))
 }
+export_abi_descriptor!(execution_context_factory_abi);
@@ -59,3 +59,4 @@ pub unsafe extern "Rust" fn execution_context_factory() -> JuizResult<Arc<Mutex<
     env_logger::init();
     Ok(Arc::new(Mutex::new(OneShotECFactory{})))
 }
+export_abi_descriptor!(execution_context_factory_abi);
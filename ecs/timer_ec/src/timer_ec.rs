@@ -1,27 +1,95 @@
-use std::{sync::{Arc, Mutex, RwLock}, time::Duration};
+use std::{sync::{Arc, Mutex, RwLock}, time::{Duration, Instant}};
 use juiz_core::{env_logger, log, anyhow};
 use juiz_core::{ExecutionContext, ExecutionContextCore, ExecutionContextFactory};
 
 use juiz_core::prelude::*;
+
+/// svc()が周期に間に合わなかった時の振る舞い
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverrunPolicy {
+    /// 過ぎてしまった周期は飛ばして、次の周期の開始時刻から再開する
+    Skip,
+    /// 待たずに続けて実行し、遅れを取り戻す
+    CatchUp,
+    /// ECをERROR状態にして止める
+    Stop,
+}
+
+impl TryFrom<&str> for OverrunPolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> JuizResult<Self> {
+        match value {
+            "skip" => Ok(OverrunPolicy::Skip),
+            "catch_up" => Ok(OverrunPolicy::CatchUp),
+            "stop" => Ok(OverrunPolicy::Stop),
+            _ => Err(anyhow::Error::from(JuizError::InvalidSettingError{message: format!("TimerEC overrun policy must be 'skip', 'catch_up' or 'stop' but '{value}'.")})),
+        }
+    }
+}
+
+impl std::fmt::Display for OverrunPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OverrunPolicy::Skip => f.write_str("skip"),
+            OverrunPolicy::CatchUp => f.write_str("catch_up"),
+            OverrunPolicy::Stop => f.write_str("stop"),
+        }
+    }
+}
+
 pub struct TimerEC {
     rate: f64,
     name: String,
-    timeout: Duration, 
+    period: Duration,
+    overrun: OverrunPolicy,
+    /// 次の周期の開始時刻。周期の開始時刻を基準に積み上げるのでsvc()の実行時間で周期がずれない
+    next_deadline: Mutex<Option<Instant>>,
 }
 
 impl TimerEC {
     pub fn new(name: &str, rate: f64) -> Arc<RwLock<TimerEC>> {
+        Self::new_with_overrun_policy(name, rate, OverrunPolicy::Skip)
+    }
+
+    pub fn new_with_overrun_policy(name: &str, rate: f64, overrun: OverrunPolicy) -> Arc<RwLock<TimerEC>> {
         let rate_sec: u64 = rate.floor() as u64;
-        let rate_nsec: u32 = ((rate - rate.floor()) * (1000_000_000.0)) as u32;
-        let timeout = Duration::new(rate_sec, rate_nsec);
+        let rate_nsec: u32 = ((rate - rate.floor()) * (1_000_000_000.0)) as u32;
+        let period = Duration::new(rate_sec, rate_nsec);
         Arc::new( RwLock::new(TimerEC{
-            //thread_handle: None,
-            //end_flag: Arc::new(Mutex::new(AtomicBool::from(false))),
             rate,
             name: name.to_string(),
-            timeout,
+            period,
+            overrun,
+            next_deadline: Mutex::new(None),
         }))
     }
+
+    fn lock_next_deadline(&self) -> JuizResult<std::sync::MutexGuard<'_, Option<Instant>>> {
+        self.next_deadline.lock().map_err(|_| anyhow::Error::from(JuizError::ExecutionContextCanNotLockStateError{}))
+    }
+
+    /// 周期の終わりに次の開始時刻を決める。Stopポリシーで周期を過ぎていたらエラー
+    ///
+    /// 過ぎたかどうかは統計のデッドラインミスと同じ判断 (missed) に従う
+    fn next_deadline_after(&self, deadline: Instant, now: Instant, missed: bool) -> JuizResult<Instant> {
+        let next = deadline + self.period;
+        if !missed {
+            return Ok(next);
+        }
+        match self.overrun {
+            OverrunPolicy::Skip => {
+                let behind = now.saturating_duration_since(next).as_nanos() / self.period.as_nanos().max(1) + 1;
+                Ok(next + self.period * behind as u32)
+            },
+            OverrunPolicy::CatchUp => Ok(next),
+            OverrunPolicy::Stop => Err(anyhow::Error::from(JuizError::ExecutionContextDeadlineMissedError{
+                name: self.name.clone(),
+                elapsed_sec: now.saturating_duration_since(deadline).as_secs_f64(),
+                period_sec: self.period.as_secs_f64(),
+            })),
+        }
+    }
 }
 
 impl ExecutionContext for TimerEC {
@@ -36,22 +104,43 @@ impl ExecutionContext for TimerEC {
     fn profile(&self) -> JuizResult<Value> {
         Ok(jvalue!({
             "rate": self.rate,
+            "overrun": self.overrun.to_string(),
         }))
     }
 
+    fn on_starting(&mut self, core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<()> {
+        juiz_lock(core)?.set_period(Some(self.period));
+        *self.lock_next_deadline()? = Some(Instant::now() + self.period);
+        Ok(())
+    }
+
+    /// 止まっていた間の周期は数えず、再開した時刻から周期を始める
+    fn on_resuming(&mut self, _core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<()> {
+        *self.lock_next_deadline()? = Some(Instant::now() + self.period);
+        Ok(())
+    }
+
     fn execute(&self, core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<bool> {
         log::trace!("TimerExecutionContext.execute called");
-        std::thread::sleep(self.timeout);
-        match core.lock() {
+        let deadline = self.lock_next_deadline()?.unwrap_or_else(|| Instant::now() + self.period);
+        let now = Instant::now();
+        if deadline > now {
+            std::thread::sleep(deadline - now);
+        }
+        let missed = match core.lock() {
             Err(e) => {
                 log::error!("Error({e:?}) in Locking ECServiceFunction");
                 return Err(anyhow::Error::from(JuizError::ExecutionContextCanNotLockStateError{}));
             },
-            Ok(svc_func) => { 
+            Ok(mut svc_func) => { 
+                svc_func.set_deadline(Some(deadline + self.period));
                 let _ = svc_func.svc().map_err(|e| -> () {log::error!("Error({e:?}) in Service function in ExecutionContext."); }); 
+                svc_func.statistics().last_missed()
             }
-        }
-        return Ok(true);
+        };
+        let next = self.next_deadline_after(deadline, Instant::now(), missed)?;
+        *self.lock_next_deadline()? = Some(next);
+        Ok(true)
     }
 }
 
@@ -67,8 +156,12 @@ impl ExecutionContextFactory for TimerECFactory {
     fn create(&self, manifest: Value) -> JuizResult<Arc<RwLock<dyn ExecutionContext>>> {
         let name = obj_get_str(&manifest, "name")?;
         let rate = obj_get_f64(&manifest, "rate")?;
+        let overrun = match obj_get_str(&manifest, "overrun") {
+            Ok(v) => OverrunPolicy::try_from(v)?,
+            Err(_) => OverrunPolicy::Skip,
+        };
         Ok(
-            TimerEC::new(name, rate)
+            TimerEC::new_with_overrun_policy(name, rate, overrun)
         )
     }
 }
@@ -78,3 +171,4 @@ pub unsafe extern "Rust" fn execution_context_factory() -> JuizResult<Arc<Mutex<
     env_logger::init();
     Ok(Arc::new(Mutex::new(TimerECFactory{})))
 }
+export_abi_descriptor!(execution_context_factory_abi);
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+use juiz_core::prelude::*;
+use juiz_core::{ExecutionContext, ExecutionContextCore};
+use timer_ec::timer_ec::{OverrunPolicy, TimerEC};
+
+fn sleep_function(v: CapsuleMap) -> JuizResult<Capsule> {
+    let msec = v.get("msec")?.lock_as_value(|value| value.as_u64().unwrap())?;
+    std::thread::sleep(Duration::from_millis(msec));
+    Ok(jvalue!(msec).into())
+}
+
+fn new_sleep_core(msec: u64) -> JuizResult<std::sync::Arc<std::sync::Mutex<ExecutionContextCore>>> {
+    let manifest = jvalue!({
+        "name": "sleep0",
+        "type_name": "sleep",
+        "arguments": [{"name": "msec", "type": "int", "description": "", "default": msec}],
+    });
+    let core = ExecutionContextCore::new();
+    juiz_lock(&core)?.bind(ProcessPtr::new(process_new(manifest.try_into()?, sleep_function)?))?;
+    Ok(core)
+}
+
+#[test]
+fn timer_ec_drift_compensation_test() -> JuizResult<()> {
+    let ec = TimerEC::new("timer0", 0.05);
+    let core = new_sleep_core(20)?;
+    ec.write().unwrap().on_starting(&core)?;
+    let started = Instant::now();
+    for _ in 0..4 {
+        assert!(ec.read().unwrap().execute(&core)?);
+    }
+    // svc()の20msは周期50msの中に含まれる
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(200) && elapsed < Duration::from_millis(280), "elapsed={elapsed:?}");
+    let statistics = juiz_lock(&core)?.statistics().clone();
+    assert_eq!(statistics.num_cycles(), 4);
+    assert_eq!(statistics.num_deadline_misses(), 0);
+    Ok(())
+}
+
+#[test]
+fn timer_ec_overrun_policy_test() -> JuizResult<()> {
+    let core = new_sleep_core(30)?;
+    let ec = TimerEC::new_with_overrun_policy("timer1", 0.02, OverrunPolicy::Stop);
+    ec.write().unwrap().on_starting(&core)?;
+    assert!(ec.read().unwrap().execute(&core).is_err());
+    assert_eq!(juiz_lock(&core)?.statistics().num_deadline_misses(), 1);
+
+    // 遅れを取り戻すなら待たずに続けて実行する
+    let ec = TimerEC::new_with_overrun_policy("timer2", 0.02, OverrunPolicy::CatchUp);
+    ec.write().unwrap().on_starting(&core)?;
+    assert!(ec.read().unwrap().execute(&core)?);
+    let started = Instant::now();
+    assert!(ec.read().unwrap().execute(&core)?);
+    assert!(started.elapsed() < Duration::from_millis(40));
+
+    // svc()が周期より短くても、始まりが遅れてデッドラインを過ぎたらデッドラインミスとして数える
+    let core = new_sleep_core(0)?;
+    let ec = TimerEC::new_with_overrun_policy("timer4", 0.02, OverrunPolicy::Stop);
+    ec.write().unwrap().on_starting(&core)?;
+    assert!(ec.read().unwrap().execute(&core)?);
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(ec.read().unwrap().execute(&core).is_err());
+    assert_eq!(juiz_lock(&core)?.statistics().num_deadline_misses(), 1);
+
+    assert!(OverrunPolicy::try_from("later").is_err());
+    Ok(())
+}
+
+#[test]
+fn timer_ec_resume_test() -> JuizResult<()> {
+    for overrun in [OverrunPolicy::Skip, OverrunPolicy::CatchUp, OverrunPolicy::Stop] {
+        let core = new_sleep_core(0)?;
+        let ec = TimerEC::new_with_overrun_policy("timer3", 0.02, overrun);
+        ec.write().unwrap().on_starting(&core)?;
+        assert!(ec.read().unwrap().execute(&core)?, "overrun={overrun}");
+        // PAUSEDの間に何周期分も過ぎる
+        std::thread::sleep(Duration::from_millis(100));
+        ec.write().unwrap().on_resuming(&core)?;
+        let started = Instant::now();
+        for _ in 0..3 {
+            assert!(ec.read().unwrap().execute(&core)?, "overrun={overrun}");
+        }
+        // 再開した時刻から周期を数え直すので、遅れとして扱わず続けて実行もしない
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(60), "overrun={overrun} elapsed={elapsed:?}");
+        assert_eq!(juiz_lock(&core)?.statistics().num_deadline_misses(), 0, "overrun={overrun}");
+    }
+    Ok(())
+}
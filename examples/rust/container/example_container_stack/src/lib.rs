@@ -34,5 +34,4 @@ pub unsafe extern "Rust" fn container_factory() -> JuizResult<ContainerStackFact
     let manifest = ExampleContainerStack::manifest();
     Ok(container_stack_factory(manifest, create_example_container_ex))
 }
-
-
+export_abi_descriptor!(container_factory_abi);
@@ -26,3 +26,4 @@ pub unsafe extern "Rust" fn container_process_factory() -> JuizResult<ContainerP
         .container(ExampleContainerStack::manifest());
     Ok(juiz_sdk::factory::container_process_factory(manifest, get_function))
 }
+export_abi_descriptor!(container_process_factory_abi);
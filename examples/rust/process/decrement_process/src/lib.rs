@@ -20,3 +20,4 @@ pub unsafe extern "Rust" fn process_factory() -> JuizResult<(ProcessManifest, fn
     Ok((manifest(), decrement_process))
     // process_factory_create(manifest(), decrement_process)
 }
+export_abi_descriptor!(process_factory_abi);
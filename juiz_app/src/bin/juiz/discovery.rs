@@ -0,0 +1,60 @@
+//
+// juiz discovery --wait 2.0
+// juiz -d --attach robot_a
+
+
+use std::path::Path;
+use std::time::Duration;
+use juiz_core::log;
+
+use juiz_core::prelude::*;
+use juiz_core::utils::manifest_util::manifest_merge;
+use clap::Parser;
+
+use crate::Args;
+
+#[derive(Debug, Parser, Clone)]
+pub(crate) struct DiscoveryArgs {
+    #[arg(long = "wait", default_value = "2.0", help = "Listen for announcements during this duration [sec]")]
+    wait: f64,
+
+    #[arg(long = "loopback", help = "Discover only juiz systems on this host.")]
+    loopback: bool,
+}
+
+/// マニフェストに"discovery"が無ければ既定の設定で足す
+pub(crate) fn with_discovery(manifest: Value, loopback: bool) -> JuizResult<Value> {
+    let discovery = match manifest.get("discovery") {
+        Some(_) if !loopback => return Ok(manifest),
+        Some(_) => jvalue!({"loopback": true}),
+        None => jvalue!({"loopback": loopback}),
+    };
+    manifest_merge(manifest, &jvalue!({"discovery": discovery}))
+}
+
+pub(crate) fn on_discovery(manifest: Value, working_dir: &Path, discovery_args: DiscoveryArgs, args: Args) -> JuizResult<()> {
+    match on_discovery_inner(manifest, working_dir, discovery_args, args) {
+        Ok(_) => return Ok(()),
+        Err(e) => println!("Error: {e:?}")
+    };
+    Ok(())
+}
+
+fn on_discovery_inner(manifest: Value, working_dir: &Path, discovery_args: DiscoveryArgs, args: Args) -> JuizResult<()> {
+    log::trace!("discovery command is selected. args={discovery_args:?}");
+    System::new(with_discovery(manifest, discovery_args.loopback)?)?
+        .set_working_dir(working_dir)
+        .start_http_broker(args.start_http_broker)
+        .setup()?
+        .run_and_do_once(|system| {
+            std::thread::sleep(Duration::from_secs_f64(discovery_args.wait));
+            let peers = system.core_broker().lock()?.system_discovered_peers()?;
+            for peer in get_array(&peers)?.iter() {
+                let brokers = get_array(obj_get(peer, "brokers")?)?.iter()
+                    .map(|b| Ok(format!("{}://{}", obj_get_str(b, "type_name")?, obj_get_str(b, "name")?)))
+                    .collect::<JuizResult<Vec<String>>>()?;
+                println!("{} ({}) {brokers:?}", obj_get_str(peer, "name")?, obj_get_str(peer, "uuid")?);
+            }
+            Ok(())
+        })
+}
@@ -27,6 +27,49 @@ pub(crate) enum EcSubCommands {
         #[arg(short = 'f', default_value = "./juiz.conf", help = "Input system definition file path")]
         filepath: String,
     },
+
+    /// pause started execution context
+    #[clap(arg_required_else_help = false)]
+    Pause {
+        #[arg(help = "ID of Execution context")]
+        identifier: String,
+
+        #[arg(short = 'f', default_value = "./juiz.conf", help = "Input system definition file path")]
+        filepath: String,
+    },
+
+    /// resume paused execution context
+    #[clap(arg_required_else_help = false)]
+    Resume {
+        #[arg(help = "ID of Execution context")]
+        identifier: String,
+
+        #[arg(short = 'f', default_value = "./juiz.conf", help = "Input system definition file path")]
+        filepath: String,
+    },
+
+    /// reset execution context in ERROR state to STOPPED
+    #[clap(arg_required_else_help = false)]
+    Reset {
+        #[arg(help = "ID of Execution context")]
+        identifier: String,
+
+        #[arg(short = 'f', default_value = "./juiz.conf", help = "Input system definition file path")]
+        filepath: String,
+    },
+
+    /// advance step-mode execution context by N cycles and print outputs
+    #[clap(arg_required_else_help = false)]
+    Step {
+        #[arg(help = "ID of Execution context")]
+        identifier: String,
+
+        #[arg(short = 'n', default_value = "1", help = "Number of cycles")]
+        num_steps: usize,
+
+        #[arg(short = 'f', default_value = "./juiz.conf", help = "Input system definition file path")]
+        filepath: String,
+    },
 }
 
 
@@ -73,6 +116,37 @@ pub(crate) fn on_ec_inner(_manifest: Value, working_dir: &Path, subcommand: EcSu
                 
             }) 
         },
+
+        EcSubCommands::Pause { identifier, filepath } => {
+            log::trace!("ec pause command is selected.");
+            on_ec_operation(working_dir, args, filepath, identifier, |cb, id| cb.ec_pause(id))
+        },
+
+        EcSubCommands::Resume { identifier, filepath } => {
+            log::trace!("ec resume command is selected.");
+            on_ec_operation(working_dir, args, filepath, identifier, |cb, id| cb.ec_resume(id))
+        },
+
+        EcSubCommands::Reset { identifier, filepath } => {
+            log::trace!("ec reset command is selected.");
+            on_ec_operation(working_dir, args, filepath, identifier, |cb, id| cb.ec_reset(id))
+        },
+
+        EcSubCommands::Step { identifier, num_steps, filepath } => {
+            log::trace!("ec step command is selected.");
+            let manifest2 = yaml_conf_load(filepath.clone())?;
+            let server = args.server;
+            System::new(manifest2)?
+                .set_working_dir(working_dir)
+                .start_http_broker(args.start_http_broker)
+                .setup()?
+                .add_systemproxy_by_id(Some(server.clone()))?
+                .run_and_do_once( |system| { 
+                    let steps = system.core_broker().lock_mut()?.ec_step(&identifier, num_steps)?;
+                    println!("{steps}");
+                    Ok(())
+            }) 
+        },
     }
 }
 
@@ -98,4 +172,21 @@ fn on_ec_start(system: &mut System, _server: Option<String>, id: String) -> Juiz
         Err(e) => println!("Error: {e:?}"),
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// 状態を変える操作を一つ実行して、変化後の状態を表示する
+fn on_ec_operation(working_dir: &Path, args: Args, filepath: String, id: String, operation: fn(&mut CoreBroker, &Identifier) -> JuizResult<Value>) -> JuizResult<()> {
+    let manifest2 = yaml_conf_load(filepath)?;
+    let server = args.server;
+    System::new(manifest2)?
+        .set_working_dir(working_dir)
+        .start_http_broker(args.start_http_broker)
+        .setup()?
+        .add_systemproxy_by_id(Some(server))?
+        .run_and_do_once( |system| { 
+            let mut core_broker = system.core_broker().lock_mut()?;
+            operation(&mut core_broker, &id)?;
+            println!("{}", core_broker.ec_get_state(&id)?);
+            Ok(())
+        })
+}
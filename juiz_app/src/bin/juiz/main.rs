@@ -8,11 +8,15 @@ mod default_juiz_conf;
 mod container;
 mod container_process;
 mod connection;
+mod recording;
+mod discovery;
 
 use std::path::PathBuf;
 use std::time::Duration;
 
 use connection::{ConnectionSubCommands, on_connection};
+use recording::{on_play, on_record, PlayArgs, RecordArgs};
+use discovery::{on_discovery, with_discovery, DiscoveryArgs};
 use execution_context::{on_execution_context, EcSubCommands};
 use container::{on_container, ContSubCommands};
 use container_process::{on_container_process, ContProcSubCommands};
@@ -59,6 +63,15 @@ struct Args {
     #[arg(short = 's', long = "server", default_value = "http://localhost:8000", help = "Host of server (ex., http://localhost:8000)")]
     server: String,
 
+    #[arg(long = "token", help = "Token for the HTTP broker of --server. If omitted, environment variable JUIZ_TOKEN is used.")]
+    token: Option<String>,
+
+    #[arg(long = "attach", help = "Attach a juiz system found on the network by its name as subsystem.")]
+    attach: Option<String>,
+
+    #[arg(long = "attach_timeout", default_value = "5.0", help = "Wait this duration [sec] for the system passed with --attach to be discovered.")]
+    attach_timeout: f64,
+
     #[arg(long = "process", help = "ProcessModule loader mode.")]
     process: Option<String>,
 
@@ -138,6 +151,18 @@ enum SubCommands {
         #[clap(subcommand)]
         subcommand: ConnectionSubCommands
     },
+
+    /// Record topics and connections to a file
+    #[clap(arg_required_else_help = false)]
+    Record(RecordArgs),
+
+    /// Play a recorded file into topics and connections
+    #[clap(arg_required_else_help = true)]
+    Play(PlayArgs),
+
+    /// List juiz systems discovered on the network
+    #[clap(arg_required_else_help = false)]
+    Discovery(DiscoveryArgs),
 }
 
 
@@ -431,6 +456,21 @@ fn create_container_process_by_cid_and_pm(system: &mut System, cid: Identifier,
     system.core_broker().lock_mut()?.worker_mut().any_process_from_identifier(&pid.unwrap(), true)
 }
 
+/// --serverのBrokerProxyをトークン付きでマニフェストの"broker_proxies"に足す
+fn with_server_token(mut manifest: Value, server: &str, token: Option<String>) -> JuizResult<Value> {
+    let token = match token.or_else(|| std::env::var("JUIZ_TOKEN").ok()) {
+        Some(token) => token,
+        None => return Ok(manifest),
+    };
+    let mut proxy_manifest = IdentifierStruct::new_broker_id(server.to_owned())?.to_broker_manifest();
+    proxy_manifest["token"] = jvalue!(token);
+    match manifest.get_mut("broker_proxies").and_then(|v| v.as_array_mut()) {
+        Some(proxies) => proxies.push(proxy_manifest),
+        None => manifest["broker_proxies"] = jvalue!([proxy_manifest]),
+    }
+    Ok(manifest)
+}
+
 fn main() -> () {
     env_logger::init();
     match do_once() {
@@ -442,14 +482,18 @@ fn main() -> () {
 fn do_once() -> JuizResult<()>{
     log::trace!("main::do_once called");
     let args = Args::parse();
-    let manifest = yaml_conf_load(args.filepath.clone())?;
+    let manifest = with_server_token(yaml_conf_load(args.filepath.clone())?, args.server.as_str(), args.token.clone())?;
     let flag_start = if args.daemonize { true } else { args.start_http_broker };
     let manifest_filepath = PathBuf::from(args.filepath.as_str().to_string());
     let working_dir = manifest_filepath.parent().unwrap();
     let server = args.server.clone();
     let ratio = args.ratio;
+    let attach = args.attach.clone();
+    let attach_timeout = Duration::from_secs_f64(args.attach_timeout);
     // サブコマンドが指定されていない場合は単純に起動。
     if args.subcommand.is_none() {
+        // 名前でつなぐ相手を探すためにdiscoveryを有効にする
+        let manifest = if attach.is_some() { with_discovery(manifest, false)? } else { manifest };
         //let daemonize = ratio.is_some() || args.daemonize;
         if args.daemonize || ratio.is_some() {
             return System::new(manifest)?
@@ -457,6 +501,7 @@ fn do_once() -> JuizResult<()>{
                 .start_http_broker(flag_start)
                 .setup()?
                 //.add_subsystem_by_id(Some(server))?
+                .add_subsystem_by_peer_name(attach, attach_timeout)?
                 .run_and_do(|system| { 
                     do_task(system, args)
                 });
@@ -466,6 +511,7 @@ fn do_once() -> JuizResult<()>{
                 .start_http_broker(flag_start)
                 .setup()?
                 //.add_subsystem_by_id(Some(server))?
+                .add_subsystem_by_peer_name(attach, attach_timeout)?
                 .run_and_do_once(|system| {
                     do_task_once(system, args)
                 });
@@ -492,6 +538,15 @@ fn do_once() -> JuizResult<()>{
         SubCommands::Connection { subcommand } => {
             on_connection(manifest, working_dir, subcommand, args)
         },
+        SubCommands::Record(record_args) => {
+            on_record(manifest, working_dir, record_args, args)
+        },
+        SubCommands::Play(play_args) => {
+            on_play(manifest, working_dir, play_args, args)
+        },
+        SubCommands::Discovery(discovery_args) => {
+            on_discovery(manifest, working_dir, discovery_args, args)
+        },
         /* _ => {
             return Ok(())
         } */
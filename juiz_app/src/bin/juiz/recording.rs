@@ -0,0 +1,159 @@
+//
+// juiz record -o log.jbag --topic image,pose
+// juiz play log.jbag --rate 2.0
+
+
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use juiz_core::{anyhow, log};
+
+use juiz_core::prelude::*;
+use juiz_core::utils::manifest_util::manifest_merge;
+use clap::Parser;
+
+use crate::Args;
+
+#[derive(Debug, Parser, Clone)]
+pub(crate) struct RecordArgs {
+    #[arg(short = 'o', long = "output", default_value = "./juiz.jbag", help = "Output record file path")]
+    output: String,
+
+    #[arg(short = 't', long = "topic", value_delimiter = ',', help = "Names of topics to record")]
+    topics: Vec<String>,
+
+    #[arg(long = "connection", value_delimiter = ',', help = "IDs of connections to record")]
+    connections: Vec<String>,
+
+    #[arg(long = "duration", help = "Stop recording after this duration [sec]. If not set, record until Ctrl-C.")]
+    duration: Option<f64>,
+}
+
+#[derive(Debug, Parser, Clone)]
+pub(crate) struct PlayArgs {
+    #[arg(help = "Record file path")]
+    file: String,
+
+    #[arg(long = "rate", default_value = "1.0", help = "Playback rate. 2.0 plays twice as fast as recorded.")]
+    rate: f64,
+
+    #[arg(long = "step", help = "Play one record each time Enter key is pressed.")]
+    step: bool,
+
+    #[arg(long = "loop", help = "Repeat playback until Ctrl-C.")]
+    looping: bool,
+
+    #[arg(long = "remap", value_delimiter = ',', help = "Play a channel into another topic or connection (ex., --remap image=image_replay)")]
+    remap: Vec<String>,
+}
+
+const CLI_RECORDER_NAME: &str = "juiz_record";
+const CLI_PLAYER_NAME: &str = "juiz_play";
+
+/// コマンドを実行したディレクトリからのパスにする
+fn absolute_path(file: &str) -> JuizResult<String> {
+    let path = PathBuf::from(file);
+    let path = if path.is_relative() { std::env::current_dir()?.join(path) } else { path };
+    Ok(path.to_string_lossy().into_owned())
+}
+
+pub(crate) fn on_record(manifest: Value, working_dir: &Path, record_args: RecordArgs, args: Args) -> JuizResult<()> {
+    match on_record_inner(manifest, working_dir, record_args, args) {
+        Ok(_) => return Ok(()),
+        Err(e) => println!("Error: {e:?}")
+    };
+    Ok(())
+}
+
+fn on_record_inner(manifest: Value, working_dir: &Path, record_args: RecordArgs, args: Args) -> JuizResult<()> {
+    log::trace!("record command is selected. args={record_args:?}");
+    let output = absolute_path(record_args.output.as_str())?;
+    let manifest = manifest_merge(manifest, &jvalue!({
+        "recorders": [{
+            "name": CLI_RECORDER_NAME,
+            "file": output,
+            "topics": record_args.topics,
+            "connections": record_args.connections,
+        }]
+    }))?;
+    let mut system = System::new(manifest)?
+        .set_working_dir(working_dir)
+        .start_http_broker(args.start_http_broker)
+        .setup()?;
+    println!("Recording to {output}. Press Ctrl-C to stop.");
+    match record_args.duration {
+        Some(duration) => system.run_and_do_once(|system| {
+            std::thread::sleep(Duration::from_secs_f64(duration));
+            print_recorder_status(system)
+        }),
+        None => system.run(),
+    }
+}
+
+fn print_recorder_status(system: &System) -> JuizResult<()> {
+    let recorder = system.core_broker().lock()?.worker().store().recorders.get(CLI_RECORDER_NAME).cloned();
+    if let Some(recorder) = recorder {
+        println!("{}", juiz_lock(&recorder)?.profile_full()?);
+    }
+    Ok(())
+}
+
+pub(crate) fn on_play(manifest: Value, working_dir: &Path, play_args: PlayArgs, args: Args) -> JuizResult<()> {
+    match on_play_inner(manifest, working_dir, play_args, args) {
+        Ok(_) => return Ok(()),
+        Err(e) => println!("Error: {e:?}")
+    };
+    Ok(())
+}
+
+fn on_play_inner(manifest: Value, working_dir: &Path, play_args: PlayArgs, args: Args) -> JuizResult<()> {
+    log::trace!("play command is selected. args={play_args:?}");
+    let speed: Value = if play_args.step { jvalue!("step") } else { jvalue!(play_args.rate) };
+    let mut remap = jvalue!({});
+    for r in play_args.remap.iter() {
+        match r.split_once('=') {
+            Some((from, to)) => { remap.as_object_mut().unwrap().insert(from.to_owned(), jvalue!(to)); },
+            None => return Err(anyhow::anyhow!(JuizError::InvalidArgumentError{message: format!("--remap must be 'from=to' but '{r}'.")})),
+        }
+    }
+    let manifest = manifest_merge(manifest, &jvalue!({
+        "players": [{
+            "name": CLI_PLAYER_NAME,
+            "file": absolute_path(play_args.file.as_str())?,
+            "speed": speed,
+            "loop": play_args.looping,
+            "auto_start": false,
+            "remap": remap,
+        }]
+    }))?;
+    let mut system = System::new(manifest)?
+        .set_working_dir(working_dir)
+        .start_http_broker(args.start_http_broker)
+        .setup()?;
+    let player = system.core_broker().lock()?.worker().store().players.get(CLI_PLAYER_NAME).cloned()
+        .ok_or_else(|| anyhow::anyhow!(JuizError::CanNotFindError{target: format!("Player({CLI_PLAYER_NAME})")}))?;
+    if play_args.step {
+        system.run_and_do_once(|_system| {
+            println!("Press Enter to play next record. Type 'q' to quit.");
+            for line in std::io::stdin().lock().lines() {
+                if line?.trim() == "q" || !juiz_lock(&player)?.step()? {
+                    break;
+                }
+                println!("{} records played.", juiz_lock(&player)?.num_played());
+            }
+            Ok(())
+        })
+    } else if play_args.looping {
+        system.run_and_do(|_system| {
+            let _ = Player::start(&player)?;
+            Ok(())
+        })
+    } else {
+        system.run_and_do_once(|_system| {
+            let num_played = Player::start(&player)?.join()
+                .map_err(|_| anyhow::anyhow!(JuizError::InvalidValueError{message: "Player thread panicked.".to_owned()}))??;
+            println!("{num_played} records played.");
+            Ok(())
+        })
+    }
+}
@@ -4,7 +4,7 @@ use uuid::Uuid;
 
 use crate::prelude::*;
 
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 
 
@@ -55,6 +55,11 @@ pub trait SystemBrokerProxy {
     /// 
     fn system_load_component(&mut self, language: String, filepath: String) -> JuizResult<Value>;
 
+    /// ネットワーク上で見つけたjuizシステムのリストを取得する
+    /// 
+    /// discoveryが設定されていなければ空のリストを返す
+    fn system_discovered_peers(&self) -> JuizResult<Value>;
+
 }
 
 pub trait ProcessBrokerProxy {
@@ -86,7 +91,14 @@ pub trait ProcessBrokerProxy {
     /// プロセスをExecuteする
     /// 
     /// * id: プロセスのID
-    fn process_execute(&self, id: &Identifier) -> JuizResult<CapsulePtr>;
+    fn process_execute(&self, id: &Identifier) -> JuizResult<CapsulePtr> {
+        self.process_execute_with_timeout(id, None)
+    }
+
+    /// timeoutまでに終わらなければRemoteCallTimeoutErrorを返すExecute
+    ///
+    /// process_callではargsのパラメータ"timeout"で同じことができる。Noneならブローカープロキシの既定のタイムアウトを使う
+    fn process_execute_with_timeout(&self, id: &Identifier, timeout: Option<Duration>) -> JuizResult<CapsulePtr>;
 
     fn process_push_by(&self, id: &Identifier, arg_name: String, value: CapsulePtr) -> JuizResult<CapsulePtr>;
 
@@ -157,6 +169,17 @@ pub trait ExecutionContextBrokerProxy {
     fn ec_start(&mut self, id: &Identifier) -> JuizResult<Value>;
 
     fn ec_stop(&mut self, id: &Identifier) -> JuizResult<Value>;
+
+    fn ec_pause(&mut self, id: &Identifier) -> JuizResult<Value>;
+
+    fn ec_resume(&mut self, id: &Identifier) -> JuizResult<Value>;
+
+    fn ec_reset(&mut self, id: &Identifier) -> JuizResult<Value>;
+
+    /// StepECをnサイクル進める
+    ///
+    /// サイクルごとの{step, time, outputs}の配列を返す。outputsはバインドしたプロセスの識別子と出力の組
+    fn ec_step(&mut self, id: &Identifier, n: usize) -> JuizResult<Value>;
 }
 pub trait BrokerBrokerProxy {
     
@@ -232,4 +255,13 @@ pub trait BrokerProxy : Send + JuizObject + SystemBrokerProxy + ProcessBrokerPro
         }
         self.container_process_call(id, args)
     }
+
+    fn any_process_execute(&self, id: &Identifier) -> JuizResult<CapsulePtr> {
+        log::info!("BrokerProxy::any_process_execute({id}) called");
+        let id_struct = IdentifierStruct::try_from(id.clone())?;
+        if id_struct.class_name == "Process" {
+            return self.process_execute(id)
+        }
+        self.container_process_execute(id)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,126 @@
+//! プロセスの呼び出しのタイムアウト
+//!
+//! 呼び出しごとのタイムアウトはCapsuleMapのパラメータ"timeout"で渡し、ブローカーをまたいでも引き継ぐ。
+//! 省略するとブローカープロキシのマニフェストの"timeout"を使う。
+
+use std::{collections::HashMap, sync::{mpsc, Arc, Mutex, MutexGuard}, time::Duration};
+use juiz_sdk::anyhow::anyhow;
+
+use crate::prelude::*;
+
+pub(crate) fn timeout_error(target: &str, timeout: Duration) -> juiz_sdk::anyhow::Error {
+    anyhow!(JuizError::RemoteCallTimeoutError{target: target.to_owned(), timeout_sec: timeout.as_secs_f64()})
+}
+
+pub(crate) fn is_timeout_error(e: &juiz_sdk::anyhow::Error) -> bool {
+    matches!(e.downcast_ref::<JuizError>(), Some(JuizError::RemoteCallTimeoutError{..}))
+}
+
+/// ブローカープロキシのマニフェストの"timeout" [sec]
+pub(crate) fn default_timeout_from_manifest(manifest: &Value) -> JuizResult<Option<Duration>> {
+    match obj_get_f64(manifest, TIMEOUT_PARAM_KEY) {
+        Ok(sec) => Duration::try_from_secs_f64(sec).map(Some)
+            .map_err(|e| anyhow!(JuizError::InvalidSettingError{message: format!("BrokerProxy timeout ({sec}) is invalid. {e}")})),
+        Err(_) => Ok(None),
+    }
+}
+
+/// CRUDBrokerProxyに渡すパラメータのタイムアウト
+pub(crate) fn timeout_from_param(param: &HashMap<String, String>) -> JuizResult<Option<Duration>> {
+    param.get(TIMEOUT_PARAM_KEY).map(|v| parse_timeout(v)).transpose()
+}
+
+/// 時間切れで打ち切ったが、まだ走っている呼び出しの数 (呼び出し先ごと)
+///
+/// 呼び出し側が持ち、クローンしたものとは共有する
+#[derive(Clone, Default)]
+pub(crate) struct AbandonedCalls {
+    counts: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+/// 呼び出しのスレッドの状態。AbandonedCallsのロックの中で変える
+#[derive(Clone, Copy, PartialEq)]
+enum CallState {
+    Running,
+    Finished,
+    Abandoned,
+}
+
+impl AbandonedCalls {
+
+    fn counts(&self) -> MutexGuard<'_, HashMap<String, usize>> {
+        self.counts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_running(&self, target: &str) -> bool {
+        self.counts().contains_key(target)
+    }
+
+    /// 走っている呼び出しを打ち切ったことにする。すでに終わっていればfalse
+    fn abandon(&self, target: &str, state: &Mutex<CallState>) -> bool {
+        let mut counts = self.counts();
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        if *state == CallState::Finished {
+            return false;
+        }
+        *state = CallState::Abandoned;
+        *counts.entry(target.to_owned()).or_default() += 1;
+        true
+    }
+
+    /// 呼び出しのスレッドが終わった。打ち切られていたなら数を戻す
+    fn finish(&self, target: &str, state: &Mutex<CallState>) {
+        let mut counts = self.counts();
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        if *state == CallState::Abandoned {
+            if let Some(count) = counts.get_mut(target) {
+                *count -= 1;
+                if *count == 0 {
+                    counts.remove(target);
+                }
+            }
+        }
+        *state = CallState::Finished;
+    }
+}
+
+/// funcを別のスレッドで走らせ、timeoutまでに終わらなければRemoteCallTimeoutErrorを返す
+///
+/// 打ち切ったあともfuncは最後まで走り、その結果は捨てる。funcはふつうプロセスのロックを持ったまま走るので、
+/// 打ち切った呼び出しが終わるまで同じ呼び出し先への呼び出しはスレッドを積み上げずにRemoteCallStillRunningErrorで断る。
+/// timeoutがNoneならこのスレッドで走らせる。
+pub(crate) fn call_with_timeout<T: Send + 'static>(abandoned: &AbandonedCalls, target: &str, timeout: Option<Duration>, func: impl FnOnce() -> JuizResult<T> + Send + 'static) -> JuizResult<T> {
+    if abandoned.is_running(target) {
+        log::warn!("call_with_timeout({target}) refused. The previous call that timed out is still running.");
+        return Err(anyhow!(JuizError::RemoteCallStillRunningError{target: target.to_owned()}));
+    }
+    let Some(timeout) = timeout else {
+        return func();
+    };
+    let (sender, receiver) = mpsc::channel();
+    let state = Arc::new(Mutex::new(CallState::Running));
+    let thread_state = state.clone();
+    let thread_abandoned = abandoned.clone();
+    let thread_target = target.to_owned();
+    std::thread::Builder::new().name(format!("call_{target}")).spawn(move || {
+        let _ = sender.send(func());
+        thread_abandoned.finish(&thread_target, &thread_state);
+    })?;
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            // 時間切れと同時に終わったなら、打ち切らずに結果を使う
+            if !abandoned.abandon(target, &state) {
+                if let Ok(result) = receiver.try_recv() {
+                    return result;
+                }
+            }
+            log::error!("call_with_timeout({target}) timed out after {timeout:?}.");
+            Err(timeout_error(target, timeout))
+        },
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            log::error!("call_with_timeout({target}) failed. The calling thread panicked.");
+            Err(anyhow!(JuizError::RemoteCallPanickedError{target: target.to_owned()}))
+        },
+    }
+}
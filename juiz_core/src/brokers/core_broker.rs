@@ -1,6 +1,7 @@
 
 
 use std::path::PathBuf;
+use std::time::Duration;
 use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use juiz_sdk::anyhow::{anyhow, Context};
 use juiz_sdk::connections::ConnectionManifest;
@@ -10,6 +11,7 @@ use crate::prelude::*;
 
 
 use crate::brokers::BrokerProxy;
+use crate::brokers::call_timeout::{call_with_timeout, AbandonedCalls};
 use crate::brokers::broker_proxy::{
     BrokerBrokerProxy, 
     ConnectionBrokerProxy, 
@@ -22,7 +24,7 @@ use crate::brokers::broker_proxy::{
 
 
 use crate::core::CoreWorker;
-use crate::core::SubSystemProxy;
+use crate::core::{SubSystemHeartbeat, SubSystemProxy, SystemDiscovery};
 use crate::core::SystemStorePtr;
 
 #[allow(unused)]
@@ -34,6 +36,10 @@ pub struct CoreBroker {
     master_system_proxy: Option<SubSystemProxy>,
     subsystem_proxies: Vec<SubSystemProxy>,
     system_store: SystemStorePtr,
+    heartbeat: SubSystemHeartbeat,
+    discovery: Option<SystemDiscovery>,
+    /// 時間切れで打ち切ったプロセスの呼び出し
+    abandoned_calls: AbandonedCalls,
 }
 
 #[derive(Clone)]
@@ -55,6 +61,16 @@ impl CoreBrokerPtr {
     pub fn lock_mut(&self) -> JuizResult<RwLockWriteGuard<CoreBroker>> {
         self.ptr.write().or_else(|_|{ Err(anyhow!(JuizError::ObjectLockError{target:"CoreBrokerPtr".to_owned()})) })
     }
+
+    /// マスターシステムとサブシステムにハートビートを送る
+    pub fn heartbeat(&self) -> JuizResult<()> {
+        crate::core::heartbeat(self)
+    }
+
+    /// 前のハートビートからintervalが過ぎていればハートビートを送る
+    pub fn heartbeat_if_due(&self) -> JuizResult<()> {
+        crate::core::heartbeat_if_due(self)
+    }
 }
 
 impl CoreBroker {
@@ -67,7 +83,10 @@ impl CoreBroker {
             manifest: check_corebroker_manifest(manifest)?,
             master_system_proxy: None, 
             subsystem_proxies: Vec::new(),
-            system_store
+            system_store,
+            heartbeat: SubSystemHeartbeat::default(),
+            discovery: None,
+            abandoned_calls: AbandonedCalls::default(),
         })
     }
 
@@ -87,6 +106,27 @@ impl CoreBroker {
         &mut self.system_store
     }
 
+    pub fn heartbeat(&self) -> &SubSystemHeartbeat {
+        &self.heartbeat
+    }
+
+    pub fn heartbeat_mut(&mut self) -> &mut SubSystemHeartbeat {
+        &mut self.heartbeat
+    }
+
+    pub fn discovery(&self) -> Option<&SystemDiscovery> {
+        self.discovery.as_ref()
+    }
+
+    pub fn discovery_mut(&mut self) -> &mut Option<SystemDiscovery> {
+        &mut self.discovery
+    }
+
+    /// ハートビートを送る相手 (マスターシステムとサブシステム)
+    pub(crate) fn peers(&self) -> Vec<SubSystemProxy> {
+        self.master_system_proxy.iter().chain(self.subsystem_proxies.iter()).cloned().collect()
+    }
+
 
 
     fn find_subsystem_by_uuid(&self, uuid: Uuid) -> Option<SubSystemProxy> {
@@ -128,10 +168,12 @@ impl JuizObject for CoreBroker {
             "core_store" : self.worker().store().profile_full()?,
         }))?;
         let master_profile = if let Some(system) = self.master_system_proxy.as_ref() { system.profile_full()? } else {  Value::Null };
+        let discovery_profile = if let Some(discovery) = self.discovery.as_ref() { discovery.profile_full()? } else { Value::Null };
         Ok(obj_merge(v, &jvalue!({
             "system_store" : self.system_store.profile_full()?,
             "mastersystem": master_profile,
-            "subsystems": self.subsystem_proxies.iter().map(|p|{p.profile_full().unwrap()}).collect::<Vec<Value>>()
+            "discovery": discovery_profile,
+            "subsystems": self.subsystem_proxies.iter().map(|p|{p.profile_full()}).collect::<JuizResult<Vec<Value>>>()?
         }))?.into())
     }
 }
@@ -211,15 +253,22 @@ impl SystemBrokerProxy for CoreBroker {
         let uuid_str = uuid_value.as_str().unwrap();
         let uuid: Uuid = Uuid::parse_str(uuid_str).unwrap();
         // ここですでにuuidが登録されているかを確認する。
-        for subsystem_proxy in self.subsystem_proxies.iter() {
-            if &uuid == subsystem_proxy.uuid() {
-                log::error!("system_add_subsystem failed. Subsystem(uuid={uuid}) has already added.");
-                return Err(anyhow!(JuizError::ObjectAlreadyRegisteredError{message: format!("system_add_subsystem failed. Subsystem(uuid={uuid}) has already added.")}));
+        // 同じUUIDなら再起動した相手が登録し直しに来たので、次のハートビートでトピックと接続を張り直す。
+        // ここで張り直すと、相手がsystem_add_mastersystem()の途中でこちらを待っているので止まってしまう。
+        let rejoined = self.subsystem_proxies.iter().find(|ssp| ssp.uuid() == &uuid).cloned();
+        if let Some(subsystem_proxy) = rejoined.as_ref() {
+            log::info!("Subsystem(uuid={uuid}) has rejoined.");
+            subsystem_proxy.request_restore()?;
+            if confirmation_request {
+                return Ok(profile);
             }
         }
         // さらにサブシステムのサブシステムまでこれから登録するUUIDがあるかみようとするけど、これは無意味かも。
         // ループ構造ができないようにする責任は設計者にある
         for subsystem_proxy in self.subsystem_proxies.iter() {
+            if !subsystem_proxy.is_alive()? {
+                continue;
+            }
             let ss = subsystem_proxy.subsystems()?;
             log::warn!("WARNING: SUBSYSTEM's SUBSYSTEM mining.... But this is useless...");
             log::warn!("value is {ss:}");
@@ -227,8 +276,13 @@ impl SystemBrokerProxy for CoreBroker {
 
         // 自分のUUID
         let my_uuid = self.system_store.uuid()?;
-        self.worker_mut().store_mut().broker_proxies.register(bp.clone())?; // 作った相手方のBrokerProxyを自分に登録しておく
-        let subsystem_proxy = SubSystemProxy::new(uuid, bp.clone())?;
+        let subsystem_proxy = match rejoined.as_ref() {
+            Some(subsystem_proxy) => subsystem_proxy.clone(),
+            None => {
+                self.worker_mut().store_mut().broker_proxies.register(bp.clone())?; // 作った相手方のBrokerProxyを自分に登録しておく
+                SubSystemProxy::new(uuid, bp.clone())?
+            }
+        };
         let ssprofile = juiz_lock(&subsystem_proxy.broker_proxy())?.profile_full().context("subsystem_proxy.broker_proxy().profile_full() in system_add_subsystem")?;
         // 相手方がどのIPアドレスを辿ってきたかがaccessed_broker_idでわかる
         let _accessed_broker_id = match profile.as_object() {
@@ -281,7 +335,9 @@ impl SystemBrokerProxy for CoreBroker {
             })?;
         }
         // 最後にサブシステムのProxyを登録しておく。
-        self.subsystem_proxies.push(subsystem_proxy);
+        if rejoined.is_none() {
+            self.subsystem_proxies.push(subsystem_proxy);
+        }
         Ok(profile)
     }
     
@@ -372,6 +428,13 @@ impl SystemBrokerProxy for CoreBroker {
         self.worker_mut().load_component(language, filepath)
     }
 
+    fn system_discovered_peers(&self) -> JuizResult<Value> {
+        match self.discovery.as_ref() {
+            Some(discovery) => discovery.peers(),
+            None => Ok(jvalue!([])),
+        }
+    }
+
 }
 
 
@@ -379,19 +442,24 @@ impl ProcessBrokerProxy for CoreBroker {
     fn process_call(&self, id: &Identifier, args: CapsuleMap) -> JuizResult<CapsulePtr> {
         let idstruct = IdentifierStruct::try_from(id.clone())?;
         if idstruct.broker_type_name == "core" {
-            self.worker().store().processes.get(id)?.lock()?.call(args)
+            // 別のブローカーのプロセスならタイムアウトはargsのままプロキシに渡る
+            let p = self.worker().store().processes.get(id)?.clone();
+            call_with_timeout(&self.abandoned_calls, id, args.timeout()?, move || p.lock()?.call(args))
         } else {
             self.worker().process_proxy_from_identifier(id, true)?.lock()?.call(args)
         }
     }
 
-    fn process_execute(&self, id: &Identifier) -> JuizResult<CapsulePtr> {
-        log::trace!("CoreBroker::process_execute({id:}) called");
+    fn process_execute_with_timeout(&self, id: &Identifier, timeout: Option<Duration>) -> JuizResult<CapsulePtr> {
+        log::trace!("CoreBroker::process_execute_with_timeout({id:}, {timeout:?}) called");
         let idstruct = IdentifierStruct::try_from(id.clone())?;
         if idstruct.broker_type_name == "core" {
-            self.worker().store().processes.get(id)?.lock()?.execute()
+            let p = self.worker().store().processes.get(id)?.clone();
+            call_with_timeout(&self.abandoned_calls, id, timeout, move || p.lock()?.execute())
         } else {
-            self.worker().process_proxy_from_identifier(id, true)?.lock()?.execute()
+            let broker_proxy = self.worker().broker_proxy(&idstruct.broker_type_name, &idstruct.broker_name, true)?;
+            let result = juiz_lock(&broker_proxy)?.process_execute_with_timeout(id, timeout);
+            result
         }
     }
 
@@ -650,7 +718,7 @@ impl TopicBrokerProxy for CoreBroker {
         // Systemに対するProxyを新しく生成したTopicに登録してデータがリレーされるようにする
         if let Some(msp) = self.master_system_proxy.clone() {
             // 呼び出し元のUUIDがマスターと一緒でなければマスターを検査
-            if opt_system_uuid.is_some() && (msp.uuid() != &opt_system_uuid.unwrap()) {
+            if opt_system_uuid.is_some() && (msp.uuid() != &opt_system_uuid.unwrap()) && msp.is_alive()? {
                 let result_value = juiz_lock(&msp.broker_proxy())?.topic_request_subscribe(name, Some(my_uuid))?;
                 if obj_get_bool(&result_value, "subscribe")? {
                     // システムが購読を希望していたら、自分のlocalにTopicPtrを作り、それと相手システムを接続する
@@ -663,7 +731,7 @@ impl TopicBrokerProxy for CoreBroker {
         }
         for ssp in self.subsystem_proxies.clone().iter() {
             // 呼び出し元のUUIDがサブシステムと一緒でなければ検査
-            if opt_system_uuid.is_some() && (ssp.uuid() != &opt_system_uuid.unwrap()) {
+            if opt_system_uuid.is_some() && (ssp.uuid() != &opt_system_uuid.unwrap()) && ssp.is_alive()? {
                 let result_value = juiz_lock(&ssp.broker_proxy())?.topic_request_subscribe(name, Some(my_uuid))?;
                 if obj_get_bool(&result_value, "subscribe")? {
                     log::trace!("found subscriber of topic(name={name}) in subsystem");
@@ -710,7 +778,7 @@ impl TopicBrokerProxy for CoreBroker {
         // Systemに対するProxyを新しく生成したTopicに登録してデータがリレーされるようにする
         if let Some(msp) = self.master_system_proxy.clone() {
             // 呼び出し元のUUIDがマスターと一緒でなければマスターを検査
-            if opt_system_uuid.is_some() && (msp.uuid() != &opt_system_uuid.unwrap()) {
+            if opt_system_uuid.is_some() && (msp.uuid() != &opt_system_uuid.unwrap()) && msp.is_alive()? {
                 let uuid = msp.uuid();
                 let result_value = juiz_lock(&msp.broker_proxy())?.topic_request_publish(name, Some(my_uuid))?;
                 if obj_get_bool(&result_value, "publish")? {
@@ -726,7 +794,7 @@ impl TopicBrokerProxy for CoreBroker {
         }
         for ssp in self.subsystem_proxies.clone().iter() {
             // 呼び出し元のUUIDがサブシステムと一緒でなければ検査
-            if opt_system_uuid.is_some() && (ssp.uuid() != &opt_system_uuid.unwrap()) {
+            if opt_system_uuid.is_some() && (ssp.uuid() != &opt_system_uuid.unwrap()) && ssp.is_alive()? {
                 let result_value = juiz_lock(&ssp.broker_proxy())?.topic_request_publish(name, Some(my_uuid))?;
                 if obj_get_bool(&result_value, "publish")? {
                     let uuid = ssp.uuid();
@@ -781,14 +849,32 @@ impl ExecutionContextBrokerProxy for CoreBroker {
     fn ec_stop(&mut self, id: &Identifier) -> JuizResult<Value> {
         Ok(juiz_lock(&self.worker().store().ecs.get(id)?).with_context(||format!("locking ec(id={id:}) in CoreBroker::ec_get_state() function"))?.stop()?.into())
     }
+
+    fn ec_pause(&mut self, id: &Identifier) -> JuizResult<Value> {
+        juiz_lock(&self.worker().store().ecs.get(id)?).with_context(||format!("locking ec(id={id:}) in CoreBroker::ec_pause() function"))?.pause()
+    }
+
+    fn ec_resume(&mut self, id: &Identifier) -> JuizResult<Value> {
+        juiz_lock(&self.worker().store().ecs.get(id)?).with_context(||format!("locking ec(id={id:}) in CoreBroker::ec_resume() function"))?.resume()
+    }
+
+    fn ec_reset(&mut self, id: &Identifier) -> JuizResult<Value> {
+        juiz_lock(&self.worker().store().ecs.get(id)?).with_context(||format!("locking ec(id={id:}) in CoreBroker::ec_reset() function"))?.reset()
+    }
+
+    fn ec_step(&mut self, id: &Identifier, n: usize) -> JuizResult<Value> {
+        juiz_lock(&self.worker().store().ecs.get(id)?).with_context(||format!("locking ec(id={id:}) in CoreBroker::ec_step() function"))?.step(n)
+    }
     
     fn ec_create(&mut self, manifest: &Value) -> JuizResult<Value> {
         let ec = self.worker_mut().create_ec_ref(manifest.clone())?;
         juiz_lock(&ec.clone())?.profile_full()
     }
     
-    fn ec_destroy(&mut self, _identifier: &Identifier) -> JuizResult<Value> {
-        todo!()
+    fn ec_destroy(&mut self, identifier: &Identifier) -> JuizResult<Value> {
+        let ec = self.worker_mut().destroy_ec_ref(identifier)?;
+        let prof = juiz_lock(&ec)?.profile_full()?;
+        Ok(prof)
     }
 
 }
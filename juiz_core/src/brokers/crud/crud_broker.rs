@@ -106,6 +106,10 @@ impl CRUDBroker {
         self.manifest.clone()
     }
 
+    pub(crate) fn core_broker(&self) -> &CoreBrokerPtr {
+        &self.core_broker
+    }
+
     pub fn update_broker_name(&mut self, name: &str) -> () {
         log::trace!("update_broker_name({name}) called");
         self._name = name.to_owned();
@@ -154,11 +158,24 @@ impl CRUDBroker {
 
 
 
-    pub fn on_value_request(&self, val: Value, opt_requesting_host_addr: Option<SocketAddr>) -> JuizResult<CapsulePtr> {
-        let mut cp: CapsuleMap = val.try_into()?;
-        let class_name = cp.get_param("class_name").unwrap().clone();
-        let method_name = cp.get_param("method_name").unwrap().clone();
-        let function_name = cp.get_param("function_name").unwrap().clone();
+    /// 値で届いた要求を処理する
+    ///
+    /// 引数とパラメータは"__map__"と"__param__"で受ける。QMPのプロキシのreadとcreateが送る"map"と"param"も同じに扱う。
+    pub fn on_value_request(&self, mut val: Value, opt_requesting_host_addr: Option<SocketAddr>) -> JuizResult<CapsulePtr> {
+        if let Some(obj) = val.as_object_mut() {
+            if !obj.contains_key("__map__") && obj.contains_key("map") {
+                for (legacy, key) in [("map", "__map__"), ("param", "__param__")] {
+                    if let Some(v) = obj.remove(legacy) {
+                        obj.insert(key.to_owned(), v);
+                    }
+                }
+            }
+        }
+        let mut cp = CapsuleMap::from_json_value(val)?;
+        let param = |key: &str| cp.get_param(key).cloned().ok_or_else(|| anyhow!(JuizError::CapsuleDoesNotIncludeParamError{name: key.to_owned()}));
+        let class_name = param("class_name")?;
+        let method_name = param("method_name")?;
+        let function_name = param("function_name")?;
         if let Some(requesting_host_addr) = opt_requesting_host_addr {
             cp.set_param("requesting_host_addr", requesting_host_addr.to_string().as_str());
         }
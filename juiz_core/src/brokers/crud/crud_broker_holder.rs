@@ -84,6 +84,7 @@ impl<F, Fut> Broker for CRUDBrokerHolder<F, Fut>  where F: Fn(Value, Arc<Mutex<C
         let type_name = self.type_name().to_string();
         log::trace!("CRUDBrokerHolder::stop(type_name={type_name}) called");
         self.thread_handle.take().unwrap().abort();
+        self.crud_broker.lock().unwrap().clear_started();
         log::trace!("CRUDBrokerHolder::stop(type_name={type_name}) exit");
         Ok(())
     }
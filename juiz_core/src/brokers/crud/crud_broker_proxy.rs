@@ -1,4 +1,4 @@
-use std::{collections::HashMap, sync::{Mutex, Arc}};
+use std::{collections::HashMap, sync::{Mutex, Arc}, time::Duration};
 
 use juiz_sdk::{anyhow::anyhow, connections::ConnectionManifest, identifier::connection_identifier_split};
 use uuid::Uuid;
@@ -17,6 +17,8 @@ pub trait CRUDBrokerProxy : Send + Sync {
 pub struct CRUDBrokerProxyHolder {
     core: ObjectCore,
     broker: Box<dyn CRUDBrokerProxy>,
+    /// 呼び出しにタイムアウトの指定が無い時に使うタイムアウト
+    default_timeout: Option<Duration>,
 }
 
 fn param(param_map: &[(&str, &str)]) -> HashMap<String, String> {
@@ -55,9 +57,23 @@ impl CRUDBrokerProxyHolder {
         Ok(Arc::new(Mutex::new(CRUDBrokerProxyHolder{
             core: ObjectCore::create(JuizObjectClass::BrokerProxy(impl_class_name), type_name, name),
             broker: broker_proxy,
+            default_timeout: None,
         })))
     }
 
+    pub fn set_default_timeout(&mut self, timeout: Option<Duration>) {
+        self.default_timeout = timeout;
+    }
+
+    /// プロセスを呼ぶときのパラメータ。timeoutが無ければ既定のタイムアウトを載せる
+    fn call_param(&self, id: &Identifier, timeout: Option<Duration>) -> HashMap<String, String> {
+        let mut map = param(&[("identifier", id)]);
+        if let Some(timeout) = timeout.or(self.default_timeout) {
+            map.insert(TIMEOUT_PARAM_KEY.to_owned(), timeout.as_secs_f64().to_string());
+        }
+        map
+    }
+
     fn convert_identifier_name(&self, id_array: &Value) -> JuizResult<Value> {
         let mut ids: Vec<String> = Vec::new();
         for vid in get_array(id_array)?.iter() {
@@ -120,11 +136,12 @@ impl ContainerProcessBrokerProxy for CRUDBrokerProxyHolder {
     }
     
     fn container_process_call(&self, id: &Identifier, args: CapsuleMap) -> JuizResult<CapsulePtr> {
-        self.broker.update("container_process", "call", args, param(&[("identifier", id)]))
+        let param = self.call_param(id, args.timeout()?);
+        self.broker.update("container_process", "call", args, param)
     }
     
     fn container_process_execute(&self, id: &Identifier) -> JuizResult<CapsulePtr> {
-        self.broker.update("container_process", "execute", CapsuleMap::new(), param(&[("identifier", id)]))
+        self.broker.update("container_process", "execute", CapsuleMap::new(), self.call_param(id, None))
     }
     
     fn container_process_create(&mut self, container_id: &Identifier, manifest: ProcessManifest) -> JuizResult<Value> {
@@ -181,11 +198,12 @@ impl ProcessBrokerProxy for CRUDBrokerProxyHolder {
     }
 
     fn process_call(&self, id: &Identifier, args: CapsuleMap) -> JuizResult<CapsulePtr> {
-        self.broker.update("process", "call", args, param(&[("identifier", id)]))
+        let param = self.call_param(id, args.timeout()?);
+        self.broker.update("process", "call", args, param)
     }
 
-    fn process_execute(&self, id: &Identifier) -> JuizResult<CapsulePtr> {
-        self.broker.update("process", "execute", CapsuleMap::new(), param(&[("identifier", id)]))
+    fn process_execute_with_timeout(&self, id: &Identifier, timeout: Option<Duration>) -> JuizResult<CapsulePtr> {
+        self.broker.update("process", "execute", CapsuleMap::new(), self.call_param(id, timeout))
     }
 
     fn process_list(&self, recursive:bool) -> JuizResult<Value> {
@@ -316,6 +334,10 @@ impl SystemBrokerProxy for CRUDBrokerProxyHolder {
         cp.insert("language".to_owned(), CapsulePtr::from(Value::from(language)));
         capsule_to_value(self.broker.update("system", "load_component", cp, HashMap::new())?)
     }
+
+    fn system_discovered_peers(&self) -> JuizResult<Value> {
+        self.broker.read("system", "discovered_peers", HashMap::new())?.extract_value()
+    }
 }
 
 impl BrokerBrokerProxy for CRUDBrokerProxyHolder {
@@ -359,6 +381,24 @@ impl ExecutionContextBrokerProxy for CRUDBrokerProxyHolder {
     fn ec_stop(&mut self, id: &Identifier) -> JuizResult<Value> {
         capsule_to_value(self.broker.update("execution_context", "stop",  CapsuleMap::new(), param(&[("identifier", id)]))?)
     }
+
+    fn ec_pause(&mut self, id: &Identifier) -> JuizResult<Value> {
+        capsule_to_value(self.broker.update("execution_context", "pause",  CapsuleMap::new(), param(&[("identifier", id)]))?)
+    }
+
+    fn ec_resume(&mut self, id: &Identifier) -> JuizResult<Value> {
+        capsule_to_value(self.broker.update("execution_context", "resume",  CapsuleMap::new(), param(&[("identifier", id)]))?)
+    }
+
+    fn ec_reset(&mut self, id: &Identifier) -> JuizResult<Value> {
+        capsule_to_value(self.broker.update("execution_context", "reset",  CapsuleMap::new(), param(&[("identifier", id)]))?)
+    }
+
+    fn ec_step(&mut self, id: &Identifier, n: usize) -> JuizResult<Value> {
+        let mut map = CapsuleMap::new();
+        map.insert("n".to_owned(), jvalue!(n).into());
+        capsule_to_value(self.broker.update("execution_context", "step", map, param(&[("identifier", id)]))?)
+    }
     
     fn ec_create(&mut self, manifest: &Value) -> JuizResult<Value> {
         capsule_to_value(self.broker.create("execution_context", "create", manifest.clone(), HashMap::new())?)
@@ -378,13 +418,18 @@ impl TopicBrokerProxy for CRUDBrokerProxyHolder {
     
     fn topic_push(&self, name: &str, capsule: CapsulePtr, pushed_system_uuid: Option<Uuid>) -> JuizResult<()> {
         log::trace!("topic_push({name}) called");
+        // 画像の符号化はオプションを運べないブローカーもあるのでパラメータでも送る
+        let encoding = capsule.get_options()?.remove(TOPIC_ENCODING_KEY);
         let mut args = CapsuleMap::new();
         args.insert("input".to_owned(), capsule);
-        let param_var = if let Some(system_uuid) = pushed_system_uuid {
+        let mut param_var = if let Some(system_uuid) = pushed_system_uuid {
             topic_param(&[("topic_name", name), ("system_uuid", system_uuid.to_string().as_str())])
         } else {
             topic_param(&[("topic_name", name)])
         };
+        if let Some(encoding) = encoding {
+            param_var.insert(TOPIC_ENCODING_KEY.to_owned(), encoding);
+        }
         //let uuid_str = if let Some(uuid) = pushed_system_uuid { uuid.to_string() } else { "".to_owned() };        
         self.broker.update("topic", "push", args, param_var).and_then(|_|{Ok(())})
     }
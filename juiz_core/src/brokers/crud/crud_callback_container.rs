@@ -92,6 +92,10 @@ pub(crate) fn read_callback_container() -> ClassCallbackContainerType {
         }
         Ok(value_to_capsule(cb.lock()?.system_filesystem_list(PathBuf::from(path))?))
     });
+    system_callbacks.insert("discovered_peers", |_crud, cb, _args| {
+        log::debug!("[READ  ] system/discovered_peers called");
+        Ok(value_to_capsule(cb.lock()?.system_discovered_peers()?))
+    });
     read_cb_container.insert("system", system_callbacks);
 
     let mut broker_cbs = CallbackContainerType::new();
@@ -302,7 +306,7 @@ pub(crate) fn update_callback_container() -> ClassCallbackContainerType {
     proc_cbs.insert("execute", |_crud, cb, args| {
         log::debug!("[UPDATE] process/execute called");
         let id = args.get_param("identifier").ok_or_else(||{anyhow::Error::from(JuizError::CRUDBrokerCanNotParameterFunctionError { key_name: "identifier".to_owned() })})?;
-        cb.lock()?.process_execute(id)
+        cb.lock()?.process_execute_with_timeout(id, args.timeout()?)
     });
     proc_cbs.insert("p_apply", |_crud, cb, args| {
         log::debug!("[UPDATE] process/p_apply called");
@@ -382,6 +386,27 @@ pub(crate) fn update_callback_container() -> ClassCallbackContainerType {
         let id = args.get_param("identifier").ok_or_else(||{anyhow::Error::from(JuizError::CRUDBrokerCanNotParameterFunctionError { key_name: "identifier".to_owned() })})?;
         Ok(value_to_capsule(cb.lock_mut()?.ec_stop(id)?))
     });
+    ec_cbs.insert("pause", |_crud,cb, args| {
+        log::debug!("[UPDATE] ec/pause called");
+        let id = args.get_param("identifier").ok_or_else(||{anyhow::Error::from(JuizError::CRUDBrokerCanNotParameterFunctionError { key_name: "identifier".to_owned() })})?;
+        Ok(value_to_capsule(cb.lock_mut()?.ec_pause(id)?))
+    });
+    ec_cbs.insert("resume", |_crud,cb, args| {
+        log::debug!("[UPDATE] ec/resume called");
+        let id = args.get_param("identifier").ok_or_else(||{anyhow::Error::from(JuizError::CRUDBrokerCanNotParameterFunctionError { key_name: "identifier".to_owned() })})?;
+        Ok(value_to_capsule(cb.lock_mut()?.ec_resume(id)?))
+    });
+    ec_cbs.insert("reset", |_crud,cb, args| {
+        log::debug!("[UPDATE] ec/reset called");
+        let id = args.get_param("identifier").ok_or_else(||{anyhow::Error::from(JuizError::CRUDBrokerCanNotParameterFunctionError { key_name: "identifier".to_owned() })})?;
+        Ok(value_to_capsule(cb.lock_mut()?.ec_reset(id)?))
+    });
+    ec_cbs.insert("step", |_crud,cb, args| {
+        log::debug!("[UPDATE] ec/step called");
+        let id = args.get_param("identifier").ok_or_else(||{anyhow::Error::from(JuizError::CRUDBrokerCanNotParameterFunctionError { key_name: "identifier".to_owned() })})?;
+        let n = args.get("n")?.extract_value()?.as_u64().ok_or(anyhow!(JuizError::ArgumentError { message: "n must be non-negative integer".to_owned() }))?;
+        Ok(value_to_capsule(cb.lock_mut()?.ec_step(id, n as usize)?))
+    });
     update_cb_container.insert("execution_context", ec_cbs);
 
 
@@ -398,7 +423,11 @@ pub(crate) fn update_callback_container() -> ClassCallbackContainerType {
                 None
             }
         };
-        let input = args.get("input")?;
+        let mut input = args.get("input")?;
+        // 転送元が画像を符号化していれば、受け取ったトピックが画像に戻せるようにオプションに戻す
+        if let Some(encoding) = args.get_param(TOPIC_ENCODING_KEY) {
+            input.set_option(TOPIC_ENCODING_KEY, encoding.as_str())?;
+        }
         cb.lock()?.topic_push(topic_name.as_str(), input, system_uuid).and(Ok(CapsulePtr::new()))
     });
     topic_cbs.insert("request_subscribe", |_crud,cb, args| {
@@ -0,0 +1,133 @@
+//! HTTPBrokerの認証とアクセス制御
+//!
+//! ブローカーのマニフェストの"auth"でトークンと、それぞれのトークンに許すCRUDのクラスと識別子を決める。
+//!
+//! ```json
+//! "auth": {
+//!     "tokens": [
+//!         {"token": "admin_secret"},
+//!         {"token": "viewer_secret", "allow": [
+//!             {"method": "read"},
+//!             {"method": "update", "class_name": "process", "function_name": "call", "identifier": "core://core/Process/increment*"}
+//!         ]}
+//!     ]
+//! }
+//! ```
+//!
+//! "allow"を省いたトークンには全てを許す。パターンの'*'は任意の文字列にマッチする。
+//! トークンはリクエストの`Authorization: Bearer <token>`ヘッダで渡す。
+
+use std::collections::HashMap;
+use juiz_sdk::anyhow::anyhow;
+use regex::Regex;
+
+use crate::prelude::*;
+
+/// トークンを載せるヘッダの接頭辞
+pub(crate) const BEARER_PREFIX: &str = "Bearer ";
+
+const METHOD_NAMES: [&str; 4] = ["create", "read", "update", "delete"];
+
+/// 認証の結果
+#[derive(Debug, PartialEq)]
+pub(crate) enum AuthDecision {
+    Allowed,
+    /// トークンが無いか、知らないトークン (401)
+    Unauthorized,
+    /// トークンは正しいがACLが許していない (403)
+    Forbidden,
+}
+
+#[derive(Debug, Clone)]
+struct AccessRule {
+    method: Regex,
+    class_name: Regex,
+    function_name: Regex,
+    identifier: Regex,
+}
+
+fn pattern_to_regex(pattern: &str) -> JuizResult<Regex> {
+    let escaped = regex::escape(pattern).replace("\\*", ".*");
+    Ok(Regex::new(format!("^{escaped}$").as_str())?)
+}
+
+fn rule_pattern<'a>(rule: &'a Value, key: &str) -> JuizResult<&'a str> {
+    match rule.get(key) {
+        None => Ok("*"),
+        Some(v) => v.as_str().ok_or_else(|| anyhow!(JuizError::InvalidSettingError{message: format!("HTTPBroker auth rule '{key}' must be string. ({rule})")})),
+    }
+}
+
+impl AccessRule {
+
+    fn new(rule: &Value) -> JuizResult<Self> {
+        let method = rule_pattern(rule, "method")?;
+        if method != "*" && !METHOD_NAMES.contains(&method) {
+            return Err(anyhow!(JuizError::InvalidSettingError{message: format!("HTTPBroker auth rule method must be one of {METHOD_NAMES:?} or '*'. ({rule})")}));
+        }
+        Ok(AccessRule {
+            method: pattern_to_regex(method)?,
+            class_name: pattern_to_regex(rule_pattern(rule, "class_name")?)?,
+            function_name: pattern_to_regex(rule_pattern(rule, "function_name")?)?,
+            identifier: pattern_to_regex(rule_pattern(rule, "identifier")?)?,
+        })
+    }
+
+    fn permits(&self, method: &str, class_name: &str, function_name: &str, identifier: &str) -> bool {
+        self.method.is_match(method)
+            && self.class_name.is_match(class_name)
+            && self.function_name.is_match(function_name)
+            && self.identifier.is_match(identifier)
+    }
+}
+
+/// HTTPBrokerのトークンとACL
+#[derive(Debug, Clone)]
+pub(crate) struct HTTPAuth {
+    /// トークンごとの許可。Noneなら全てを許す
+    tokens: HashMap<String, Option<Vec<AccessRule>>>,
+}
+
+impl HTTPAuth {
+
+    /// ブローカーのマニフェストの"auth"を読む。"auth"が無ければ認証しない
+    pub(crate) fn from_broker_manifest(manifest: &Value) -> JuizResult<Option<Self>> {
+        let auth = match manifest.get("auth") {
+            None => return Ok(None),
+            Some(auth) => auth,
+        };
+        let mut tokens = HashMap::new();
+        for entry in get_array(obj_get(auth, "tokens")?)?.iter() {
+            let token = obj_get_str(entry, "token")?;
+            if token.is_empty() {
+                return Err(anyhow!(JuizError::InvalidSettingError{message: "HTTPBroker auth token must not be empty.".to_owned()}));
+            }
+            let rules = match entry.get("allow") {
+                None => None,
+                Some(allow) => Some(get_array(allow)?.iter().map(AccessRule::new).collect::<JuizResult<Vec<AccessRule>>>()?),
+            };
+            if tokens.insert(token.to_owned(), rules).is_some() {
+                return Err(anyhow!(JuizError::InvalidSettingError{message: "HTTPBroker auth has duplicated token.".to_owned()}));
+            }
+        }
+        Ok(Some(HTTPAuth{tokens}))
+    }
+
+    /// Authorizationヘッダの値からトークンを取り出す
+    pub(crate) fn token_from_header(authorization: Option<&str>) -> Option<&str> {
+        authorization.and_then(|v| v.strip_prefix(BEARER_PREFIX)).map(|v| v.trim())
+    }
+
+    /// methodは"create", "read", "update", "delete"のどれか。識別子が無い呼び出しは空文字列として照合する
+    pub(crate) fn check(&self, token: Option<&str>, method: &str, class_name: &str, function_name: &str, identifier: Option<&str>) -> AuthDecision {
+        let rules = match token.and_then(|t| self.tokens.get(t)) {
+            None => return AuthDecision::Unauthorized,
+            Some(rules) => rules,
+        };
+        let permitted = match rules {
+            None => true,
+            Some(rules) => rules.iter().any(|r| r.permits(method, class_name, function_name, identifier.unwrap_or(""))),
+        };
+        if permitted { AuthDecision::Allowed } else { AuthDecision::Forbidden }
+    }
+}
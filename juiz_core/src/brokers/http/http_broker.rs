@@ -1,12 +1,18 @@
 use std::{io::ErrorKind, net::SocketAddr, path::PathBuf, sync::{Arc, Mutex}};
 //use axum::extract::path::ErrorKind;
+use axum::{extract::ConnectInfo, Extension, Router};
+use hyper_util::{rt::{TokioExecutor, TokioIo}, server::conn::auto, service::TowerToHyperService};
+use rustls::ServerConfig;
 use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
 
 use super::super::core_broker::CoreBrokerPtr;
 use crate::{brokers::broker_ptr::BrokerPtr, prelude::*};
 use crate::brokers::{broker_factory_impl::create_broker_factory_impl, BrokerFactory, CRUDBrokerHolder};
 use crate::brokers::CRUDBroker;
 
+use super::http_auth::HTTPAuth;
+use super::http_tls::ServerTLS;
 use super::http_router::app_new;
 
 fn into_address(host: &str, port: i64) -> String {
@@ -38,6 +44,28 @@ async fn on_start_inner(broker_manifest: Value, crud_broker: Arc<Mutex<CRUDBroke
             None
         }
     };
+    let auth = match HTTPAuth::from_broker_manifest(&broker_manifest) {
+        Ok(auth) => auth,
+        Err(e) => {
+            log::error!("on_start(broker_manifest='{broker_manifest:}') failed. Error({e:?}, {e})");
+            return;
+        }
+    };
+    if auth.is_none() {
+        log::warn!("http_broker (host={host}, port={port}) accepts requests without authentication.");
+    }
+    // 証明書を読むか作るのはここで一度だけ
+    let tls_config = match ServerTLS::from_broker_manifest(&broker_manifest).and_then(|tls| tls.map(|tls| tls.server_config()).transpose()) {
+        Ok(Some((config, fingerprint))) => {
+            log::info!("http_broker (host={host}, port={port}) serves HTTPS. Certificate fingerprint (SHA-256) is {fingerprint}");
+            Some(config)
+        },
+        Ok(None) => None,
+        Err(e) => {
+            log::error!("on_start(broker_manifest='{broker_manifest:}') failed. Error({e:?}, {e})");
+            return;
+        }
+    };
 
     loop {
         let address = into_address(host, port);
@@ -49,7 +77,11 @@ async fn on_start_inner(broker_manifest: Value, crud_broker: Arc<Mutex<CRUDBroke
                 let new_broker_name = format!("127.0.0.1:{:}", port);
                 juiz_lock(&crud_broker).unwrap().update_broker_name(new_broker_name.as_str());
                 log::trace!("http_broker::on_start() exit");
-                return axum::serve(listener, app_new(crud_broker, static_filepaths).into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+                let app = app_new(crud_broker, static_filepaths, auth);
+                return match tls_config {
+                    Some(config) => serve_tls(listener, app, config).await,
+                    None => axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap(),
+                };
             },
             Err(e) => {
                 if e.kind() == ErrorKind::AddrInUse {
@@ -91,8 +123,41 @@ async fn on_start_inner(broker_manifest: Value, crud_broker: Arc<Mutex<CRUDBroke
 }
 
 
+/// axum::serveはTLSを扱わないので、接続ごとにハンドシェイクしてからhyperに渡す
+async fn serve_tls(listener: TcpListener, app: Router, config: Arc<ServerConfig>) {
+    let acceptor = TlsAcceptor::from(config);
+    loop {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("http_broker accept failed. Error({e})");
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        // ハンドラがConnectInfoで相手のアドレスを読めるようにする
+        let app = app.clone().layer(Extension(ConnectInfo(remote_addr)));
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("http_broker TLS handshake with {remote_addr} failed. Error({e})");
+                    return;
+                }
+            };
+            let service = TowerToHyperService::new(app);
+            if let Err(e) = auto::Builder::new(TokioExecutor::new()).serve_connection_with_upgrades(TokioIo::new(stream), service).await {
+                log::debug!("http_broker connection with {remote_addr} closed. Error({e})");
+            }
+        });
+    }
+}
+
 pub fn http_broker_factory(core_broker: CoreBrokerPtr) -> JuizResult<Arc<Mutex<dyn BrokerFactory>>> {
     fn create_broker_function(core_broker: CoreBrokerPtr, manifest: Value) -> JuizResult<BrokerPtr> {
+        let _ = HTTPAuth::from_broker_manifest(&manifest)?;
+        // 証明書を作るなどの副作用があるので、ここでは"tls"を読んで確かめるだけにする
+        let _ = ServerTLS::from_broker_manifest(&manifest)?;
         Ok(BrokerPtr::new(CRUDBrokerHolder::new("HTTPBroker", "http", core_broker, &on_start, manifest.clone())?))
     }
 
@@ -3,10 +3,13 @@ use std::{collections::HashMap, io::Read, sync::{Arc, Mutex}};
 use juiz_sdk::anyhow;
 
 use crate::{core::CoreWorker, prelude::*};
-use crate::brokers::{create_broker_proxy_factory_impl, BrokerProxy, BrokerProxyFactory};
+use crate::brokers::{create_broker_proxy_factory_impl, BrokerProxy, BrokerProxyFactory, PayloadCodec};
+use crate::brokers::http::http_router::{NUMERIC_ARRAY_CONTENT_TYPE, NUMERIC_ARRAY_HEADER, OPTION_HEADER};
+use crate::brokers::call_timeout::{default_timeout_from_manifest, timeout_error, timeout_from_param};
+use crate::brokers::http::http_tls::client_config_from_manifest;
 
 //use reqwest::Response;
-use reqwest::blocking::Response;
+use reqwest::blocking::{RequestBuilder, Response};
 use crate::brokers::{CRUDBrokerProxy, CRUDBrokerProxyHolder};
 use thiserror::Error;
 
@@ -37,8 +40,13 @@ fn name_to_host_and_port<'a>(name: &'a String) -> JuizResult<(&'a str, i64)> {
 
 
 struct HTTPBrokerProxy {
+    name: String,
     base_url: String,
     client: reqwest::blocking::Client,
+    /// HTTPBrokerの"auth"に登録されたトークン
+    token: Option<String>,
+    /// 値を送るときと受け取るときの符号化。マニフェストの"codec"で選ぶ
+    codec: PayloadCodec,
 }
 
 impl HTTPBrokerProxy {
@@ -47,11 +55,52 @@ impl HTTPBrokerProxy {
         log::trace!("new({manifest:}) called");
         let name = obj_get_str(manifest, "name")?.to_string();
         let (addr, port) = name_to_host_and_port(&name)?;
+        let token = match manifest.get("token") {
+            None => None,
+            Some(v) => Some(v.as_str().ok_or_else(|| anyhow::anyhow!(JuizError::InvalidSettingError{message: format!("HTTPBrokerProxy({name}) token must be string.")}))?.to_owned()),
+        };
+        let codec = PayloadCodec::from_manifest(manifest, PayloadCodec::Json)?;
+        // "tls"があればHTTPSでつなぎ、相手の証明書をマニフェストのとおりに確かめる
+        let (scheme, client) = match client_config_from_manifest(name.as_str(), manifest)? {
+            Some(config) => ("https://", reqwest::blocking::Client::builder().use_preconfigured_tls(config).build()?),
+            None => ("http://", reqwest::blocking::Client::new()),
+        };
         Ok(HTTPBrokerProxy{
-            client: reqwest::blocking::Client::new(),
-            base_url: "http://".to_string() + addr + ":" + i64::to_string(&port).as_str() + "/api"
+            client,
+            base_url: scheme.to_string() + addr + ":" + i64::to_string(&port).as_str() + "/api",
+            name,
+            token,
+            codec,
         })
     }
+
+    /// トークンを載せ、応答をcodecで符号化するようにAcceptで頼む
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = request.header(reqwest::header::ACCEPT, self.codec.content_type());
+        match self.token.as_ref() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// 値をcodecで符号化して本体に載せる
+    fn encode_body(&self, request: RequestBuilder, value: &Value) -> JuizResult<RequestBuilder> {
+        Ok(request.header(reqwest::header::CONTENT_TYPE, self.codec.content_type()).body(self.codec.encode(value)?))
+    }
+
+    /// 401と403はブローカーが呼び出しを断ったことを表すエラーにする
+    fn check_auth_status(&self, response: &Response, method: &str, class_name: &str, function_name: &str) -> JuizResult<()> {
+        match response.status() {
+            reqwest::StatusCode::UNAUTHORIZED => Err(anyhow::anyhow!(JuizError::BrokerUnauthorizedError{broker_name: self.name.clone()})),
+            reqwest::StatusCode::FORBIDDEN => Err(anyhow::anyhow!(JuizError::BrokerForbiddenError{
+                broker_name: self.name.clone(),
+                method: method.to_owned(),
+                class_name: class_name.to_owned(),
+                function_name: function_name.to_owned(),
+            })),
+            _ => Ok(()),
+        }
+    }
 }
 
 fn construct_param(key: &String, value: &String) -> String {
@@ -78,28 +127,29 @@ fn construct_url(base_url: &String, class_name: &str, function_name: &str, param
 impl CRUDBrokerProxy for HTTPBrokerProxy {
     fn create(&self, class_name: &str, function_name: &str, payload: Value, param: std::collections::HashMap<String, String>) -> JuizResult<CapsulePtr> {
         log::trace!("HTTPBrokerProxy({}).create({class_name:}, {function_name}, {payload}, {param:?}) called", self.base_url);
-        let client = reqwest::blocking::Client::new();
-        match client.post(construct_url(&self.base_url, class_name, function_name, &param))
-            .json(&payload)
+        match self.encode_body(self.authorize(self.client.post(construct_url(&self.base_url, class_name, function_name, &param))), &payload)?
             .send() {
             Err(e) => Err(anyhow::Error::from(e)),
             Ok(response) => {
+                self.check_auth_status(&response, "create", class_name, function_name)?;
                 log::error!("response: {:?}", response);
                 if response.status() != 200 {
                     return Err(anyhow::Error::from(HTTPBrokerError::GeneralError{}));
                 }
-                Ok(response.json::<Value>().map_err(|e| anyhow::Error::from(e))?.into())
+                let options = response_options(&response);
+                with_options(response_to_capsule_ptr(response)?, options)
             }
         }
     }
 
     fn delete(&self, class_name: &str, function_name: &str, param: std::collections::HashMap<String, String>) -> JuizResult<CapsulePtr> {
         log::trace!("HTTPBrokerProxy({}).delete({class_name:}, {function_name}, {param:?}) called", self.base_url);
-        let client = reqwest::blocking::Client::new();
-        match client.delete(construct_url(&self.base_url, class_name, function_name, &param)).send() {
+        match self.authorize(self.client.delete(construct_url(&self.base_url, class_name, function_name, &param))).send() {
             Err(e) => Err(anyhow::Error::from(e)),
             Ok(response) => {
-                Ok(response.json::<Value>().map_err(|e| anyhow::Error::from(e))?.into())
+                self.check_auth_status(&response, "delete", class_name, function_name)?;
+                let options = response_options(&response);
+                with_options(response_to_capsule_ptr(response)?, options)
             }
         }
     }
@@ -111,16 +161,18 @@ impl CRUDBrokerProxy for HTTPBrokerProxy {
         // let client = reqwest::blocking::Client::new();
         let url  =construct_url(&self.base_url, class_name, function_name, &param);
         log::trace!("HTTPBrokerProxy({}).read(url={url:})", self.base_url);
-        match self.client.get(url.clone()).send() {
+        match self.authorize(self.client.get(url.clone())).send() {
             Err(e) => Err(anyhow::Error::from(e)),
             Ok(response) => {
+                self.check_auth_status(&response, "read", class_name, function_name)?;
                 if response.status() != 200 {
                     log::error!("HTTPBrokerProxy.read(url={url:}) failed. Response is {response:?}");
                     return Err(anyhow::Error::from(HTTPBrokerError::HTTPStatusError{status_code: response.status(), message: format!("{:?}", response) }));
                 }
-                let value = response.json::<Value>().map_err(|e| anyhow::Error::from(e))?;
+                let options = response_options(&response);
+                let value = response_to_capsule_ptr(response)?;
                 log::trace!("HTTPBrokerProxy.read({}) Response = {value:?}", self.base_url);
-                let return_value = Ok(value.into());
+                let return_value = with_options(value, options);
                 //log::trace!("HTTPBrokerProxy.read({}) returns {return_value:?}", self.base_url);
                 return_value
             }
@@ -130,28 +182,79 @@ impl CRUDBrokerProxy for HTTPBrokerProxy {
 
     fn update(&self, class_name: &str, function_name: &str, payload: CapsuleMap, param: std::collections::HashMap<String, String>) -> JuizResult<CapsulePtr>{
         log::trace!("HTTPBrokerProxy({}).update({class_name:}, {function_name}, {payload}, {param:?}) called", self.base_url);
-        let client = reqwest::blocking::Client::new();
-        let v: Value = payload.into();
-        match client.patch(construct_url(&self.base_url, class_name, function_name, &param))
-            .json(&v)
-            .send() {
-            Err(e) => Err(anyhow::Error::from(e)),
+        let timeout = timeout_from_param(&param)?;
+        let url = construct_url(&self.base_url, class_name, function_name, &param);
+        // JSONで画像やバイナリを含むときはBase64にせずmultipartで送る。MessagePackとCBORはそのまま本体に載せられる
+        let mut request = if self.codec == PayloadCodec::Json && has_binary(&payload)? {
+            self.authorize(self.client.put(url.clone())).multipart(capsule_map_to_form(&payload)?)
+        } else {
+            self.authorize(self.client.patch(url.clone()))
+                .header(reqwest::header::CONTENT_TYPE, self.codec.content_type())
+                .body(self.codec.encode_capsule_map(payload)?)
+        };
+        if let Some(timeout) = timeout {
+            request = request.timeout(timeout);
+        }
+        match request.send() {
+            Err(e) => match timeout {
+                Some(timeout) if e.is_timeout() => Err(timeout_error(&url, timeout)),
+                _ => Err(anyhow::Error::from(e)),
+            },
             Ok(response) => {
-                let hdr = response.headers();
-                if hdr["content-type"] == "image/png" {
-                    image_png_response_to_capsule_ptr(response)
-                } else {
-                    match response.json::<Value>() {
-                        Err(e) => Err(anyhow::Error::from(e)),
-                        Ok(v) => Ok(v.into())
-                    }
+                self.check_auth_status(&response, "update", class_name, function_name)?;
+                // サーバー側で時間切れになった
+                if response.status() == reqwest::StatusCode::GATEWAY_TIMEOUT {
+                    return Err(timeout_error(&url, timeout.unwrap_or_default()));
+                }
+                // サーバーの"plugin_policy"がプラグインを断った
+                if response.status() == reqwest::StatusCode::UNPROCESSABLE_ENTITY {
+                    return Err(plugin_rejected_error(response));
                 }
+                let options = response_options(&response);
+                with_options(response_to_capsule_ptr(response)?, options)
             }
         }
     }
 }
 
 
+/// レスポンスの"plugin_rejected"からPluginRejectedErrorを作り直す
+fn plugin_rejected_error(response: Response) -> anyhow::Error {
+    let body = response.json::<Value>().unwrap_or_default();
+    let rejected = body.get("plugin_rejected");
+    let field = |key: &str| rejected.and_then(|r| r.get(key)).and_then(|v| v.as_str()).unwrap_or_default().to_owned();
+    anyhow::anyhow!(JuizError::PluginRejectedError{plugin_path: field("plugin_path"), reason: field("reason")})
+}
+
+/// Content-Typeを見てレスポンスの本体を読む。値はJSON, MessagePack, CBORのどれでも読める
+fn response_to_capsule_ptr(response: Response) -> JuizResult<CapsulePtr> {
+    let content_type = response.headers().get("content-type").and_then(|hv| hv.to_str().ok()).unwrap_or_default().to_owned();
+    match content_type.as_str() {
+        "image/png" => image_png_response_to_capsule_ptr(response),
+        "application/octet-stream" => Ok(response.bytes()?.to_vec().into()),
+        NUMERIC_ARRAY_CONTENT_TYPE => numeric_array_response_to_capsule_ptr(response),
+        _ => {
+            let codec = PayloadCodec::from_content_type(content_type.as_str()).unwrap_or_default();
+            codec.decode_capsule_ptr(&response.bytes()?)
+        }
+    }
+}
+
+/// サーバーがヘッダに載せたCapsuleのオプションを読む
+fn response_options(response: &Response) -> HashMap<String, String> {
+    response.headers().get(OPTION_HEADER)
+        .and_then(|hv| hv.to_str().ok())
+        .and_then(|s| juiz_sdk::serde_json::from_str::<HashMap<String, String>>(s).ok())
+        .unwrap_or_default()
+}
+
+fn with_options(mut capsule: CapsulePtr, options: HashMap<String, String>) -> JuizResult<CapsulePtr> {
+    for (k, v) in options.iter() {
+        capsule.set_option(k.as_str(), v.as_str())?;
+    }
+    Ok(capsule)
+}
+
 // #[cfg(feature="opencv4")]
 // fn image_png_response_to_capsule_ptr(mut response: Response) -> JuizResult<CapsulePtr> {
 //         let mut buf: Vec<u8> = Vec::new();
@@ -169,10 +272,54 @@ fn image_png_response_to_capsule_ptr(mut response: Response) -> JuizResult<Capsu
     Ok(image.into())
 }
 
+fn numeric_array_response_to_capsule_ptr(response: Response) -> JuizResult<CapsulePtr> {
+    let header = response.headers().get(NUMERIC_ARRAY_HEADER)
+        .and_then(|hv| hv.to_str().ok())
+        .and_then(|s| juiz_sdk::serde_json::from_str::<Value>(s).ok())
+        .ok_or_else(|| anyhow::anyhow!(JuizError::ValueTypeError{message: format!("HTTPBrokerProxy received numeric array without '{NUMERIC_ARRAY_HEADER}' header.")}))?;
+    Ok(NumericArray::from_header_and_bytes(&header, &response.bytes()?)?.into())
+}
+
+fn has_binary(payload: &CapsuleMap) -> JuizResult<bool> {
+    for (_k, v) in payload.iter() {
+        if v.is_image()? || v.is_bytes()? || v.is_numeric_array()? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// CapsuleMapをmultipartのフォームにする。受け手はhttp_router::multipart_to_capsule_map()
+fn capsule_map_to_form(payload: &CapsuleMap) -> JuizResult<reqwest::blocking::multipart::Form> {
+    use reqwest::blocking::multipart::{Form, Part};
+    let mut form = Form::new();
+    for (k, v) in payload.iter() {
+        let part = if v.is_image()? {
+            let mut buf = std::io::Cursor::new(Vec::new());
+            v.lock_as_image_view(|image| image.write_to(&mut buf, juiz_sdk::image::ImageFormat::Png))??;
+            Part::bytes(buf.into_inner()).file_name(k.clone()).mime_str("image/png")?
+        } else if v.is_bytes()? {
+            Part::bytes(v.lock_as_bytes(|bytes| bytes.to_vec())?).file_name(k.clone()).mime_str("application/octet-stream")?
+        } else if v.is_numeric_array()? {
+            let (header, bytes) = v.lock_as_numeric_array(|array| (array.header().to_string(), array.to_le_bytes()))?;
+            let mut headers = reqwest::header::HeaderMap::new();
+            headers.insert(NUMERIC_ARRAY_HEADER, reqwest::header::HeaderValue::from_str(header.as_str())?);
+            Part::bytes(bytes).file_name(k.clone()).mime_str(NUMERIC_ARRAY_CONTENT_TYPE)?.headers(headers)
+        } else if v.is_value()? {
+            Part::text(v.lock_as_value(|value| value.to_string())?).mime_str("application/json")?
+        } else {
+            continue;
+        };
+        form = form.part(k.clone(), part);
+    }
+    Ok(form)
+}
 
 fn create_broker_proxy_function(_core_broker: &CoreWorker, manifest: Value) -> JuizResult<Arc<Mutex<dyn BrokerProxy>>> {
     let name = obj_get_str(&manifest, "name")?;
-    Ok(CRUDBrokerProxyHolder::new("HTTPBrokerProxy", "http", name, Box::new(HTTPBrokerProxy::new(&manifest)?))?)
+    let holder = CRUDBrokerProxyHolder::new("HTTPBrokerProxy", "http", name, Box::new(HTTPBrokerProxy::new(&manifest)?))?;
+    juiz_lock(&holder)?.set_default_timeout(default_timeout_from_manifest(&manifest)?);
+    Ok(holder)
 }
 
 pub fn http_broker_proxy_factory() -> JuizResult<Arc<Mutex<dyn BrokerProxyFactory>>> {
@@ -3,12 +3,12 @@
 use juiz_sdk::anyhow;
 use reqwest::StatusCode;
 use std::{net::SocketAddr, sync::{Arc, Mutex}};
-use axum::{extract::{ConnectInfo, Multipart, Path, Query, State}, http::HeaderMap, response::IntoResponse, routing, Json, Router};
+use axum::{body::Bytes, extract::{ConnectInfo, Multipart, Path, Query, Request, State}, http::{header, HeaderMap, Method}, middleware::{self, Next}, response::{IntoResponse, Response}, routing, Json, Router};
 
-use crate::{brokers::http::http_router::{multipart_to_capsule_map, FullQuery}, prelude::*};
+use crate::{brokers::http::{http_auth::{AuthDecision, HTTPAuth}, http_router::{multipart_to_capsule_map, FullQuery}}, prelude::*};
 use crate::brokers::crud_broker::CRUDBroker;
 
-use super::{json_output_wrap, full_query_to_map};
+use super::{codec_output_wrap, full_query_to_map, request_codec, response_codec};
 use utoipa::OpenApi;
 
 #[utoipa::path(
@@ -29,19 +29,39 @@ pub async fn object_post_handler(
     headers: HeaderMap,
     remote_addr: ConnectInfo<SocketAddr>,
     State(crud_broker): State<Arc<Mutex<CRUDBroker>>>, 
-    Json(body): Json<Value>,
+    body: Bytes,
 ) -> impl IntoResponse {
     let map = full_query_to_map(&query);
+    let body = match decode_body(&body, &headers) {
+        Ok(body) => body,
+        Err(r) => return r,
+    };
     log::trace!("[POST] HTTPBroker/object_post_handler({class_name}, {function_name}, {body}, {map:?}) called");
+    let codec = response_codec(&headers);
     let v = tokio::task::spawn_blocking(move ||{
         juiz_lock(&crud_broker).unwrap().create_class(class_name.as_str(), function_name.as_str(), construct_capsule_map(CapsuleMap::new(), "CREATE", class_name.as_str(), function_name.as_str(), query, headers, remote_addr))
     }).await;
-    let r = json_output_wrap(v.unwrap());
-    r
+    codec_output_wrap(v.unwrap(), codec)
 }
 
-fn body_to_capsule_map(body: Value, _headers: &HeaderMap) -> Result<CapsuleMap, anyhow::Error> {
-    body.try_into()
+/// 本体をContent-Typeの符号化 (JSON, MessagePack, CBOR) で読む
+fn decode_body(body: &Bytes, headers: &HeaderMap) -> Result<Value, Response> {
+    let codec = request_codec(headers)?;
+    codec.decode(body).map_err(|e| {
+        (StatusCode::BAD_REQUEST, Json(jvalue!({
+            "message": format!("Bad Request: body can not be decoded as {codec}. {e}")
+        }))).into_response()
+    })
+}
+
+/// 本体をContent-Typeの符号化で引数のCapsuleMapとして読む。MessagePackとCBORならバイナリや画像もそのまま受け取る
+fn decode_body_capsule_map(body: &Bytes, headers: &HeaderMap) -> Result<CapsuleMap, Response> {
+    let codec = request_codec(headers)?;
+    codec.decode_capsule_map(body).map_err(|e| {
+        (StatusCode::BAD_REQUEST, Json(jvalue!({
+            "message": format!("Bad Request: body can not be decoded as {codec}. {e}")
+        }))).into_response()
+    })
 }
 
 
@@ -79,6 +99,18 @@ fn construct_capsule_map(mut capsule_map: CapsuleMap, method_name: &str, class_n
             capsule_map.set_param("topic_name", v.as_str());
         }
     }
+    match query.timeout.clone() {
+        None => {},
+        Some(v) => {
+            capsule_map.set_param(TIMEOUT_PARAM_KEY, v.as_str());
+        }
+    }
+    match query.topic_encoding.clone() {
+        None => {},
+        Some(v) => {
+            capsule_map.set_param(TOPIC_ENCODING_KEY, v.as_str());
+        }
+    }
     // println!("HEADER>>>> {headers:?}");
     match headers.get("host") {
         Some(header) => {
@@ -140,15 +172,19 @@ pub async fn object_patch_handler(
     // multipart: Multipart,
     remote_addr: ConnectInfo<SocketAddr>,
     State(crud_broker): State<Arc<Mutex<CRUDBroker>>>, 
-    Json(body): Json<Value>,
+    body: Bytes,
 ) -> impl IntoResponse {
     let map = full_query_to_map(&query);
+    let body = match decode_body_capsule_map(&body, &headers) {
+        Ok(body) => body,
+        Err(r) => return r,
+    };
     log::trace!("[PATCH] ({class_name}, {function_name}, {body}, {map:?}) called");
+    let codec = response_codec(&headers);
     let v = tokio::task::spawn_blocking(move ||{
-        juiz_lock(&crud_broker).unwrap().update_class(class_name.as_str(), function_name.as_str(), construct_capsule_map(body_to_capsule_map(body, &headers)?, "UPDATE", class_name.as_str(), function_name.as_str(), query, headers, remote_addr))
+        juiz_lock(&crud_broker).unwrap().update_class(class_name.as_str(), function_name.as_str(), construct_capsule_map(body, "UPDATE", class_name.as_str(), function_name.as_str(), query, headers, remote_addr))
     }).await;
-    let r = json_output_wrap(v.unwrap());
-    r
+    codec_output_wrap(v.unwrap(), codec)
 }
 
 
@@ -177,11 +213,11 @@ pub async fn object_put_handler(
     log::trace!("[PUT] ({class_name}, {function_name}, {map:?}, {multipart:?}) called");
     match multipart_to_capsule_map(multipart).await {
         Ok(capsule_map) => {
+            let codec = response_codec(&headers);
             let v = tokio::task::spawn_blocking(move ||{
                 juiz_lock(&crud_broker).unwrap().update_class(class_name.as_str(), function_name.as_str(), construct_capsule_map(capsule_map, "UPDATE", class_name.as_str(), function_name.as_str(), query, headers, remote_addr))
             }).await;
-            let r = json_output_wrap(v.unwrap());
-            r.into_response()
+            codec_output_wrap(v.unwrap(), codec)
         }
         Err(e) => {
             log::error!("multipart_to_capsule_map() failed. Err({e:?})");
@@ -220,11 +256,11 @@ pub async fn object_get_handler(
     let full_path = "";
     let map = full_query_to_map(&query);
     log::trace!("[GET] ({class_name}, {function_name}, {map:?}, {full_path:?}, {headers:?}) called");
+    let codec = response_codec(&headers);
     let v = tokio::task::spawn_blocking(move ||{
         juiz_lock(&crud_broker).unwrap().read_class(class_name.as_str(), function_name.as_str(), construct_capsule_map(CapsuleMap::new(), "READ", class_name.as_str(), function_name.as_str(), query, headers, remote_addr))
     }).await;
-    let r = json_output_wrap(v.unwrap());
-    r
+    codec_output_wrap(v.unwrap(), codec)
 }
 
 #[utoipa::path(
@@ -247,23 +283,64 @@ pub async fn object_delete_handler(
 ) -> impl IntoResponse {
     let map = full_query_to_map(&query);
     log::trace!("HTTPBroker/object_delete_handler({class_name}, {map:?}) called");
+    let codec = response_codec(&headers);
     let v = tokio::task::spawn_blocking(move ||{
         juiz_lock(&crud_broker).unwrap().read_class(class_name.as_str(), function_name.as_str(), construct_capsule_map(CapsuleMap::new(), "DELETE", class_name.as_str(), function_name.as_str(), query, headers, remote_addr))
     }).await;
-    let r = json_output_wrap(v.unwrap());
-    r
+    codec_output_wrap(v.unwrap(), codec)
+}
+
+/// HTTPのメソッドに対応するCRUDの名前
+fn crud_method_name(method: &Method) -> &'static str {
+    match *method {
+        Method::POST => "create",
+        Method::PATCH | Method::PUT => "update",
+        Method::DELETE => "delete",
+        _ => "read",
+    }
+}
+
+/// トークンを確かめてACLが許す呼び出しだけをハンドラに通す
+async fn auth_middleware(
+    State(auth): State<Arc<HTTPAuth>>,
+    Path((class_name, function_name)): Path<(String, String)>,
+    query: Query<FullQuery>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = crud_method_name(request.method());
+    let token = HTTPAuth::token_from_header(request.headers().get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()));
+    match auth.check(token, method, class_name.as_str(), function_name.as_str(), query.identifier.as_deref()) {
+        AuthDecision::Allowed => next.run(request).await,
+        AuthDecision::Unauthorized => {
+            log::warn!("HTTPBroker refused {method} {class_name}/{function_name}. Token is missing or unknown.");
+            (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")], Json(jvalue!({
+                "message": "Unauthorized"
+            }))).into_response()
+        },
+        AuthDecision::Forbidden => {
+            log::warn!("HTTPBroker refused {method} {class_name}/{function_name}. Token is not permitted.");
+            (StatusCode::FORBIDDEN, Json(jvalue!({
+                "message": format!("Forbidden: {method} {class_name}/{function_name}")
+            }))).into_response()
+        },
+    }
 }
 
-pub fn object_router(crud_broker: Arc<Mutex<CRUDBroker>>) -> Router {
-    Router::new()
+pub fn object_router(crud_broker: Arc<Mutex<CRUDBroker>>, auth: Option<HTTPAuth>) -> Router {
+    let router = Router::new()
         .route("/:class_name/:function_name", 
                 routing::patch(object_patch_handler)
                 .get(object_get_handler)
                 .delete(object_delete_handler)
                 .post(object_post_handler)
                 .put(object_put_handler)
-        )
-        .with_state(Arc::clone(&crud_broker))
+        );
+    let router = match auth {
+        Some(auth) => router.route_layer(middleware::from_fn_with_state(Arc::new(auth), auth_middleware)),
+        None => router,
+    };
+    router.with_state(Arc::clone(&crud_broker))
 }
 
 #[derive(OpenApi)]
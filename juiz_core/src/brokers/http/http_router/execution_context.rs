@@ -42,6 +42,74 @@ pub fn stop_dummy(
     Json(_body): Json<Value>) {
 }
 
+#[allow(unused)]
+#[utoipa::path(
+    patch,
+    path = "/api/execution_context/pause",
+    params(
+        IdentifierQuery
+    ),
+    responses(
+        (status = 200, description = "System")
+    ),
+    tag = "universal.execution_context",
+)]
+pub fn pause_dummy(
+    _query: Query<IdentifierQuery>,
+    Json(_body): Json<Value>) {
+}
+
+#[allow(unused)]
+#[utoipa::path(
+    patch,
+    path = "/api/execution_context/resume",
+    params(
+        IdentifierQuery
+    ),
+    responses(
+        (status = 200, description = "System")
+    ),
+    tag = "universal.execution_context",
+)]
+pub fn resume_dummy(
+    _query: Query<IdentifierQuery>,
+    Json(_body): Json<Value>) {
+}
+
+#[allow(unused)]
+#[utoipa::path(
+    patch,
+    path = "/api/execution_context/reset",
+    params(
+        IdentifierQuery
+    ),
+    responses(
+        (status = 200, description = "System")
+    ),
+    tag = "universal.execution_context",
+)]
+pub fn reset_dummy(
+    _query: Query<IdentifierQuery>,
+    Json(_body): Json<Value>) {
+}
+
+#[allow(unused)]
+#[utoipa::path(
+    patch,
+    path = "/api/execution_context/step",
+    params(
+        IdentifierQuery
+    ),
+    request_body = Value,
+    responses(
+        (status = 200, description = "System")
+    ),
+    tag = "universal.execution_context",
+)]
+pub fn step_dummy(
+    _query: Query<IdentifierQuery>,
+    Json(_body): Json<Value>) {
+}
 
 #[allow(unused)]
 #[utoipa::path(
@@ -99,6 +167,10 @@ pub fn list_dummy(_query: Query<RecursiveQuery>) {
         get_state_dummy,
         start_dummy,
         stop_dummy,
+        pause_dummy,
+        resume_dummy,
+        reset_dummy,
+        step_dummy,
     ),
     components(schemas(
     ))
@@ -6,9 +6,12 @@ use std::path::PathBuf;
 use std::sync::{Mutex, Arc};
 // use crate::prelude::*;
 use crate::brokers::CRUDBroker;
+use crate::brokers::call_timeout::is_timeout_error;
+use crate::brokers::PayloadCodec;
+use crate::brokers::http::http_auth::HTTPAuth;
 
 use axum::extract::Multipart;
-use axum::http::HeaderValue;
+use axum::http::{header, HeaderMap, HeaderValue};
 
 // #[cfg(feature="opencv4")]
 // use opencv::{core::{Vector, VectorToVec}, imgcodecs::imencode};
@@ -77,6 +80,10 @@ pub struct FullQuery {
     recursive: Option<String>,
     system_uuid: Option<String>,
     topic_name: Option<String>,
+    /// 呼び出しのタイムアウト [sec]
+    timeout: Option<String>,
+    /// トピックへ転送された画像の符号化 (png, jpeg, raw)
+    topic_encoding: Option<String>,
 }
 
 #[allow(unused)]
@@ -174,17 +181,51 @@ pub fn full_query_to_map(query: &Query<FullQuery>) -> HashMap<String, String> {
             map.insert("topic_name".to_owned(), v);
         }
     }
+    match query.timeout.clone() {
+        None => {},
+        Some(v) => {
+            map.insert(TIMEOUT_PARAM_KEY.to_owned(), v);
+        }
+    }
+    match query.topic_encoding.clone() {
+        None => {},
+        Some(v) => {
+            map.insert(TOPIC_ENCODING_KEY.to_owned(), v);
+        }
+    }
     map
 }
 
+/// エラーのステータスコード。呼び出しが時間切れならGATEWAY_TIMEOUT、プラグインが"plugin_policy"に断られたならUNPROCESSABLE_ENTITY
+fn error_status_code(e: &juiz_sdk::anyhow::Error) -> StatusCode {
+    if is_timeout_error(e) {
+        StatusCode::GATEWAY_TIMEOUT
+    } else if matches!(e.downcast_ref::<JuizError>(), Some(JuizError::PluginRejectedError{..})) {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// エラーのレスポンスの本体。プラグインが断られたならプロキシがエラーを作り直せるように理由を載せる
+fn error_body(e: &juiz_sdk::anyhow::Error) -> Value {
+    let mut body = jvalue!({
+        "message": format!("Internal Server Error:  {:#}, {:}", e, e.to_string())
+    });
+    if let Some(JuizError::PluginRejectedError{plugin_path, reason}) = e.downcast_ref::<JuizError>() {
+        body["plugin_rejected"] = jvalue!({
+            "plugin_path": plugin_path,
+            "reason": reason,
+        });
+    }
+    body
+}
+
 #[allow(unused)]
 pub fn json_wrap(result: JuizResult<CapsulePtr>) -> impl IntoResponse {
     match result {
         Err(e) => {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(
-                jvalue!({
-                    "message": format!("Internal Server Error:  {:#}, {:}", e, e.to_string())
-                }))).into_response()
+            (error_status_code(&e), Json(error_body(&e))).into_response()
         },
         Ok(arc) => {
             
@@ -201,35 +242,81 @@ pub fn json_wrap(result: JuizResult<CapsulePtr>) -> impl IntoResponse {
     }
 }
 
-pub fn json_output_wrap(result: JuizResult<CapsulePtr>) -> impl IntoResponse {
-    //log::trace!("json_output_wrap() called");
-    if result.is_err() {
-        let e = result.err().unwrap();
-        return (StatusCode::INTERNAL_SERVER_ERROR, Json(
-            jvalue!({
-                "message": format!("Internal Server Error:  {:#}, {:}", e, e.to_string())
-            }))).into_response()
-    }
+/// 値の応答をcodecで符号化して返す。画像などのバイナリはcodecによらず同じ形で返す。エラーはいつもJSON
+pub fn codec_output_wrap(result: JuizResult<CapsulePtr>, codec: PayloadCodec) -> Response {
+    //log::trace!("codec_output_wrap() called");
     match result {
         Err(e) => {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(
-                jvalue!({
-                    "message": format!("Internal Server Error:  {:#}, {:}", e, e.to_string())
-                }))).into_response()
+            (error_status_code(&e), Json(error_body(&e))).into_response()
         },
         Ok(v) => {
-            capsule_ptr_to_response(v)
+            capsule_ptr_to_response(v, codec)
         }
     }
 }
 
+/// リクエストの本体の符号化をContent-Typeから決める。書かれていなければJSON
+pub(crate) fn request_codec(headers: &HeaderMap) -> Result<PayloadCodec, Response> {
+    match headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) {
+        None => Ok(PayloadCodec::Json),
+        Some(content_type) => PayloadCodec::from_content_type(content_type).ok_or_else(|| {
+            (StatusCode::UNSUPPORTED_MEDIA_TYPE, Json(jvalue!({
+                "message": format!("Unsupported Content-Type: {content_type}")
+            }))).into_response()
+        }),
+    }
+}
+
+/// 応答の符号化をAcceptから決める。書かれていなければJSON
+pub(crate) fn response_codec(headers: &HeaderMap) -> PayloadCodec {
+    headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()).map(PayloadCodec::from_accept).unwrap_or_default()
+}
+
+
+/// Capsuleのオプション (時刻・通し番号・作成元など) をJSONにして載せるヘッダ
+pub(crate) const OPTION_HEADER: &str = "X-Juiz-Options";
+
+/// 数値の配列を送るときのContent-Type。本体はリトルエンディアンの要素の列
+pub(crate) const NUMERIC_ARRAY_CONTENT_TYPE: &str = "application/x-juiz-numeric-array";
+
+/// 数値の配列の要素の型と形 (NumericArray::header()) をJSONにして載せるヘッダ
+pub(crate) const NUMERIC_ARRAY_HEADER: &str = "X-Juiz-Numeric-Array";
+
+/// オプションがあればレスポンスのヘッダに載せる
+fn append_option_header(v: &CapsulePtr, response: &mut axum::http::Response<Body>) {
+    let options = match v.get_options() {
+        Ok(options) if !options.is_empty() => options,
+        _ => return,
+    };
+    match serde_json::to_string(&options).ok().and_then(|s| HeaderValue::from_str(s.as_str()).ok()) {
+        Some(hv) => { response.headers_mut().append(OPTION_HEADER, hv); },
+        None => log::warn!("capsule_ptr_to_response() failed to encode options({options:?}) to header."),
+    }
+}
 
 //#[cfg(not(feature= "opencv4"))]
-fn capsule_ptr_to_response(v: CapsulePtr) -> axum::http::Response<Body> {
+fn capsule_ptr_to_response(v: CapsulePtr, codec: PayloadCodec) -> axum::http::Response<Body> {
+    let mut response = capsule_ptr_to_response_body(&v, codec);
+    append_option_header(&v, &mut response);
+    response
+}
+
+fn capsule_ptr_to_response_body(v: &CapsulePtr, codec: PayloadCodec) -> axum::http::Response<Body> {
     use juiz_sdk::image::ImageFormat;
     
 
-    if v.is_value().unwrap() {
+    // MessagePackとCBORでは値と空のCapsuleを__capsule__に包んで返す
+    if codec != PayloadCodec::Json && (v.is_value().unwrap() || v.is_empty().unwrap()) {
+        let mut r = match codec.encode_capsule_ptr(v.clone()) {
+            Ok(bytes) => Response::builder()
+                .header("Content-Type", codec.content_type())
+                .status(StatusCode::OK)
+                .body(Body::from(bytes)).unwrap().into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(error_body(&e))).into_response(),
+        };
+        r.headers_mut().append("Cache-Control", HeaderValue::from_str("no-cache").unwrap());
+        r
+    } else if v.is_value().unwrap() {
         v.lock_as_value(|value| {
             let mut r = Json(value).into_response();
             let hdrs = r.headers_mut();
@@ -240,7 +327,7 @@ fn capsule_ptr_to_response(v: CapsulePtr) -> axum::http::Response<Body> {
 
         use std::io::{BufWriter, Cursor};
         // log::trace!("Detect Image Response v={v:?}");
-        v.lock_as_image(|image| {
+        v.lock_as_image_view(|image| {
 
             let mut buffer = BufWriter::new(Cursor::new(Vec::new()));
             image.write_to(&mut buffer, ImageFormat::Png).unwrap();
@@ -252,6 +339,21 @@ fn capsule_ptr_to_response(v: CapsulePtr) -> axum::http::Response<Body> {
                         .status(StatusCode::OK)
                         .body(Body::from(buffer.into_inner().unwrap().into_inner())).unwrap().into_response()
         }).unwrap()
+    } else if v.is_bytes().unwrap() {
+        v.lock_as_bytes(|bytes| {
+            Response::builder()
+                        .header("Content-Type", "application/octet-stream")
+                        .status(StatusCode::OK)
+                        .body(Body::from(bytes.to_vec())).unwrap().into_response()
+        }).unwrap()
+    } else if v.is_numeric_array().unwrap() {
+        v.lock_as_numeric_array(|array| {
+            Response::builder()
+                        .header("Content-Type", NUMERIC_ARRAY_CONTENT_TYPE)
+                        .header(NUMERIC_ARRAY_HEADER, array.header().to_string())
+                        .status(StatusCode::OK)
+                        .body(Body::from(array.to_le_bytes())).unwrap().into_response()
+        }).unwrap()
     } else {
         Json(jvalue!({})).into_response()
     }
@@ -319,11 +421,23 @@ pub async fn multipart_to_capsule_map(mut multipart: Multipart) -> JuizResult<Ca
                                     }
                                 }
                                 "application/json" => {
-                                    let ft = field.text().await;
-                                    println!("match ({field_name}, {:?})", ft);
-                                    let v = serde_json::Value::from(ft.unwrap().as_str());
+                                    let text = field.text().await.map_err(|e| anyhow!(JuizError::InvalidArgumentError{message:format!("Multipart/FormData field '{field_name}' can not be read. Err({e}).")}))?;
+                                    // JSONとして読めなければ文字列として渡す
+                                    let v = serde_json::from_str::<Value>(text.as_str()).unwrap_or_else(|_| Value::from(text));
                                     cm.insert(field_name,v.into());
                                 }
+                                "application/octet-stream" => {
+                                    let bytes = field.bytes().await.map_err(|e| anyhow!(JuizError::InvalidArgumentError{message:format!("Multipart/FormData field '{field_name}' can not be read. Err({e}).")}))?;
+                                    cm.insert(field_name, bytes.to_vec().into());
+                                }
+                                NUMERIC_ARRAY_CONTENT_TYPE => {
+                                    let header = field.headers().get(NUMERIC_ARRAY_HEADER)
+                                        .and_then(|hv| hv.to_str().ok())
+                                        .and_then(|s| serde_json::from_str::<Value>(s).ok())
+                                        .ok_or_else(|| anyhow!(JuizError::InvalidArgumentError{message:format!("Multipart/FormData field '{field_name}' does not have '{NUMERIC_ARRAY_HEADER}' header.")}))?;
+                                    let bytes = field.bytes().await.map_err(|e| anyhow!(JuizError::InvalidArgumentError{message:format!("Multipart/FormData field '{field_name}' can not be read. Err({e}).")}))?;
+                                    cm.insert(field_name, NumericArray::from_header_and_bytes(&header, &bytes)?.into());
+                                }
                                 _ => {
                                     log::error!("Multipart/FormData does not have available 'content-type' field ({content_type}). This can not be handled.");
                                     return Err(anyhow!(JuizError::InvalidArgumentError{message:format!("Multipart/FormData does not have available 'content-type' field ({content_type}). This can not be handled.")}))
@@ -359,7 +473,7 @@ pub async fn multipart_to_capsule_map(mut multipart: Multipart) -> JuizResult<Ca
 )]
 struct ApiDoc;
 
-pub fn app_new(crud_broker: Arc<Mutex<CRUDBroker>>, static_filepaths: Option<Vec<(String, PathBuf)>>) -> Router {
+pub fn app_new(crud_broker: Arc<Mutex<CRUDBroker>>, static_filepaths: Option<Vec<(String, PathBuf)>>, auth: Option<HTTPAuth>) -> Router {
     let mut api = ApiDoc::openapi();
     api.merge(system::ApiDoc::openapi());
     api.merge(process::ApiDoc::openapi());
@@ -372,7 +486,7 @@ pub fn app_new(crud_broker: Arc<Mutex<CRUDBroker>>, static_filepaths: Option<Vec
     log::trace!("app_new(static_filepaths: {static_filepaths:?}) called");
     let mut r = Router::new()
             .merge(SwaggerUi::new("/docs").url("/api-docs/openapi.json", api))
-            .nest("/api/", any::object_router(crud_broker.clone()));
+            .nest("/api/", any::object_router(crud_broker.clone(), auth));
 
     match static_filepaths {
         Some(paths) => {
@@ -46,6 +46,18 @@ pub fn fslist_handler_dummy(
 ){
 }
 
+#[allow(unused)]
+#[utoipa::path(
+    get,
+    path = "/api/system/discovered_peers",
+    responses(
+        (status = 200, description = "System")
+    ),
+    tag = "universal.system",
+)]
+pub fn discovered_peers_dummy(){
+}
+
 #[allow(unused)]
 #[utoipa::path(
     patch,
@@ -90,6 +102,7 @@ Json(_body): Json<Value>) {
         profile_handler_dummy,
         uuid_dummy,
         fslist_handler_dummy,
+        discovered_peers_dummy,
         add_subsystem_dummy,
         add_mastersystem_dummy,
     ),
@@ -0,0 +1,277 @@
+//! HTTPBrokerとHTTPBrokerProxyのTLS
+//!
+//! ブローカーのマニフェストの"tls"で証明書と鍵を渡すか、自己署名の証明書を作らせる。
+//!
+//! ```json
+//! "tls": {"cert_path": "server.pem", "key_path": "server.key"}
+//! "tls": {"self_signed": true, "subject_alt_names": ["robot-a.local"], "cert_path": "self.pem", "key_path": "self.key"}
+//! ```
+//!
+//! 自己署名でcert_pathとkey_pathを渡すと、ファイルが無いときだけ作って保存し、次からはそれを使う。
+//! 再起動しても指紋が変わらないので、プロキシ側で指紋を固定できる。
+//!
+//! プロキシのマニフェストの"tls"では相手の確かめ方を一つ選ぶ。
+//!
+//! ```json
+//! "tls": {"ca_path": "ca.pem"}
+//! "tls": {"fingerprint": "AB:CD:..."}
+//! "tls": {"trust_on_first_use": true, "known_hosts_path": "known_hosts"}
+//! ```
+//!
+//! trust_on_first_useは初めてつないだときの指紋をknown_hosts_path (省略時は~/.juiz/known_hosts) に記録し、
+//! 以降はその指紋と違う相手を拒む。
+
+use std::{fs, io::Write, path::{Path, PathBuf}, sync::Arc};
+use juiz_sdk::anyhow::anyhow;
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{ring::default_provider, verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig, SignatureScheme,
+};
+
+use crate::prelude::*;
+
+fn tls_setting_error(message: String) -> juiz_sdk::anyhow::Error {
+    anyhow!(JuizError::InvalidSettingError{message})
+}
+
+/// 証明書のSHA-256の指紋。`openssl x509 -fingerprint -sha256`と同じくコロン区切りの大文字16進数
+pub(crate) fn fingerprint(cert: &CertificateDer<'_>) -> String {
+    ring::digest::digest(&ring::digest::SHA256, cert.as_ref()).as_ref().iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.chars().filter(|c| *c != ':').collect::<String>().to_ascii_uppercase()
+}
+
+fn load_certs(path: &Path) -> JuizResult<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| tls_setting_error(format!("Loading certificate ({path:?}) failed. {e}")))?;
+    if certs.is_empty() {
+        return Err(tls_setting_error(format!("Certificate file ({path:?}) has no certificate.")));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> JuizResult<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path).map_err(|e| tls_setting_error(format!("Loading private key ({path:?}) failed. {e}")))
+}
+
+fn optional_path(tls: &Value, key: &str) -> JuizResult<Option<PathBuf>> {
+    match tls.get(key) {
+        None => Ok(None),
+        Some(v) => v.as_str().map(|s| Some(PathBuf::from(s))).ok_or_else(|| tls_setting_error(format!("TLS setting '{key}' must be string."))),
+    }
+}
+
+/// 自己署名の証明書に載せる名前
+fn subject_alt_names(tls: &Value, host: &str) -> JuizResult<Vec<String>> {
+    let mut names = vec!["localhost".to_owned(), "127.0.0.1".to_owned()];
+    if host != "0.0.0.0" && !names.iter().any(|n| n == host) {
+        names.push(host.to_owned());
+    }
+    if let Some(v) = tls.get("subject_alt_names") {
+        for name in get_array(v)?.iter() {
+            let name = name.as_str().ok_or_else(|| tls_setting_error("TLS setting 'subject_alt_names' must be array of string.".to_owned()))?;
+            names.push(name.to_owned());
+        }
+    }
+    Ok(names)
+}
+
+/// 自己署名の証明書を作る。パスが渡されていれば保存する
+fn generate_self_signed(names: Vec<String>, cert_path: Option<&Path>, key_path: Option<&Path>) -> JuizResult<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let certified = rcgen::generate_simple_self_signed(names)
+        .map_err(|e| tls_setting_error(format!("Generating self-signed certificate failed. {e}")))?;
+    if let (Some(cert_path), Some(key_path)) = (cert_path, key_path) {
+        fs::write(cert_path, certified.cert.pem())?;
+        write_private(key_path, certified.key_pair.serialize_pem().as_bytes())?;
+        log::info!("Self-signed certificate is saved to {cert_path:?}.");
+    }
+    let key = PrivateKeyDer::try_from(certified.key_pair.serialize_der()).map_err(|e| tls_setting_error(e.to_owned()))?;
+    Ok((vec![certified.cert.der().clone()], key))
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &[u8]) -> JuizResult<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+    fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?.write_all(contents)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &[u8]) -> JuizResult<()> {
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// ブローカーのマニフェストの"tls"を読んだもの
+///
+/// 読むだけでファイルは読み書きしない。証明書を読んだり作ったりするのはserver_config()を呼んだとき。
+#[derive(Debug, Clone)]
+pub(crate) struct ServerTLS {
+    cert_path: Option<PathBuf>,
+    key_path: Option<PathBuf>,
+    /// 自己署名なら証明書に載せる名前
+    self_signed_names: Option<Vec<String>>,
+}
+
+impl ServerTLS {
+
+    /// ブローカーのマニフェストの"tls"を読んで確かめる。"tls"が無ければNone
+    pub(crate) fn from_broker_manifest(manifest: &Value) -> JuizResult<Option<Self>> {
+        let tls = match manifest.get("tls") {
+            None => return Ok(None),
+            Some(tls) => tls,
+        };
+        let cert_path = optional_path(tls, "cert_path")?;
+        let key_path = optional_path(tls, "key_path")?;
+        let self_signed = obj_get_bool(tls, "self_signed").unwrap_or(false);
+        if !self_signed {
+            match (cert_path.as_deref(), key_path.as_deref()) {
+                (Some(cert_path), Some(key_path)) => for path in [cert_path, key_path] {
+                    if !path.exists() {
+                        return Err(tls_setting_error(format!("HTTPBroker tls file ({path:?}) does not exist.")));
+                    }
+                },
+                _ => return Err(tls_setting_error("HTTPBroker tls needs both 'cert_path' and 'key_path', or 'self_signed'.".to_owned())),
+            }
+        }
+        let self_signed_names = match self_signed {
+            true => Some(subject_alt_names(tls, obj_get_str(manifest, "host").unwrap_or("0.0.0.0"))?),
+            false => None,
+        };
+        Ok(Some(ServerTLS{cert_path, key_path, self_signed_names}))
+    }
+
+    /// サーバーの設定と証明書の指紋を作る。自己署名で証明書が無ければここで作る
+    pub(crate) fn server_config(&self) -> JuizResult<(Arc<ServerConfig>, String)> {
+        let (certs, key) = match (self.cert_path.as_deref(), self.key_path.as_deref(), self.self_signed_names.as_ref()) {
+            (Some(cert_path), Some(key_path), names) if names.is_none() || (cert_path.exists() && key_path.exists()) => {
+                (load_certs(cert_path)?, load_key(key_path)?)
+            },
+            (cert_path, key_path, Some(names)) => generate_self_signed(names.clone(), cert_path, key_path)?,
+            _ => return Err(tls_setting_error("HTTPBroker tls needs both 'cert_path' and 'key_path', or 'self_signed'.".to_owned())),
+        };
+        let fingerprint = fingerprint(&certs[0]);
+        let mut config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| tls_setting_error(format!("HTTPBroker certificate and key do not match. {e}")))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok((Arc::new(config), fingerprint))
+    }
+}
+
+/// 指紋で相手の証明書を確かめる
+///
+/// 証明書の連鎖やホスト名は見ないが、ハンドシェイクの署名は確かめる。
+#[derive(Debug)]
+struct FingerprintVerifier {
+    broker_name: String,
+    /// Noneならknown_hostsで確かめる
+    pinned: Option<String>,
+    known_hosts_path: PathBuf,
+    provider: Arc<CryptoProvider>,
+}
+
+impl FingerprintVerifier {
+
+    fn known_fingerprint(&self) -> std::io::Result<Option<String>> {
+        if !self.known_hosts_path.exists() {
+            return Ok(None);
+        }
+        Ok(fs::read_to_string(&self.known_hosts_path)?.lines()
+            .filter_map(|line| line.split_once(' '))
+            .find(|(name, _)| *name == self.broker_name)
+            .map(|(_, fp)| fp.trim().to_owned()))
+    }
+
+    fn remember(&self, fingerprint: &str) -> std::io::Result<()> {
+        if let Some(dir) = self.known_hosts_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&self.known_hosts_path)?;
+        writeln!(file, "{} {fingerprint}", self.broker_name)
+    }
+}
+
+impl ServerCertVerifier for FingerprintVerifier {
+    fn verify_server_cert(&self, end_entity: &CertificateDer<'_>, _intermediates: &[CertificateDer<'_>], _server_name: &ServerName<'_>, _ocsp_response: &[u8], _now: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+        let actual = fingerprint(end_entity);
+        let expected = match self.pinned.clone() {
+            Some(pinned) => pinned,
+            None => match self.known_fingerprint().map_err(|e| rustls::Error::General(format!("Reading {:?} failed. {e}", self.known_hosts_path)))? {
+                Some(known) => known,
+                None => {
+                    log::warn!("HTTPBrokerProxy({}) trusts the certificate (fingerprint={actual}) on first use.", self.broker_name);
+                    self.remember(&actual).map_err(|e| rustls::Error::General(format!("Writing {:?} failed. {e}", self.known_hosts_path)))?;
+                    return Ok(ServerCertVerified::assertion());
+                }
+            }
+        };
+        if normalize_fingerprint(&expected) != normalize_fingerprint(&actual) {
+            log::error!("HTTPBrokerProxy({}) refused the certificate. fingerprint={actual}, expected={expected}", self.broker_name);
+            return Err(rustls::Error::General(format!("Certificate fingerprint of {} does not match. fingerprint={actual}", self.broker_name)));
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn default_known_hosts_path() -> JuizResult<PathBuf> {
+    home::home_dir().map(|home| home.join(".juiz").join("known_hosts"))
+        .ok_or_else(|| tls_setting_error("Home directory can not be found. Set 'known_hosts_path'.".to_owned()))
+}
+
+/// プロキシのマニフェストの"tls"からクライアントの設定を作る。"tls"が無ければNone
+pub(crate) fn client_config_from_manifest(broker_name: &str, manifest: &Value) -> JuizResult<Option<ClientConfig>> {
+    let tls = match manifest.get("tls") {
+        None => return Ok(None),
+        Some(tls) => tls,
+    };
+    let provider = Arc::new(default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+    let pinned = match tls.get("fingerprint") {
+        None => None,
+        Some(v) => Some(v.as_str().ok_or_else(|| tls_setting_error("TLS setting 'fingerprint' must be string.".to_owned()))?.to_owned()),
+    };
+    let trust_on_first_use = obj_get_bool(tls, "trust_on_first_use").unwrap_or(false);
+    let config = match (optional_path(tls, "ca_path")?, pinned) {
+        (Some(ca_path), None) if !trust_on_first_use => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(&ca_path)? {
+                roots.add(cert).map_err(|e| tls_setting_error(format!("CA certificate ({ca_path:?}) is invalid. {e}")))?;
+            }
+            builder.with_root_certificates(roots).with_no_client_auth()
+        },
+        (None, pinned) if pinned.is_some() != trust_on_first_use => {
+            let known_hosts_path = match optional_path(tls, "known_hosts_path")? {
+                Some(path) => path,
+                None if trust_on_first_use => default_known_hosts_path()?,
+                None => PathBuf::new(),
+            };
+            let verifier = FingerprintVerifier{broker_name: broker_name.to_owned(), pinned, known_hosts_path, provider};
+            builder.dangerous().with_custom_certificate_verifier(Arc::new(verifier)).with_no_client_auth()
+        },
+        _ => return Err(tls_setting_error(format!("HTTPBrokerProxy({broker_name}) tls needs exactly one of 'ca_path', 'fingerprint' or 'trust_on_first_use'."))),
+    };
+    Ok(Some(config))
+}
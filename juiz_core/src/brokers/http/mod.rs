@@ -1,6 +1,8 @@
 pub mod http_router;
 pub mod http_broker;
 pub mod http_broker_proxy;
+pub(crate) mod http_auth;
+pub(crate) mod http_tls;
 
 pub use http_broker::http_broker_factory;
 pub use http_broker_proxy::http_broker_proxy_factory;
\ No newline at end of file
@@ -3,8 +3,9 @@ use std::{sync::{Arc, Mutex}, thread::sleep, time::Duration};
 use juiz_sdk::anyhow;
 
 use super::super::core_broker::CoreBrokerPtr;
+use super::ipc_codec::{check_frame_size, decode_capsule_map, encode_response, negotiate, Protocol, CAPABILITY_SHARED_MEMORY, MAGIC, PROTOCOL_VERSION, READ_CHUNK_SIZE};
 use crate::{brokers::broker_ptr::BrokerPtr, prelude::*};
-use crate::brokers::{broker_factory_impl::create_broker_factory_impl, BrokerFactory, CRUDBrokerHolder};
+use crate::brokers::{broker_factory_impl::create_broker_factory_impl, messenger::messenger_broker::handle_function, BrokerFactory, CRUDBrokerHolder};
 use crate::brokers::CRUDBroker;
 use interprocess::local_socket::{prelude::*, traits::Stream, GenericFilePath, GenericNamespaced, ListenerOptions};
 use std::io::{self, prelude::*, BufReader};
@@ -31,49 +32,8 @@ async fn on_start(broker_manifest: Value, crud_broker: Arc<Mutex<CRUDBroker>>) -
     }
 }
 
-fn extract_method_name(args: & CapsuleMap) -> JuizResult<&String> {
-    let err = |name: &str | anyhow::Error::from(JuizError::CapsuleDoesNotIncludeParamError{ name: name.to_owned() });
-    Ok(args.get_param("method_name").ok_or_else( || err("method_name") )?)
-}
-
-
-fn extract_class_name<'a>(args: &'a CapsuleMap) -> JuizResult<String> {
-    // method_name, class_name, function_name, params
-    let err = |name: &str | anyhow::Error::from(JuizError::CapsuleDoesNotIncludeParamError{ name: name.to_owned() });
-    let class_name = args.get_param("class_name").ok_or_else( || err("class_name") )?;
-    Ok(class_name.to_owned())
-}
-
-
-fn extract_function_name<'a>(args: &'a CapsuleMap) -> JuizResult<&String> {
-    let err = |name: &str | anyhow::Error::from(JuizError::CapsuleDoesNotIncludeParamError{ name: name.to_owned() });
-    let function_name = args.get_param("function_name").ok_or_else( || err("function_name") )?;
-    Ok(function_name)
-}
-
-fn handle_function(crud_broker: Arc<Mutex<CRUDBroker>>, args: CapsuleMap) -> JuizResult<CapsulePtr> {
-    log::info!("MessengerBroker::handle_function() called");
-    log::trace!(" - args: CapsuleMap = {args:?}");
-    let class_name = extract_class_name(&args)?;
-    let function_name = extract_function_name(&args)?.to_owned();
-    //log::trace!("MessengerBroker::handle_function() with method_name = {method_name:}");
-    let response = match  extract_method_name(&args)?.as_str() {
-        "CREATE" => juiz_lock(&crud_broker)?.create_class(class_name.as_str(),  function_name.as_str(), args),
-        "READ" =>  juiz_lock(&crud_broker)?.read_class(class_name.as_str(), function_name.as_str(), args),
-        "UPDATE" =>  juiz_lock(&crud_broker)?.update_class(class_name.as_str(), function_name.as_str(), args),
-        "DELETE" => juiz_lock(&crud_broker)?.delete_class(class_name.as_str(), function_name.as_str(), args),
-        _ => {
-            Err(anyhow::Error::from(JuizError::CRUDBRokerCanNotFindMethodError{method_name: "".to_owned()}))
-        }
-    };
-    log::trace!(" - returns: {response:?}");
-    response
-}
-
-
 fn handle_buffer_function(crud_broker: Arc<Mutex<CRUDBroker>>, conn: &mut BufReader<LocalSocketStream>, buffer: &String) -> JuizResult<()> {
     let value: Value = handle_buffer(crud_broker, buffer)?;
-    println!("vout{:?}, vstr{:}", value, (value.to_string() + "\n"));
     match conn.get_mut().write_all((value.to_string() + "\n").as_bytes()) {
         Err(e) => {
             log::error!("Error({e:?}) in IPCBroker::routine()");
@@ -89,34 +49,83 @@ fn handle_buffer_function(crud_broker: Arc<Mutex<CRUDBroker>>, conn: &mut BufRea
 }
 
 fn handle_buffer(crud_broker: Arc<Mutex<CRUDBroker>>, buffer: &String) -> JuizResult<Value> {
-    let value: CapsuleMap = juiz_sdk::serde_json::from_str::<Value>(buffer.as_str())?.try_into()?;
+    let value = CapsuleMap::from_json_value(juiz_sdk::serde_json::from_str::<Value>(buffer.as_str())?)?;
     let result = handle_function(crud_broker.clone(), value)?;
+    // 改行区切りJSONでは画像は送れない
+    if !result.is_value()? {
+        return Err(anyhow::Error::from(JuizError::CapsuleIsNotValueTypeError{}));
+    }
     capsule_to_value(result)
 }
 
-async fn handle_conn(crud_broker: &Arc<Mutex<CRUDBroker>>, mut conn: interprocess::local_socket::tokio::Stream) -> io::Result<()> {
-    use tokio::{
-        io::{AsyncBufReadExt, AsyncWriteExt},
-        try_join,
-    };
+async fn handle_conn(crud_broker: &Arc<Mutex<CRUDBroker>>, conn: interprocess::local_socket::tokio::Stream, supported: Protocol) -> JuizResult<()> {
+    use tokio::io::AsyncReadExt;
     let mut recver = tokio::io::BufReader::new(&conn);
-    //let mut sender = conn;
+    let sender = &conn;
 
-    // Allocate a sizeable buffer for receiving. This size should be big enough and easy to
-    // find for the allocator.
-    let mut buffer = String::with_capacity(128);
-    let recv = recver.read_line(&mut buffer);
-    try_join!(recv)?;
-    let value = handle_buffer(crud_broker.clone(), &mut buffer).or_else(|_e| { Err(io::Error::new(io::ErrorKind::Other, "handle_buffer failed.")) })?;
-    //let value: CapsuleMap = serde_json::from_str::<Value>(buffer.as_str())?.try_into()?;
-    //let result = handle_function(crud_broker.clone(), value)?;
-    //let value: Value =  capsule_to_value(result)?;
-    println!("vout{:?}, vstr{:}", value, (value.to_string() + "\n"));
-    let vstr = (value.to_string() + "\n").to_owned();
-    let send = conn.write_all(vstr.as_bytes());
-    // Run both operations concurrently.
-    try_join!(send)?;
-    Ok(())
+    // 先頭4バイトでバイナリのフレームか従来の改行区切りJSONかを判別する
+    let mut head = [0u8; 4];
+    match recver.read_exact(&mut head).await {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+        x => x?,
+    };
+    if &head == MAGIC {
+        handle_frames(crud_broker, recver, sender, supported).await
+    } else {
+        handle_lines(crud_broker, recver, sender, head.to_vec()).await
+    }
+}
+
+async fn handle_frames<R, W>(crud_broker: &Arc<Mutex<CRUDBroker>>, mut recver: R, mut sender: W, supported: Protocol) -> JuizResult<()> 
+        where R: tokio::io::AsyncRead + Unpin, W: tokio::io::AsyncWrite + Unpin {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let version = recver.read_u32().await?;
+    let protocol = negotiate(Protocol::new(version, recver.read_u32().await?), supported)?;
+    sender.write_all(MAGIC).await?;
+    sender.write_u32(protocol.version).await?;
+    sender.write_u32(protocol.capabilities).await?;
+    log::trace!("ipc_broker::handle_frames() negotiated {protocol:?}");
+
+    // 前の応答で共有メモリに置いた画像。クライアントは応答を読み終えてから次のリクエストを送るので、それまで持っておく。
+    // 書き込みに失敗したり接続が切れたりしてdropされると、開かれなかった画像の参照は取り消される。
+    let mut leases: Vec<SharedImageLease> = Vec::new();
+    loop {
+        let size = match recver.read_u32().await {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            x => x?,
+        };
+        leases.clear();
+        // 申告された長さではなく、実際に届いた分だけバッファを伸ばす
+        let size = check_frame_size(size as usize)?;
+        let mut payload = Vec::with_capacity(size.min(READ_CHUNK_SIZE));
+        (&mut recver).take(size as u64).read_to_end(&mut payload).await?;
+        if payload.len() != size {
+            return Err(anyhow::Error::from(io::Error::from(io::ErrorKind::UnexpectedEof)));
+        }
+        let crud = crud_broker.clone();
+        let response = tokio::task::spawn_blocking(move || {
+            let mut leases = Vec::new();
+            let response = encode_response(&decode_capsule_map(&payload).and_then(|args| handle_function(crud, args)), protocol, &mut leases)?;
+            Ok::<_, anyhow::Error>((response, leases))
+        }).await??;
+        leases = response.1;
+        sender.write_u32(check_frame_size(response.0.len())? as u32).await?;
+        sender.write_all(&response.0).await?;
+    }
+}
+
+async fn handle_lines<R, W>(crud_broker: &Arc<Mutex<CRUDBroker>>, mut recver: R, mut sender: W, mut line: Vec<u8>) -> JuizResult<()> 
+        where R: tokio::io::AsyncBufRead + Unpin, W: tokio::io::AsyncWrite + Unpin {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+    loop {
+        if recver.read_until(b'\n', &mut line).await? == 0 && line.is_empty() {
+            return Ok(());
+        }
+        let buffer = String::from_utf8(std::mem::take(&mut line))?;
+        let crud = crud_broker.clone();
+        let value = tokio::task::spawn_blocking(move || handle_buffer(crud, &buffer)).await??;
+        sender.write_all((value.to_string() + "\n").as_bytes()).await?;
+    }
 }
 
 async fn on_start_inner_tokio(broker_manifest: Value, crud_broker: Arc<Mutex<CRUDBroker>>) -> JuizResult<()> {
@@ -134,6 +143,11 @@ async fn on_start_inner_tokio(broker_manifest: Value, crud_broker: Arc<Mutex<CRU
         namespace.as_str().to_fs_name::<GenericFilePath>()?
     };
     log::trace!("IPBrokerCore (namespace={:?})", name);
+    // 共有メモリで画像を受け渡すかどうか。受け手が同じマシンにいることが前提。
+    let supported = match obj_get_bool(&broker_manifest, "shared_memory") {
+        Ok(true) => Protocol::new(PROTOCOL_VERSION, CAPABILITY_SHARED_MEMORY),
+        _ => Protocol::new(PROTOCOL_VERSION, 0),
+    };
     let opts = ListenerOptions::new().name(name);
 
     let listener = match opts.create_tokio() {
@@ -153,7 +167,10 @@ async fn on_start_inner_tokio(broker_manifest: Value, crud_broker: Arc<Mutex<CRU
 		}
 		x => x?,
 	};
+    crud_broker.lock().unwrap().set_started();
 
+    // 接続ごとのタスクはこのループと一緒に止まるように持っておく (stop()でon_startが止まったら切断する)
+    let mut connections = tokio::task::JoinSet::new();
     loop {
         let conn = match listener.accept().await {
 			Ok(c) => c,
@@ -167,11 +184,12 @@ async fn on_start_inner_tokio(broker_manifest: Value, crud_broker: Arc<Mutex<CRU
         // Spawn new parallel asynchronous tasks onto the Tokio runtime and hand the connection
 		// over to them so that multiple clients could be processed simultaneously in a
 		// lightweight fashion.
-		tokio::spawn(async move {
+		while connections.try_join_next().is_some() {}
+		connections.spawn(async move {
 			// The outer match processes errors that happen when we're connecting to something.
 			// The inner if-let processes errors that happen during the connection.
-			if let Err(e) = handle_conn(&crud, conn).await {
-				eprintln!("Error while handling connection: {e}");
+			if let Err(e) = handle_conn(&crud, conn, supported).await {
+				log::error!("IPCBroker: Error while handling connection. Error({e})");
 			}
 		});
     }
@@ -1,7 +1,8 @@
-use std::{cell::RefCell, io::BufReader, sync::{Arc, Mutex}, time::Duration};
-use std::io::prelude::*;
+use std::{cell::{Cell, RefCell}, io::{BufReader, ErrorKind}, sync::{Arc, Mutex}, time::Duration};
 
 use crate::prelude::*;
+use crate::brokers::call_timeout::timeout_error;
+use super::ipc_codec::{decode_response, encode_capsule_map, handshake, read_frame, write_frame, Protocol, CAPABILITY_SHARED_MEMORY};
 use interprocess::local_socket::{prelude::*, GenericFilePath, Stream};
 
 use crate::brokers::messenger_broker_proxy_factory::create_messenger_broker_proxy_factory;
@@ -12,11 +13,11 @@ use crate::brokers::{BrokerProxyFactory, MessengerBrokerProxyCore, MessengerBrok
 
 // pub type IPCBrokerProxy = MessengerBrokerProxy;
 
-#[allow(unused)]
 pub struct IPCBrokerProxyCore {
     name: String,
-    buf_reader: RefCell<BufReader<Stream>>,
-    buf_size: usize,
+    /// タイムアウトした接続は応答が途中で残っているかもしれないので捨てて、次の呼び出しでつなぎ直す
+    buf_reader: RefCell<Option<BufReader<Stream>>>,
+    protocol: Cell<Protocol>,
     //sender_receiver: Arc<Mutex<ProxySideSenderReceiverPair>>,
 }
 
@@ -24,6 +25,33 @@ impl IPCBrokerProxyCore {
     //pub fn new(name: &str, buf_reader: BufReader<Stream>, buf_size: usize) -> IPCBrokerProxyCore {
     //    IPCBrokerProxyCore{name: name.to_owned(), buf_reader: RefCell::new(buf_reader), buf_size}
     // }
+
+    fn connect(object_name: &str) -> JuizResult<(Stream, Protocol)> {
+        /* let name = if GenericNamespaced::is_supported() {
+            object_name.to_ns_name::<GenericNamespaced>()?
+        } else {
+            object_name.to_fs_name::<GenericFilePath>()?
+        }; */
+        let name = format!("/tmp/{:}", object_name).to_fs_name::<GenericFilePath>()?;
+        let mut conn = Stream::connect(name)?;
+        // ローカルソケットでつながる相手は同じマシンにいるので、共有メモリはいつでも使える
+        let protocol = handshake(&mut conn, CAPABILITY_SHARED_MEMORY)?;
+        log::trace!("IPCBrokerProxyCore({object_name}) negotiated {protocol:?}");
+        Ok((conn, protocol))
+    }
+
+    fn send_and_receive_inner(&self, buf_reader: &mut BufReader<Stream>, value: &CapsuleMap, timeout: Option<Duration>) -> JuizResult<CapsulePtr> {
+        buf_reader.get_ref().set_recv_timeout(timeout)?;
+        // サーバーはリクエストを読み終えてから応答するので、共有メモリのleaseは応答が来るまで持っておけばよい
+        let mut leases = Vec::new();
+        let payload = encode_capsule_map(value, self.protocol.get(), &mut leases)?;
+        write_frame(buf_reader.get_mut(), &payload)?;
+        decode_response(&read_frame(buf_reader)?)
+    }
+}
+
+fn is_io_timeout(e: &juiz_sdk::anyhow::Error) -> bool {
+    e.downcast_ref::<std::io::Error>().is_some_and(|e| matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut))
 }
 
 pub struct IPCBrokerProxyCoreFactory {
@@ -38,34 +66,36 @@ impl IPCBrokerProxyCoreFactory {
 
 impl MessengerBrokerProxyCoreFactory for IPCBrokerProxyCoreFactory {
     fn create_core(&self, object_name: &str) -> JuizResult<Box<dyn MessengerBrokerProxyCore>> {
-        /* let name = if GenericNamespaced::is_supported() {
-            object_name.to_ns_name::<GenericNamespaced>()?
-        } else {
-            object_name.to_fs_name::<GenericFilePath>()?
-        }; */
-        let name = format!("/tmp/{:}", object_name).to_fs_name::<GenericFilePath>()?;
-        let buf_size: usize = 4096;
-        let conn = Stream::connect(name)?;
-        Ok(Box::new(IPCBrokerProxyCore{name: object_name.to_owned(), buf_reader: RefCell::new(BufReader::new(conn)), buf_size}))
+        let (conn, protocol) = IPCBrokerProxyCore::connect(object_name)?;
+        Ok(Box::new(IPCBrokerProxyCore{name: object_name.to_owned(), buf_reader: RefCell::new(Some(BufReader::new(conn))), protocol: Cell::new(protocol)}))
     }
 }
 
 impl MessengerBrokerProxyCore for IPCBrokerProxyCore {
-    fn send_and_receive(&self, value: CapsuleMap, _timeout: Duration) -> JuizResult<CapsulePtr> {
+    fn send_and_receive(&self, value: CapsuleMap, timeout: Option<Duration>) -> JuizResult<CapsulePtr> {
         log::trace!("IPCBrokerProxyCore::send_and_receive(value={value:?}) called");
-        let mut buffer = String::with_capacity(self.buf_size);
-        let v: Value = value.into();
-        let strv = v.to_string() + "\n";
-        log::trace!(" - payload = {strv:}");
-        self.buf_reader.borrow_mut().get_mut().write_all(strv.as_bytes())?;
-
-        self.buf_reader.borrow_mut().read_line(&mut buffer)?;
-        Ok(juiz_sdk::serde_json::from_str::<Value>(buffer.as_str())?.into())
-        //Ok(Value::from(buffer).try_into()?)
-    }
-
-    fn send_and_receive_output(&self, _v: CapsuleMap, _timeout: Duration) -> JuizResult<CapsulePtr> {
-        todo!("Outputクラスをどう扱うか。")
+        let mut buf_reader = self.buf_reader.borrow_mut();
+        if buf_reader.is_none() {
+            log::debug!("IPCBrokerProxyCore({}) reconnecting.", self.name);
+            let (conn, protocol) = IPCBrokerProxyCore::connect(&self.name)?;
+            self.protocol.set(protocol);
+            *buf_reader = Some(BufReader::new(conn));
+        }
+        let result = self.send_and_receive_inner(buf_reader.as_mut().unwrap(), &value, timeout);
+        match (result, timeout) {
+            (Err(e), Some(timeout)) if is_io_timeout(&e) => {
+                log::error!("IPCBrokerProxyCore({}) timed out after {timeout:?}.", self.name);
+                *buf_reader = None;
+                Err(timeout_error(&self.name, timeout))
+            },
+            (Err(e), _) if e.downcast_ref::<std::io::Error>().is_some() => {
+                // 相手が落ちたかもしれないので、次の呼び出しでつなぎ直す
+                log::error!("IPCBrokerProxyCore({}) connection lost. Error({e})", self.name);
+                *buf_reader = None;
+                Err(e)
+            },
+            (result, _) => result,
+        }
     }
 }
 
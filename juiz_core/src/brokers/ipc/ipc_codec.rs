@@ -0,0 +1,437 @@
+//! IPCブローカーの通信路で使うフレームとCapsuleMapのバイナリ表現
+//!
+//! 接続直後にクライアントは `MAGIC` とプロトコルバージョン(u32)と使える機能のフラグ(u32)を送り、
+//! サーバーは同じ形で合意したバージョンと機能を返す。機能はクライアントとサーバーの両方が使えるものだけになる。
+//! 以降は長さ(u32)を先頭につけたフレームでリクエストと応答をやりとりする。整数はすべてビッグエンディアン。
+//! `MAGIC` で始まらない接続は従来の改行区切りJSONとして扱う。
+//! バイト列と数値の配列はJSONに包まず、そのままフレームに載せる。
+//!
+//! 機能`CAPABILITY_SHARED_MEMORY`が合意されたときは、画像の画素をPOSIX共有メモリに置き、フレームにはセグメントのハンドルだけを載せる。
+//! 送り手は符号化で作られたSharedImageLeaseを、受け手がフレームを読み終えるまで (次のフレームを受け取るか接続が切れるまで) 持っておく。
+//! 合意されなかったときは画素をフレームに直接載せる。
+//!
+//! 呼び出しがサーバー側で時間切れになったときは、RemoteCallTimeoutErrorとして戻せるように専用の応答を返す。
+
+use std::io::{Read, Write};
+
+use juiz_sdk::anyhow;
+use juiz_sdk::image::{DynamicImage, ImageBuffer};
+use thiserror::Error;
+
+use crate::prelude::*;
+
+pub(crate) const MAGIC: &[u8; 4] = b"JUIZ";
+pub(crate) const PROTOCOL_VERSION: u32 = 1;
+/// 画像を共有メモリで受け渡す。受け手が同じマシンにいることが前提
+pub(crate) const CAPABILITY_SHARED_MEMORY: u32 = 1;
+const MAX_FRAME_SIZE: usize = 1 << 30;
+/// フレームを読むときに一度に確保するバッファの大きさ。相手の申告した長さではなく、実際に届いた分だけ伸ばす
+pub(crate) const READ_CHUNK_SIZE: usize = 1 << 16;
+
+const CAPSULE_EMPTY: u8 = 0;
+const CAPSULE_VALUE: u8 = 1;
+const CAPSULE_IMAGE: u8 = 2;
+const CAPSULE_SHARED_IMAGE: u8 = 3;
+const CAPSULE_BYTES: u8 = 4;
+const CAPSULE_NUMERIC_ARRAY: u8 = 5;
+
+const RESPONSE_OK: u8 = 0;
+const RESPONSE_ERROR: u8 = 1;
+const RESPONSE_TIMEOUT: u8 = 2;
+
+// 画像のピクセル形式。16bit, 32bit浮動小数点はネイティブのバイト順のまま送る(同一マシン内の通信なので)。
+const COLOR_L8: u8 = 0;
+const COLOR_LA8: u8 = 1;
+const COLOR_RGB8: u8 = 2;
+const COLOR_RGBA8: u8 = 3;
+const COLOR_L16: u8 = 4;
+const COLOR_LA16: u8 = 5;
+const COLOR_RGB16: u8 = 6;
+const COLOR_RGBA16: u8 = 7;
+const COLOR_RGB32F: u8 = 8;
+const COLOR_RGBA32F: u8 = 9;
+
+#[derive(Error, Debug, PartialEq)]
+pub(crate) enum IPCBrokerError {
+    #[error("IPCBroker returned error (message={message})")]
+    RemoteError{ message: String },
+
+    #[error("IPCBroker received invalid frame (message={message})")]
+    InvalidFrameError{ message: String },
+
+    #[error("IPCBroker protocol version {version} is not supported.")]
+    UnsupportedVersionError{ version: u32 },
+
+    #[error("IPCBroker frame size {size} exceeds limit.")]
+    FrameTooLargeError{ size: usize },
+}
+
+fn invalid_frame(message: &str) -> anyhow::Error {
+    anyhow::Error::from(IPCBrokerError::InvalidFrameError{ message: message.to_owned() })
+}
+
+/// 通信路で使うバージョンと機能
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Protocol {
+    pub version: u32,
+    pub capabilities: u32,
+}
+
+impl Protocol {
+
+    pub fn new(version: u32, capabilities: u32) -> Self {
+        Protocol{ version, capabilities }
+    }
+
+    pub fn shared_memory(&self) -> bool {
+        self.capabilities & CAPABILITY_SHARED_MEMORY != 0
+    }
+}
+
+/// クライアントから要求されたバージョンと機能に対してサーバーが使うものを決める
+pub(crate) fn negotiate(requested: Protocol, supported: Protocol) -> JuizResult<Protocol> {
+    if requested.version == 0 {
+        return Err(anyhow::Error::from(IPCBrokerError::UnsupportedVersionError{ version: requested.version }));
+    }
+    Ok(Protocol::new(requested.version.min(supported.version), requested.capabilities & supported.capabilities))
+}
+
+pub(crate) fn check_frame_size(size: usize) -> JuizResult<usize> {
+    if size > MAX_FRAME_SIZE {
+        return Err(anyhow::Error::from(IPCBrokerError::FrameTooLargeError{ size }));
+    }
+    Ok(size)
+}
+
+/// ハンドシェイクを送って、サーバーが合意したバージョンと機能を返す
+pub(crate) fn handshake<S: Read + Write>(stream: &mut S, capabilities: u32) -> JuizResult<Protocol> {
+    let mut request = MAGIC.to_vec();
+    request.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    request.extend_from_slice(&capabilities.to_be_bytes());
+    stream.write_all(&request)?;
+    let mut response = [0u8; 12];
+    stream.read_exact(&mut response)?;
+    let mut decoder = Decoder::new(&response);
+    if decoder.take(4)? != MAGIC {
+        return Err(invalid_frame("handshake response does not start with magic."));
+    }
+    let protocol = Protocol::new(decoder.u32()?, decoder.u32()?);
+    if protocol.version == 0 || protocol.version > PROTOCOL_VERSION {
+        return Err(anyhow::Error::from(IPCBrokerError::UnsupportedVersionError{ version: protocol.version }));
+    }
+    if protocol.capabilities & !capabilities != 0 {
+        return Err(invalid_frame("handshake response has capabilities which were not requested."));
+    }
+    Ok(protocol)
+}
+
+pub(crate) fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> JuizResult<()> {
+    check_frame_size(payload.len())?;
+    writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub(crate) fn read_frame<R: Read>(reader: &mut R) -> JuizResult<Vec<u8>> {
+    let mut size = [0u8; 4];
+    reader.read_exact(&mut size)?;
+    read_payload(reader, check_frame_size(u32::from_be_bytes(size) as usize)?)
+}
+
+/// size バイトを読む。長さの申告だけで大きなバッファを確保しないよう、届いた分だけバッファを伸ばす
+pub(crate) fn read_payload<R: Read>(reader: &mut R, size: usize) -> JuizResult<Vec<u8>> {
+    let mut payload = Vec::with_capacity(size.min(READ_CHUNK_SIZE));
+    reader.take(size as u64).read_to_end(&mut payload)?;
+    if payload.len() != size {
+        return Err(anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+    }
+    Ok(payload)
+}
+
+fn put_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) -> JuizResult<()> {
+    put_u32(buf, check_frame_size(bytes.len())? as u32);
+    buf.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) -> JuizResult<()> {
+    put_bytes(buf, s.as_bytes())
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+
+    fn new(bytes: &'a [u8]) -> Self {
+        Decoder{ bytes, pos: 0 }
+    }
+
+    fn take(&mut self, size: usize) -> JuizResult<&'a [u8]> {
+        if self.bytes.len() - self.pos < size {
+            return Err(invalid_frame("frame is shorter than expected."));
+        }
+        let s = &self.bytes[self.pos..self.pos + size];
+        self.pos += size;
+        Ok(s)
+    }
+
+    fn u8(&mut self) -> JuizResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> JuizResult<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn bytes(&mut self) -> JuizResult<&'a [u8]> {
+        let size = self.u32()? as usize;
+        self.take(size)
+    }
+
+    fn string(&mut self) -> JuizResult<String> {
+        Ok(std::str::from_utf8(self.bytes()?)?.to_owned())
+    }
+
+    fn finish(&self) -> JuizResult<()> {
+        if self.pos != self.bytes.len() {
+            return Err(invalid_frame("frame has trailing bytes."));
+        }
+        Ok(())
+    }
+}
+
+fn put_image(buf: &mut Vec<u8>, image: &DynamicImage) -> JuizResult<()> {
+    let color = match image {
+        DynamicImage::ImageLuma8(_) => COLOR_L8,
+        DynamicImage::ImageLumaA8(_) => COLOR_LA8,
+        DynamicImage::ImageRgb8(_) => COLOR_RGB8,
+        DynamicImage::ImageRgba8(_) => COLOR_RGBA8,
+        DynamicImage::ImageLuma16(_) => COLOR_L16,
+        DynamicImage::ImageLumaA16(_) => COLOR_LA16,
+        DynamicImage::ImageRgb16(_) => COLOR_RGB16,
+        DynamicImage::ImageRgba16(_) => COLOR_RGBA16,
+        DynamicImage::ImageRgb32F(_) => COLOR_RGB32F,
+        DynamicImage::ImageRgba32F(_) => COLOR_RGBA32F,
+        _ => return put_image(buf, &DynamicImage::ImageRgba8(image.to_rgba8())),
+    };
+    put_u32(buf, image.width());
+    put_u32(buf, image.height());
+    buf.push(color);
+    put_bytes(buf, image.as_bytes())
+}
+
+fn to_u16s(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks_exact(2).map(|b| u16::from_ne_bytes([b[0], b[1]])).collect()
+}
+
+fn to_f32s(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4).map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]])).collect()
+}
+
+fn get_image(decoder: &mut Decoder) -> JuizResult<DynamicImage> {
+    let width = decoder.u32()?;
+    let height = decoder.u32()?;
+    let color = decoder.u8()?;
+    let bytes = decoder.bytes()?;
+    let image = match color {
+        COLOR_L8 => ImageBuffer::from_raw(width, height, bytes.to_vec()).map(DynamicImage::ImageLuma8),
+        COLOR_LA8 => ImageBuffer::from_raw(width, height, bytes.to_vec()).map(DynamicImage::ImageLumaA8),
+        COLOR_RGB8 => ImageBuffer::from_raw(width, height, bytes.to_vec()).map(DynamicImage::ImageRgb8),
+        COLOR_RGBA8 => ImageBuffer::from_raw(width, height, bytes.to_vec()).map(DynamicImage::ImageRgba8),
+        COLOR_L16 => ImageBuffer::from_raw(width, height, to_u16s(bytes)).map(DynamicImage::ImageLuma16),
+        COLOR_LA16 => ImageBuffer::from_raw(width, height, to_u16s(bytes)).map(DynamicImage::ImageLumaA16),
+        COLOR_RGB16 => ImageBuffer::from_raw(width, height, to_u16s(bytes)).map(DynamicImage::ImageRgb16),
+        COLOR_RGBA16 => ImageBuffer::from_raw(width, height, to_u16s(bytes)).map(DynamicImage::ImageRgba16),
+        COLOR_RGB32F => ImageBuffer::from_raw(width, height, to_f32s(bytes)).map(DynamicImage::ImageRgb32F),
+        COLOR_RGBA32F => ImageBuffer::from_raw(width, height, to_f32s(bytes)).map(DynamicImage::ImageRgba32F),
+        _ => return Err(invalid_frame("unknown image color type.")),
+    };
+    image.ok_or_else(|| invalid_frame("image size does not match pixel buffer."))
+}
+
+fn put_shared_image(buf: &mut Vec<u8>, capsule: &CapsulePtr, leases: &mut Vec<SharedImageLease>) -> JuizResult<()> {
+    let (handle, lease) = if capsule.is_shared_image()? {
+        capsule.lock_as_shared_image(|image| image.share())?
+    } else {
+        // 送る側で一度だけ共有メモリに複製する。作ったSharedImageはここで手放し、参照は受け手に引き継ぐ。
+        capsule.lock_as_image(SharedImage::from_image)??.share()
+    };
+    leases.push(lease);
+    put_str(buf, handle.name.as_str())?;
+    put_u32(buf, handle.width);
+    put_u32(buf, handle.height);
+    put_str(buf, handle.color.as_str())
+}
+
+fn get_shared_image(decoder: &mut Decoder) -> JuizResult<SharedImage> {
+    let name = decoder.string()?;
+    let width = decoder.u32()?;
+    let height = decoder.u32()?;
+    let color = SharedImageColor::try_from(decoder.string()?.as_str())?;
+    SharedImage::open(&SharedImageHandle{ name, width, height, color })
+}
+
+/// 要素の型の番号、次元数、各次元の長さ、リトルエンディアンの要素の列の順に並べる
+fn put_numeric_array(buf: &mut Vec<u8>, array: &NumericArray) -> JuizResult<()> {
+    let to_u32 = |n: usize, what: &str| u32::try_from(n).map_err(|_| invalid_frame(format!("numeric array {what} ({n}) exceeds u32.").as_str()));
+    buf.push(array.numeric_type().code() as u8);
+    put_u32(buf, to_u32(array.shape().len(), "dimension")?);
+    for d in array.shape() {
+        put_u32(buf, to_u32(*d, "shape")?);
+    }
+    put_bytes(buf, &array.to_le_bytes())
+}
+
+fn get_numeric_array(decoder: &mut Decoder) -> JuizResult<NumericArray> {
+    let numeric_type = NumericType::from_code(decoder.u8()? as i64).ok_or_else(|| invalid_frame("unknown numeric type."))?;
+    let ndim = decoder.u32()? as usize;
+    let mut shape = Vec::with_capacity(ndim.min(64));
+    for _ in 0..ndim {
+        shape.push(decoder.u32()? as usize);
+    }
+    NumericArray::from_le_bytes(numeric_type, shape, decoder.bytes()?)
+}
+
+fn put_capsule(buf: &mut Vec<u8>, capsule: &CapsulePtr, protocol: Protocol, leases: &mut Vec<SharedImageLease>) -> JuizResult<()> {
+    if protocol.shared_memory() && capsule.is_image()? {
+        buf.push(CAPSULE_SHARED_IMAGE);
+        put_shared_image(buf, capsule, leases)?;
+    } else if capsule.is_image()? {
+        buf.push(CAPSULE_IMAGE);
+        capsule.lock_as_image(|image| put_image(buf, image))??;
+    } else if capsule.is_bytes()? {
+        buf.push(CAPSULE_BYTES);
+        capsule.lock_as_bytes(|bytes| put_bytes(buf, bytes))??;
+    } else if capsule.is_numeric_array()? {
+        buf.push(CAPSULE_NUMERIC_ARRAY);
+        capsule.lock_as_numeric_array(|array| put_numeric_array(buf, array))??;
+    } else if capsule.is_value()? {
+        buf.push(CAPSULE_VALUE);
+        let bytes = capsule.lock_as_value(juiz_sdk::serde_json::to_vec)??;
+        put_bytes(buf, &bytes)?;
+    } else {
+        buf.push(CAPSULE_EMPTY);
+    }
+    let options = capsule.get_options()?;
+    put_u32(buf, options.len() as u32);
+    for (k, v) in options.iter() {
+        put_str(buf, k)?;
+        put_str(buf, v)?;
+    }
+    Ok(())
+}
+
+fn get_capsule(decoder: &mut Decoder) -> JuizResult<CapsulePtr> {
+    let mut capsule: CapsulePtr = match decoder.u8()? {
+        CAPSULE_EMPTY => CapsulePtr::new(),
+        CAPSULE_VALUE => juiz_sdk::serde_json::from_slice::<Value>(decoder.bytes()?)?.into(),
+        CAPSULE_IMAGE => get_image(decoder)?.into(),
+        CAPSULE_SHARED_IMAGE => get_shared_image(decoder)?.into(),
+        CAPSULE_BYTES => decoder.bytes()?.to_vec().into(),
+        CAPSULE_NUMERIC_ARRAY => get_numeric_array(decoder)?.into(),
+        _ => return Err(invalid_frame("unknown capsule type.")),
+    };
+    for _ in 0..decoder.u32()? {
+        let k = decoder.string()?;
+        let v = decoder.string()?;
+        capsule.set_option(k.as_str(), v.as_str())?;
+    }
+    Ok(capsule)
+}
+
+/// 1つのCapsuleを符号化する。画像は共有メモリを使わず画素をそのまま含める
+pub(crate) fn encode_capsule(capsule: &CapsulePtr) -> JuizResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    put_capsule(&mut buf, capsule, Protocol::new(PROTOCOL_VERSION, 0), &mut Vec::new())?;
+    Ok(buf)
+}
+
+pub(crate) fn decode_capsule(bytes: &[u8]) -> JuizResult<CapsulePtr> {
+    let mut decoder = Decoder::new(bytes);
+    let capsule = get_capsule(&mut decoder)?;
+    decoder.finish()?;
+    Ok(capsule)
+}
+
+/// 共有メモリに置いた画像のleaseはleasesに足す
+pub(crate) fn encode_capsule_map(map: &CapsuleMap, protocol: Protocol, leases: &mut Vec<SharedImageLease>) -> JuizResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    put_u32(&mut buf, map.get_map().len() as u32);
+    for (k, v) in map.iter() {
+        put_str(&mut buf, k)?;
+        put_capsule(&mut buf, v, protocol, leases)?;
+    }
+    put_u32(&mut buf, map.get_params().len() as u32);
+    for (k, v) in map.get_params().iter() {
+        put_str(&mut buf, k)?;
+        put_str(&mut buf, v)?;
+    }
+    Ok(buf)
+}
+
+pub(crate) fn decode_capsule_map(bytes: &[u8]) -> JuizResult<CapsuleMap> {
+    let mut decoder = Decoder::new(bytes);
+    let mut map = CapsuleMap::new();
+    for _ in 0..decoder.u32()? {
+        let k = decoder.string()?;
+        map.insert(k, get_capsule(&mut decoder)?);
+    }
+    for _ in 0..decoder.u32()? {
+        let k = decoder.string()?;
+        let v = decoder.string()?;
+        map.set_param(k.as_str(), v.as_str());
+    }
+    decoder.finish()?;
+    Ok(map)
+}
+
+/// 共有メモリに置いた画像のleaseはleasesに足す
+pub(crate) fn encode_response(result: &JuizResult<CapsulePtr>, protocol: Protocol, leases: &mut Vec<SharedImageLease>) -> JuizResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    match result {
+        Ok(capsule) => {
+            buf.push(RESPONSE_OK);
+            put_capsule(&mut buf, capsule, protocol, leases)?;
+        },
+        Err(e) => match e.downcast_ref::<JuizError>() {
+            Some(JuizError::RemoteCallTimeoutError{target, timeout_sec}) => {
+                buf.push(RESPONSE_TIMEOUT);
+                put_str(&mut buf, target)?;
+                put_str(&mut buf, timeout_sec.to_string().as_str())?;
+            },
+            _ => {
+                buf.push(RESPONSE_ERROR);
+                put_str(&mut buf, format!("{e:#}").as_str())?;
+            }
+        }
+    }
+    Ok(buf)
+}
+
+pub(crate) fn decode_response(bytes: &[u8]) -> JuizResult<CapsulePtr> {
+    let mut decoder = Decoder::new(bytes);
+    match decoder.u8()? {
+        RESPONSE_OK => {
+            let capsule = get_capsule(&mut decoder)?;
+            decoder.finish()?;
+            Ok(capsule)
+        },
+        RESPONSE_ERROR => Err(anyhow::Error::from(IPCBrokerError::RemoteError{ message: decoder.string()? })),
+        RESPONSE_TIMEOUT => {
+            let target = decoder.string()?;
+            let timeout_sec = decoder.string()?.parse::<f64>().map_err(|_| invalid_frame("invalid timeout."))?;
+            decoder.finish()?;
+            Err(anyhow::Error::from(JuizError::RemoteCallTimeoutError{ target, timeout_sec }))
+        },
+        _ => Err(invalid_frame("unknown response status.")),
+    }
+}
@@ -2,6 +2,7 @@
 
 pub mod ipc_broker;
 pub mod ipc_broker_proxy;
+pub(crate) mod ipc_codec;
 
 //pub use local_broker::LocalBroker;
 //pub use local_broker_proxy::LocalBrokerProxy;
@@ -2,6 +2,7 @@ use std::{sync::{Arc, Mutex}, time::Duration, ops::Deref};
 
 use juiz_sdk::anyhow;
 use crate::prelude::*;
+use crate::brokers::call_timeout::timeout_error;
 use crate::brokers::messenger_broker_proxy_factory::create_messenger_broker_proxy_factory;
 
 use super::local_broker::ProxySideSenderReceiverPair;
@@ -30,7 +31,8 @@ impl MessengerBrokerProxyCoreFactory  for LocalBrokerProxyCoreFactory {
 }
 
 impl MessengerBrokerProxyCore for LocalBrokerProxyCore {
-    fn send_and_receive(&self, value: CapsuleMap, timeout: Duration) -> JuizResult<CapsulePtr> {
+    fn send_and_receive(&self, value: CapsuleMap, timeout: Option<Duration>) -> JuizResult<CapsulePtr> {
+        let timeout = timeout.unwrap_or(Duration::new(3, 0));
         let us = timeout.as_micros();
         log::trace!("LocaBrokerProxyCore::send_and_receive(timeout_us={us}) called");
         let sndr_recvr = self.sender_receiver.lock().map_err(|_e| return anyhow::Error::from(JuizError::BrokerSendCanNotLockSenderError{}))?;
@@ -38,15 +40,14 @@ impl MessengerBrokerProxyCore for LocalBrokerProxyCore {
         let _ = sndr.send(value).map_err(|_e| return anyhow::Error::from(JuizError::LocalBrokerProxySendError{}))?;
         let result = recvr.recv_timeout(timeout).map_err(|e| {
                 log::error!("LocalBrokerProxyCore::send_and_receive() failed. Error is {e:}");
-                return anyhow::Error::from(JuizError::LocalBrokerProxyReceiveTimeoutError{error: e});
+                match e {
+                    std::sync::mpsc::RecvTimeoutError::Timeout => timeout_error("LocalBrokerProxyCore", timeout),
+                    _ => anyhow::Error::from(JuizError::LocalBrokerProxyReceiveTimeoutError{error: e}),
+                }
         });
         log::trace!("LocaBrokerProxyCore::send_and_receive() exit");
         result
     }
-
-    fn send_and_receive_output(&self, _v: CapsuleMap, _timeout: Duration) -> JuizResult<CapsulePtr> {
-        todo!("Outputクラスをどう扱うか。")
-    }
 }
 
 impl LocalBrokerProxyCore {
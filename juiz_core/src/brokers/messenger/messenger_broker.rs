@@ -68,7 +68,7 @@ fn extract_function_name<'a>(args: &'a CapsuleMap) -> JuizResult<&String> {
 }
 
 
-fn handle_function(crud_broker: Arc<Mutex<CRUDBroker>>, args: CapsuleMap) -> JuizResult<CapsulePtr> {
+pub(crate) fn handle_function(crud_broker: Arc<Mutex<CRUDBroker>>, args: CapsuleMap) -> JuizResult<CapsulePtr> {
     log::info!("MessengerBroker::handle_function() called");
     let class_name = extract_class_name(&args)?;
     let function_name = extract_function_name(&args)?.to_owned();
@@ -13,6 +13,8 @@ use super::super::broker_proxy::{SystemBrokerProxy, ProcessBrokerProxy};
 pub struct MessengerBrokerProxy {
     core: ObjectCore, 
     messenger: Box<dyn MessengerBrokerProxyCore>,
+    /// 呼び出しにタイムアウトの指定が無い時に使うタイムアウト
+    default_timeout: Option<Duration>,
 }
 
 // pub type SenderType = dyn Fn(CapsuleMap) -> JuizResult<()>;
@@ -20,8 +22,8 @@ pub struct MessengerBrokerProxy {
 
 // pub struct SendReceivePair(pub Box<SenderType>, pub Box<ReceiverType>);
 pub trait MessengerBrokerProxyCore : Send {
-    fn send_and_receive(&self, v: CapsuleMap, timeout: Duration) -> JuizResult<CapsulePtr>;
-    fn send_and_receive_output(&self, v: CapsuleMap, timeout: Duration) -> JuizResult<CapsulePtr>;
+    /// timeoutがNoneなら実装ごとの既定の待ち時間を使う。時間切れはRemoteCallTimeoutError
+    fn send_and_receive(&self, v: CapsuleMap, timeout: Option<Duration>) -> JuizResult<CapsulePtr>;
 }
 
 pub trait MessengerBrokerProxyCoreFactory { 
@@ -40,10 +42,11 @@ fn to_map(params: &[(String, String)]) -> Map<String, Value> {
 
 impl MessengerBrokerProxy {
 
-    pub fn new(class_name: &'static str, type_name: &str, object_name: &str, messenger: Box<dyn MessengerBrokerProxyCore>) -> JuizResult<Arc<Mutex<dyn BrokerProxy>>>{
+    pub fn new(class_name: &'static str, type_name: &str, object_name: &str, messenger: Box<dyn MessengerBrokerProxyCore>, default_timeout: Option<Duration>) -> JuizResult<Arc<Mutex<dyn BrokerProxy>>>{
         Ok(Arc::new(Mutex::new(MessengerBrokerProxy{
             core: ObjectCore::create(JuizObjectClass::BrokerProxy(class_name), type_name, object_name),
-            messenger})))
+            messenger,
+            default_timeout})))
     }
 
     fn construct_capsule_map(&self, method_name: &str, class_name: &str, function_name: &str, mut arguments: CapsuleMap, params: &[(String, String)]) -> CapsuleMap {
@@ -65,14 +68,14 @@ impl MessengerBrokerProxy {
     pub fn send_recv_and<F: Fn(CapsulePtr)->JuizResult<T>, T>(&self, method_name: &str, class_name: &str, function_name: &str, arguments: CapsuleMap, params: &[(String, String)], func: F) -> JuizResult<T> {
         log::trace!("MessengerBrokerProxy::send_recv_and({class_name}, {function_name}, arguments) called");
         //let SendReceivePair(sndr, recvr) = self.messenger.send_receive()?;
-    
+        let timeout = arguments.timeout()?.or(self.default_timeout);
         let value = self.messenger.send_and_receive(self.construct_capsule_map(
             method_name, 
             class_name,
             function_name,
             arguments,
             params
-        ), Duration::new(3, 0)).context("MessengerBrokerProxyCore.send_and_receive() failed in MessengerBrokerProxy.send_recv_and()")?;
+        ), timeout).context("MessengerBrokerProxyCore.send_and_receive() failed in MessengerBrokerProxy.send_recv_and()")?;
         //let value = (recvr)(timeout)?;
         log::trace!("MessengerBrokerProxy::send_recv_and() received value {value:?}");
         let response_function_name_result = self.extract_function_param(&value);//obj_get_str(&value, "function_name")?;
@@ -96,43 +99,6 @@ impl MessengerBrokerProxy {
         result
     }
 
-    fn _construct_argument(_method_name: &str, _class_name: &str, _function_name: &str, _arguments: CapsuleMap, _params: &[(String, String)]) -> JuizResult<CapsuleMap> {
-
-        todo!("ここにmethod_nameなどをArgumentMapに埋め込む作業を書く")
-        /* 
-        jvalue!({
-            "method_name": method_name,
-            "class_name": class_name,
-            "function_name": function_name, 
-            "arguments": arguments,
-            "params": to_map(params),
-        }
-        */
-    }
-
-    pub fn send_recv_output_and<F: Fn(CapsulePtr)->JuizResult<T>, T>(&self, method_name: &str, class_name: &str, function_name: &str, arguments: CapsuleMap, params: &[(String, String)], func: F) -> JuizResult<T> {
-        //log::trace!("MessengerBrokerProxy::send_recv_output_and({class_name}, {function_name}, {arguments}) called");
-        log::trace!("MessengerBrokerProxy::send_recv_output_and({class_name}, {function_name}, arguments) called");
-        //let SendReceivePair(sndr, recvr) = self.messenger.send_receive()?;
-        let value = self.messenger.send_and_receive_output(
-            Self::_construct_argument(method_name, class_name, function_name, arguments, params)?, Duration::new(3, 0)).context("MessengerBrokerProxyCore.send_and_receive() failed in MessengerBrokerProxy.send_recv_and()")?;
-        //let value = (recvr)(timeout)?;
-        //let response_function_name = obj_get_str(juiz_lock(&value)?.as_value().unwrap(), "function_name")?.to_owned();
-        let response_function_name = value.get_function_name()?;
-        
-        match response_function_name.as_str() {
-            "RequestFunctionNameNotSupported" => {
-                return Err(anyhow::Error::from(JuizError::BrokerProxyRequestFunctionNameNotSupportedError{request_function_name: function_name.to_string()}));
-            },
-            _ => {
-                if response_function_name != function_name {
-                    return Err(anyhow::Error::from(JuizError::BrokerProxyFunctionNameInResponseDoesNotMatchError{function_name: function_name.to_string(), response_function_name: response_function_name.to_string()}));
-                }
-                func(value)
-            }
-        }
-    }
-
     pub fn read(&self, class_name: &str, function_name: &str) -> JuizResult<CapsulePtr> {
         self.send_recv_and(
             "READ", 
@@ -166,26 +132,6 @@ impl MessengerBrokerProxy {
             |value| Ok(value))
     }
 
-    pub fn update_output_by_id(&self, class_name: &str, function_name: &str, args: CapsuleMap, id: &Identifier) -> JuizResult<CapsulePtr>  {
-        self.send_recv_output_and(
-            "UPDATE",
-            class_name, 
-            function_name, 
-            args, 
-            &[("identifier".to_owned(), id.clone())],
-            |value| Ok(value))
-    }
-
-    pub fn update_output(&self, class_name: &str, function_name: &str, args: CapsuleMap, params: &[(String, String)]) -> JuizResult<CapsulePtr>  {
-        self.send_recv_output_and(
-            "UPDATE",
-            class_name, 
-            function_name, 
-            args, 
-            params,
-            |value| Ok(value))
-    }
-
     pub fn update_by_id(&self, class_name: &str, function_name: &str, args: CapsuleMap, id: &Identifier) -> JuizResult<CapsulePtr>  {
         self.send_recv_and(
             "UPDATE",
@@ -260,17 +206,17 @@ impl SystemBrokerProxy for MessengerBrokerProxy {
     fn system_add_subsystem(&mut self, profile: Value) -> JuizResult<Value> {
         let mut cp = CapsuleMap::new();
         cp.insert("profile".to_owned(), profile.into());
-        capsule_to_value(self.update("system", "add_subsystem", cp, &[])?)
+        self.update("system", "add_subsystem", cp, &[])?.extract_value()
     }
     
     fn system_uuid(&self) -> JuizResult<Value> {
-        capsule_to_value(self.read("system", "uuid")?)
+        self.read("system", "uuid")?.extract_value()
     }
     
     fn system_add_mastersystem(&mut self, profile: Value) -> JuizResult<Value> {
         let mut cp = CapsuleMap::new();
         cp.insert("profile".to_owned(), profile.into());
-        capsule_to_value(self.update("system", "add_mastersystem", cp, &[])?)
+        self.update("system", "add_mastersystem", cp, &[])?.extract_value()
     }
     
     fn system_load_process(&mut self, language: String, filepath: String) -> JuizResult<Value> {
@@ -301,16 +247,24 @@ impl SystemBrokerProxy for MessengerBrokerProxy {
         capsule_to_value(self.update("system", "load_component", cp, &[])?)
     }
 
+    fn system_discovered_peers(&self) -> JuizResult<Value> {
+        self.read("system", "discovered_peers")?.extract_value()
+    }
+
 }
 
 impl ProcessBrokerProxy for MessengerBrokerProxy {
 
     fn process_call(&self, id: &Identifier, args: CapsuleMap) -> JuizResult<CapsulePtr> {
-        self.update_output_by_id("process", "call", args, id)
+        self.update_by_id("process", "call", args, id)
     }
 
-    fn process_execute(&self, id: &Identifier) -> JuizResult<CapsulePtr> {
-        self.update_output_by_id("process", "execute", CapsuleMap::new(), id)
+    fn process_execute_with_timeout(&self, id: &Identifier, timeout: Option<Duration>) -> JuizResult<CapsulePtr> {
+        let mut args = CapsuleMap::new();
+        if let Some(timeout) = timeout {
+            args.set_timeout(timeout);
+        }
+        self.update_by_id("process", "execute", args, id)
     }
 
     fn process_profile_full(&self, id: &Identifier) -> JuizResult<Value> {
@@ -335,7 +289,7 @@ impl ProcessBrokerProxy for MessengerBrokerProxy {
             "process", 
             "push_by", 
             cm,
-            &[], 
+            &[("identifier".to_owned(), id.clone())], 
             |value| Ok(value))
         
     }
@@ -355,7 +309,7 @@ impl ProcessBrokerProxy for MessengerBrokerProxy {
             Into::<Value>::into(connection_manifest).try_into()?,
             &[], 
             |value| Ok(value))?;
-        capsule_to_value(capsule)
+        capsule.extract_value()
     }
 
     fn process_notify_connected_from(&mut self, source_process_id: &Identifier, arg_name: &str, destination_process_id: &Identifier, connection_type: String, connection_id: Option<String>) -> JuizResult<Value> {
@@ -373,7 +327,7 @@ impl ProcessBrokerProxy for MessengerBrokerProxy {
             Into::<Value>::into(connection_manifest).try_into()?, 
             &[], 
             |value| Ok(value))?;
-        capsule_to_value(value)
+        value.extract_value()
     }
     
     fn process_p_apply(&mut self, id: &Identifier, arg_name: &str, value: CapsulePtr) -> JuizResult<CapsulePtr> {
@@ -420,11 +374,11 @@ impl ContainerProcessBrokerProxy for MessengerBrokerProxy {
     }
     
     fn container_process_call(&self, id: &Identifier, args: CapsuleMap) -> JuizResult<CapsulePtr> {       
-        self.update_output_by_id("container_process", "call", args, id)
+        self.update_by_id("container_process", "call", args, id)
     }
     
     fn container_process_execute(&self, id: &Identifier) -> JuizResult<CapsulePtr> {
-        self.update_output_by_id("container_process", "execute", CapsuleMap::new(), id)
+        self.update_by_id("container_process", "execute", CapsuleMap::new(), id)
     }
     
     fn container_process_create(&mut self, container_id: &Identifier, manifest: ProcessManifest) -> JuizResult<Value> {
@@ -462,6 +416,23 @@ impl ExecutionContextBrokerProxy for MessengerBrokerProxy {
     fn ec_stop(&mut self, id: &Identifier) -> JuizResult<Value> {
         capsule_to_value(self.update_by_id("execution_context", "stop", CapsuleMap::new(), id)?)
     }
+
+    fn ec_pause(&mut self, id: &Identifier) -> JuizResult<Value> {
+        capsule_to_value(self.update_by_id("execution_context", "pause", CapsuleMap::new(), id)?)
+    }
+
+    fn ec_resume(&mut self, id: &Identifier) -> JuizResult<Value> {
+        capsule_to_value(self.update_by_id("execution_context", "resume", CapsuleMap::new(), id)?)
+    }
+
+    fn ec_reset(&mut self, id: &Identifier) -> JuizResult<Value> {
+        capsule_to_value(self.update_by_id("execution_context", "reset", CapsuleMap::new(), id)?)
+    }
+
+    fn ec_step(&mut self, id: &Identifier, n: usize) -> JuizResult<Value> {
+        let arg = vec!(("n", jvalue!(n)));
+        capsule_to_value(self.update_by_id("execution_context", "step", arg.into(), id)?)
+    }
     
     fn ec_create(&mut self, manifest: &Value) -> JuizResult<Value> {
         capsule_to_value(self.create("execution_context","create", manifest.clone().try_into()?)?)
@@ -488,28 +459,32 @@ impl TopicBrokerProxy for MessengerBrokerProxy {
     }
     
     fn topic_push(&self, name: &str, capsule: CapsulePtr, pushed_system_uuid: Option<Uuid>) -> JuizResult<()> {
+        let encoding = capsule.get_options()?.remove(TOPIC_ENCODING_KEY);
         let mut argument = CapsuleMap::new();
         argument.insert("input".to_owned(), capsule);
-        let uuid_str = if let Some(uuid) = pushed_system_uuid { uuid.to_string() } else { "".to_owned() };        
-        self.update_output("topic", "push", argument, 
-            &[
-                ("topic_name".to_owned(), name.to_owned()),
-                ("system_uuid".to_owned(), uuid_str)]
-        ).and_then(|_| { Ok(()) })
+        let mut param = vec![("topic_name".to_owned(), name.to_owned())];
+        // 空のsystem_uuidは受け手側でUUIDとして読めない
+        if let Some(uuid) = pushed_system_uuid {
+            param.push(("system_uuid".to_owned(), uuid.to_string()));
+        }
+        if let Some(encoding) = encoding {
+            param.push((TOPIC_ENCODING_KEY.to_owned(), encoding));
+        }
+        self.update("topic", "push", argument, &param).and_then(|_| { Ok(()) })
     }
     
     fn topic_request_subscribe(&mut self, name: &str, system_uuid: Option<Uuid>) -> JuizResult<Value> {
         let param = &[
             ("topic_name".to_owned(), name.to_owned()),
             ("system_uuid".to_owned(), system_uuid.unwrap().to_string())];
-        capsule_to_value(self.update("topic", "request_subscribe", CapsuleMap::new(), param)?)
+        self.update("topic", "request_subscribe", CapsuleMap::new(), param)?.extract_value()
     }
     
     fn topic_request_publish(&mut self, name: &str, system_uuid: Option<Uuid>) -> JuizResult<Value> {
         let param = &[
             ("topic_name".to_owned(), name.to_owned()),
             ("system_uuid".to_owned(), system_uuid.unwrap().to_string())];
-        capsule_to_value(self.update("topic", "request_publish", CapsuleMap::new(), param)?)
+        self.update("topic", "request_publish", CapsuleMap::new(), param)?.extract_value()
     }
 }
 
@@ -7,6 +7,7 @@ use juiz_sdk::anyhow;
 use crate::core::CoreWorker;
 
 use crate::prelude::*;
+use crate::brokers::call_timeout::default_timeout_from_manifest;
 use crate::brokers::{BrokerProxyFactory, BrokerProxy, MessengerBrokerProxy, MessengerBrokerProxyCoreFactory};
 
 
@@ -47,7 +48,7 @@ impl BrokerProxyFactory for MessengerBrokerProxyFactory {
         let object_name = obj_get_str(&manifest, "name").context("LocalBrokerProxyFactory::create_broker_proxy")?;
         let class_name = "BrokerProxy";
         let type_name = self.type_name();
-        MessengerBrokerProxy::new(class_name, type_name, object_name, self.core_factory.create_core(object_name)?)
+        MessengerBrokerProxy::new(class_name, type_name, object_name, self.core_factory.create_core(object_name)?, default_timeout_from_manifest(&manifest)?)
     }
 
     fn profile_full(&self) -> JuizResult<Value> {
@@ -7,6 +7,8 @@ pub mod broker_factory_impl;
 mod core_broker;
 
 pub mod broker_proxy;
+pub(crate) mod call_timeout;
+pub mod payload_codec;
 pub mod broker_proxy_factory;
 pub mod broker_factories_wrapper;
 
@@ -15,6 +17,7 @@ pub mod messenger;
 pub mod crud;
 pub mod ipc;
 pub mod http;
+pub mod websocket;
 // pub mod _http_;
 
 
@@ -27,6 +30,7 @@ pub use broker_factory_impl::create_broker_factory_impl;
 pub use broker_proxy::BrokerProxy;
 pub use broker_proxy_factory::BrokerProxyFactory;
 pub use broker_proxy_factory::create_broker_proxy_factory_impl;
+pub use payload_codec::PayloadCodec;
 pub use local::*;
 pub use messenger::*;
 pub use crud::*;
@@ -38,6 +42,7 @@ pub use broker_proxy::{
     ExecutionContextBrokerProxy,
     BrokerBrokerProxy,
     ConnectionBrokerProxy,
+    TopicBrokerProxy,
 };
 
 
@@ -0,0 +1,403 @@
+//! ブローカーとブローカープロキシの間で送るペイロードの符号化
+//!
+//! JSONのほかに、より小さく速いMessagePackとCBORを選べる。
+//!
+//! リクエストのCapsuleMapと応答のCapsulePtrのうち、バイト列と数値の配列はJSONと同じ`__bytes__`、`__numeric_array__`のキーで包む。
+//! JSONではBase64の文字列にするが、MessagePackとCBORではバイナリ (bin, byte string) のまま載せる。
+//! 画像はMessagePackとCBORでだけ送れ、`__image__`のキーで[高さ, 幅, チャンネル]のu8の数値の配列として載せる。
+//! MessagePackとCBORの応答はいつも`{"__capsule__": {"value": 値, "option": オプション}}`に包み、空のCapsuleでは`value`を省く。
+//! 包みは必ずあるので、利用者の値がどんなキーを持っていても取り違えない。
+//! マニフェストの `"codec"` (`"json"`, `"msgpack"`, `"cbor"`) で選び、
+//! HTTPでは `Content-Type` でリクエストの、`Accept` で応答の符号化を決める。
+
+use std::fmt::Display;
+
+use juiz_sdk::anyhow::{self, anyhow};
+use serde::{de::{MapAccess, SeqAccess, Visitor}, ser::{SerializeMap, SerializeSeq}, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Number};
+
+use crate::prelude::*;
+use juiz_sdk::value::{CapsuleValue, BYTES_VALUE_KEY, NUMERIC_ARRAY_VALUE_KEY};
+use crate::topics::{image_to_raw, raw_to_image};
+
+/// MessagePackとCBORで画像を送るときのキー
+const IMAGE_VALUE_KEY: &str = "__image__";
+
+/// MessagePackとCBORで応答のCapsuleを包むキー
+const CAPSULE_ENVELOPE_KEY: &str = "__capsule__";
+
+/// ペイロードの符号化の方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PayloadCodec {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl PayloadCodec {
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayloadCodec::Json => "json",
+            PayloadCodec::MessagePack => "msgpack",
+            PayloadCodec::Cbor => "cbor",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            PayloadCodec::Json => "application/json",
+            PayloadCodec::MessagePack => "application/msgpack",
+            PayloadCodec::Cbor => "application/cbor",
+        }
+    }
+
+    /// Content-Typeから選ぶ。`; charset=...` などの引数は無視する
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type.split(';').next().unwrap_or("").trim() {
+            "application/json" => Some(PayloadCodec::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(PayloadCodec::MessagePack),
+            "application/cbor" => Some(PayloadCodec::Cbor),
+            _ => None,
+        }
+    }
+
+    /// Acceptから選ぶ。並んでいる順に最初に知っているものを使い、無ければJSON
+    pub fn from_accept(accept: &str) -> Self {
+        accept.split(',').find_map(Self::from_content_type).unwrap_or_default()
+    }
+
+    /// マニフェストの `"codec"` から選ぶ。書かれていなければdefault_codecを使う
+    pub fn from_manifest(manifest: &Value, default_codec: PayloadCodec) -> JuizResult<Self> {
+        match obj_get_str(manifest, "codec") {
+            Ok(name) => PayloadCodec::try_from(name),
+            Err(_) => Ok(default_codec),
+        }
+    }
+
+    pub fn encode(&self, value: &Value) -> JuizResult<Vec<u8>> {
+        match self {
+            PayloadCodec::Json => Ok(serde_json::to_vec(value)?),
+            _ => self.encode_binary(value),
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> JuizResult<Value> {
+        match self {
+            PayloadCodec::Json => Ok(serde_json::from_slice::<Value>(bytes)?),
+            _ => self.decode_binary::<Value>(bytes),
+        }
+    }
+
+    fn encode_binary<T: Serialize>(&self, value: &T) -> JuizResult<Vec<u8>> {
+        match self {
+            PayloadCodec::Json => Ok(serde_json::to_vec(value)?),
+            PayloadCodec::MessagePack => rmp_serde::to_vec(value).map_err(|e| anyhow!(e)),
+            PayloadCodec::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(value, &mut buf).map_err(|e| anyhow!(e.to_string()))?;
+                Ok(buf)
+            },
+        }
+    }
+
+    fn decode_binary<T: for<'de> Deserialize<'de>>(&self, bytes: &[u8]) -> JuizResult<T> {
+        match self {
+            PayloadCodec::Json => Ok(serde_json::from_slice::<T>(bytes)?),
+            PayloadCodec::MessagePack => rmp_serde::from_slice::<T>(bytes).map_err(|e| anyhow!(e)),
+            PayloadCodec::Cbor => ciborium::from_reader::<T, _>(bytes).map_err(|e| anyhow!(e.to_string())),
+        }
+    }
+
+    /// リクエストのCapsuleMapを符号化する
+    pub fn encode_capsule_map(&self, map: CapsuleMap) -> JuizResult<Vec<u8>> {
+        if *self == PayloadCodec::Json {
+            for (_, v) in map.iter() {
+                self.check_json_capsule(v)?;
+            }
+            return self.encode(&map.into());
+        }
+        let mut values = Vec::new();
+        for (k, v) in map.iter() {
+            values.push((k.clone(), capsule_to_wire(v)?));
+        }
+        let params = map.get_params().iter().map(|(k, v)| (k.clone(), Wire::String(v.clone()))).collect();
+        self.encode_binary(&Wire::Map(vec![
+            ("__map__".to_owned(), Wire::Map(values)),
+            ("__param__".to_owned(), Wire::Map(params)),
+        ]))
+    }
+
+    pub fn decode_capsule_map(&self, bytes: &[u8]) -> JuizResult<CapsuleMap> {
+        if *self == PayloadCodec::Json {
+            return CapsuleMap::from_json_value(self.decode(bytes)?);
+        }
+        let mut entries = self.decode_binary::<Wire>(bytes)?.into_map()?;
+        let mut map = CapsuleMap::new();
+        match take_entry(&mut entries, "__map__") {
+            // __map__で包まれていなければ、全体が引数のマップ
+            None => {
+                for (k, v) in entries {
+                    map.insert(k, wire_to_capsule(v)?);
+                }
+            },
+            Some(values) => {
+                for (k, v) in values.into_map()? {
+                    map.insert(k, wire_to_capsule(v)?);
+                }
+                if let Some(params) = take_entry(&mut entries, "__param__") {
+                    for (k, v) in params.into_map()? {
+                        map.set_param(k.as_str(), wire_to_value(v)?.as_str().unwrap_or_default());
+                    }
+                }
+            },
+        }
+        Ok(map)
+    }
+
+    /// 応答のCapsulePtrを符号化する。オプションも送るため、JSONでは__value__と__option__に、MessagePackとCBORでは__capsule__に包む
+    pub fn encode_capsule_ptr(&self, capsule: CapsulePtr) -> JuizResult<Vec<u8>> {
+        if *self == PayloadCodec::Json {
+            if capsule.is_empty()? {
+                return self.encode(&jvalue!({}));
+            }
+            self.check_json_capsule(&capsule)?;
+            return self.encode(&capsule_to_value(capsule)?);
+        }
+        let mut envelope = Vec::new();
+        if !capsule.is_empty()? {
+            envelope.push(("value".to_owned(), capsule_to_wire(&capsule)?));
+        }
+        let options = capsule.get_options()?.into_iter().map(|(k, v)| (k, Wire::String(v))).collect();
+        envelope.push(("option".to_owned(), Wire::Map(options)));
+        self.encode_binary(&Wire::tagged(CAPSULE_ENVELOPE_KEY, Wire::Map(envelope)))
+    }
+
+    /// encode_capsule_ptrの逆。MessagePackとCBORでは__capsule__に包まれていなければエラーにする
+    pub fn decode_capsule_ptr(&self, bytes: &[u8]) -> JuizResult<CapsulePtr> {
+        if *self == PayloadCodec::Json {
+            return CapsulePtr::from_json_value(self.decode(bytes)?);
+        }
+        let mut entries = match self.decode_binary::<Wire>(bytes)? {
+            Wire::Map(mut entries) if entries.len() == 1 && entries[0].0 == CAPSULE_ENVELOPE_KEY => entries.pop().unwrap().1.into_map()?,
+            _ => return Err(anyhow!(JuizError::ValueTypeError{message: format!("PayloadCodec({self}) expected {CAPSULE_ENVELOPE_KEY} envelope in payload.")})),
+        };
+        let mut capsule = match take_entry(&mut entries, "value") {
+            Some(value) => wire_to_capsule(value)?,
+            None => CapsulePtr::new(),
+        };
+        if let Some(options) = take_entry(&mut entries, "option") {
+            for (k, v) in options.into_map()? {
+                if let Wire::String(s) = v {
+                    capsule.set_option(k.as_str(), s.as_str())?;
+                }
+            }
+        }
+        Ok(capsule)
+    }
+
+    /// JSONでは画像を送れない
+    fn check_json_capsule(&self, capsule: &CapsulePtr) -> JuizResult<()> {
+        if capsule.is_image()? {
+            return Err(anyhow!(JuizError::ValueTypeError{message: format!("PayloadCodec({self}) can not encode image. Use msgpack or cbor.")}));
+        }
+        Ok(())
+    }
+}
+
+/// MessagePackとCBORで送る値。serde_json::Valueにバイナリを足したもの
+#[derive(Debug, Clone, PartialEq)]
+enum Wire {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Bytes(Vec<u8>),
+    Array(Vec<Wire>),
+    /// キーの順番はそのまま保つ
+    Map(Vec<(String, Wire)>),
+}
+
+impl Wire {
+
+    fn tagged(key: &str, value: Wire) -> Wire {
+        Wire::Map(vec![(key.to_owned(), value)])
+    }
+
+    fn into_map(self) -> JuizResult<Vec<(String, Wire)>> {
+        match self {
+            Wire::Map(entries) => Ok(entries),
+            _ => Err(anyhow!(JuizError::ValueTypeError{message: "PayloadCodec expected map in payload.".to_owned()})),
+        }
+    }
+}
+
+fn take_entry(entries: &mut Vec<(String, Wire)>, key: &str) -> Option<Wire> {
+    let index = entries.iter().position(|(k, _)| k == key)?;
+    Some(entries.remove(index).1)
+}
+
+impl Serialize for Wire {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Wire::Null => serializer.serialize_unit(),
+            Wire::Bool(b) => serializer.serialize_bool(*b),
+            Wire::Number(n) => n.serialize(serializer),
+            Wire::String(s) => serializer.serialize_str(s),
+            Wire::Bytes(b) => serializer.serialize_bytes(b),
+            Wire::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            },
+            Wire::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (k, v) in entries {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            },
+        }
+    }
+}
+
+struct WireVisitor;
+
+impl<'de> Visitor<'de> for WireVisitor {
+    type Value = Wire;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a value of juiz payload")
+    }
+
+    fn visit_unit<E>(self) -> Result<Wire, E> { Ok(Wire::Null) }
+    fn visit_none<E>(self) -> Result<Wire, E> { Ok(Wire::Null) }
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Wire, D::Error> { Wire::deserialize(d) }
+    fn visit_bool<E>(self, v: bool) -> Result<Wire, E> { Ok(Wire::Bool(v)) }
+    fn visit_i64<E>(self, v: i64) -> Result<Wire, E> { Ok(Wire::Number(v.into())) }
+    fn visit_u64<E>(self, v: u64) -> Result<Wire, E> { Ok(Wire::Number(v.into())) }
+    fn visit_f64<E>(self, v: f64) -> Result<Wire, E> { Ok(Number::from_f64(v).map_or(Wire::Null, Wire::Number)) }
+    fn visit_str<E>(self, v: &str) -> Result<Wire, E> { Ok(Wire::String(v.to_owned())) }
+    fn visit_string<E>(self, v: String) -> Result<Wire, E> { Ok(Wire::String(v)) }
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Wire, E> { Ok(Wire::Bytes(v.to_vec())) }
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Wire, E> { Ok(Wire::Bytes(v)) }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Wire, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Wire::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Wire, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+        while let Some((k, v)) = map.next_entry::<String, Wire>()? {
+            entries.push((k, v));
+        }
+        Ok(Wire::Map(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for Wire {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Wire, D::Error> {
+        deserializer.deserialize_any(WireVisitor)
+    }
+}
+
+fn value_to_wire(value: &Value) -> Wire {
+    match value {
+        Value::Null => Wire::Null,
+        Value::Bool(b) => Wire::Bool(*b),
+        Value::Number(n) => Wire::Number(n.clone()),
+        Value::String(s) => Wire::String(s.clone()),
+        Value::Array(items) => Wire::Array(items.iter().map(value_to_wire).collect()),
+        Value::Object(map) => Wire::Map(map.iter().map(|(k, v)| (k.clone(), value_to_wire(v))).collect()),
+    }
+}
+
+fn wire_to_value(wire: Wire) -> JuizResult<Value> {
+    Ok(match wire {
+        Wire::Null => Value::Null,
+        Wire::Bool(b) => Value::Bool(b),
+        Wire::Number(n) => Value::Number(n),
+        Wire::String(s) => Value::String(s),
+        Wire::Bytes(_) => return Err(anyhow!(JuizError::ValueTypeError{message: "PayloadCodec found binary where JSON value is expected.".to_owned()})),
+        Wire::Array(items) => Value::Array(items.into_iter().map(wire_to_value).collect::<JuizResult<Vec<Value>>>()?),
+        Wire::Map(entries) => Value::Object(entries.into_iter().map(|(k, v)| Ok((k, wire_to_value(v)?))).collect::<JuizResult<Map<String, Value>>>()?),
+    })
+}
+
+fn numeric_array_to_wire(array: &NumericArray) -> Wire {
+    let Wire::Map(mut header) = value_to_wire(&array.header()) else { unreachable!() };
+    header.push(("data".to_owned(), Wire::Bytes(array.to_le_bytes())));
+    Wire::Map(header)
+}
+
+fn wire_to_numeric_array(wire: Wire) -> JuizResult<NumericArray> {
+    let mut header = wire.into_map()?;
+    let data = match take_entry(&mut header, "data") {
+        Some(Wire::Bytes(data)) => data,
+        _ => return Err(anyhow!(JuizError::ValueTypeError{message: "PayloadCodec numeric array must have binary data.".to_owned()})),
+    };
+    NumericArray::from_header_and_bytes(&wire_to_value(Wire::Map(header))?, &data)
+}
+
+fn capsule_to_wire(capsule: &CapsulePtr) -> JuizResult<Wire> {
+    if capsule.is_bytes()? {
+        capsule.lock_as_bytes(|bytes| Wire::tagged(BYTES_VALUE_KEY, Wire::Bytes(bytes.to_vec())))
+    } else if capsule.is_numeric_array()? {
+        capsule.lock_as_numeric_array(|array| Wire::tagged(NUMERIC_ARRAY_VALUE_KEY, numeric_array_to_wire(array)))
+    } else if capsule.is_image()? {
+        let array = capsule.lock_as_image(image_to_raw)??;
+        Ok(Wire::tagged(IMAGE_VALUE_KEY, numeric_array_to_wire(&array)))
+    } else if capsule.is_empty()? {
+        Ok(Wire::Map(Vec::new()))
+    } else {
+        capsule.lock_as_value(value_to_wire)
+    }
+}
+
+fn wire_to_capsule(wire: Wire) -> JuizResult<CapsulePtr> {
+    if let Wire::Map(entries) = &wire {
+        if let [(key, value)] = entries.as_slice() {
+            match (key.as_str(), value) {
+                (BYTES_VALUE_KEY, Wire::Bytes(_)) => {
+                    let Some(Wire::Bytes(bytes)) = wire.into_map()?.pop().map(|(_, v)| v) else { unreachable!() };
+                    return Ok(bytes.into());
+                },
+                (NUMERIC_ARRAY_VALUE_KEY, Wire::Map(_)) => {
+                    return Ok(wire_to_numeric_array(wire.into_map()?.pop().unwrap().1)?.into());
+                },
+                (IMAGE_VALUE_KEY, Wire::Map(_)) => {
+                    return Ok(raw_to_image(&wire_to_numeric_array(wire.into_map()?.pop().unwrap().1)?)?.into());
+                },
+                _ => {},
+            }
+        }
+    }
+    // Valueからの変換で__value__と__option__の包みを解かないよう、値のまま入れる
+    Ok(Capsule::from(CapsuleValue::from(wire_to_value(wire)?)).into())
+}
+
+impl Display for PayloadCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl TryFrom<&str> for PayloadCodec {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "json" => Ok(PayloadCodec::Json),
+            "msgpack" | "messagepack" => Ok(PayloadCodec::MessagePack),
+            "cbor" => Ok(PayloadCodec::Cbor),
+            _ => Err(anyhow!(JuizError::InvalidValueError{message: format!("PayloadCodec '{value}' is not supported. Use json, msgpack or cbor.")})),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! WebSocketブローカー
+//!
+//! 1本のWebSocket接続の上でCRUD呼び出しと、プロセス出力やトピックのサーバープッシュを行う。
+//!
+//! メッセージはJSONのテキストフレームで、リクエストは
+//! `{"request_id", "method_name", "class_name", "function_name", "params", "payload"}`。
+//! method_nameはCREATE/READ/UPDATE/DELETEに加えてSUBSCRIBE/UNSUBSCRIBEが使える。
+//! 応答は `{"request_id", "value"}` か `{"request_id", "error"}`、
+//! 購読中の出力は `{"subscription_id", "value"}` で送られる。
+//! 画像の場合は `"value"` の代わりに `"image": "png"` を持つヘッダの直後にPNGのバイナリフレームが続く。
+//! バイト列は `"bytes": true`、数値の配列は要素の型と形 `"numeric_array": {"dtype", "shape"}` を持つヘッダの直後に、
+//! それぞれのバイト列 (数値の配列はリトルエンディアンの要素の列) がバイナリフレームで続く。
+
+pub mod websocket_broker;
+pub mod websocket_broker_proxy;
+mod websocket_message;
+
+pub use websocket_broker::websocket_broker_factory;
+pub use websocket_broker_proxy::{websocket_broker_proxy_factory, WebSocketBrokerProxy};
@@ -0,0 +1,221 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use axum::{extract::{ws::{Message, WebSocket, WebSocketUpgrade}, State}, response::IntoResponse, routing, Router};
+use futures::{SinkExt, StreamExt};
+use juiz_sdk::{anyhow, connections::{ConnectionManifest, ConnectionType}};
+use tokio::{net::TcpListener, sync::mpsc::{unbounded_channel, UnboundedSender}};
+use uuid::Uuid;
+
+use super::super::core_broker::CoreBrokerPtr;
+use super::websocket_message::{capsule_to_frames, error_frame, Frame};
+use crate::{brokers::broker_ptr::BrokerPtr, prelude::*};
+use crate::brokers::{broker_factory_impl::create_broker_factory_impl, messenger::messenger_broker::handle_function, BrokerFactory, CRUDBroker, CRUDBrokerHolder};
+use crate::connections::connect;
+use crate::processes::process_from_clousure_new_with_class_name;
+use crate::connections::ConnectionFactoryImpl;
+
+/// 購読の登録情報。sourceの出力接続arg_nameに購読用のプロセスがつながっている。
+struct Subscription {
+    source: ProcessPtr,
+    arg_name: String,
+}
+
+type SubscriptionMap = Arc<Mutex<HashMap<String, Subscription>>>;
+
+async fn on_start(broker_manifest: Value, crud_broker: Arc<Mutex<CRUDBroker>>) -> () {
+    tokio::spawn(on_start_inner(broker_manifest, crud_broker) );
+}
+
+async fn on_start_inner(broker_manifest: Value, crud_broker: Arc<Mutex<CRUDBroker>>) -> () {
+    log::trace!("websocket_broker::on_start(broker_manifest={broker_manifest:}) called");
+    let host = obj_get_str(&broker_manifest, "host").unwrap_or("0.0.0.0");
+    let port = obj_get_i64(&broker_manifest, "port").unwrap_or(8090);
+    match TcpListener::bind(format!("{host}:{port}")).await {
+        Ok(listener) => {
+            log::info!("websocket_broker is starting with (host={host}, port={port})");
+            crud_broker.lock().unwrap().set_started();
+            let app = Router::new()
+                .route("/ws", routing::get(websocket_handler))
+                .with_state(crud_broker);
+            log::trace!("websocket_broker::on_start() exit");
+            axum::serve(listener, app).await.unwrap()
+        },
+        Err(e) => {
+            log::error!("websocket_broker::on_start(broker_manifest='{broker_manifest:}') failed. Error({e:?}, {e})");
+        }
+    }
+}
+
+async fn websocket_handler(ws: WebSocketUpgrade, State(crud_broker): State<Arc<Mutex<CRUDBroker>>>) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_socket(socket, crud_broker))
+}
+
+async fn handle_socket(socket: WebSocket, crud_broker: Arc<Mutex<CRUDBroker>>) {
+    log::trace!("websocket_broker::handle_socket() called");
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+    // 応答とプッシュは同じ送信キューに積まれるので順序が保たれる
+    let (sender, mut receiver) = unbounded_channel::<Frame>();
+    let send_task = tokio::spawn(async move {
+        while let Some(frame) = receiver.recv().await {
+            let message = match frame {
+                Frame::Text(text) => Message::Text(text),
+                Frame::Binary(bytes) => Message::Binary(bytes),
+            };
+            if ws_sender.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let subscriptions: SubscriptionMap = Arc::new(Mutex::new(HashMap::new()));
+    while let Some(Ok(message)) = ws_receiver.next().await {
+        match message {
+            Message::Text(text) => {
+                let crud = crud_broker.clone();
+                let subs = subscriptions.clone();
+                let sndr = sender.clone();
+                let _ = tokio::task::spawn_blocking(move || {
+                    handle_text(crud, subs, sndr, text)
+                }).await;
+            },
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    let _ = tokio::task::spawn_blocking(move || {
+        for (subscription_id, subscription) in juiz_lock(&subscriptions)?.drain() {
+            log::trace!("websocket_broker: subscription({subscription_id}) is released.");
+            subscription.source.lock_mut()?.disconnect_to(subscription.arg_name.as_str())?;
+        }
+        JuizResult::Ok(())
+    }).await;
+    send_task.abort();
+    log::trace!("websocket_broker::handle_socket() exit");
+}
+
+fn handle_text(crud_broker: Arc<Mutex<CRUDBroker>>, subscriptions: SubscriptionMap, sender: UnboundedSender<Frame>, text: String) {
+    let request = match juiz_sdk::serde_json::from_str::<Value>(text.as_str()) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("websocket_broker received invalid message. Error({e})");
+            return;
+        }
+    };
+    let header = jvalue!({"request_id": request.get("request_id").cloned().unwrap_or(Value::Null)});
+    let frames = handle_request(crud_broker, subscriptions, sender.clone(), &request)
+        .and_then(|capsule| capsule_to_frames(header.clone(), &capsule))
+        .unwrap_or_else(|e| vec![error_frame(header, &e)]);
+    for frame in frames {
+        let _ = sender.send(frame);
+    }
+}
+
+fn request_to_capsule_map(request: &Value) -> JuizResult<CapsuleMap> {
+    let mut args = match request.get("payload") {
+        Some(payload) if payload.is_object() => CapsuleMap::try_from(payload.clone())?,
+        _ => CapsuleMap::new(),
+    };
+    if let Some(params) = request.get("params").and_then(|p| p.as_object()) {
+        for (k, v) in params.iter() {
+            if let Some(s) = v.as_str() {
+                args.set_param(k.as_str(), s);
+            }
+        }
+    }
+    for key in ["method_name", "class_name", "function_name"] {
+        args.set_param(key, obj_get_str(request, key)?);
+    }
+    Ok(args)
+}
+
+fn handle_request(crud_broker: Arc<Mutex<CRUDBroker>>, subscriptions: SubscriptionMap, sender: UnboundedSender<Frame>, request: &Value) -> JuizResult<CapsulePtr> {
+    let args = request_to_capsule_map(request)?;
+    match obj_get_str(request, "method_name")? {
+        "SUBSCRIBE" => subscribe(crud_broker, subscriptions, sender, args),
+        "UNSUBSCRIBE" => unsubscribe(subscriptions, args),
+        _ => handle_function(crud_broker, args),
+    }
+}
+
+fn get_param<'a>(args: &'a CapsuleMap, key: &str) -> JuizResult<&'a String> {
+    args.get_param(key).ok_or_else(|| anyhow::Error::from(JuizError::CRUDBrokerCanNotParameterFunctionError { key_name: key.to_owned() }))
+}
+
+fn subscribe(crud_broker: Arc<Mutex<CRUDBroker>>, subscriptions: SubscriptionMap, sender: UnboundedSender<Frame>, args: CapsuleMap) -> JuizResult<CapsulePtr> {
+    let class_name = get_param(&args, "class_name")?;
+    log::trace!("websocket_broker::subscribe(class_name={class_name}) called");
+    let core_broker: CoreBrokerPtr = juiz_lock(&crud_broker)?.core_broker().clone();
+    let source = match class_name.as_str() {
+        "process" | "container_process" => {
+            core_broker.lock()?.worker().any_process_from_identifier(get_param(&args, "identifier")?, false)?
+        },
+        "topic" => {
+            let topic_name = get_param(&args, "topic_name")?;
+            core_broker.lock()?.worker().store().topics.get(topic_name)
+                .ok_or_else(|| anyhow::Error::from(JuizError::ObjectCanNotFoundByIdError { id: topic_name.to_owned() + ":topic" }))?
+                .process_ptr()
+        },
+        _ => return Err(anyhow::Error::from(JuizError::CRUDBrokerCanNotFindFunctionError { class_name: class_name.to_owned(), function_name: "subscribe".to_owned() })),
+    };
+
+    let subscription_id = Uuid::new_v4().to_string();
+    let arg_name = format!("websocket_{subscription_id}");
+    let subscriber = subscriber_new(subscription_id.as_str(), arg_name.as_str(), sender)?;
+    connect(source.clone(), subscriber.clone(), ConnectionManifest::new(
+        ConnectionType::Push,
+        source.identifier().clone(),
+        arg_name.clone(),
+        subscriber.identifier().clone(),
+        None))?;
+    juiz_lock(&subscriptions)?.insert(subscription_id.clone(), Subscription{source, arg_name});
+    Ok(jvalue!({"subscription_id": subscription_id}).into())
+}
+
+fn unsubscribe(subscriptions: SubscriptionMap, args: CapsuleMap) -> JuizResult<CapsulePtr> {
+    let subscription_id = get_param(&args, "subscription_id")?;
+    log::trace!("websocket_broker::unsubscribe(subscription_id={subscription_id}) called");
+    let subscription = juiz_lock(&subscriptions)?.remove(subscription_id)
+        .ok_or_else(|| anyhow::Error::from(JuizError::CanNotFindError { target: format!("Subscription({subscription_id})") }))?;
+    subscription.source.lock_mut()?.disconnect_to(subscription.arg_name.as_str())?;
+    Ok(jvalue!({"subscription_id": subscription_id}).into())
+}
+
+/// 購読用のプロセス。pushされた値をそのままWebSocketに流す。
+fn subscriber_new(subscription_id: &str, arg_name: &str, sender: UnboundedSender<Frame>) -> JuizResult<ProcessPtr> {
+    let manifest: ProcessManifest = jvalue!({
+        "type_name": "websocket_subscriber",
+        "name": format!("websocket_subscriber_{subscription_id}"),
+        "use_memo": false,
+        "arguments": [
+            {
+                "name": arg_name,
+                "default": {},
+                "type": "object",
+            }
+        ]
+    }).try_into()?;
+    let my_subscription_id = subscription_id.to_owned();
+    let my_arg_name = arg_name.to_owned();
+    let func = move |args: CapsuleMap| -> JuizResult<Capsule> {
+        let v = args.get(my_arg_name.as_str())?;
+        for frame in capsule_to_frames(jvalue!({"subscription_id": my_subscription_id}), &v)? {
+            if sender.send(frame).is_err() {
+                log::trace!("subscription({my_subscription_id}) is already closed.");
+            }
+        }
+        Ok(Capsule::empty())
+    };
+    Ok(ProcessPtr::new(process_from_clousure_new_with_class_name(JuizObjectClass::Process("WebSocketSubscriber"), manifest, func, Box::new(ConnectionFactoryImpl::new()))?))
+}
+
+pub fn websocket_broker_factory(core_broker: CoreBrokerPtr) -> JuizResult<Arc<Mutex<dyn BrokerFactory>>> {
+    fn create_broker_function(core_broker: CoreBrokerPtr, manifest: Value) -> JuizResult<BrokerPtr> {
+        Ok(BrokerPtr::new(CRUDBrokerHolder::new("WebSocketBroker", "websocket", core_broker, &on_start, manifest.clone())?))
+    }
+
+    let manifest = jvalue!({
+        "type_name": "websocket"
+    });
+    create_broker_factory_impl(core_broker, manifest, create_broker_function)
+}
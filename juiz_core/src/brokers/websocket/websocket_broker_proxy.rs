@@ -0,0 +1,244 @@
+use std::{collections::HashMap, sync::{atomic::{AtomicU64, Ordering}, mpsc, Arc, Mutex}, time::Duration};
+
+use futures::{SinkExt, StreamExt};
+use juiz_sdk::anyhow::{self, anyhow};
+use tokio::{runtime, sync::mpsc::{unbounded_channel, UnboundedSender}};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+use super::websocket_message::{binary_to_capsule, header_to_payload, BinaryKind, Payload, WebSocketBrokerError};
+use crate::{core::CoreWorker, prelude::*};
+use crate::brokers::call_timeout::{default_timeout_from_manifest, timeout_error, timeout_from_param};
+use crate::brokers::{create_broker_proxy_factory_impl, BrokerProxy, BrokerProxyFactory, CRUDBrokerProxy, CRUDBrokerProxyHolder};
+
+pub type SubscriptionCallback = Box<dyn Fn(CapsulePtr) + Send + 'static>;
+
+/// 受信したヘッダの宛先
+#[derive(Clone)]
+enum Destination {
+    Response(u64),
+    Subscription(String),
+}
+
+#[derive(Default)]
+struct ReceiverState {
+    responses: HashMap<u64, mpsc::Sender<JuizResult<CapsulePtr>>>,
+    subscriptions: HashMap<String, SubscriptionCallback>,
+}
+
+impl ReceiverState {
+
+    fn deliver(&mut self, destination: Destination, result: JuizResult<CapsulePtr>) {
+        match destination {
+            Destination::Response(request_id) => {
+                if let Some(sender) = self.responses.remove(&request_id) {
+                    let _ = sender.send(result);
+                }
+            },
+            Destination::Subscription(subscription_id) => {
+                match (self.subscriptions.get(&subscription_id), result) {
+                    (Some(callback), Ok(capsule)) => callback(capsule),
+                    (Some(_), Err(e)) => log::error!("WebSocketBrokerProxy received invalid data for subscription({subscription_id}). Error({e})"),
+                    (None, _) => log::trace!("WebSocketBrokerProxy received data for unknown subscription({subscription_id})."),
+                }
+            }
+        }
+    }
+}
+
+fn header_to_destination(header: &Value) -> Option<Destination> {
+    if let Some(request_id) = header.get("request_id").and_then(|v| v.as_u64()) {
+        Some(Destination::Response(request_id))
+    } else {
+        header.get("subscription_id").and_then(|v| v.as_str()).map(|id| Destination::Subscription(id.to_owned()))
+    }
+}
+
+/// WebSocketBrokerへのプロキシ
+///
+/// CRUDBrokerProxyとして振る舞うほか、プロセス出力やトピックを購読できる。
+pub struct WebSocketBrokerProxy {
+    url: String,
+    timeout: Duration,
+    request_id: AtomicU64,
+    sender: UnboundedSender<Message>,
+    state: Arc<Mutex<ReceiverState>>,
+    _tokio_runtime: runtime::Runtime,
+}
+
+impl WebSocketBrokerProxy {
+
+    pub fn new(manifest: &Value) -> JuizResult<WebSocketBrokerProxy> {
+        log::trace!("WebSocketBrokerProxy::new({manifest:}) called");
+        let name = obj_get_str(manifest, "name")?;
+        let url = format!("ws://{name}/ws");
+        let timeout = Duration::new(3, 0);
+        let tokio_runtime = runtime::Builder::new_multi_thread().worker_threads(2).enable_all().build()?;
+
+        // 呼び出し元がtokioのコンテキストにいても良いように、接続は自前のランタイムで行って結果だけ待つ
+        let (connected_sender, connected_receiver) = mpsc::channel();
+        let connecting_url = url.clone();
+        tokio_runtime.spawn(async move {
+            let _ = connected_sender.send(connect_async(connecting_url).await);
+        });
+        let (stream, _response) = connected_receiver.recv_timeout(timeout)
+            .map_err(|_| anyhow!(JuizError::TimeoutError{}))??;
+        let (mut ws_sender, mut ws_receiver) = stream.split();
+
+        let (sender, mut receiver) = unbounded_channel::<Message>();
+        tokio_runtime.spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                if ws_sender.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let state = Arc::new(Mutex::new(ReceiverState::default()));
+        let receiver_state = state.clone();
+        tokio_runtime.spawn(async move {
+            let mut binary_destination: Option<(Destination, BinaryKind)> = None;
+            while let Some(Ok(message)) = ws_receiver.next().await {
+                let (destination, result) = match message {
+                    Message::Text(text) => {
+                        let header = match juiz_sdk::serde_json::from_str::<Value>(text.as_str()) {
+                            Ok(v) => v,
+                            Err(e) => {
+                                log::error!("WebSocketBrokerProxy received invalid message. Error({e})");
+                                continue;
+                            }
+                        };
+                        let Some(destination) = header_to_destination(&header) else { continue; };
+                        match header_to_payload(&header) {
+                            Payload::Binary(kind) => {
+                                binary_destination = Some((destination, kind));
+                                continue;
+                            },
+                            Payload::Ready(result) => (destination, result),
+                        }
+                    },
+                    Message::Binary(bytes) => {
+                        let Some((destination, kind)) = binary_destination.take() else { continue; };
+                        (destination, binary_to_capsule(&kind, bytes.as_ref()))
+                    },
+                    Message::Close(_) => break,
+                    _ => continue,
+                };
+                match receiver_state.lock() {
+                    Ok(mut s) => s.deliver(destination, result),
+                    Err(_) => break,
+                }
+            }
+            // 接続が切れたら待っている呼び出しはすべて解放する
+            if let Ok(mut s) = receiver_state.lock() {
+                s.responses.clear();
+            }
+        });
+
+        Ok(WebSocketBrokerProxy{
+            url,
+            timeout,
+            request_id: AtomicU64::new(0),
+            sender,
+            state,
+            _tokio_runtime: tokio_runtime,
+        })
+    }
+
+    fn request(&self, method_name: &str, class_name: &str, function_name: &str, payload: Value, param: HashMap<String, String>) -> JuizResult<CapsulePtr> {
+        log::trace!("WebSocketBrokerProxy({}).request({method_name}, {class_name}, {function_name}, {param:?}) called", self.url);
+        let timeout = timeout_from_param(&param)?.unwrap_or(self.timeout);
+        let params = construct_params(param)?;
+        let request_id = self.request_id.fetch_add(1, Ordering::SeqCst);
+        let (response_sender, response_receiver) = mpsc::channel();
+        juiz_lock(&self.state)?.responses.insert(request_id, response_sender);
+        let request = jvalue!({
+            "request_id": request_id,
+            "method_name": method_name,
+            "class_name": class_name,
+            "function_name": function_name,
+            "params": params,
+            "payload": payload,
+        });
+        self.sender.send(Message::Text(request.to_string()))
+            .map_err(|_| anyhow::Error::from(WebSocketBrokerError::ConnectionClosedError{}))?;
+        match response_receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                juiz_lock(&self.state)?.responses.remove(&request_id);
+                log::error!("WebSocketBrokerProxy({}).request({class_name}, {function_name}) timeout.", self.url);
+                Err(timeout_error(&self.url, timeout))
+            },
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(anyhow::Error::from(WebSocketBrokerError::ConnectionClosedError{})),
+        }
+    }
+
+    fn subscribe(&self, class_name: &str, param: HashMap<String, String>, callback: SubscriptionCallback) -> JuizResult<String> {
+        let response = self.request("SUBSCRIBE", class_name, "subscribe", Value::Null, param)?;
+        let subscription_id = response.lock_as_value(|v| obj_get_str(v, "subscription_id").map(|s| s.to_owned()))??;
+        juiz_lock(&self.state)?.subscriptions.insert(subscription_id.clone(), callback);
+        Ok(subscription_id)
+    }
+
+    /// プロセスの出力を購読する。プロセスがexecuteされるたびにcallbackが呼ばれる。
+    ///
+    /// callbackは受信スレッドから呼ばれるので、callbackの中でこのプロキシを呼び出してはいけない。
+    pub fn subscribe_process(&self, identifier: &Identifier, callback: impl Fn(CapsulePtr) + Send + 'static) -> JuizResult<String> {
+        self.subscribe("process", HashMap::from([("identifier".to_owned(), identifier.clone())]), Box::new(callback))
+    }
+
+    /// トピックを購読する。トピックにデータが届くたびにcallbackが呼ばれる。
+    pub fn subscribe_topic(&self, topic_name: &str, callback: impl Fn(CapsulePtr) + Send + 'static) -> JuizResult<String> {
+        self.subscribe("topic", HashMap::from([("topic_name".to_owned(), topic_name.to_owned())]), Box::new(callback))
+    }
+
+    pub fn unsubscribe(&self, subscription_id: &str) -> JuizResult<()> {
+        juiz_lock(&self.state)?.subscriptions.remove(subscription_id);
+        self.request("UNSUBSCRIBE", "subscription", "unsubscribe", Value::Null, HashMap::from([("subscription_id".to_owned(), subscription_id.to_owned())]))?;
+        Ok(())
+    }
+}
+
+fn construct_params(param: HashMap<String, String>) -> JuizResult<HashMap<String, String>> {
+    param.into_iter().map(|(key, value)| {
+        if key == "identifier" {
+            let mut id_struct = IdentifierStruct::try_from(value)?;
+            id_struct.broker_type_name = "core".to_owned();
+            id_struct.broker_name = "core".to_owned();
+            Ok((key, id_struct.into()))
+        } else {
+            Ok((key, value))
+        }
+    }).collect()
+}
+
+impl CRUDBrokerProxy for WebSocketBrokerProxy {
+    fn create(&self, class_name: &str, function_name: &str, payload: Value, param: HashMap<String, String>) -> JuizResult<CapsulePtr> {
+        self.request("CREATE", class_name, function_name, payload, param)
+    }
+
+    fn delete(&self, class_name: &str, function_name: &str, param: HashMap<String, String>) -> JuizResult<CapsulePtr> {
+        self.request("DELETE", class_name, function_name, Value::Null, param)
+    }
+
+    fn read(&self, class_name: &str, function_name: &str, param: HashMap<String, String>) -> JuizResult<CapsulePtr> {
+        self.request("READ", class_name, function_name, Value::Null, param)
+    }
+
+    fn update(&self, class_name: &str, function_name: &str, payload: CapsuleMap, param: HashMap<String, String>) -> JuizResult<CapsulePtr> {
+        self.request("UPDATE", class_name, function_name, payload.into(), param)
+    }
+}
+
+fn create_broker_proxy_function(_core_broker: &CoreWorker, manifest: Value) -> JuizResult<Arc<Mutex<dyn BrokerProxy>>> {
+    let name = obj_get_str(&manifest, "name")?;
+    let holder = CRUDBrokerProxyHolder::new("WebSocketBrokerProxy", "websocket", name, Box::new(WebSocketBrokerProxy::new(&manifest)?))?;
+    juiz_lock(&holder)?.set_default_timeout(default_timeout_from_manifest(&manifest)?);
+    Ok(holder)
+}
+
+pub fn websocket_broker_proxy_factory() -> JuizResult<Arc<Mutex<dyn BrokerProxyFactory>>> {
+    let manifest = jvalue!({
+        "type_name": "websocket"
+    });
+    create_broker_proxy_factory_impl(manifest, create_broker_proxy_function)
+}
@@ -0,0 +1,106 @@
+use std::io::{BufWriter, Cursor};
+
+use juiz_sdk::image::ImageFormat;
+use juiz_sdk::anyhow;
+use thiserror::Error;
+
+use crate::prelude::*;
+
+
+#[derive(Error, Debug, PartialEq)]
+pub(crate) enum WebSocketBrokerError {
+    #[error("WebSocketBroker returned error (message={message})")]
+    RemoteError{ message: String },
+
+    #[error("WebSocketBroker connection is closed.")]
+    ConnectionClosedError{},
+}
+
+/// サーバー、クライアントで共通に使うフレーム
+pub(crate) enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// ヘッダの後に続くバイナリフレームの中身
+pub(crate) enum BinaryKind {
+    Image,
+    Bytes,
+    /// 要素の型と形 (NumericArray::header())
+    NumericArray(Value),
+}
+
+/// ヘッダの受信後に受け取るべきもの
+pub(crate) enum Payload {
+    Ready(JuizResult<CapsulePtr>),
+    Binary(BinaryKind),
+}
+
+/// headerにcapsuleを詰めてフレームにする。画像はPNGの、バイト列と数値の配列はそのままのバイナリフレームを後ろにつける。
+pub(crate) fn capsule_to_frames(mut header: Value, capsule: &CapsulePtr) -> JuizResult<Vec<Frame>> {
+    let map = get_hashmap_mut(&mut header)?;
+    if capsule.is_image()? {
+        let bytes = capsule.lock_as_image_view(|image| -> JuizResult<Vec<u8>> {
+            let mut buffer = BufWriter::new(Cursor::new(Vec::new()));
+            image.write_to(&mut buffer, ImageFormat::Png)?;
+            Ok(buffer.into_inner()?.into_inner())
+        })??;
+        map.insert("image".to_owned(), jvalue!("png"));
+        Ok(vec![Frame::Text(header.to_string()), Frame::Binary(bytes)])
+    } else if capsule.is_bytes()? {
+        let bytes = capsule.lock_as_bytes(|bytes| bytes.to_vec())?;
+        map.insert("bytes".to_owned(), jvalue!(true));
+        Ok(vec![Frame::Text(header.to_string()), Frame::Binary(bytes)])
+    } else if capsule.is_numeric_array()? {
+        let (array_header, bytes) = capsule.lock_as_numeric_array(|array| (array.header(), array.to_le_bytes()))?;
+        map.insert("numeric_array".to_owned(), array_header);
+        Ok(vec![Frame::Text(header.to_string()), Frame::Binary(bytes)])
+    } else if capsule.is_value()? {
+        let value = capsule.lock_as_value(|v| v.clone())?;
+        map.insert("value".to_owned(), value);
+        Ok(vec![Frame::Text(header.to_string())])
+    } else {
+        map.insert("value".to_owned(), Value::Null);
+        Ok(vec![Frame::Text(header.to_string())])
+    }
+}
+
+pub(crate) fn error_frame(mut header: Value, e: &anyhow::Error) -> Frame {
+    if let Some(map) = header.as_object_mut() {
+        map.insert("error".to_owned(), jvalue!(format!("{e:#}")));
+        // 時間切れはクライアントでRemoteCallTimeoutErrorに戻す
+        if let Some(JuizError::RemoteCallTimeoutError{target, timeout_sec}) = e.downcast_ref::<JuizError>() {
+            map.insert("timeout".to_owned(), jvalue!({"target": target, "timeout_sec": timeout_sec}));
+        }
+    }
+    Frame::Text(header.to_string())
+}
+
+pub(crate) fn header_to_payload(header: &Value) -> Payload {
+    if let Some(message) = header.get("error") {
+        if let Some(timeout) = header.get("timeout") {
+            let target = obj_get_str(timeout, "target").unwrap_or("").to_owned();
+            let timeout_sec = obj_get_f64(timeout, "timeout_sec").unwrap_or_default();
+            return Payload::Ready(Err(anyhow::Error::from(JuizError::RemoteCallTimeoutError{target, timeout_sec})));
+        }
+        return Payload::Ready(Err(anyhow::Error::from(WebSocketBrokerError::RemoteError{message: message.as_str().unwrap_or("").to_owned()})));
+    }
+    if header.get("image").is_some() {
+        return Payload::Binary(BinaryKind::Image);
+    }
+    if header.get("bytes").is_some() {
+        return Payload::Binary(BinaryKind::Bytes);
+    }
+    if let Some(array_header) = header.get("numeric_array") {
+        return Payload::Binary(BinaryKind::NumericArray(array_header.clone()));
+    }
+    Payload::Ready(Ok(header.get("value").cloned().unwrap_or(Value::Null).into()))
+}
+
+pub(crate) fn binary_to_capsule(kind: &BinaryKind, bytes: &[u8]) -> JuizResult<CapsulePtr> {
+    match kind {
+        BinaryKind::Image => Ok(juiz_sdk::image::load_from_memory(bytes)?.into()),
+        BinaryKind::Bytes => Ok(bytes.to_vec().into()),
+        BinaryKind::NumericArray(array_header) => Ok(NumericArray::from_header_and_bytes(array_header, bytes)?.into()),
+    }
+}
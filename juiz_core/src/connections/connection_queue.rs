@@ -0,0 +1,217 @@
+//! Push型接続のQoSを実現するキュー
+//!
+//! ConnectionQoSのqueue_depthが0の時は送り手のスレッドでそのまま受け手に渡す。
+//! 1以上の時は受け手への配送用のスレッドを立てて、送り手はキューに積むだけで戻る。
+
+use std::{collections::VecDeque, sync::{atomic::{AtomicU64, Ordering}, Arc, Condvar, Mutex, MutexGuard}, thread, time::{Duration, Instant}};
+
+use juiz_sdk::{anyhow::anyhow, connections::{ConnectionQoS, QueuePolicy}};
+use crate::prelude::*;
+
+type DeliverFunction = Arc<dyn Fn(CapsulePtr) -> JuizResult<CapsulePtr> + Send + Sync>;
+
+#[derive(Default)]
+struct ConnectionStatistics {
+    queued: AtomicU64,
+    dropped: AtomicU64,
+    delivered: AtomicU64,
+}
+
+struct QueueState {
+    items: VecDeque<CapsulePtr>,
+    last_delivery: Option<Instant>,
+    closed: bool,
+}
+
+struct QueueShared {
+    state: Mutex<QueueState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    statistics: ConnectionStatistics,
+}
+
+impl QueueShared {
+    fn lock(&self) -> JuizResult<MutexGuard<'_, QueueState>> {
+        self.state.lock().map_err(|e| anyhow!(JuizError::MutexLockFailedError{error: e.to_string()}))
+    }
+
+    /// min_periodに対して前回の配送からまだ待つ必要がある時間
+    fn remaining(state: &QueueState, min_period: Option<Duration>) -> Option<Duration> {
+        match (min_period, state.last_delivery) {
+            (Some(period), Some(last)) => period.checked_sub(last.elapsed()).filter(|d| !d.is_zero()),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) struct ConnectionQueue {
+    name: String,
+    qos: ConnectionQoS,
+    shared: Arc<QueueShared>,
+    deliver: DeliverFunction,
+}
+
+impl ConnectionQueue {
+
+    pub(crate) fn new(name: &str, mut qos: ConnectionQoS, deliver: impl Fn(CapsulePtr) -> JuizResult<CapsulePtr> + Send + Sync + 'static) -> Self {
+        log::trace!("ConnectionQueue::new({name}, {qos:?}) called");
+        let shared = Arc::new(QueueShared{
+            state: Mutex::new(QueueState{ items: VecDeque::new(), last_delivery: None, closed: false }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            statistics: ConnectionStatistics::default(),
+        });
+        let deliver: DeliverFunction = Arc::new(deliver);
+        if qos.is_queued() {
+            let worker_shared = shared.clone();
+            let worker_deliver = deliver.clone();
+            let worker_name = name.to_owned();
+            let min_period = qos.min_period;
+            let result = thread::Builder::new().name(format!("juiz_connection_{name}")).spawn(move || {
+                if let Err(e) = deliver_loop(&worker_shared, &worker_deliver, min_period) {
+                    log::error!("ConnectionQueue({worker_name}) delivery thread stopped. Error({e})");
+                }
+            });
+            if let Err(e) = result {
+                log::error!("ConnectionQueue({name}) can not start delivery thread. Falls back to synchronous push. Error({e})");
+                qos.queue_depth = 0;
+            }
+        }
+        Self{ name: name.to_owned(), qos, shared, deliver }
+    }
+
+    /// 受け手にデータを渡す。キューを使う場合は積むだけで、受け手の出力は待たない。
+    pub(crate) fn push(&self, value: CapsulePtr) -> JuizResult<CapsulePtr> {
+        if self.qos.is_queued() {
+            return self.enqueue(value);
+        }
+        if self.qos.min_period.is_some() {
+            let mut state = self.shared.lock()?;
+            if QueueShared::remaining(&state, self.qos.min_period).is_some() {
+                log::trace!("ConnectionQueue({})::push() dropped data because min_period has not passed.", self.name);
+                self.shared.statistics.dropped.fetch_add(1, Ordering::SeqCst);
+                return Ok(value);
+            }
+            state.last_delivery = Some(Instant::now());
+        }
+        let output = (self.deliver)(value)?;
+        self.shared.statistics.delivered.fetch_add(1, Ordering::SeqCst);
+        Ok(output)
+    }
+
+    fn enqueue(&self, value: CapsulePtr) -> JuizResult<CapsulePtr> {
+        let depth = self.qos.queue_depth;
+        let mut state = self.shared.lock()?;
+        if state.items.len() >= depth {
+            match self.qos.policy {
+                QueuePolicy::DropOldest => {
+                    state.items.pop_front();
+                    self.shared.statistics.dropped.fetch_add(1, Ordering::SeqCst);
+                },
+                QueuePolicy::DropNewest => {
+                    self.shared.statistics.dropped.fetch_add(1, Ordering::SeqCst);
+                    return Ok(value);
+                },
+                QueuePolicy::Block => {
+                    state = self.shared.not_full.wait_while(state, |s| s.items.len() >= depth && !s.closed)
+                        .map_err(|e| anyhow!(JuizError::MutexLockFailedError{error: e.to_string()}))?;
+                }
+            }
+        }
+        // 閉じられたキューには積まない。Blockで待っている間に閉じられた時もここで断る
+        if state.closed {
+            return Err(anyhow!(JuizError::ConnectionClosedError{identifier: self.name.clone()}));
+        }
+        state.items.push_back(value.clone());
+        self.shared.statistics.queued.fetch_add(1, Ordering::SeqCst);
+        self.shared.not_empty.notify_one();
+        Ok(value)
+    }
+
+    pub(crate) fn profile_full(&self) -> JuizResult<Value> {
+        let queue_length = self.shared.lock()?.items.len();
+        let statistics = &self.shared.statistics;
+        Ok(jvalue!({
+            "queued": statistics.queued.load(Ordering::SeqCst),
+            "dropped": statistics.dropped.load(Ordering::SeqCst),
+            "delivered": statistics.delivered.load(Ordering::SeqCst),
+            "queue_length": queue_length,
+        }))
+    }
+
+    /// キューを閉じる。積まれたデータは捨て、配送用のスレッドとBlockで待っている送り手を起こす
+    pub(crate) fn close(&self) {
+        if let Ok(mut state) = self.shared.lock() {
+            state.closed = true;
+            state.items.clear();
+        }
+        self.shared.not_empty.notify_all();
+        self.shared.not_full.notify_all();
+    }
+}
+
+impl Drop for ConnectionQueue {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+fn deliver_loop(shared: &QueueShared, deliver: &DeliverFunction, min_period: Option<Duration>) -> JuizResult<()> {
+    loop {
+        let value = {
+            let mut state = shared.not_empty.wait_while(shared.lock()?, |s| s.items.is_empty() && !s.closed)
+                .map_err(|e| anyhow!(JuizError::MutexLockFailedError{error: e.to_string()}))?;
+            if state.closed {
+                return Ok(());
+            }
+            if let Some(remaining) = QueueShared::remaining(&state, min_period) {
+                // 待っている間も送り手はキューに積めるようにロックを外す
+                drop(state);
+                thread::sleep(remaining);
+                continue;
+            }
+            state.last_delivery = Some(Instant::now());
+            let value = state.items.pop_front();
+            shared.not_full.notify_one();
+            value
+        };
+        if let Some(v) = value {
+            match deliver(v) {
+                Ok(_) => { shared.statistics.delivered.fetch_add(1, Ordering::SeqCst); },
+                Err(e) => log::error!("ConnectionQueue delivery failed. Error({e})"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc};
+    use std::{thread, time::Duration};
+    use juiz_sdk::connections::{ConnectionQoS, QueuePolicy};
+    use crate::prelude::*;
+    use super::ConnectionQueue;
+
+    #[test]
+    fn blocked_push_fails_after_close_test() -> JuizResult<()> {
+        // 配送を止めておき、キューが一杯のままになるようにする
+        let (release, blocker) = mpsc::channel::<()>();
+        let blocker = std::sync::Mutex::new(blocker);
+        let queue = Arc::new(ConnectionQueue::new("blocked", ConnectionQoS::new(1, QueuePolicy::Block, None), move |v| {
+            let _ = blocker.lock().unwrap().recv();
+            Ok(v)
+        }));
+        queue.push(jvalue!(1).into())?;
+        thread::sleep(Duration::from_millis(100));
+        queue.push(jvalue!(2).into())?;
+
+        let pusher = queue.clone();
+        let handle = thread::spawn(move || pusher.push(jvalue!(3).into()));
+        thread::sleep(Duration::from_millis(100));
+        queue.close();
+        assert!(handle.join().unwrap().is_err());
+        assert!(queue.push(jvalue!(4).into()).is_err());
+        drop(release);
+        Ok(())
+    }
+}
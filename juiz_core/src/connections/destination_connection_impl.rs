@@ -7,21 +7,32 @@ use crate::prelude::*;
 
 use core::fmt::Debug;
 use std::clone::Clone;
+use std::sync::Arc;
 
 use juiz_sdk::connections::{DestinationConnection, Connection, ConnectionCore};
 
+use super::connection_queue::ConnectionQueue;
+
 
 pub struct DestinationConnectionImpl{
     core: ConnectionCore,
-    destination_process: ProcessPtr
+    destination_process: ProcessPtr,
+    queue: Arc<ConnectionQueue>,
 }
 
 impl DestinationConnectionImpl {
 
     pub fn new_from_manifest(connection_manifest: ConnectionManifest,  destination_process: ProcessPtr) -> Self {
+        let core = ConnectionCore::new("DestinationConnection",  connection_manifest);
+        let process = destination_process.clone();
+        let arg_name = core.arg_name().clone();
+        let queue = ConnectionQueue::new(&core.object_core().identifier(), core.qos().clone(), move |value| {
+            process.lock()?.push_by(arg_name.as_str(), value)
+        });
         DestinationConnectionImpl{
-            core: ConnectionCore::new("DestinationConnection",  connection_manifest),
-            destination_process}
+            core,
+            destination_process,
+            queue: Arc::new(queue)}
     }
 
     // pub fn new(owner_identifier: &Identifier, destination_process_id: &Identifier, dest_process: ProcessPtr, connection_manifest: Value, arg_name: String) -> JuizResult<Self> {
@@ -54,7 +65,11 @@ impl JuizObjectCoreHolder for DestinationConnectionImpl {
 impl JuizObject for DestinationConnectionImpl {
 
     fn profile_full(&self) -> JuizResult<Value> {
-        self.core.profile_full()
+        let mut v = self.core.profile_full()?;
+        obj_merge_mut(&mut v, &jvalue!({
+            "statistics": self.queue.profile_full()?,
+        }))?;
+        Ok(v)
     }
 
 }
@@ -74,9 +89,8 @@ impl DestinationConnection for DestinationConnectionImpl {
 
     fn push(&self, value: CapsulePtr) -> JuizResult<CapsulePtr> {
         log::trace!("DestinationConnectionImpl::push() called");
-        let proc = self.destination_process.lock()?;
         if self.connection_type() == ConnectionType::Push {
-            proc.push_by(self.arg_name(), value)
+            self.queue.push(value)
         } else {
             Ok(value)
         }
@@ -91,6 +105,6 @@ impl<'a> Debug for DestinationConnectionImpl {
 
 impl Clone for DestinationConnectionImpl {
     fn clone(&self) -> Self {
-        Self { core: self.core.clone(), destination_process: self.destination_process.clone() }
+        Self { core: self.core.clone(), destination_process: self.destination_process.clone(), queue: self.queue.clone() }
     }
 }
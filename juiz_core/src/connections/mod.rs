@@ -5,6 +5,7 @@ pub mod destination_connection_impl;
 pub mod connection_builder;
 pub mod connection_factory;
 pub mod connection_factory_impl;
+mod connection_queue;
 
 pub use connection_factory::ConnectionFactory;
 pub use connection_factory_impl::ConnectionFactoryImpl;
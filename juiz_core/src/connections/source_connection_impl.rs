@@ -74,6 +74,10 @@ impl SourceConnection for SourceConnectionImpl {
         log::trace!("SourceConnectionImpl({}).pull() called", self.identifier());
         self.source_process.lock()?.invoke()
     }
+
+    fn source_process(&self) -> ProcessPtr {
+        self.source_process.clone()
+    }
 }
 
 impl<'a> Debug for SourceConnectionImpl {
@@ -112,6 +112,10 @@ impl Process for ContainerProcessImpl {
         self.process_mut()?.try_connect_to(target, connection_manifest)
     }
 
+    fn disconnect_to(&mut self, arg_name: &str) -> JuizResult<()> {
+        self.process_mut()?.disconnect_to(arg_name)
+    }
+
     fn source_connections(&self) -> JuizResult<Vec<&Box<dyn SourceConnection>>> {
         self.process()?.source_connections()
     }
@@ -123,6 +127,10 @@ impl Process for ContainerProcessImpl {
     fn p_apply(&mut self, arg_name: &str, value: CapsulePtr) -> JuizResult<CapsulePtr> {
         self.process_mut()?.p_apply(arg_name, value)
     }
+
+    fn stale_inlets(&self) -> JuizResult<Vec<String>> {
+        self.process()?.stale_inlets()
+    }
     
     fn purge(&mut self) -> JuizResult<()> {
         log::trace!("ContainerProcessImpl({})::purge() called", self.identifier());
@@ -1,9 +1,10 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::{Arc, Mutex}};
 use super::buffer_object_collection::BufferObjectCollection;
 use super::object_collection::ObjectCollection;
 use super::mutex_object_collection::MutexObjectCollection;
 
 use crate::topics::TopicPtr;
+use crate::recording::{Player, Recorder};
 use crate::prelude::*;
 use crate::ecs::{execution_context_function::ExecutionContextFunction, execution_context_holder_factory::ExecutionContextHolderFactory};
 
@@ -15,6 +16,12 @@ pub struct CoreStore {
     broker_factories_manifests: HashMap<Identifier, Value>,
     brokers_manifests: HashMap<Identifier, Value>,
     pub topics: HashMap<Identifier, TopicPtr>,
+    pub recorders: HashMap<String, Arc<Mutex<Recorder>>>,
+    pub players: HashMap<String, Arc<Mutex<Player>>>,
+    /// 合成プロセスの型名ごとの定義
+    pub composite_processes: HashMap<String, CompositeProcessManifest>,
+    /// 合成プロセスのIDごとの内部プロセスのID
+    pub composite_members: HashMap<Identifier, Vec<Identifier>>,
 
     pub processes: Box<ObjectCollection::<ProcessPtr, ProcessFactoryPtr>>,
     pub containers: Box<ObjectCollection::<ContainerPtr, ContainerFactoryPtr>>,
@@ -32,6 +39,10 @@ impl CoreStore {
             broker_proxies: BufferObjectCollection::new("broker_proxy"),
             broker_factories_manifests: HashMap::new(),
             topics: HashMap::new(),
+            recorders: HashMap::new(),
+            players: HashMap::new(),
+            composite_processes: HashMap::new(),
+            composite_members: HashMap::new(),
             //processes: RwObjectCollection::new("process"), 
             processes: ObjectCollection::new("process"), 
             //containers: RwObjectCollection::new("container"), 
@@ -85,6 +96,8 @@ impl CoreStore {
     fn clear_process_factories(&mut self) -> JuizResult<()> {
         log::trace!("clear_broker_factories() called");
         self.processes.clear()?;
+        self.composite_processes.clear();
+        self.composite_members.clear();
         Ok(())
     }
 
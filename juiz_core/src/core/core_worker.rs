@@ -4,7 +4,7 @@ use std::{collections::HashMap, env::current_dir, path::PathBuf, sync::{Arc, Mut
 use juiz_sdk::{connections::ConnectionManifest, identifier::{connection_identifier_split, identifier_from_manifest}, utils::manifest_util::{construct_id, id_from_manifest, id_from_manifest_and_class_name, type_name}};
 use uuid::Uuid;
 
-use crate::{connections::connection_builder::connection_builder, containers::{ContainerProcessImpl, ContainerProxy}, core::system_builder::register_component, ecs::{execution_context_function::ExecutionContextFunction, execution_context_proxy::ExecutionContextProxy}, plugin::JuizObjectPlugin, prelude::*, topics::TopicPtr};
+use crate::{connections::connection_builder::connection_builder, containers::{ContainerProcessImpl, ContainerProxy}, processes::composite_process_new, core::system_builder::register_component, ecs::{execution_context_function::ExecutionContextFunction, execution_context_proxy::ExecutionContextProxy}, plugin::{JuizObjectPlugin, PluginPolicy}, prelude::*, topics::TopicPtr};
 
 use super::{core_store::CoreStore, system_builder::{register_container_factory, register_container_process_factory, register_process_factory}};
 use juiz_sdk::anyhow::anyhow;
@@ -147,15 +147,65 @@ impl CoreWorker {
 
     pub fn create_process_ref(&mut self, manifest: ProcessManifest) -> JuizResult<ProcessPtr> {
         log::trace!("CoreBroker::create_process_ref(manifest={:?}) called", manifest);
+        if let Some(definition) = self.store().composite_processes.get(manifest.type_name.as_str()).cloned() {
+            return self.create_composite_process_ref(&definition, manifest);
+        }
         let arc_pf = self.store().processes.factory(manifest.type_name.as_str())?;
         let p = arc_pf.lock()?.create_process(manifest)?;
         let id = p.identifier().clone();
         Ok(self.store_mut().processes.register(&id, p)?.clone())
     }
 
+    /// 合成プロセスの型を登録する。以後create_process_refでこの型名のプロセスを作れる
+    pub fn register_composite_process(&mut self, definition: CompositeProcessManifest) -> JuizResult<()> {
+        log::trace!("CoreBroker::register_composite_process(type_name={}) called", definition.type_name);
+        if self.store().processes.factory(definition.type_name.as_str()).is_ok() {
+            return Err(anyhow!(JuizError::InvalidSettingError{message: format!("CompositeProcess type_name '{}' is already used by ProcessFactory.", definition.type_name)}));
+        }
+        self.store_mut().composite_processes.insert(definition.type_name.clone(), definition);
+        Ok(())
+    }
+
+    /// 内部プロセスを作り、それらをまとめた合成プロセスを登録する。内部プロセスどうしは接続せず、合成プロセスが順に評価する
+    fn create_composite_process_ref(&mut self, definition: &CompositeProcessManifest, manifest: ProcessManifest) -> JuizResult<ProcessPtr> {
+        log::trace!("CoreBroker::create_composite_process_ref(type_name={}) called", definition.type_name);
+        let instance_name = manifest.name.clone().ok_or_else(|| anyhow!(JuizError::ProcessManifestInvalidError{message: format!("CompositeProcess({}) must have 'name'.", definition.type_name)}))?;
+        let mut processes: HashMap<String, ProcessPtr> = HashMap::new();
+        let mut member_ids = Vec::new();
+        let result = (|| {
+            for inner_manifest in definition.processes.iter() {
+                let inner_name = inner_manifest.name.clone().unwrap_or_default();
+                let p = self.create_process_ref(inner_manifest.clone().name(definition.inner_name(instance_name.as_str(), inner_name.as_str()).as_str()))?;
+                member_ids.push(p.identifier().clone());
+                processes.insert(inner_name, p);
+            }
+            composite_process_new(definition, manifest, &processes)
+        })();
+        let p = match result {
+            Ok(p) => p,
+            Err(e) => {
+                // 途中まで作った内部プロセスを残さない
+                for member_id in member_ids.iter() {
+                    let _ = self.destroy_process_ref(member_id);
+                }
+                return Err(e);
+            }
+        };
+        let id = p.identifier().clone();
+        self.store_mut().composite_members.insert(id.clone(), member_ids);
+        Ok(self.store_mut().processes.register(&id, p)?.clone())
+    }
+
     pub fn destroy_process_ref(&mut self, identifier: &Identifier) -> JuizResult<ProcessPtr> {
         log::trace!("CoreBroker::destroy_process(identifier={}) called", identifier);
-        self.store_mut().processes.deregister_by_id(identifier)
+        let p = self.store_mut().processes.deregister_by_id(identifier)?;
+        // 合成プロセスなら内部プロセスも一緒に消す
+        if let Some(member_ids) = self.store_mut().composite_members.remove(identifier) {
+            for member_id in member_ids.iter() {
+                self.destroy_process_ref(member_id)?.lock_mut()?.purge()?;
+            }
+        }
+        Ok(p)
     }
 
     pub fn create_container_ref(&mut self, type_name: &str, mut manifest: CapsuleMap) -> JuizResult<ContainerPtr> {
@@ -349,6 +399,7 @@ impl CoreWorker {
             log::error!("create_ec_ref({:}) failed. Error({e})", manifest.clone());
             Err(e)
         })?;
+        juiz_lock(&p)?.on_create(self)?;
 
         self.store_mut().ecs.register(p)
     }
@@ -367,6 +418,18 @@ impl CoreWorker {
         Ok(ExecutionContextProxy::new(JuizObjectClass::ExecutionContext("ExecutionContextProxy"),identifier, broker_proxy)?)
     }
     
+    /// ECを止めてon_destroyを呼び、ストアから外す
+    pub fn destroy_ec_ref(&mut self, id: &Identifier) -> JuizResult<Arc<Mutex<dyn ExecutionContextFunction>>> {
+        log::trace!("CoreBroker::destroy_ec_ref(id={id}) called");
+        let ec = self.store().ecs.get(id)?;
+        {
+            let mut ec_lock = juiz_lock(&ec)?;
+            ec_lock.stop()?;
+            ec_lock.on_destroy(self)?;
+        }
+        self.store_mut().ecs.deregister_by_id(id)
+    }
+
     pub fn cleanup_ecs(&mut self) -> JuizResult<()> {
         for ec in self.store_mut().ecs.objects().values() {
             juiz_lock(&ec)?.stop()?;
@@ -397,6 +460,10 @@ impl CoreWorker {
         log::error!("process_publish_topic({topic_info:?}) called");
         let topic_name = topic_info.name.as_str();
         let topic = self.create_topic(topic_name.to_owned())?;
+        // 符号化が書かれていない出版者は、他の出版者が決めた符号化を変えない
+        if topic_info.encoding != TopicEncoding::default() {
+            topic.set_encoding(topic_info.encoding)?;
+        }
         self.connect_to_topic(process, topic)?;
         Ok(())
     }
@@ -442,16 +509,15 @@ impl CoreWorker {
         Ok(())
     }
 
+    /// システムのマニフェストの"plugin_policy"。相対パスはworking_dirを基準にする
+    pub fn plugin_policy(&self, working_dir: Option<PathBuf>) -> JuizResult<PluginPolicy> {
+        PluginPolicy::from_system_manifest(&self.store.manifest(), working_dir)
+    }
+
     pub fn load_process_factory(&mut self, language: String, filepath: String) -> JuizResult<Value> {
         log::trace!("load_process_factory({language}, {filepath}) called");
-        let plugin = match language.as_str() {
-            "rust" => JuizObjectPlugin::new_rust(PathBuf::from(filepath))?,
-            "python" => JuizObjectPlugin::new_python(PathBuf::from(filepath))?,
-            "cpp" => JuizObjectPlugin::new_cpp(PathBuf::from(filepath), "manifest")?,
-            _ => {
-                panic!("invalid langauge {language}")
-            }
-        };
+        let policy = self.plugin_policy(current_dir().ok())?;
+        let plugin = load_plugin(&language, filepath, "manifest", &policy)?;
         let proc_factory_ptr = register_process_factory(self, current_dir().map_or_else(|_|{None}, |wd|{Some(wd)}), plugin, "process_factory", None)?;
         let p = proc_factory_ptr.lock()?.profile_full()?;
         Ok(p)
@@ -459,14 +525,8 @@ impl CoreWorker {
 
     pub fn load_container_factory(&mut self, language: String, filepath: String) -> JuizResult<Value> {
         log::trace!("load_container_factory({language}, {filepath}) called");
-        let plugin = match language.as_str() {
-            "rust" => JuizObjectPlugin::new_rust(PathBuf::from(filepath))?,
-            "python" => JuizObjectPlugin::new_python(PathBuf::from(filepath))?,
-            "cpp" => JuizObjectPlugin::new_cpp(PathBuf::from(filepath), "manifest")?,
-            _ => {
-                panic!("invalid langauge {language}")
-            }
-        };
+        let policy = self.plugin_policy(current_dir().ok())?;
+        let plugin = load_plugin(&language, filepath, "manifest", &policy)?;
         // let plugin = JuizObjectPlugin::new_rust(PathBuf::from(filepath))?;
         let cont_factory_ptr = register_container_factory(self, current_dir().map_or_else(|_|{None}, |wd|{Some(wd)}), plugin, "container_factory", None)?;
         let p = cont_factory_ptr.lock()?.profile_full()?;
@@ -475,14 +535,8 @@ impl CoreWorker {
 
 
     pub fn load_container_process_factory(&mut self, language: String, filepath: String) -> JuizResult<Value> {
-        let plugin = match language.as_str() {
-            "rust" => JuizObjectPlugin::new_rust(PathBuf::from(filepath))?,
-            "python" => JuizObjectPlugin::new_python(PathBuf::from(filepath))?,
-            "cpp" => JuizObjectPlugin::new_cpp(PathBuf::from(filepath), "manifest")?,
-            _ => {
-                panic!("invalid langauge {language}")
-            }
-        };
+        let policy = self.plugin_policy(current_dir().ok())?;
+        let plugin = load_plugin(&language, filepath, "manifest", &policy)?;
         //let plugin = JuizObjectPlugin::new_rust(PathBuf::from(filepath))?;
         let contproc_factory_ptr = register_container_process_factory(self, current_dir().map_or_else(|_|{None}, |wd|{Some(wd)}), plugin, "container_process_factory", None)?;
         let p = contproc_factory_ptr.lock()?.profile_full()?;
@@ -491,14 +545,8 @@ impl CoreWorker {
 
     pub fn load_component(&mut self, language: String, filepath: String) -> JuizResult<Value> {
         log::trace!("load_component({language}, {filepath}) called");
-        let plugin = match language.as_str() {
-            "rust" => JuizObjectPlugin::new_rust(PathBuf::from(filepath))?,
-            "python" => JuizObjectPlugin::new_python(PathBuf::from(filepath))?,
-            "cpp" => JuizObjectPlugin::new_cpp(PathBuf::from(filepath), "component_manifest")?,
-            _ => {
-                panic!("invalid langauge {language}")
-            }
-        };
+        let policy = self.plugin_policy(current_dir().ok())?;
+        let plugin = load_plugin(&language, filepath, "component_manifest", &policy)?;
 
         let cont_manifest = register_component(self, current_dir().map_or_else(|_|{None}, |wd|{Some(wd)}), plugin)?;
         Ok(cont_manifest.into())
@@ -514,14 +562,15 @@ impl CoreWorker {
     pub fn connection_profile_full(&self, identifier: Identifier, create_when_not_found: bool) -> JuizResult<Value> {
         let (source_id, destination_id, _arg_name) = connection_identifier_split(identifier.clone())?;
         
-        let dst_proc = self.any_process_from_identifier(&destination_id, create_when_not_found)?;
-        for con in dst_proc.lock()?.source_connections()?.into_iter() {
+        // Push型接続のキューの統計は送り手側の接続が持っているので先に探す
+        let src_proc = self.any_process_from_identifier(&source_id, create_when_not_found)?;
+        for con in src_proc.lock()?.destination_connections()?.into_iter() {
             if con.identifier() == identifier {
                 return con.profile_full()
             }
         }
-        let src_proc = self.any_process_from_identifier(&source_id, create_when_not_found)?;
-        for con in src_proc.lock()?.destination_connections()?.into_iter() {
+        let dst_proc = self.any_process_from_identifier(&destination_id, create_when_not_found)?;
+        for con in dst_proc.lock()?.source_connections()?.into_iter() {
             if con.identifier() == identifier {
                 return con.profile_full()
             }
@@ -590,4 +639,16 @@ fn check_has_type_name(manifest: Value) -> JuizResult<Value> {
     // let _ = obj_get_str(&manifest,"name")?;
     let _ = obj_get_str(&manifest, "type_name")?;
     return Ok(manifest_updated)
-}
\ No newline at end of file
+}
+/// languageのプラグインをpolicyで確かめてから読み込む。知らないlanguageはエラーにする
+fn load_plugin(language: &str, filepath: String, manifest_entry_point: &str, policy: &PluginPolicy) -> JuizResult<JuizObjectPlugin> {
+    match language {
+        "rust" => JuizObjectPlugin::new_rust(PathBuf::from(filepath), policy),
+        "python" => JuizObjectPlugin::new_python(PathBuf::from(filepath), policy),
+        "cpp" => JuizObjectPlugin::new_cpp(PathBuf::from(filepath), manifest_entry_point, policy),
+        _ => {
+            log::error!("invalid language ({language}) for plugin ({filepath})");
+            Err(anyhow!(JuizError::InvalidSettingError{message: format!("Language '{language}' of plugin ({filepath}) is invalid. It must be one of 'rust', 'python' or 'cpp'.")}))
+        }
+    }
+}
@@ -4,6 +4,8 @@ mod system_builder;
 mod core_store;
 mod system_store;
 mod subsystem_proxy;
+mod subsystem_heartbeat;
+mod system_discovery;
 mod core_worker;
 
 // pub use core_broker::CoreBroker;
@@ -11,4 +13,8 @@ mod core_worker;
 pub use core_worker::CoreWorker;
 pub use system::System;
 pub use system_store::{SystemStore, SystemStorePtr};
-pub use subsystem_proxy::SubSystemProxy;
\ No newline at end of file
+pub use subsystem_proxy::{SubSystemProxy, SubSystemState};
+pub use subsystem_heartbeat::SubSystemHeartbeat;
+pub use system_discovery::{SystemDiscovery, SystemDiscoveryConfig};
+pub(crate) use system_discovery::select_peer_broker;
+pub(crate) use subsystem_heartbeat::{heartbeat, heartbeat_if_due};
\ No newline at end of file
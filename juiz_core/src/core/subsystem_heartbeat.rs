@@ -0,0 +1,174 @@
+//! マスターシステム・サブシステムとのハートビート
+//!
+//! System::spin()から定期的に相手のUUIDを問い合わせ、続けて応答が無ければSubSystemProxyをDEADにしてトピックの転送を止める。
+//! 同じUUIDの相手が戻ってきたら、トピックの購読と接続を張り直す。
+
+use std::time::{Duration, Instant};
+
+use juiz_sdk::anyhow::anyhow;
+
+use crate::{brokers::CoreBrokerPtr, core::SubSystemProxy, prelude::*, topics::TopicPtr};
+
+/// システムのマニフェストの"heartbeat"の設定
+///
+/// ```json
+/// "heartbeat": {"interval": 1.0, "timeout": 1.0, "max_missed": 3}
+/// ```
+#[derive(Clone, Debug)]
+pub struct SubSystemHeartbeat {
+    interval: Duration,
+    timeout: Duration,
+    max_missed: usize,
+    last_beat: Option<Instant>,
+}
+
+impl Default for SubSystemHeartbeat {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(1),
+            max_missed: 3,
+            last_beat: None,
+        }
+    }
+}
+
+fn duration_from_manifest(manifest: &Value, key: &str, default: Duration) -> JuizResult<Duration> {
+    match obj_get_f64(manifest, key) {
+        Ok(sec) => Duration::try_from_secs_f64(sec)
+            .map_err(|e| anyhow!(JuizError::InvalidSettingError{message: format!("heartbeat {key} ({sec}) is invalid. {e}")})),
+        Err(_) => Ok(default),
+    }
+}
+
+impl SubSystemHeartbeat {
+
+    pub fn new(manifest: &Value) -> JuizResult<Self> {
+        let default = Self::default();
+        let max_missed = match obj_get_i64(manifest, "max_missed") {
+            Ok(n) if n > 0 => n as usize,
+            Ok(n) => return Err(anyhow!(JuizError::InvalidSettingError{message: format!("heartbeat max_missed ({n}) must be positive.")})),
+            Err(_) => default.max_missed,
+        };
+        Ok(Self {
+            interval: duration_from_manifest(manifest, "interval", default.interval)?,
+            timeout: duration_from_manifest(manifest, "timeout", default.timeout)?,
+            max_missed,
+            last_beat: None,
+        })
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn max_missed(&self) -> usize {
+        self.max_missed
+    }
+
+    /// intervalが過ぎていればtrueを返し、次の周期を始める
+    pub(crate) fn start_if_due(&mut self) -> bool {
+        let now = Instant::now();
+        match self.last_beat {
+            Some(last) if now.duration_since(last) < self.interval => false,
+            _ => {
+                self.last_beat = Some(now);
+                true
+            }
+        }
+    }
+}
+
+/// すべての相手にハートビートを送る
+///
+/// 相手が応答を待たせてもこちらの処理が止まらないように、CoreBrokerのロックを持たずに問い合わせる。
+pub(crate) fn heartbeat(core_broker: &CoreBrokerPtr) -> JuizResult<()> {
+    let (peers, timeout, max_missed) = {
+        let cb = core_broker.lock()?;
+        (cb.peers(), cb.heartbeat().timeout(), cb.heartbeat().max_missed())
+    };
+    for peer in peers.iter() {
+        if peer.heartbeat(timeout, max_missed)? {
+            if let Err(e) = restore(core_broker, peer) {
+                log::error!("restore({peer}) failed. Retry at next heartbeat. Error({e})");
+                peer.request_restore()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn heartbeat_if_due(core_broker: &CoreBrokerPtr) -> JuizResult<()> {
+    if !core_broker.lock_mut()?.heartbeat_mut().start_if_due() {
+        return Ok(());
+    }
+    heartbeat(core_broker)
+}
+
+/// 戻ってきた相手とトピックの購読と接続を張り直す
+fn restore(core_broker: &CoreBrokerPtr, peer: &SubSystemProxy) -> JuizResult<()> {
+    log::info!("restore topics and connections with {peer}");
+    let (my_uuid, topics, processes) = {
+        let cb = core_broker.lock()?;
+        let store = cb.worker().store();
+        let processes = store.processes.objects().values()
+            .chain(store.container_processes.objects().values())
+            .cloned().collect::<Vec<ProcessPtr>>();
+        (cb.system_store().uuid()?, store.topics.values().cloned().collect::<Vec<TopicPtr>>(), processes)
+    };
+    let broker_proxy = peer.broker_proxy();
+
+    for topic in topics.iter() {
+        if topic.num_local_publishers()? > 0 {
+            let result = juiz_lock(&broker_proxy)?.topic_request_subscribe(topic.name(), Some(my_uuid))?;
+            if obj_get_bool(&result, "subscribe")? {
+                topic.register_subscriber_subsystem(peer.clone())?;
+            }
+        }
+        if topic.num_local_subscribers()? > 0 {
+            juiz_lock(&broker_proxy)?.topic_request_publish(topic.name(), Some(my_uuid))?;
+        }
+    }
+
+    let (peer_type_name, peer_name) = {
+        let bp = juiz_lock(&broker_proxy)?;
+        (bp.type_name().to_owned(), bp.name().to_owned())
+    };
+    let is_on_peer = |id: &Identifier| -> bool {
+        IdentifierStruct::try_from(id.clone())
+            .map(|s| s.broker_type_name == peer_type_name && s.broker_name == peer_name)
+            .unwrap_or(false)
+    };
+    // connection_builder::connect()と同じように、相手側の端にだけ接続を知らせ直す
+    for process in processes.iter() {
+        let (to_peer, from_peer) = {
+            let p = process.lock()?;
+            let to_peer = p.destination_connections()?.into_iter()
+                .filter(|c| is_on_peer(c.connection_core().destination_identifier()))
+                .map(|c| (c.connection_core().source_identifier().clone(), c.arg_name().clone(), c.connection_core().destination_identifier().clone(), c.connection_type().to_string(), c.identifier().clone()))
+                .collect::<Vec<_>>();
+            let from_peer = p.source_connections()?.into_iter()
+                .filter(|c| is_on_peer(c.connection_core().source_identifier()))
+                .map(|c| (c.connection_core().source_identifier().clone(), c.arg_name().clone(), c.connection_core().destination_identifier().clone(), c.connection_type().to_string(), c.identifier().clone()))
+                .collect::<Vec<_>>();
+            (to_peer, from_peer)
+        };
+        for (source_id, arg_name, destination_id, connection_type, connection_id) in to_peer.into_iter() {
+            log::trace!("restore connection({connection_id}) to {peer}");
+            juiz_lock(&broker_proxy)?.process_notify_connected_from(&source_id, &arg_name, &to_core_identifier(&destination_id)?, connection_type, Some(connection_id))?;
+        }
+        for (source_id, arg_name, destination_id, connection_type, connection_id) in from_peer.into_iter() {
+            log::trace!("restore connection({connection_id}) from {peer}");
+            juiz_lock(&broker_proxy)?.process_try_connect_to(&to_core_identifier(&source_id)?, &arg_name, &destination_id, connection_type, Some(connection_id))?;
+        }
+    }
+    Ok(())
+}
+
+/// 相手側から見たプロセスのidentifier
+fn to_core_identifier(id: &Identifier) -> JuizResult<Identifier> {
+    let mut id_struct = IdentifierStruct::try_from(id.clone())?;
+    id_struct.broker_type_name = "core".to_owned();
+    id_struct.broker_name = "core".to_owned();
+    Ok(id_struct.to_identifier())
+}
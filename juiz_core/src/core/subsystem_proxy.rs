@@ -1,8 +1,34 @@
-use std::{fmt::Display, sync::{Arc, Mutex}};
+use std::{fmt::Display, sync::{Arc, Mutex}, time::{Duration, SystemTime, UNIX_EPOCH}};
 
 use uuid::Uuid;
 
-use crate::prelude::*;
+use crate::{brokers::call_timeout::{call_with_timeout, AbandonedCalls}, prelude::*};
+
+/// ハートビートで見た相手のシステムの状態
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SubSystemState {
+    ALIVE,
+    DEAD,
+}
+
+impl Display for SubSystemState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubSystemState::ALIVE => f.write_str("ALIVE"),
+            SubSystemState::DEAD => f.write_str("DEAD"),
+        }
+    }
+}
+
+struct SubSystemLiveness {
+    state: SubSystemState,
+    /// 続けて応答が無かったハートビートの数
+    missed_heartbeats: usize,
+    /// 最後に応答があった時刻 [sec]
+    last_heartbeat: Option<f64>,
+    /// 同じUUIDで登録し直された。次のハートビートでトピックと接続を張り直す
+    restore_requested: bool,
+}
 
 #[allow(unused)]
 #[derive(Clone)]
@@ -10,6 +36,10 @@ pub struct SubSystemProxy {
     uuid: Uuid, 
     broker_proxy: Arc<Mutex<dyn BrokerProxy>>,
     profile: Value,
+    /// TopicPtrなどに渡したコピーとも共有する
+    liveness: Arc<Mutex<SubSystemLiveness>>,
+    /// 応答が無く打ち切ったハートビート
+    abandoned_heartbeats: AbandonedCalls,
 }
 
 impl Display for SubSystemProxy {
@@ -30,7 +60,14 @@ impl SubSystemProxy {
         Ok(SubSystemProxy{ 
             uuid: system_uuid,
             broker_proxy,
-            profile
+            profile,
+            liveness: Arc::new(Mutex::new(SubSystemLiveness{
+                state: SubSystemState::ALIVE,
+                missed_heartbeats: 0,
+                last_heartbeat: Some(now_sec()),
+                restore_requested: false,
+            })),
+            abandoned_heartbeats: AbandonedCalls::default(),
         })
     }
     
@@ -43,8 +80,83 @@ impl SubSystemProxy {
         &self.uuid
     }
 
+    /// 相手のプロファイルにハートビートの状態を足したもの。相手に届かなければUUIDと状態だけ返す
     pub fn profile_full(&self) -> JuizResult<Value> {
-        self.broker_proxy.lock().unwrap().profile_full()
+        let liveness = self.liveness_profile()?;
+        if !self.is_alive()? {
+            return Ok(jvalue!({"uuid": self.uuid.to_string(), "liveness": liveness}));
+        }
+        match juiz_lock(&self.broker_proxy)?.profile_full() {
+            Ok(profile) => obj_merge(profile, &jvalue!({"liveness": liveness})),
+            Err(e) => {
+                log::warn!("{self}.profile_full() failed. Error({e})");
+                Ok(jvalue!({"uuid": self.uuid.to_string(), "liveness": liveness, "error": e.to_string()}))
+            }
+        }
+    }
+
+    pub fn state(&self) -> JuizResult<SubSystemState> {
+        Ok(juiz_lock(&self.liveness)?.state)
+    }
+
+    pub fn is_alive(&self) -> JuizResult<bool> {
+        Ok(self.state()? == SubSystemState::ALIVE)
+    }
+
+    pub fn liveness_profile(&self) -> JuizResult<Value> {
+        let liveness = juiz_lock(&self.liveness)?;
+        Ok(jvalue!({
+            "state": liveness.state.to_string(),
+            "missed_heartbeats": liveness.missed_heartbeats,
+            "last_heartbeat": liveness.last_heartbeat,
+        }))
+    }
+
+    /// 同じUUIDの相手が登録し直しに来た
+    pub(crate) fn request_restore(&self) -> JuizResult<()> {
+        juiz_lock(&self.liveness)?.restore_requested = true;
+        Ok(())
+    }
+
+    /// 相手にUUIDを尋ねて生きているか確かめる
+    ///
+    /// max_missed回続けて応答が無ければDEADにする。DEADから戻ったとき、
+    /// または登録し直されたときはtrueを返すので、呼び出し側でトピックと接続を張り直す。
+    pub(crate) fn heartbeat(&self, timeout: Duration, max_missed: usize) -> JuizResult<bool> {
+        let broker_proxy = self.broker_proxy.clone();
+        let result = call_with_timeout(&self.abandoned_heartbeats, &self.uuid.to_string(), Some(timeout), move || {
+            juiz_lock(&broker_proxy)?.system_uuid()
+        });
+        let responded = match result {
+            Ok(v) if v.as_str() == Some(self.uuid.to_string().as_str()) => true,
+            Ok(v) => {
+                log::warn!("{self} answered different uuid ({v}).");
+                false
+            },
+            Err(e) => {
+                log::debug!("{self} heartbeat failed. Error({e})");
+                false
+            }
+        };
+        let mut liveness = juiz_lock(&self.liveness)?;
+        if responded {
+            let restore = liveness.state == SubSystemState::DEAD || liveness.restore_requested;
+            if liveness.state == SubSystemState::DEAD {
+                log::info!("{self} is alive again.");
+            }
+            liveness.state = SubSystemState::ALIVE;
+            liveness.missed_heartbeats = 0;
+            liveness.last_heartbeat = Some(now_sec());
+            liveness.restore_requested = false;
+            Ok(restore)
+        } else {
+            liveness.missed_heartbeats += 1;
+            if liveness.state == SubSystemState::ALIVE && liveness.missed_heartbeats >= max_missed {
+                log::error!("{self} is dead. ({} heartbeats missed)", liveness.missed_heartbeats);
+                liveness.state = SubSystemState::DEAD;
+            }
+            Ok(false)
+        }
     }
 
     pub fn subsystems(&self) -> JuizResult<Value> {
@@ -65,4 +177,8 @@ impl SubSystemProxy {
         }).unwrap();
         Ok(jvalue!(r))
     }
-}
\ No newline at end of file
+}
+
+fn now_sec() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or_default()
+}
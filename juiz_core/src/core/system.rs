@@ -10,12 +10,14 @@ use juiz_sdk::utils::manifest_util::manifest_merge;
 use juiz_sdk::utils::yaml_conf_load::yaml_conf_load_with;
 
 use crate::brokers::broker_ptr::BrokerPtr;
+use crate::plugin::PluginPolicy;
 use crate::prelude::*;
 
 use crate::brokers::{
         broker_proxy::SystemBrokerProxy,
         broker_factories_wrapper::BrokerFactoriesWrapper};
 
+use super::select_peer_broker;
 use super::system_builder;
 use super::system_store::{SystemStore, SystemStorePtr};
 
@@ -59,12 +61,20 @@ impl System {
 
     pub fn new(manifest: Value) -> JuizResult<System> {
         let checked_manifest = check_system_manifest(manifest)?;
-        let _updated_manifest:Value = merge_home_manifest(checked_manifest)?;
+        let updated_manifest:Value = merge_home_manifest(checked_manifest)?;
         let store = SystemStorePtr::new(SystemStore::new());
+        // 再起動してもマスターシステムから同じシステムと分かるように、UUIDはマニフェストで固定できる
+        if let Ok(uuid_str) = obj_get_str(&updated_manifest, "uuid") {
+            store.lock_mut()?.uuid = uuid::Uuid::parse_str(uuid_str)
+                .map_err(|e| anyhow!(JuizError::InvalidSettingError{message: format!("System uuid ({uuid_str}) is invalid. {e}")}))?;
+        }
+        let mut core_broker = CoreBroker::new(jvalue!({"type_name": "CoreBroker", "name": "core_broker"}), store.clone())?;
+        // setup()はCoreStoreのマニフェストを見てオブジェクトを作る
+        *core_broker.worker_mut().manifest_mut() = updated_manifest;
         Ok(System {
             core: ObjectCore::create(JuizObjectClass::System("System"), "system", "system"),
             //manifest: updated_manifest.clone(),
-            core_broker: CoreBrokerPtr::new(CoreBroker::new(jvalue!({"type_name": "CoreBroker", "name": "core_broker"}), store.clone())?),
+            core_broker: CoreBrokerPtr::new(core_broker),
             sleep_time: time::Duration::from_millis(100),
             store,
             tokio_runtime: tokio::runtime::Builder::new_multi_thread().thread_name("juiz_core::System").worker_threads(4).enable_all().build().unwrap(),
@@ -95,6 +105,11 @@ impl System {
         self.working_dir.clone()
     }
 
+    /// システムのマニフェストの"plugin_policy"。相対パスは作業ディレクトリを基準にする
+    pub(crate) fn plugin_policy(&self) -> JuizResult<PluginPolicy> {
+        self.core_broker().lock()?.worker().plugin_policy(self.get_working_dir())
+    }
+
 
     // pub fn any_process_from_typename_and_name(&self, type_name: &str, name: &str) -> JuizResult<ProcessPtr> {
     //     let result = self.process_from_typename_and_name(type_name, name);
@@ -179,6 +194,37 @@ impl System {
         Ok(self)
     }
 
+    /// discoveryで見つけたシステムを名前で探してサブシステムに加える。見つかるまでtimeoutだけ待つ
+    pub fn add_subsystem_by_peer_name(self, name: Option<String>, timeout: Duration) -> JuizResult<Self> {
+        log::trace!("add_subsystem_by_peer_name(name={name:?}) called");
+        let name = match name {
+            Some(name) => name,
+            None => return Ok(self),
+        };
+        let start = time::Instant::now();
+        let peer = loop {
+            // 待っている間もBrokerが動けるように、CoreBrokerのロックは探すたびに離す
+            let found = match self.core_broker().lock()?.discovery() {
+                Some(discovery) => discovery.find_peer(name.as_str())?,
+                None => return Err(anyhow!(JuizError::InvalidSettingError{message: format!("add_subsystem_by_peer_name({name}) needs 'discovery' in system manifest.")})),
+            };
+            match found {
+                Some(peer) => break peer,
+                None if start.elapsed() >= timeout => return Err(anyhow!(JuizError::DiscoveredPeerCanNotFoundError{name})),
+                None => std::thread::sleep(Duration::from_millis(50)),
+            }
+        };
+        let local_broker_types = self.core_broker().lock()?.worker().store().brokers_profile_full()?
+            .as_object().map(|m| m.keys().cloned().collect::<Vec<String>>()).unwrap_or_default();
+        let profile = select_peer_broker(&peer, &local_broker_types)?;
+        log::info!("attach discovered System({name}) via {profile}");
+        if let Err(e) = self.core_broker().lock_mut()?.system_add_subsystem(profile) {
+            log::error!("Error in add_subsystem_by_peer_name(name={name}). Error({e:})");
+            return Err(e);
+        }
+        Ok(self)
+    }
+
     pub fn start_brokers(&mut self) -> JuizResult<()> {
         match self.store.lock_mut() {
             Ok(store) => {
@@ -287,11 +333,19 @@ impl System {
     /// 
     fn spin(&mut self) -> () {
         // log::debug!("System::spin() called");
+        if let Err(e) = self.core_broker.heartbeat_if_due() {
+            log::error!("System::spin() heartbeat failed. Error({e})");
+        }
         if self.spin_callback.is_some() {
             let _ = self.spin_callback.as_ref().unwrap()();
         }
     }
 
+    /// マスターシステムとサブシステムにすぐハートビートを送る。普段はrun中のspin()から周期的に送っている
+    pub fn heartbeat(&self) -> JuizResult<()> {
+        self.core_broker.heartbeat()
+    }
+
     pub fn run(&mut self) -> JuizResult<()> {
         log::debug!("System::run() called");
         log::info!("Juiz System({}) Now Started.", self.store.uuid()?);
@@ -18,18 +18,19 @@ pub(super) fn setup_broker_factories(system: &mut System, manifest: &Value) -> J
 
 fn setup_broker_factory(system: &mut System, manifest: &Value, name: &String, v: &Value) -> JuizResult<()> {
     log::trace!("setup_broker_factory(name={name:}) called");
-    let plugin_filename = concat_dirname(v, plugin_name_to_file_name(&name.to_string()))?;
+    let policy = system.plugin_policy()?;
+    let plugin_filename = policy.resolve(&concat_dirname(v, plugin_name_to_file_name(&name.to_string()))?)?;
     let bf;
     let bpf;
     unsafe {
-        let plugin: RustPlugin = RustPlugin::load(plugin_filename)?;
+        let plugin: RustPlugin = RustPlugin::load(plugin_filename)?.allow_missing_abi(policy.allow_missing_abi());
         {
             type BrokerFactorySymbolType<'a> = libloading::Symbol<'a, unsafe extern "Rust" fn(CoreBrokerPtr) -> JuizResult<Arc<Mutex<dyn BrokerFactory>>>>;
             type BrokerProxyFactorySymbolType<'a> = libloading::Symbol<'a, unsafe extern "Rust" fn() -> JuizResult<Arc<Mutex<dyn BrokerProxyFactory>>>>;
-            let symbol_bf = plugin.load_symbol::<BrokerFactorySymbolType>(b"broker_factory")?;
+            let symbol_bf = plugin.load_factory_symbol::<BrokerFactorySymbolType>("broker_factory")?;
             bf = (symbol_bf)(system.core_broker().clone()).with_context(||format!("calling symbol 'broker_factory'. arg is {manifest:}"))?;
             log::trace!("BrokerFactory (type_name={:?}) created.", juiz_lock(&bf)?.type_name());
-            let symbol_bpf = plugin.load_symbol::<BrokerProxyFactorySymbolType>(b"broker_proxy_factory")?;
+            let symbol_bpf = plugin.load_factory_symbol::<BrokerProxyFactorySymbolType>("broker_proxy_factory")?;
             bpf = (symbol_bpf)().with_context(||format!("calling symbol 'broker_proxy_factory'. arg is {manifest:}"))?;
             log::trace!("BrokerProxyFactory (type_name={:?}) created.", juiz_lock(&bpf)?.type_name());
         }
@@ -1,8 +1,10 @@
 use juiz_sdk::anyhow::Context;
-use crate::{core::system_builder::{brokers::cleanup_brokers, containers::cleanup_containers, ecs::cleanup_ecs, processes::cleanup_processes}, prelude::*};
+use crate::{core::system_builder::{brokers::cleanup_brokers, discovery::cleanup_discovery, recordings::cleanup_recordings, containers::cleanup_containers, ecs::cleanup_ecs, processes::cleanup_processes}, prelude::*};
 
 pub(crate) fn cleanup_objects(system: &mut System) -> JuizResult<()> {
     log::trace!("System::cleanup() called");
+    cleanup_discovery(system).context("system_builder::cleanup_discovery in System::cleanup() failed")?;
+    cleanup_recordings(system).context("system_builder::cleanup_recordings in System::cleanup() failed")?;
     cleanup_containers(system).context("system_builder::cleanup_cotainers in System::cleanup() failed")?;
     cleanup_processes(system).context("system_builder::cleanup_processes in System::cleanup() failed")?;
     cleanup_ecs(system).context("system_builder::cleanup_ecs in System::cleanup() failed")?;
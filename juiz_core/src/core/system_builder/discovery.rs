@@ -0,0 +1,28 @@
+use juiz_sdk::anyhow::Context;
+
+use crate::{core::{SystemDiscovery, SystemDiscoveryConfig}, prelude::*};
+
+/// Brokerが動き出してから、そのBrokerを知らせる
+pub(super) fn setup_discovery(system: &System, manifest: &Value) -> JuizResult<()> {
+    log::trace!("system_builder::setup_discovery({manifest}) called");
+    let config = SystemDiscoveryConfig::new(manifest).context("SystemDiscoveryConfig::new() failed.")?;
+    system.core_broker().lock_mut().and_then(|mut cb| {
+        let name = obj_get_str(&cb.worker().manifest(), "name").unwrap_or("juiz").to_owned();
+        // localはプロセスの中でしか使えないので知らせない
+        let brokers = cb.worker().store().brokers_profile_full()?.as_object().map(|m| m.values()
+            .filter(|p| obj_get_str(p, "type_name").is_ok_and(|t| t != "local"))
+            .map(|p| Ok(jvalue!({"type_name": obj_get_str(p, "type_name")?, "name": obj_get_str(p, "name")?})))
+            .collect::<JuizResult<Vec<Value>>>()).unwrap_or(Ok(Vec::new()))?;
+        let uuid = cb.system_store().uuid()?;
+        *cb.discovery_mut() = Some(SystemDiscovery::start(config, uuid, name.as_str(), brokers)?);
+        Ok(())
+    })
+}
+
+pub(super) fn cleanup_discovery(system: &System) -> JuizResult<()> {
+    log::trace!("system_builder::cleanup_discovery() called");
+    if let Some(mut discovery) = system.core_broker().lock_mut()?.discovery_mut().take() {
+        discovery.stop()?;
+    }
+    Ok(())
+}
@@ -1,21 +1,22 @@
 use std::sync::{Arc, Mutex};
 use juiz_sdk::anyhow::Context;
 
-use crate::{ecs::{execution_context_function::ExecutionContextFunction, execution_context_holder_factory::ExecutionContextHolderFactory, ExecutionContextFactory}, plugin::{concat_dirname, plugin_name_to_file_name, RustPlugin}, prelude::*};
+use crate::{ecs::{event_ec::EventECFactory, step_ec::StepECFactory, execution_context_function::ExecutionContextFunction, execution_context_holder_factory::ExecutionContextHolderFactory, ExecutionContextFactory}, plugin::{concat_dirname, plugin_name_to_file_name, RustPlugin}, prelude::*};
 
 pub(super) fn setup_execution_context_factories(system: &System, manifest: &Value) -> JuizResult<()> {
     log::trace!("system_builder::setup_execution_context_factories() called");
+    let policy = system.plugin_policy()?;
     for (name, value) in get_hashmap(manifest)?.iter() {
         log::debug!("ExecutionContextFactory (name={name:}, value='{value:}') Loading...");
-        let plugin_filename = concat_dirname(value, plugin_name_to_file_name(name))?;
+        let plugin_filename = policy.resolve(&concat_dirname(value, plugin_name_to_file_name(name))?)?;
 
         log::debug!(" - filename: {plugin_filename:?}");
         let cpf;
         unsafe {
             type ECFactorySymbolType<'a> = libloading::Symbol<'a, unsafe extern "Rust" fn() -> JuizResult<Arc<Mutex<dyn ExecutionContextFactory>>>>;
-            let plugin: RustPlugin = RustPlugin::load(plugin_filename)?;
+            let plugin: RustPlugin = RustPlugin::load(plugin_filename)?.allow_missing_abi(policy.allow_missing_abi());
             {
-                let symbol = plugin.load_symbol::<ECFactorySymbolType>(b"execution_context_factory")?;
+                let symbol = plugin.load_factory_symbol::<ECFactorySymbolType>("execution_context_factory")?;
                 cpf = (symbol)().with_context(||format!("calling symbol 'execution_context_factory'. arg is {manifest:}"))?;
                 let _ccpf = juiz_lock(&cpf)?;
             }
@@ -26,6 +27,13 @@ pub(super) fn setup_execution_context_factories(system: &System, manifest: &Valu
     Ok(())
 }
 
+/// juiz_coreに組み込まれたECのファクトリを登録する
+pub(super) fn setup_builtin_execution_context_factories(system: &System) -> JuizResult<()> {
+    log::trace!("system_builder::setup_builtin_execution_context_factories() called");
+    system.core_broker().lock_mut()?.worker_mut().store_mut().ecs.register_factory(ExecutionContextHolderFactory::new_builtin(Arc::new(Mutex::new(EventECFactory{})))?)?;
+    system.core_broker().lock_mut()?.worker_mut().store_mut().ecs.register_factory(ExecutionContextHolderFactory::new_builtin(Arc::new(Mutex::new(StepECFactory{})))?)?;
+    Ok(())
+}
 
 pub(super) fn setup_ecs(system: &mut System, manifest: &Value) -> JuizResult<()> {
     log::trace!("system_builder::setup_ecs({manifest}) called");
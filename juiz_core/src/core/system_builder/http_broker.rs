@@ -35,9 +35,14 @@ fn get_http_staticfilepaths(options: Option<&Value>) -> Option<&Value> {
    
 }
 
+/// option.http_broker.<key>
+fn get_http_option_value<'a>(options: Option<&'a Value>, key: &str) -> Option<&'a Value> {
+    options.and_then(|opt| obj_get_obj(opt, "http_broker").ok()).and_then(|http_opt| http_opt.get(key))
+}
+
 pub(super) fn setup_http_broker(system: &mut System, port_number: i64, options: Option<&Value>) -> JuizResult<()> {
     log::trace!("system_builder::setup_http_broker() called");
-    let manifest = match get_http_staticfilepaths(options) {
+    let mut manifest = match get_http_staticfilepaths(options) {
         None => {
             jvalue!({
                 "type_name": "http",
@@ -56,6 +61,11 @@ pub(super) fn setup_http_broker(system: &mut System, port_number: i64, options:
             })
         }
     };
+    for key in ["auth", "tls"] {
+        if let Some(v) = get_http_option_value(options, key) {
+            manifest[key] = v.clone();
+        }
+    }
     
     let _http_broker = system.create_broker(&manifest).context("system.create_broker() failed in system_builder::setup_http_broker()")?;
     //system.register_broker(http_broker)?;
@@ -9,8 +9,11 @@ mod components;
 mod connections;
 mod subsystems;
 mod topics;
+mod recordings;
+mod discovery;
 
 mod http_broker;
+mod websocket_broker;
 mod ipc_broker;
 mod local_broker;
 
@@ -29,7 +29,8 @@ fn setup_process_factory(system: &System, name: &String, v: &Value, option: &Val
         Some(obj) => {
             let language = obj.get("language").and_then(|v| { v.as_str() }).or(Some("rust")).unwrap();
             let working_dir = system.get_working_dir();
-            register_process_factory(&mut system.core_broker().lock_mut()?.worker_mut(), working_dir, JuizObjectPlugin::new(language, name, v, manifest_entry_point, option)?, "process_factory", None)
+            let plugin = JuizObjectPlugin::new(language, name, v, manifest_entry_point, option, &system.plugin_policy()?)?;
+            register_process_factory(&mut system.core_broker().lock_mut()?.worker_mut(), working_dir, plugin, "process_factory", None)
         }
     };
     log::trace!("setup_process_factory() exit");
@@ -37,6 +38,18 @@ fn setup_process_factory(system: &System, name: &String, v: &Value, option: &Val
 }
 
 
+pub(super) fn setup_composite_processes(system: &System, manifest: &Value) -> JuizResult<()> {
+    log::trace!("setup_composite_processes({manifest}) called");
+    for composite_manifest_value in get_array(manifest)?.iter() {
+        let composite_manifest: CompositeProcessManifest = composite_manifest_value.clone().try_into()?;
+        let type_name = composite_manifest.type_name.clone();
+        system.core_broker().lock_mut()?.worker_mut().register_composite_process(composite_manifest)?;
+        log::info!("CompositeProcess (type_name={type_name}) registered");
+    }
+    log::trace!("setup_composite_processes() exit");
+    Ok(())
+}
+
 pub(super) fn setup_processes(system: &System, manifest: &Value) -> JuizResult<()> {
     log::trace!("setup_processes({manifest}) called");
     for process_manifest_value  in get_array(manifest)?.iter() {
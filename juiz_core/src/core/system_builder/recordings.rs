@@ -0,0 +1,104 @@
+use std::{path::PathBuf, sync::{Arc, Mutex}};
+
+use juiz_sdk::{anyhow::{anyhow, Context}, identifier::connection_identifier_split};
+
+use crate::{prelude::*, recording::{BagChannelKind, PlaybackSpeed, PlaybackTarget, Player, Recorder}};
+
+/// マニフェストのファイル名が相対パスなら作業ディレクトリからのパスにする
+fn resolve_file_path(system: &System, manifest: &Value) -> JuizResult<PathBuf> {
+    let file = PathBuf::from(obj_get_str(manifest, "file")?);
+    Ok(match system.get_working_dir() {
+        Some(dir) if file.is_relative() => dir.join(file),
+        _ => file,
+    })
+}
+
+fn get_str_array(manifest: &Value, key: &str) -> JuizResult<Vec<String>> {
+    match manifest.get(key) {
+        None => Ok(Vec::new()),
+        Some(v) => v.as_array()
+            .ok_or_else(|| anyhow!(JuizError::InvalidSettingError{message: format!("'{key}' in {manifest} must be array of string.")}))?
+            .iter()
+            .map(|s| s.as_str().map(|s| s.to_owned()).ok_or_else(|| anyhow!(JuizError::InvalidSettingError{message: format!("'{key}' in {manifest} must be array of string.")})))
+            .collect(),
+    }
+}
+
+pub(super) fn setup_recorders(system: &System, manifest: &Value) -> JuizResult<()> {
+    log::trace!("system_builder::setup_recorders({manifest}) called");
+    for r in get_array(manifest)?.iter() {
+        let name = obj_get_str(r, "name")?;
+        let path = resolve_file_path(system, r)?;
+        log::debug!("Recorder ({name}) Creating...");
+        let mut recorder = Recorder::create(name, &path).with_context(|| format!("Recorder::create({path:?})"))?;
+        system.core_broker().lock_mut().and_then(|mut cb| {
+            let worker = cb.worker_mut();
+            for topic_name in get_str_array(r, "topics")?.into_iter() {
+                let topic = worker.create_topic(topic_name)?;
+                recorder.record_topic(&topic)?;
+            }
+            for connection_id in get_str_array(r, "connections")?.into_iter() {
+                let (source_id, _destination_id, _arg_name) = connection_identifier_split(connection_id.clone())?;
+                let source = worker.any_process_from_identifier(&source_id, false)?;
+                recorder.record_connection(&connection_id, source)?;
+            }
+            worker.store_mut().recorders.insert(name.to_owned(), Arc::new(Mutex::new(recorder)));
+            Ok(())
+        })?;
+        log::info!("Recorder ({name}) Created. file={path:?}");
+    }
+    Ok(())
+}
+
+pub(super) fn setup_players(system: &System, manifest: &Value) -> JuizResult<()> {
+    log::trace!("system_builder::setup_players({manifest}) called");
+    for p in get_array(manifest)?.iter() {
+        let name = obj_get_str(p, "name")?;
+        let path = resolve_file_path(system, p)?;
+        let speed = match p.get("speed") {
+            Some(v) => v.try_into()?,
+            None => PlaybackSpeed::default(),
+        };
+        log::debug!("Player ({name}) Creating...");
+        let mut player = Player::open(name, &path, speed).with_context(|| format!("Player::open({path:?})"))?;
+        player.set_looping(obj_get_bool(p, "loop").unwrap_or(false));
+        // remapで記録した時と別のTopicや接続に流せる
+        let remap = p.get("remap").and_then(|v| v.as_object());
+        system.core_broker().lock_mut().and_then(|mut cb| {
+            let worker = cb.worker_mut();
+            for channel in player.channels()?.into_iter() {
+                let target_name = remap.and_then(|m| m.get(&channel.name)).and_then(|v| v.as_str()).unwrap_or(channel.name.as_str()).to_owned();
+                let target = match channel.kind {
+                    BagChannelKind::Topic => PlaybackTarget::Topic(worker.create_topic(target_name)?),
+                    BagChannelKind::Connection => {
+                        let (_source_id, destination_id, arg_name) = connection_identifier_split(target_name)?;
+                        PlaybackTarget::Inlet(worker.any_process_from_identifier(&destination_id, false)?, arg_name)
+                    },
+                };
+                player.set_target(channel.name.as_str(), target);
+            }
+            Ok(())
+        })?;
+        let player = Arc::new(Mutex::new(player));
+        if speed != PlaybackSpeed::Stepped && obj_get_bool(p, "auto_start").unwrap_or(true) {
+            let _ = Player::start(&player)?;
+        }
+        system.core_broker().lock_mut()?.worker_mut().store_mut().players.insert(name.to_owned(), player);
+        log::info!("Player ({name}) Created. file={path:?}");
+    }
+    Ok(())
+}
+
+pub(super) fn cleanup_recordings(system: &System) -> JuizResult<()> {
+    log::trace!("system_builder::cleanup_recordings() called");
+    system.core_broker().lock_mut().and_then(|mut cb| {
+        let store = cb.worker_mut().store_mut();
+        for (_name, player) in store.players.drain() {
+            juiz_lock(&player)?.stop();
+        }
+        for (_name, recorder) in store.recorders.drain() {
+            juiz_lock(&recorder)?.stop()?;
+        }
+        Ok(())
+    })
+}
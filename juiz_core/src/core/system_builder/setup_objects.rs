@@ -1,13 +1,18 @@
 
 use juiz_sdk::anyhow::Context;
 
-use crate::{core::system_builder::subsystems::{setup_mastersystem, setup_subsystems}, prelude::*};
-use crate::core::system_builder::{brokers::{setup_broker_proxies, setup_brokers}, connections::setup_connections, containers::setup_containers, ecs::setup_ecs, http_broker::{setup_http_broker, setup_http_broker_factory}, local_broker::{setup_local_broker, setup_local_broker_factory}, processes::setup_processes};
+use crate::{core::{system_builder::subsystems::{setup_mastersystem, setup_subsystems}, SubSystemHeartbeat}, prelude::*};
+use crate::core::system_builder::{brokers::{setup_broker_proxies, setup_brokers}, connections::setup_connections, containers::setup_containers, discovery::setup_discovery, ecs::{setup_builtin_execution_context_factories, setup_ecs}, http_broker::{setup_http_broker, setup_http_broker_factory}, ipc_broker::setup_ipc_broker_factory, local_broker::{setup_local_broker, setup_local_broker_factory}, processes::{setup_composite_processes, setup_processes}, recordings::{setup_players, setup_recorders}, websocket_broker::setup_websocket_broker_factory};
 
 pub(crate) fn setup_objects(system: &mut System, manifest: &Value) -> JuizResult<()> {
     log::trace!("System::setup() called");
     let manifest_copied = manifest.clone();
 
+    // 合成プロセスの型はそれを使うプロセスより先に登録する
+    let _ = when_contains_do(manifest, "composite_processes", |v| {
+        setup_composite_processes(system, v).context("system_builder::setup_composite_processes in System::setup() failed")
+    })?;
+
     let _ = when_contains_do(manifest, "processes", |v| {
         setup_processes(system, v).context("system_builder::setup_processes in System::setup() failed")
     })?;
@@ -30,10 +35,12 @@ pub(crate) fn setup_objects(system: &mut System, manifest: &Value) -> JuizResult
         }
     }
 
+    setup_websocket_broker_factory(system).context("system_builder::setup_websocket_broker_factory in System::setup() failed.")?;
+
     setup_local_broker_factory(system).context("system_builder::setup_local_broker_factory in System::setup() failed.")?;
     setup_local_broker(system).context("system_builder::setup_local_broker in System::setup() failed.")?;
 
-    // system_builder::setup_ipc_broker_factory(self).context("system_builder::setup_ipc_broker_factory in System::setup() failed.")?;
+    setup_ipc_broker_factory(system).context("system_builder::setup_ipc_broker_factory in System::setup() failed.")?;
     //system_builder::setup_ipc_broker(self).context("system_builder::setup_ipc_broker in System::setup() failed.")?;
     
     let _ = when_contains_do_mut(&manifest_copied, "brokers", |v| {
@@ -52,6 +59,15 @@ pub(crate) fn setup_objects(system: &mut System, manifest: &Value) -> JuizResult
     let manifest_updated = system.core_broker().lock()?.worker().manifest();
     log::trace!("manifest_updated: {manifest_updated:?}");
 
+    let _ = when_contains_do(&manifest_updated, "heartbeat", |v| {
+        *system.core_broker().lock_mut()?.heartbeat_mut() = SubSystemHeartbeat::new(v).context("SubSystemHeartbeat::new in System::setup() failed.")?;
+        Ok(())
+    })?;
+
+    let _ = when_contains_do(&manifest_updated, "discovery", |v| {
+        setup_discovery(system, v).context("system_builder::setup_discovery in System::setup() failed.")
+    })?;
+
     let _ =  when_contains_do(&manifest_updated, "subsystems", |v| {
         setup_subsystems(system, v).context("system_builder::setup_subsystems in System::setup() failed.")
     })?;
@@ -60,6 +76,8 @@ pub(crate) fn setup_objects(system: &mut System, manifest: &Value) -> JuizResult
         setup_mastersystem(system, v).context("system_builder::setup_mastersystem in System::setup() failed.")
     })?;
 
+    setup_builtin_execution_context_factories(system).context("system_builder::setup_builtin_execution_context_factories in System::setup() failed.")?;
+
     let _ = when_contains_do(&manifest_copied, "ecs", |v| {
         setup_ecs(system, v).context("system_builder::setup_ecs in System::setup() failed")
     })?;
@@ -67,6 +85,15 @@ pub(crate) fn setup_objects(system: &mut System, manifest: &Value) -> JuizResult
     let _ =  when_contains_do(&manifest, "connections", |v| {
         setup_connections(system, v).context("system_builder::setup_connections in System::setup() failed.")
     })?;
+
+    // 接続ができてから記録と再生を始める
+    let _ =  when_contains_do(manifest, "recorders", |v| {
+        setup_recorders(system, v).context("system_builder::setup_recorders in System::setup() failed.")
+    })?;
+
+    let _ =  when_contains_do(manifest, "players", |v| {
+        setup_players(system, v).context("system_builder::setup_players in System::setup() failed.")
+    })?;
     log::debug!("System::setup() successfully finished.");
     Ok(())
 }
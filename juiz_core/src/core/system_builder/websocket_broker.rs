@@ -0,0 +1,10 @@
+use crate::{brokers::{broker_factories_wrapper::BrokerFactoriesWrapper, websocket::{websocket_broker_factory, websocket_broker_proxy_factory}}, prelude::*};
+
+
+pub fn setup_websocket_broker_factory(system: &mut System) -> JuizResult<()> {
+    log::trace!("system_builder::setup_websocket_broker_factory() called");
+    let wbf = websocket_broker_factory(system.core_broker().clone())?;
+    let wbpf = websocket_broker_proxy_factory()?;
+    let _wrapper = system.register_broker_factories_wrapper(BrokerFactoriesWrapper::new(None, wbf, wbpf)?)?;
+    Ok(())
+}
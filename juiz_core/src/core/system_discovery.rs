@@ -0,0 +1,320 @@
+//! ネットワーク上のjuizシステムの自動発見
+//!
+//! UDPマルチキャストで自分のUUID、名前、Brokerを定期的に知らせ、ほかのシステムからの知らせを集める。
+//! "loopback"をtrueにすると同じ計算機の中だけで知らせ合う。
+
+use std::{collections::HashMap, net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, thread::JoinHandle, time::{Duration, Instant}};
+
+use juiz_sdk::anyhow::anyhow;
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+/// 知らせのパケットに付ける目印。ほかのアプリのパケットと区別する
+const DISCOVERY_MAGIC: &str = "juiz_discovery";
+
+/// 停止要求を確かめる間隔
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// システムのマニフェストの"discovery"の設定
+///
+/// ```json
+/// "discovery": {"group": "239.255.74.75", "port": 18474, "loopback": false, "interval": 1.0, "expire": 5.0}
+/// ```
+#[derive(Clone, Debug)]
+pub struct SystemDiscoveryConfig {
+    group: Ipv4Addr,
+    port: u16,
+    loopback: bool,
+    interval: Duration,
+    expire: Duration,
+}
+
+impl Default for SystemDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            group: Ipv4Addr::new(239, 255, 74, 75),
+            port: 18474,
+            loopback: false,
+            interval: Duration::from_secs(1),
+            expire: Duration::from_secs(5),
+        }
+    }
+}
+
+fn duration_from_manifest(manifest: &Value, key: &str, default: Duration) -> JuizResult<Duration> {
+    match obj_get_f64(manifest, key) {
+        Ok(sec) => Duration::try_from_secs_f64(sec)
+            .map_err(|e| anyhow!(JuizError::InvalidSettingError{message: format!("discovery {key} ({sec}) is invalid. {e}")})),
+        Err(_) => Ok(default),
+    }
+}
+
+impl SystemDiscoveryConfig {
+
+    pub fn new(manifest: &Value) -> JuizResult<Self> {
+        let default = Self::default();
+        let group = match obj_get_str(manifest, "group") {
+            Ok(s) => s.parse::<Ipv4Addr>().ok().filter(|a| a.is_multicast())
+                .ok_or_else(|| anyhow!(JuizError::InvalidSettingError{message: format!("discovery group ({s}) must be IPv4 multicast address.")}))?,
+            Err(_) => default.group,
+        };
+        let port = match obj_get_i64(manifest, "port") {
+            Ok(p) => u16::try_from(p).ok().filter(|p| *p > 0)
+                .ok_or_else(|| anyhow!(JuizError::InvalidSettingError{message: format!("discovery port ({p}) is invalid.")}))?,
+            Err(_) => default.port,
+        };
+        Ok(Self {
+            group,
+            port,
+            loopback: obj_get_bool(manifest, "loopback").unwrap_or(default.loopback),
+            interval: duration_from_manifest(manifest, "interval", default.interval)?,
+            expire: duration_from_manifest(manifest, "expire", default.expire)?,
+        })
+    }
+
+    fn interface(&self) -> Ipv4Addr {
+        if self.loopback { Ipv4Addr::LOCALHOST } else { Ipv4Addr::UNSPECIFIED }
+    }
+
+    fn profile(&self) -> Value {
+        jvalue!({
+            "group": self.group.to_string(),
+            "port": self.port,
+            "loopback": self.loopback,
+            "interval": self.interval.as_secs_f64(),
+            "expire": self.expire.as_secs_f64(),
+        })
+    }
+}
+
+/// 見つけたシステム
+#[derive(Clone, Debug)]
+struct DiscoveredPeer {
+    uuid: Uuid,
+    name: String,
+    address: Ipv4Addr,
+    brokers: Vec<Value>,
+    last_seen: Instant,
+}
+
+impl DiscoveredPeer {
+
+    fn from_packet(packet: &Value, address: Ipv4Addr) -> JuizResult<Self> {
+        let uuid_str = obj_get_str(packet, "uuid")?;
+        let uuid = Uuid::parse_str(uuid_str)
+            .map_err(|e| anyhow!(JuizError::InvalidValueError{message: format!("discovery packet uuid ({uuid_str}) is invalid. {e}")}))?;
+        let brokers = get_array(obj_get(packet, "brokers")?)?.iter()
+            .map(|b| Ok(jvalue!({
+                "type_name": obj_get_str(b, "type_name")?,
+                "name": broker_name_seen_from(obj_get_str(b, "name")?, address),
+            })))
+            .collect::<JuizResult<Vec<Value>>>()?;
+        Ok(Self {
+            uuid,
+            name: obj_get_str(packet, "name")?.to_owned(),
+            address,
+            brokers,
+            last_seen: Instant::now(),
+        })
+    }
+
+    fn profile(&self) -> Value {
+        jvalue!({
+            "uuid": self.uuid.to_string(),
+            "name": self.name,
+            "address": self.address.to_string(),
+            "brokers": self.brokers,
+            "age": self.last_seen.elapsed().as_secs_f64(),
+        })
+    }
+}
+
+/// "0.0.0.0:8000"のように全インターフェースで待っているBrokerの名前を、知らせを受け取ったアドレスで置き換える
+fn broker_name_seen_from(name: &str, address: Ipv4Addr) -> String {
+    match name.split_once(':') {
+        Some((host, port)) if host == "0.0.0.0" || host == "localhost" || host == "127.0.0.1" => format!("{address}:{port}"),
+        _ => name.to_owned(),
+    }
+}
+
+type PeerMap = Arc<Mutex<HashMap<Uuid, DiscoveredPeer>>>;
+
+pub struct SystemDiscovery {
+    config: SystemDiscoveryConfig,
+    uuid: Uuid,
+    socket: Arc<UdpSocket>,
+    peers: PeerMap,
+    stop_flag: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SystemDiscovery {
+
+    /// 自分のUUID、名前、Brokerを知らせ始める
+    pub fn start(config: SystemDiscoveryConfig, uuid: Uuid, name: &str, brokers: Vec<Value>) -> JuizResult<Self> {
+        log::trace!("SystemDiscovery::start({config:?}, uuid={uuid}, name={name}) called");
+        let socket = Arc::new(open_socket(&config)?);
+        let peers: PeerMap = Arc::new(Mutex::new(HashMap::new()));
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let announcement = jvalue!({
+            "magic": DISCOVERY_MAGIC,
+            "kind": "announce",
+            "uuid": uuid.to_string(),
+            "name": name,
+            "brokers": brokers,
+        });
+        let thread = {
+            let (config, socket, peers, stop_flag) = (config.clone(), socket.clone(), peers.clone(), stop_flag.clone());
+            std::thread::Builder::new().name("juiz_core::SystemDiscovery".to_owned()).spawn(move || {
+                discovery_loop(config, uuid, announcement, socket, peers, stop_flag)
+            })?
+        };
+        log::info!("SystemDiscovery started on {}:{} (loopback={})", config.group, config.port, config.loopback);
+        Ok(Self { config, uuid, socket, peers, stop_flag, thread: Some(thread) })
+    }
+
+    pub fn config(&self) -> &SystemDiscoveryConfig {
+        &self.config
+    }
+
+    /// 見つけているシステムのリスト。expireの間知らせが無いものは含まない
+    pub fn peers(&self) -> JuizResult<Value> {
+        let mut peers = self.peers.lock().map_err(|_| anyhow!(JuizError::ObjectLockError{target: "SystemDiscovery::peers".to_owned()}))?;
+        peers.retain(|_, p| p.last_seen.elapsed() < self.config.expire);
+        let mut list = peers.values().collect::<Vec<&DiscoveredPeer>>();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(list.into_iter().map(|p| p.profile()).collect::<Vec<Value>>().into())
+    }
+
+    /// 名前でシステムを探す
+    pub fn find_peer(&self, name: &str) -> JuizResult<Option<Value>> {
+        Ok(get_array(&self.peers()?)?.iter().find(|p| obj_get_str(p, "name").is_ok_and(|n| n == name)).cloned())
+    }
+
+    pub fn profile_full(&self) -> JuizResult<Value> {
+        obj_merge(self.config.profile(), &jvalue!({"peers": self.peers()?}))
+    }
+
+    /// 立ち去ることを知らせて止まる
+    pub fn stop(&mut self) -> JuizResult<()> {
+        log::trace!("SystemDiscovery::stop() called");
+        self.stop_flag.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+            let bye = jvalue!({"magic": DISCOVERY_MAGIC, "kind": "bye", "uuid": self.uuid.to_string()});
+            send_packet(&self.socket, &self.config, &bye);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SystemDiscovery {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// 同じ計算機の複数のシステムが同じポートで待てるように、アドレスを再利用できるソケットを作る
+fn open_socket(config: &SystemDiscoveryConfig) -> JuizResult<UdpSocket> {
+    let socket = bind_reusable(config.port)?;
+    let interface = config.interface();
+    socket.join_multicast_v4(&config.group, &interface)?;
+    set_multicast_if(&socket, &interface)?;
+    socket.set_multicast_loop_v4(true)?;
+    // 知らせはルーターを越えない
+    socket.set_multicast_ttl_v4(1)?;
+    socket.set_read_timeout(Some(STOP_CHECK_INTERVAL))?;
+    Ok(socket)
+}
+
+/// 同じホストの複数のシステムが同じポートで待ち受けられるように SO_REUSEADDR/SO_REUSEPORT を付けてbindする
+fn bind_reusable(port: u16) -> JuizResult<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddr::from(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)).into())?;
+    Ok(socket.into())
+}
+
+fn set_multicast_if(socket: &UdpSocket, interface: &Ipv4Addr) -> JuizResult<()> {
+    Ok(SockRef::from(socket).set_multicast_if_v4(interface)?)
+}
+
+fn send_packet(socket: &UdpSocket, config: &SystemDiscoveryConfig, packet: &Value) {
+    if let Err(e) = socket.send_to(packet.to_string().as_bytes(), SocketAddrV4::new(config.group, config.port)) {
+        log::error!("SystemDiscovery send failed. Error({e})");
+    }
+}
+
+fn discovery_loop(config: SystemDiscoveryConfig, uuid: Uuid, announcement: Value, socket: Arc<UdpSocket>, peers: PeerMap, stop_flag: Arc<AtomicBool>) {
+    let mut buf = [0u8; 65536];
+    let mut next_announce = Instant::now();
+    while !stop_flag.load(Ordering::SeqCst) {
+        if Instant::now() >= next_announce {
+            send_packet(&socket, &config, &announcement);
+            next_announce = Instant::now() + config.interval;
+        }
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(r) => r,
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => continue,
+            Err(e) => {
+                log::error!("SystemDiscovery recv failed. Error({e})");
+                std::thread::sleep(STOP_CHECK_INTERVAL);
+                continue;
+            }
+        };
+        let address = match from {
+            SocketAddr::V4(a) => *a.ip(),
+            SocketAddr::V6(_) => continue,
+        };
+        let packet: Value = match serde_json::from_slice(&buf[..len]) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if obj_get_str(&packet, "magic").ok() != Some(DISCOVERY_MAGIC) || obj_get_str(&packet, "uuid").ok() == Some(uuid.to_string().as_str()) {
+            continue;
+        }
+        match receive_packet(&packet, address, &peers) {
+            // 初めて見つけた相手にはすぐに知らせ返して、次のintervalまで待たせない
+            Ok(true) => next_announce = Instant::now(),
+            Ok(false) => {},
+            Err(e) => log::warn!("SystemDiscovery received invalid packet from {address}. Error({e})"),
+        }
+    }
+}
+
+/// 受け取った知らせで見つけているシステムを更新する。初めて見つけたシステムならtrue
+fn receive_packet(packet: &Value, address: Ipv4Addr, peers: &PeerMap) -> JuizResult<bool> {
+    let mut peers = peers.lock().map_err(|_| anyhow!(JuizError::ObjectLockError{target: "SystemDiscovery::peers".to_owned()}))?;
+    match obj_get_str(packet, "kind")? {
+        "bye" => {
+            let uuid_str = obj_get_str(packet, "uuid")?;
+            if let Some(peer) = Uuid::parse_str(uuid_str).ok().and_then(|uuid| peers.remove(&uuid)) {
+                log::info!("SystemDiscovery: System({}, uuid={}) has left.", peer.name, peer.uuid);
+            }
+            Ok(false)
+        },
+        _ => {
+            let peer = DiscoveredPeer::from_packet(packet, address)?;
+            let is_new = !peers.contains_key(&peer.uuid);
+            if is_new {
+                log::info!("SystemDiscovery: found System({}, uuid={}) at {address}", peer.name, peer.uuid);
+            }
+            peers.insert(peer.uuid, peer);
+            Ok(is_new)
+        },
+    }
+}
+
+/// 見つけたシステムのBrokerのうち、こちらにも同じ種類のBrokerがあるものの接続先マニフェストを選ぶ
+///
+/// サブシステムとして登録するときに、相手はこちらの同じ種類のBrokerへつなぎ返してくる。
+pub(crate) fn select_peer_broker(peer: &Value, local_broker_types: &[String]) -> JuizResult<Value> {
+    get_array(obj_get(peer, "brokers")?)?.iter()
+        .find(|b| obj_get_str(b, "type_name").is_ok_and(|t| local_broker_types.iter().any(|l| l == t)))
+        .cloned()
+        .ok_or_else(|| anyhow!(JuizError::InvalidSettingError{message: format!("System({}) has no broker of type {local_broker_types:?}.", obj_get_str(peer, "name").unwrap_or_default())}))
+}
@@ -0,0 +1,320 @@
+//! TopicへのpushやバインドしたプロセスのInletの更新を待って実行するExecutionContext
+//!
+//! ```yaml
+//! ecs:
+//!   - type_name: EventEC
+//!     name: event_ec0
+//!     triggers:
+//!       - topic: image
+//!       - inlet: arg1
+//!     debounce: 0.01
+//!     max_latency: 0.1
+//!     max_rate: 30.0
+//! ```
+//! inletのトリガーはバインドしたプロセスのうちその名前の引数を持つもの全てが対象で、
+//! processで対象のプロセスを一つに絞れる。
+//! inletのトリガーはstartの時点でバインドされているプロセスの接続元につなぐ。start後にbindしたプロセスは次のstartから対象になる。
+//!
+//! トリガーが続いてdebounceの待ちが終わらなくても、最初のトリガーからmax_latency (省略時はdebounceの10倍) が過ぎたら実行する。
+
+use std::{sync::{Arc, Condvar, Mutex, RwLock}, time::{Duration, Instant}};
+use juiz_sdk::anyhow::anyhow;
+
+use crate::{connections::ConnectionFactoryImpl, prelude::*, processes::process_from_clousure_new_with_class_name};
+
+use super::{execution_context_core::ExecutionContextState, ExecutionContext, ExecutionContextCore, ExecutionContextFactory};
+
+pub const EVENT_EC_TYPE_NAME: &str = "EventEC";
+
+/// 停止要求を確かめるためにトリガー待ちを抜ける間隔
+const WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// max_latencyを省略したときのdebounceに対する倍率
+const DEFAULT_MAX_LATENCY_RATIO: u32 = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+enum EventTriggerSource {
+    Topic(String),
+    Inlet { process: Option<Identifier>, arg_name: String },
+}
+
+impl TryFrom<&Value> for EventTriggerSource {
+    type Error = juiz_sdk::anyhow::Error;
+
+    fn try_from(value: &Value) -> JuizResult<Self> {
+        if let Ok(topic) = obj_get_str(value, "topic") {
+            return Ok(EventTriggerSource::Topic(topic.to_owned()));
+        }
+        if let Ok(arg_name) = obj_get_str(value, "inlet") {
+            let process = obj_get_str(value, "process").ok().map(|s| s.to_owned());
+            return Ok(EventTriggerSource::Inlet { process, arg_name: arg_name.to_owned() });
+        }
+        Err(anyhow!(JuizError::InvalidSettingError{message: format!("EventEC trigger ({value}) must have 'topic' or 'inlet'.")}))
+    }
+}
+
+impl From<&EventTriggerSource> for Value {
+    fn from(source: &EventTriggerSource) -> Self {
+        match source {
+            EventTriggerSource::Topic(topic) => jvalue!({"topic": topic}),
+            EventTriggerSource::Inlet { process: Some(process), arg_name } => jvalue!({"inlet": arg_name, "process": process}),
+            EventTriggerSource::Inlet { process: None, arg_name } => jvalue!({"inlet": arg_name}),
+        }
+    }
+}
+
+#[derive(Default)]
+struct EventTriggerState {
+    pending: bool,
+    num_triggered: u64,
+    num_executed: u64,
+}
+
+/// トリガーを受けてECのスレッドを起こす
+#[derive(Default)]
+struct EventTrigger {
+    state: Arc<Mutex<EventTriggerState>>,
+    condvar: Condvar,
+}
+
+impl EventTrigger {
+
+    fn notify(&self) -> JuizResult<()> {
+        let mut state = juiz_lock(&self.state)?;
+        state.pending = true;
+        state.num_triggered += 1;
+        self.condvar.notify_all();
+        Ok(())
+    }
+
+    /// 消費したトリガーを数えずに戻す
+    fn rearm(&self) -> JuizResult<()> {
+        juiz_lock(&self.state)?.pending = true;
+        Ok(())
+    }
+
+    /// トリガーされるかtimeoutまで待つ。トリガーされていたらそれを消費してtrueを返す
+    fn wait(&self, timeout: Duration) -> JuizResult<bool> {
+        let state = juiz_lock(&self.state)?;
+        let (mut state, _) = self.condvar.wait_timeout_while(state, timeout, |s| !s.pending)
+            .map_err(|_| anyhow!(JuizError::ObjectLockError{target: "EventTrigger".to_owned()}))?;
+        let triggered = state.pending;
+        state.pending = false;
+        Ok(triggered)
+    }
+}
+
+/// 送り手のプロセスにPush型で接続した、トリガーを引くためのプロセス
+struct EventTap {
+    source: ProcessPtr,
+    arg_name: String,
+}
+
+pub struct EventEC {
+    name: String,
+    sources: Vec<EventTriggerSource>,
+    /// 最後のトリガーからこの時間だけ新しいトリガーが無ければ実行する
+    debounce: Option<Duration>,
+    /// debounceで待つ時間の合計の上限
+    max_latency: Duration,
+    /// 実行の最大頻度 [Hz]
+    max_rate: Option<f64>,
+    trigger: Arc<EventTrigger>,
+    topic_processes: Vec<ProcessPtr>,
+    taps: Vec<EventTap>,
+    last_executed: Arc<Mutex<Option<Instant>>>,
+}
+
+impl EventEC {
+
+    pub fn new(name: &str, manifest: &Value) -> JuizResult<Arc<RwLock<EventEC>>> {
+        let sources = match obj_get_array(manifest, "triggers") {
+            Ok(triggers) => triggers.iter().map(|t| t.try_into()).collect::<JuizResult<Vec<EventTriggerSource>>>()?,
+            Err(_) => Vec::new(),
+        };
+        if sources.is_empty() {
+            return Err(anyhow!(JuizError::InvalidSettingError{message: format!("EventEC({name}) needs at least one trigger.")}));
+        }
+        let debounce = match obj_get_f64(manifest, "debounce") {
+            Ok(sec) => Some(Duration::try_from_secs_f64(sec).map_err(|e| anyhow!(JuizError::InvalidSettingError{message: format!("EventEC({name}) debounce is invalid. {e}")}))?),
+            Err(_) => None,
+        };
+        let max_latency = match obj_get_f64(manifest, "max_latency") {
+            Ok(sec) => Duration::try_from_secs_f64(sec).map_err(|e| anyhow!(JuizError::InvalidSettingError{message: format!("EventEC({name}) max_latency is invalid. {e}")}))?,
+            Err(_) => debounce.unwrap_or_default() * DEFAULT_MAX_LATENCY_RATIO,
+        };
+        let max_rate = match obj_get_f64(manifest, "max_rate") {
+            Ok(rate) if rate > 0.0 => Some(rate),
+            Ok(rate) => return Err(anyhow!(JuizError::InvalidSettingError{message: format!("EventEC({name}) max_rate must be positive but {rate}.")})),
+            Err(_) => None,
+        };
+        Ok(Arc::new(RwLock::new(EventEC {
+            name: name.to_owned(),
+            sources,
+            debounce,
+            max_latency,
+            max_rate,
+            trigger: Arc::new(EventTrigger::default()),
+            topic_processes: Vec::new(),
+            taps: Vec::new(),
+            last_executed: Arc::new(Mutex::new(None)),
+        })))
+    }
+
+    /// sourceが出力をpushするたびにトリガーを引くように接続する
+    fn tap(&mut self, source: ProcessPtr) -> JuizResult<()> {
+        // 送り手の出力接続は引数名で区別されるので、ECとトリガーごとに別の名前にする
+        let arg_name = format!("{}_trigger{}", self.name, self.taps.len());
+        let manifest: ProcessManifest = jvalue!({
+            "type_name": "event_trigger",
+            "name": arg_name,
+            "use_memo": false,
+            "arguments": [
+                {"name": arg_name, "type": "object", "default": {}}
+            ]
+        }).try_into()?;
+        let trigger = self.trigger.clone();
+        let func = move |_args: CapsuleMap| -> JuizResult<Capsule> {
+            trigger.notify()?;
+            Ok(Capsule::empty())
+        };
+        let sink = ProcessPtr::new(process_from_clousure_new_with_class_name(JuizObjectClass::Process("EventTrigger"), manifest, func, Box::new(ConnectionFactoryImpl::new()))?);
+        let source_id = source.identifier().clone();
+        let sink_id = sink.identifier().clone();
+        connect(source.clone(), sink, ConnectionManifest::new(ConnectionType::Push, source_id, arg_name.clone(), sink_id, None))?;
+        self.taps.push(EventTap { source, arg_name });
+        Ok(())
+    }
+
+    /// バインドしたプロセスのinletの接続元
+    fn inlet_sources(&self, core: &ExecutionContextCore) -> JuizResult<Vec<ProcessPtr>> {
+        let mut sources = Vec::new();
+        for source in self.sources.iter() {
+            if let EventTriggerSource::Inlet { process, arg_name } = source {
+                for target in core.target_processes().iter() {
+                    if process.as_ref().is_some_and(|id| id != target.identifier()) {
+                        continue;
+                    }
+                    let target = target.lock()?;
+                    for connection in target.source_connections()?.iter().filter(|c| c.arg_name() == arg_name) {
+                        sources.push(connection.source_process());
+                    }
+                }
+            }
+        }
+        Ok(sources)
+    }
+
+    /// max_rateを超えないように前回の実行から待つ
+    fn wait_for_rate(&self) -> JuizResult<()> {
+        if let Some(rate) = self.max_rate {
+            let last_executed = *juiz_lock(&self.last_executed)?;
+            if let Some(last) = last_executed {
+                let remaining = Duration::from_secs_f64(1.0 / rate).saturating_sub(last.elapsed());
+                if !remaining.is_zero() {
+                    std::thread::sleep(remaining);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ExecutionContext for EventEC {
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn type_name(&self) -> &str {
+        EVENT_EC_TYPE_NAME
+    }
+
+    fn profile(&self) -> JuizResult<Value> {
+        let state = juiz_lock(&self.trigger.state)?;
+        Ok(jvalue!({
+            "triggers": self.sources.iter().map(Value::from).collect::<Vec<Value>>(),
+            "debounce": self.debounce.map(|d| d.as_secs_f64()),
+            "max_latency": self.debounce.map(|_| self.max_latency.as_secs_f64()),
+            "max_rate": self.max_rate,
+            "num_triggered": state.num_triggered,
+            "num_executed": state.num_executed,
+        }))
+    }
+
+    fn on_create(&mut self, worker: &mut CoreWorker, _core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<()> {
+        for source in self.sources.iter() {
+            if let EventTriggerSource::Topic(topic_name) = source {
+                self.topic_processes.push(worker.create_topic(topic_name.clone())?.process_ptr());
+            }
+        }
+        Ok(())
+    }
+
+    fn on_starting(&mut self, core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<()> {
+        log::trace!("EventEC({})::on_starting() called", self.name);
+        let mut sources = self.topic_processes.clone();
+        let inlet_sources = {
+            let core = juiz_lock(core)?;
+            self.inlet_sources(&core)?
+        };
+        sources.extend(inlet_sources);
+        for source in sources.into_iter() {
+            self.tap(source)?;
+        }
+        // 止まっている間に来たトリガーでは実行しない
+        let _ = self.trigger.wait(Duration::ZERO)?;
+        Ok(())
+    }
+
+    fn on_stopping(&mut self, _core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<()> {
+        log::trace!("EventEC({})::on_stopping() called", self.name);
+        for tap in self.taps.drain(..) {
+            tap.source.lock_mut()?.disconnect_to(tap.arg_name.as_str())?;
+        }
+        Ok(())
+    }
+
+    fn execute(&self, core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<bool> {
+        if !self.trigger.wait(WAIT_INTERVAL)? {
+            return Ok(true);
+        }
+        // 待っている間にpauseされたら、トリガーはresumeまで持ち越す
+        if juiz_lock(core)?.get_state() == ExecutionContextState::PAUSED {
+            self.trigger.rearm()?;
+            return Ok(true);
+        }
+        if let Some(debounce) = self.debounce {
+            let first_triggered = Instant::now();
+            loop {
+                // 待っている間にstopされたら実行せずに抜ける
+                if juiz_lock(core)?.is_stop_requested() {
+                    return Ok(true);
+                }
+                let remaining = self.max_latency.saturating_sub(first_triggered.elapsed());
+                if remaining.is_zero() || !self.trigger.wait(debounce.min(remaining))? {
+                    break;
+                }
+            }
+        }
+        self.wait_for_rate()?;
+        *juiz_lock(&self.last_executed)? = Some(Instant::now());
+        let _ = juiz_lock(core)?.svc().map_err(|e| { log::error!("Error({e:?}) in Service function in EventEC({}).", self.name); });
+        juiz_lock(&self.trigger.state)?.num_executed += 1;
+        Ok(true)
+    }
+}
+
+pub struct EventECFactory {}
+
+impl ExecutionContextFactory for EventECFactory {
+
+    fn type_name(&self) -> &str {
+        EVENT_EC_TYPE_NAME
+    }
+
+    fn create(&self, manifest: Value) -> JuizResult<Arc<RwLock<dyn ExecutionContext>>> {
+        let name = obj_get_str(&manifest, "name")?;
+        Ok(EventEC::new(name, &manifest)?)
+    }
+}
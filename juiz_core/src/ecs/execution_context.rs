@@ -1,6 +1,8 @@
 use std::sync::{Mutex, Arc};
 
 
+use juiz_sdk::anyhow::anyhow;
+
 use crate::prelude::*;
 
 use super::execution_context_core::ExecutionContextCore;
@@ -23,17 +25,41 @@ pub trait ExecutionContext : Send + Sync {
         Ok(())
     }
 
+    /// PAUSEDからSTARTEDに戻って、次にexecute()を呼ぶ前に呼ばれる。止まっていた間の周期を数え直すのに使う
+    fn on_resuming(&mut self, _core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<()> {
+        Ok(())
+    }
+
     fn profile(&self) -> JuizResult<Value>;
 
     /// 周期的に呼ばれる関数。自身をSTOPしたいならfalseを返すこと。
     fn execute(&self, core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<bool>;
 
+    ///
+    /// CoreWorkerで作成された直後に呼ばれるコールバック。ec_createで作った時も呼ばれる。
+    /// 
+    fn on_create(&mut self, _worker: &mut CoreWorker, _core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<()> {
+        Ok(())
+    }
+
+    ///
+    /// CoreWorkerから削除される時に呼ばれるコールバック。on_createで作ったものを片付ける。
+    /// 
+    fn on_destroy(&mut self, _worker: &mut CoreWorker, _core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<()> {
+        Ok(())
+    }
+
     ///
     /// Systemにロードされた時に呼ばれるコールバック。
     /// 
     fn on_load(&mut self, _system: &mut System, _core: Arc<Mutex<ExecutionContextCore>>) -> () {
     }
 
+    /// n回だけsvc()を呼び、回ごとのバインドしたプロセスの出力を返す。手動で進めるECだけが対応する
+    fn step(&self, _core: &Arc<Mutex<ExecutionContextCore>>, _n: usize) -> JuizResult<Value> {
+        Err(anyhow!(JuizError::ExecutionContextStepNotSupportedError{type_name: self.type_name().to_owned()}))
+    }
+
     fn is_periodic(&self) -> bool {
         return true;
     }
@@ -1,15 +1,25 @@
 
 
-use std::sync::{Mutex, Arc, atomic::AtomicI64};
+use std::sync::{Mutex, Arc, atomic::{AtomicBool, AtomicI64}};
+use std::time::{Duration, Instant};
 
 
 
+use juiz_sdk::anyhow::anyhow;
 use crate::prelude::*;
 
+use super::{execution_context_statistics::ExecutionContextStatistics, execution_context_worker_pool::ExecutionContextWorkerPool, execution_schedule::ExecutionSchedule};
 
+/// ECの状態
+///
+/// STOPPED --start--> STARTED --pause--> PAUSED --resume--> STARTED --stop--> STOPPED と遷移する。
+/// svc()が続けて失敗するとERRORになり、resetでSTOPPEDに戻すまでstartできない。
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ExecutionContextState {
     STARTED = 1,
     STOPPED = 2,
+    ERROR = 3,
+    PAUSED = 4,
     UNKNOWN = 99,
 }
 
@@ -19,6 +29,8 @@ impl ExecutionContextState {
         match *self {
             ExecutionContextState::STARTED => {1},
             ExecutionContextState::STOPPED => {2},
+            ExecutionContextState::ERROR => {3},
+            ExecutionContextState::PAUSED => {4},
             ExecutionContextState::UNKNOWN => {99}
         }
     }
@@ -27,6 +39,8 @@ impl ExecutionContextState {
         match i {
             1 => ExecutionContextState::STARTED,
             2 => ExecutionContextState::STOPPED,
+            3 => ExecutionContextState::ERROR,
+            4 => ExecutionContextState::PAUSED,
             _ => ExecutionContextState::UNKNOWN,
         }
     }
@@ -37,6 +51,8 @@ impl ToString for ExecutionContextState {
         match *self {
             ExecutionContextState::STARTED => {"STARTED".to_owned()},
             ExecutionContextState::STOPPED => {"STOPPED".to_owned()},
+            ExecutionContextState::ERROR => {"ERROR".to_owned()},
+            ExecutionContextState::PAUSED => {"PAUSED".to_owned()},
             ExecutionContextState::UNKNOWN => {"UNKNOWN".to_owned()}
         }
     }
@@ -45,6 +61,14 @@ impl ToString for ExecutionContextState {
 pub struct ExecutionContextCore {
     target_processes: Vec<ProcessPtr>,
     pub state: AtomicI64,
+    /// stopが呼ばれてからスレッドが抜けるまでtrue。execute()の中で長く待つECが確かめる
+    stop_requested: AtomicBool,
+    statistics: ExecutionContextStatistics,
+    /// svc()がこの回数続けて失敗したらERRORになる。Noneなら失敗してもERRORにならない
+    error_threshold: Option<u64>,
+    num_consecutive_errors: u64,
+    /// Someなら同じ段のプロセスをこのプールで並列に実行する
+    worker_pool: Option<ExecutionContextWorkerPool>,
 }
 
 impl ExecutionContextCore {
@@ -53,6 +77,11 @@ impl ExecutionContextCore {
         Arc::new(Mutex::new(ExecutionContextCore{
             target_processes: Vec::new(),
             state: AtomicI64::new(ExecutionContextState::STOPPED.to_i64()),
+            stop_requested: AtomicBool::new(false),
+            statistics: ExecutionContextStatistics::new(),
+            error_threshold: None,
+            num_consecutive_errors: 0,
+            worker_pool: None,
         }))
     }
 
@@ -75,23 +104,158 @@ impl ExecutionContextCore {
     }
 
 
+    pub fn target_processes(&self) -> &Vec<ProcessPtr> {
+        &self.target_processes
+    }
+
     pub fn get_state(&self) -> ExecutionContextState {
         ExecutionContextState::from(self.state.load(std::sync::atomic::Ordering::SeqCst))
     }
 
+    pub fn set_state(&self, state: ExecutionContextState) {
+        self.state.store(state.to_i64(), std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn is_stop_requested(&self) -> bool {
+        self.stop_requested.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    pub fn set_stop_requested(&self, requested: bool) {
+        self.stop_requested.store(requested, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn error_threshold(&self) -> Option<u64> {
+        self.error_threshold
+    }
+
+    pub fn set_error_threshold(&mut self, error_threshold: Option<u64>) {
+        self.error_threshold = error_threshold;
+    }
+
+    /// 続けて失敗した回数を消す。resetで呼ぶ
+    pub fn clear_errors(&mut self) {
+        self.num_consecutive_errors = 0;
+    }
+
+    pub fn statistics(&self) -> &ExecutionContextStatistics {
+        &self.statistics
+    }
+
+    pub fn statistics_mut(&mut self) -> &mut ExecutionContextStatistics {
+        &mut self.statistics
+    }
+
+    /// 周期的なECが期待する周期を設定する。ジッタとデッドラインミスはこの周期に対して数える
+    pub fn set_period(&mut self, period: Option<Duration>) {
+        self.statistics.set_period(period);
+    }
+
+    /// 次のsvc()のデッドラインを設定する。ECが遅れの扱いに使う時刻とデッドラインミスの数を揃えるため
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.statistics.set_deadline(deadline);
+    }
+
+    /// num_workersが2以上なら、依存しないプロセスをその数のスレッドで並列に実行する
+    pub fn set_num_workers(&mut self, num_workers: usize) {
+        self.worker_pool = if num_workers > 1 { Some(ExecutionContextWorkerPool::new(num_workers)) } else { None };
+    }
+
+    /// バインドしたプロセスの接続から実行順を作る
+    pub fn schedule(&self) -> JuizResult<ExecutionSchedule> {
+        ExecutionSchedule::new(&self.target_processes)
+    }
+
     ///
-    /// 実行コンテキストの周期処理のコア部分。この中でターゲットプロセスすべてのexecuteを呼ぶ。
-    pub fn svc(&self) -> JuizResult<Value> {
-        for tp in self.target_processes.iter() {
-            let _ = tp.lock()?.execute()?;
-        }
+    /// 実行コンテキストの周期処理のコア部分。この中でターゲットプロセスすべてのexecuteを接続元から順に呼ぶ。
+    /// 
+    /// PAUSEDの間は何もしない。呼ばれるたびに実行時間を統計に記録する。続けての失敗がerror_thresholdに達したらERROR状態にする。
+    pub fn svc(&mut self) -> JuizResult<Value> {
+        self.execute_targets(|_, _| {})?;
         Ok(jvalue!({}))
     }
 
+    /// svc()と同じだが、ターゲットプロセスそれぞれの出力を{識別子: 値}にして返す
+    ///
+    /// バイト列と数値の配列はBase64で包んだJSONに、空の出力はnullにする。画像などJSONにできない出力があればエラーを返す
+    pub fn svc_with_outputs(&mut self) -> JuizResult<Value> {
+        let mut outputs = serde_json::Map::new();
+        let mut error = None;
+        self.execute_targets(|id, output| {
+            let value = match output.to_json_value() {
+                Ok(Some(v)) => Ok(v),
+                Ok(None) if output.is_empty().unwrap_or(false) => Ok(Value::Null),
+                Ok(None) => Err(anyhow!(JuizError::ValueTypeError{message: format!("ExecutionContextCore::svc_with_outputs() failed. Output of process({id}) can not be converted to JSON.")})),
+                Err(e) => Err(e),
+            };
+            match value {
+                Ok(v) => { outputs.insert(id.clone(), v); },
+                Err(e) => { error.get_or_insert(e); },
+            }
+        })?;
+        match error {
+            Some(e) => Err(e),
+            None => Ok(outputs.into()),
+        }
+    }
+
+    fn execute_targets(&mut self, mut on_output: impl FnMut(&Identifier, &CapsulePtr)) -> JuizResult<()> {
+        if self.get_state() == ExecutionContextState::PAUSED {
+            return Ok(());
+        }
+        let started = Instant::now();
+        // 接続は実行中にも変わるので毎回並べ直す
+        let result = self.schedule().and_then(|schedule| {
+            match self.worker_pool.as_ref() {
+                Some(pool) => schedule.levels().iter().try_for_each(|level| {
+                    pool.execute_all(level).into_iter().zip(level.iter()).try_for_each(|(output, tp)| {
+                        on_output(tp.identifier(), &output?);
+                        Ok(())
+                    })
+                }),
+                None => schedule.order().try_for_each(|tp| {
+                    let output = tp.lock()?.execute()?;
+                    on_output(tp.identifier(), &output);
+                    Ok(())
+                }),
+            }
+        });
+        self.statistics.record(started, started.elapsed());
+        match result {
+            Ok(()) => {
+                self.num_consecutive_errors = 0;
+                Ok(())
+            },
+            Err(e) => {
+                self.num_consecutive_errors += 1;
+                if self.error_threshold.is_some_and(|n| self.num_consecutive_errors >= n) {
+                    log::error!("ExecutionContextCore::svc() failed {} times in a row. Entering ERROR state.", self.num_consecutive_errors);
+                    self.set_state(ExecutionContextState::ERROR);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn schedule_profile(&self) -> Value {
+        let (mut profile, error) = match self.schedule() {
+            Ok(schedule) => (schedule.profile(), None),
+            Err(e) => (jvalue!({"order": [], "levels": []}), Some(e.to_string())),
+        };
+        let obj = profile.as_object_mut().unwrap();
+        obj.insert("parallel".to_owned(), jvalue!(self.worker_pool.is_some()));
+        obj.insert("num_workers".to_owned(), jvalue!(self.worker_pool.as_ref().map_or(1, |p| p.num_workers())));
+        obj.insert("error".to_owned(), jvalue!(error));
+        profile
+    }
+
     pub fn profile(&self) -> JuizResult<Value> {
         Ok(jvalue!({
             "targets": self.target_processes.iter().map(|tp| { Ok(tp.identifier().clone()) }).collect::<JuizResult<Vec<String>>>()?,
-            "state": ExecutionContextState::from(self.state.load(std::sync::atomic::Ordering::SeqCst)).to_string()
+            "state": ExecutionContextState::from(self.state.load(std::sync::atomic::Ordering::SeqCst)).to_string(),
+            "statistics": self.statistics.profile(),
+            "error_threshold": self.error_threshold,
+            "num_consecutive_errors": self.num_consecutive_errors,
+            "schedule": self.schedule_profile(),
         }))
     }
 }
@@ -8,6 +8,18 @@ pub trait ExecutionContextFunction : Send + Sync + JuizObject {
 
     fn stop(&mut self) -> JuizResult<Value>;
 
+    /// STARTEDからPAUSEDにする。PAUSEDの間はsvc()を呼ばない
+    fn pause(&mut self) -> JuizResult<Value>;
+
+    /// PAUSEDからSTARTEDに戻す
+    fn resume(&mut self) -> JuizResult<Value>;
+
+    /// ERRORからSTOPPEDに戻す
+    fn reset(&mut self) -> JuizResult<Value>;
+
+    /// nサイクルだけ進めて、サイクルごとの出力を返す
+    fn step(&mut self, n: usize) -> JuizResult<Value>;
+
     fn get_state(&self) -> JuizResult<ExecutionContextState>;
 
     fn bind(&mut self, target_process: ProcessPtr) -> JuizResult<()>;
@@ -15,6 +27,14 @@ pub trait ExecutionContextFunction : Send + Sync + JuizObject {
     fn unbind(&mut self, target_process_id: Identifier) -> JuizResult<()>;
 
 
+    fn on_create(&mut self, _worker: &mut CoreWorker) -> JuizResult<()> {
+        Ok(())
+    }
+
+    fn on_destroy(&mut self, _worker: &mut CoreWorker) -> JuizResult<()> {
+        Ok(())
+    }
+
     fn on_load(&mut self, _system: &mut System) -> () {
         
     }
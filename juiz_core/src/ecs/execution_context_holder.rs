@@ -1,4 +1,5 @@
 use std::sync::{Mutex, Arc, RwLock, atomic::AtomicBool};
+use std::time::Duration;
 use juiz_sdk::anyhow;
 
 
@@ -9,6 +10,9 @@ use crate::{ecs::execution_context_core::ExecutionContextState};
 
 use super::{execution_context::ExecutionContext, execution_context_core::ExecutionContextCore, execution_context_function::ExecutionContextFunction};
 
+/// PAUSEDの間にresumeやstopを確かめる間隔
+const PAUSED_POLLING_INTERVAL: Duration = Duration::from_millis(10);
+
 pub struct ExecutionContextHolder{
     object_core: ObjectCore,
     core: Arc<Mutex<ExecutionContextCore>>,
@@ -53,12 +57,9 @@ impl ExecutionContextHolder {
         log::trace!("ExecutionContextHolder::start(type_name={type_name}) called");
 
         let _  = juiz_borrow_mut(&mut self.execution_context)?.on_starting(&self.core)?;
-        let _  = self.core.lock().and_then(|s| {
-            s.state.store(ExecutionContextState::STARTED.to_i64(), std::sync::atomic::Ordering::SeqCst);
-            Ok(())
-        }).or_else(|_e| {
-            Err(anyhow::Error::from(JuizError::ObjectLockError { target: "ExecutionContextCore".to_owned() }))
-        })?;
+        let mut core = juiz_lock(&self.core)?;
+        core.statistics_mut().reset();
+        core.set_state(ExecutionContextState::STARTED);
         Ok(jvalue!({}))
     }
 
@@ -67,6 +68,13 @@ impl ExecutionContextHolder {
         {
             juiz_lock(&self.end_flag)?.swap(false, std::sync::atomic::Ordering::SeqCst);
         }        
+        {
+            // スレッドが走り出す前にpauseされても上書きしないように、ここでSTARTEDにしておく
+            let mut core = juiz_lock(&self.core)?;
+            core.statistics_mut().reset();
+            core.set_stop_requested(false);
+            core.set_state(ExecutionContextState::STARTED);
+        }
         let core = self.core.clone();
         let mut ec = self.execution_context.clone();
 
@@ -75,24 +83,54 @@ impl ExecutionContextHolder {
         self.thread_handle = Some(self.tokio_runtime.spawn_blocking(
             move || -> JuizResult<()> {
 
-                juiz_borrow_mut(&mut ec)?.on_starting(&core)?;
-                core.lock().or_else(|e|{Err(Into::<JuizError>::into(e))})?.state.store(ExecutionContextState::STARTED.to_i64(), std::sync::atomic::Ordering::SeqCst);
+                if let Err(e) = juiz_borrow_mut(&mut ec)?.on_starting(&core) {
+                    log::error!("ExecutionContextHolder::routine() failed in on_starting: {e:?}");
+                    juiz_lock(&core)?.set_state(ExecutionContextState::ERROR);
+                    return Err(e);
+                }
 
+                let mut paused = false;
                 loop {
                     if end_flag.lock().or_else(|e|{Err(Into::<JuizError>::into(e))})?
                         .load(std::sync::atomic::Ordering::SeqCst) {
                             log::debug!("Detect end_flag is raised in ExecutionContextHodler::routine()");
                             break;
                     }
-                    if !juiz_borrow(&ec)?.execute(&core)? {
-                        break;
+                    let state = juiz_lock(&core)?.get_state();
+                    match state {
+                        ExecutionContextState::PAUSED => {
+                            paused = true;
+                            std::thread::sleep(PAUSED_POLLING_INTERVAL);
+                            continue;
+                        },
+                        ExecutionContextState::ERROR => break,
+                        _ => {},
+                    }
+                    if paused {
+                        paused = false;
+                        if let Err(e) = juiz_borrow_mut(&mut ec)?.on_resuming(&core) {
+                            log::error!("ExecutionContextHolder::routine() failed in on_resuming: {e:?}");
+                            juiz_lock(&core)?.set_state(ExecutionContextState::ERROR);
+                            break;
+                        }
+                    }
+                    match juiz_borrow(&ec)?.execute(&core) {
+                        Ok(true) => {},
+                        Ok(false) => break,
+                        Err(e) => {
+                            // executeがエラーを返したらERROR状態で止まる
+                            log::error!("ExecutionContextHolder::routine() stopped by error: {e:?}");
+                            juiz_lock(&core)?.set_state(ExecutionContextState::ERROR);
+                            break;
+                        }
                     }
                 }
 
                 juiz_borrow_mut(&mut ec)?.on_stopping(&core)?;
-                core.lock().or_else(|e|{Err(Into::<JuizError>::into(e))})?
-                    .state.store(ExecutionContextState::STOPPED.to_i64(), std::sync::atomic::Ordering::SeqCst);
-                
+                let core = juiz_lock(&core)?;
+                if core.get_state() != ExecutionContextState::ERROR {
+                    core.set_state(ExecutionContextState::STOPPED);
+                }
                 Ok(())
             }
         ));
@@ -104,6 +142,7 @@ impl ExecutionContextHolder {
         log::info!("ExecutionContextHolder::stop() called");
         if self.thread_handle.is_some() {
             juiz_lock(&self.end_flag)?.swap(true, std::sync::atomic::Ordering::SeqCst);
+            juiz_lock(&self.core)?.set_stop_requested(true);
             let _ = futures::executor::block_on(self.thread_handle.take().unwrap())?;
             self.thread_handle = None;
         }
@@ -112,17 +151,36 @@ impl ExecutionContextHolder {
 
     fn stop_oneshot(&mut self) -> JuizResult<Value> {
         let _ = juiz_borrow_mut(&mut self.execution_context)?.on_stopping(&self.core)?; 
-        self.core.lock().and_then(|c| {
-            c.state.store(ExecutionContextState::STOPPED.to_i64(), std::sync::atomic::Ordering::SeqCst);
-            Ok(jvalue!({}))
-        }).or_else(|e| {
-            log::error!("ExecutionContextHolder.routine() error: {e:?}");
-            Err(anyhow::Error::from(JuizError::ObjectLockError { target: "ExecutionContext".to_owned() }))
-        })
+        let core = juiz_lock(&self.core)?;
+        if core.get_state() != ExecutionContextState::ERROR {
+            core.set_state(ExecutionContextState::STOPPED);
+        }
+        Ok(jvalue!({}))
     }
 
+    /// 現在の状態がfromならtoに遷移する
+    fn transit(&self, operation: &str, from: ExecutionContextState, to: ExecutionContextState) -> JuizResult<Value> {
+        let core = juiz_lock(&self.core)?;
+        let state = core.get_state();
+        if state != from {
+            return Err(anyhow::Error::from(JuizError::ExecutionContextInvalidStateTransitionError{
+                id: self.identifier().clone(), state: state.to_string(), operation: operation.to_owned()
+            }));
+        }
+        core.set_state(to);
+        Ok(jvalue!({}))
+    }
+
+    pub fn set_error_threshold(&self, error_threshold: Option<u64>) -> JuizResult<()> {
+        juiz_lock(&self.core)?.set_error_threshold(error_threshold);
+        Ok(())
+    }
+
+    pub fn set_num_workers(&self, num_workers: usize) -> JuizResult<()> {
+        juiz_lock(&self.core)?.set_num_workers(num_workers);
+        Ok(())
+    }
 
-    
     // pub fn identifier(&self) -> &Identifier {
     //     self.object_core.identifier()
     // }
@@ -156,6 +214,12 @@ impl JuizObject for ExecutionContextHolder {
 impl ExecutionContextFunction for ExecutionContextHolder {
 
     fn start(&mut self) -> JuizResult<Value> { 
+        let state = self.get_state()?;
+        if state == ExecutionContextState::ERROR {
+            return Err(anyhow::Error::from(JuizError::ExecutionContextInvalidStateTransitionError{
+                id: self.identifier().clone(), state: state.to_string(), operation: "start".to_owned()
+            }));
+        }
         if self.is_periodic()? {
             return self.start_periodic();
         } else {
@@ -171,6 +235,39 @@ impl ExecutionContextFunction for ExecutionContextHolder {
         }
     }
 
+    fn pause(&mut self) -> JuizResult<Value> {
+        self.transit("pause", ExecutionContextState::STARTED, ExecutionContextState::PAUSED)
+    }
+
+    fn resume(&mut self) -> JuizResult<Value> {
+        self.transit("resume", ExecutionContextState::PAUSED, ExecutionContextState::STARTED)
+    }
+
+    fn reset(&mut self) -> JuizResult<Value> {
+        if self.get_state()? == ExecutionContextState::ERROR {
+            // ERRORで抜けたスレッドを片付ける
+            if let Some(handle) = self.thread_handle.take() {
+                if let Err(e) = futures::executor::block_on(handle)? {
+                    log::warn!("ExecutionContextHolder::reset() cleaned up the thread which ended with error: {e:?}");
+                }
+            }
+            let mut core = juiz_lock(&self.core)?;
+            core.clear_errors();
+            core.statistics_mut().reset();
+        }
+        self.transit("reset", ExecutionContextState::ERROR, ExecutionContextState::STOPPED)
+    }
+
+    fn step(&mut self, n: usize) -> JuizResult<Value> {
+        let state = self.get_state()?;
+        if state == ExecutionContextState::PAUSED || state == ExecutionContextState::ERROR {
+            return Err(anyhow::Error::from(JuizError::ExecutionContextInvalidStateTransitionError{
+                id: self.identifier().clone(), state: state.to_string(), operation: "step".to_owned()
+            }));
+        }
+        juiz_borrow(&self.execution_context)?.step(&self.core, n)
+    }
+
     fn get_state(&self) -> JuizResult<ExecutionContextState> {
         let s = juiz_lock(&self.core)?.state.load(std::sync::atomic::Ordering::SeqCst);
         Ok(ExecutionContextState::from(s))
@@ -184,6 +281,14 @@ impl ExecutionContextFunction for ExecutionContextHolder {
         juiz_lock(&self.core)?.unbind(target_process_id)
     }
 
+    fn on_create(&mut self, worker: &mut CoreWorker) -> JuizResult<()> {
+        juiz_borrow_mut(&mut self.execution_context)?.on_create(worker, &self.core)
+    }
+
+    fn on_destroy(&mut self, worker: &mut CoreWorker) -> JuizResult<()> {
+        juiz_borrow_mut(&mut self.execution_context)?.on_destroy(worker, &self.core)
+    }
+
     fn on_load(&mut self, system: &mut System) -> () {
         match self.execution_context.write() {
             Ok(mut v) => {
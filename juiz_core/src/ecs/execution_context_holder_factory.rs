@@ -8,12 +8,21 @@ use super::{execution_context_factory::ExecutionContextFactory, execution_contex
 pub struct ExecutionContextHolderFactory {
     core: ObjectCore,
     ec_factory: Arc<Mutex<dyn ExecutionContextFactory>>,
-    plugin: RustPlugin,
+    /// juiz_coreに組み込まれたECならNone
+    plugin: Option<RustPlugin>,
     //tokio_runtime: &'static tokio::runtime::Runtime,
 }
 
 impl ExecutionContextHolderFactory {
     pub fn new(plugin: RustPlugin, ec_factory: Arc<Mutex<dyn ExecutionContextFactory>>) -> JuizResult<Arc<Mutex<ExecutionContextHolderFactory>>> {
+        Self::new_with_plugin(Some(plugin), ec_factory)
+    }
+
+    pub fn new_builtin(ec_factory: Arc<Mutex<dyn ExecutionContextFactory>>) -> JuizResult<Arc<Mutex<ExecutionContextHolderFactory>>> {
+        Self::new_with_plugin(None, ec_factory)
+    }
+
+    fn new_with_plugin(plugin: Option<RustPlugin>, ec_factory: Arc<Mutex<dyn ExecutionContextFactory>>) -> JuizResult<Arc<Mutex<ExecutionContextHolderFactory>>> {
         let type_name = juiz_lock(&ec_factory)?.type_name().to_string();
         Ok(Arc::new(Mutex::new(
             ExecutionContextHolderFactory{
@@ -31,14 +40,24 @@ impl ExecutionContextHolderFactory {
             Ok(v) => v,
             Err(_) => false
         };
-        Ok(
-            ExecutionContextHolder::new(
-                f.type_name(), 
-                //self.tokio_runtime,
-                f.create(manifest)?, 
-                auto_start
-            )?
-        )
+        let error_threshold = obj_get_i64(&manifest, "error_threshold").ok().map(|n| n.max(1) as u64);
+        // parallelなら依存しないプロセスをnum_workers(省略時はCPU数)のスレッドで並列に実行する
+        let num_workers = match obj_get_bool(&manifest, "parallel") {
+            Ok(true) => match obj_get_i64(&manifest, "num_workers") {
+                Ok(n) => n.max(1) as usize,
+                Err(_) => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            },
+            _ => 1,
+        };
+        let holder = ExecutionContextHolder::new(
+            f.type_name(), 
+            //self.tokio_runtime,
+            f.create(manifest)?, 
+            auto_start
+        )?;
+        juiz_lock(&holder)?.set_error_threshold(error_threshold)?;
+        juiz_lock(&holder)?.set_num_workers(num_workers)?;
+        Ok(holder)
     }
 }
 
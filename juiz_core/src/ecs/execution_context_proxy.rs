@@ -64,6 +64,22 @@ impl ExecutionContextFunction for ExecutionContextProxy {
         todo!()
     }
 
+    fn pause(&mut self) -> JuizResult<Value> {
+        juiz_lock(&self.broker_proxy)?.ec_pause(&self.identifier())
+    }
+
+    fn resume(&mut self) -> JuizResult<Value> {
+        juiz_lock(&self.broker_proxy)?.ec_resume(&self.identifier())
+    }
+
+    fn reset(&mut self) -> JuizResult<Value> {
+        juiz_lock(&self.broker_proxy)?.ec_reset(&self.identifier())
+    }
+
+    fn step(&mut self, n: usize) -> JuizResult<Value> {
+        juiz_lock(&self.broker_proxy)?.ec_step(&self.identifier(), n)
+    }
+
     fn get_state(&self) -> JuizResult<super::execution_context_core::ExecutionContextState> {
         todo!()
     }
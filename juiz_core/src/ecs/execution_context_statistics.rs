@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use crate::prelude::*;
+
+/// 実行コンテキストの周期ごとの実行時間、周期のジッタ、デッドラインミスの統計
+#[derive(Debug, Default, Clone)]
+pub struct ExecutionContextStatistics {
+    /// 期待する周期。Noneなら周期を持たないECとしてジッタとデッドラインミスは数えない
+    period: Option<Duration>,
+    num_cycles: u64,
+    execution_time_min: Option<Duration>,
+    execution_time_max: Duration,
+    execution_time_sum: Duration,
+    last_started: Option<Instant>,
+    num_intervals: u64,
+    jitter_max: Duration,
+    jitter_sum: Duration,
+    num_deadline_misses: u64,
+    /// 次に記録する周期のデッドライン。Noneなら実行時間が周期を超えたときをデッドラインミスとする
+    deadline: Option<Instant>,
+    last_missed: bool,
+}
+
+impl ExecutionContextStatistics {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn period(&self) -> Option<Duration> {
+        self.period
+    }
+
+    pub fn set_period(&mut self, period: Option<Duration>) {
+        self.period = period;
+    }
+
+    pub fn num_cycles(&self) -> u64 {
+        self.num_cycles
+    }
+
+    pub fn num_deadline_misses(&self) -> u64 {
+        self.num_deadline_misses
+    }
+
+    /// 周期の開始時刻を自分で決めるECが、次の周期のデッドラインを設定する
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+        self.last_missed = false;
+    }
+
+    /// 最後に記録した周期がデッドラインを過ぎていたか
+    pub fn last_missed(&self) -> bool {
+        self.last_missed
+    }
+
+    /// 周期以外の統計を消す
+    pub fn reset(&mut self) {
+        *self = Self { period: self.period, ..Self::default() };
+    }
+
+    /// startedに始まりelapsedかかった一周期を記録する。デッドラインを過ぎていればtrueを返す
+    ///
+    /// set_deadline()でデッドラインが設定されていれば、終わった時刻がそれを過ぎたかで判断する
+    pub fn record(&mut self, started: Instant, elapsed: Duration) -> bool {
+        let deadline = self.deadline.take();
+        self.num_cycles += 1;
+        self.execution_time_min = Some(self.execution_time_min.map_or(elapsed, |m| m.min(elapsed)));
+        self.execution_time_max = self.execution_time_max.max(elapsed);
+        self.execution_time_sum += elapsed;
+        let Some(period) = self.period else {
+            self.last_started = Some(started);
+            return false;
+        };
+        if let Some(last) = self.last_started.replace(started) {
+            let interval = started.saturating_duration_since(last);
+            let jitter = interval.abs_diff(period);
+            self.num_intervals += 1;
+            self.jitter_max = self.jitter_max.max(jitter);
+            self.jitter_sum += jitter;
+        }
+        let missed = match deadline {
+            Some(deadline) => started + elapsed > deadline,
+            None => elapsed > period,
+        };
+        if missed {
+            self.num_deadline_misses += 1;
+        }
+        self.last_missed = missed;
+        missed
+    }
+
+    pub fn profile(&self) -> Value {
+        let average = |sum: Duration, n: u64| if n == 0 { 0.0 } else { sum.as_secs_f64() / n as f64 };
+        jvalue!({
+            "period": self.period.map(|p| p.as_secs_f64()),
+            "num_cycles": self.num_cycles,
+            "execution_time": {
+                "min": self.execution_time_min.unwrap_or_default().as_secs_f64(),
+                "avg": average(self.execution_time_sum, self.num_cycles),
+                "max": self.execution_time_max.as_secs_f64(),
+            },
+            "jitter": {
+                "avg": average(self.jitter_sum, self.num_intervals),
+                "max": self.jitter_max.as_secs_f64(),
+            },
+            "num_deadline_misses": self.num_deadline_misses,
+        })
+    }
+}
@@ -0,0 +1,73 @@
+//! 依存しないプロセスを並列に実行するためのスレッドプール
+
+use std::{sync::{mpsc, Arc, Mutex}, thread::JoinHandle};
+use juiz_sdk::anyhow::anyhow;
+
+use crate::prelude::*;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+pub struct ExecutionContextWorkerPool {
+    sender: Option<mpsc::Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ExecutionContextWorkerPool {
+
+    pub fn new(num_workers: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..num_workers).map(|i| {
+            let receiver = receiver.clone();
+            std::thread::Builder::new().name(format!("ec_worker{i}")).spawn(move || loop {
+                let job = match receiver.lock() {
+                    Ok(r) => r.recv(),
+                    Err(_) => break,
+                };
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break,
+                }
+            }).expect("failed to spawn ExecutionContext worker thread")
+        }).collect();
+        Self { sender: Some(sender), workers }
+    }
+
+    pub fn num_workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// processesを並列にexecuteして、processesと同じ順で結果を返す
+    pub fn execute_all(&self, processes: &[ProcessPtr]) -> Vec<JuizResult<CapsulePtr>> {
+        let (result_sender, result_receiver) = mpsc::channel();
+        for (i, process) in processes.iter().enumerate() {
+            let process = process.clone();
+            let result_sender = result_sender.clone();
+            let job: Job = Box::new(move || {
+                let result = process.lock().and_then(|p| p.execute());
+                let _ = result_sender.send((i, result));
+            });
+            if let Some(sender) = self.sender.as_ref() {
+                let _ = sender.send(job);
+            }
+        }
+        drop(result_sender);
+        let mut results: Vec<Option<JuizResult<CapsulePtr>>> = processes.iter().map(|_| None).collect();
+        for (i, result) in result_receiver.iter() {
+            results[i] = Some(result);
+        }
+        // ワーカーがpanicすると結果が届かない
+        results.into_iter().zip(processes.iter()).map(|(r, p)| {
+            r.unwrap_or_else(|| Err(anyhow!(JuizError::ExecutionContextWorkerError{id: p.identifier().clone()})))
+        }).collect()
+    }
+}
+
+impl Drop for ExecutionContextWorkerPool {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
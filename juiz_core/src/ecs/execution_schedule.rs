@@ -0,0 +1,88 @@
+//! ECにバインドしたプロセスの実行順
+//!
+//! バインドしたプロセス同士の接続をたどり、接続元が先に実行されるように並べる。
+//! 同じ段(level)のプロセスは互いに依存しないので並列に実行できる。
+//! 依存の無いプロセス同士はバインドした順になる。
+
+use std::collections::HashMap;
+use juiz_sdk::anyhow::anyhow;
+
+use crate::prelude::*;
+
+#[derive(Clone, Default)]
+pub struct ExecutionSchedule {
+    levels: Vec<Vec<ProcessPtr>>,
+}
+
+impl ExecutionSchedule {
+
+    /// targetsの接続から実行順を作る。接続が循環していたらエラー
+    ///
+    /// 接続元がtargetsに無い接続と、他のブローカーのプロセスの接続は見ない。
+    pub fn new(targets: &[ProcessPtr]) -> JuizResult<Self> {
+        let index: HashMap<&Identifier, usize> = targets.iter().enumerate().map(|(i, p)| (p.identifier(), i)).collect();
+        let mut successors: Vec<Vec<usize>> = vec![Vec::new(); targets.len()];
+        let mut in_degrees: Vec<usize> = vec![0; targets.len()];
+        for (i, target) in targets.iter().enumerate() {
+            if !is_local(target.identifier()) {
+                continue;
+            }
+            let mut predecessors = target.lock()?.source_connections()?.iter()
+                .filter_map(|c| index.get(c.connection_core().source_identifier()).copied())
+                .collect::<Vec<usize>>();
+            predecessors.sort();
+            predecessors.dedup();
+            in_degrees[i] = predecessors.len();
+            for j in predecessors {
+                successors[j].push(i);
+            }
+        }
+
+        let mut levels = Vec::new();
+        let mut current: Vec<usize> = (0..targets.len()).filter(|i| in_degrees[*i] == 0).collect();
+        while !current.is_empty() {
+            let mut next = Vec::new();
+            for i in current.iter() {
+                for s in successors[*i].iter() {
+                    in_degrees[*s] -= 1;
+                    if in_degrees[*s] == 0 {
+                        next.push(*s);
+                    }
+                }
+            }
+            next.sort();
+            levels.push(current.iter().map(|i| targets[*i].clone()).collect::<Vec<ProcessPtr>>());
+            current = next;
+        }
+
+        let remaining = (0..targets.len()).filter(|i| in_degrees[*i] > 0).map(|i| targets[i].identifier().clone()).collect::<Vec<Identifier>>();
+        if !remaining.is_empty() {
+            return Err(anyhow!(JuizError::ExecutionContextScheduleCycleError{processes: remaining}));
+        }
+        Ok(Self { levels })
+    }
+
+    /// 互いに依存しないプロセスの組を、実行する順に並べたもの
+    pub fn levels(&self) -> &Vec<Vec<ProcessPtr>> {
+        &self.levels
+    }
+
+    /// 一つずつ実行するときの順番
+    pub fn order(&self) -> impl Iterator<Item = &ProcessPtr> {
+        self.levels.iter().flatten()
+    }
+
+    pub fn profile(&self) -> Value {
+        let ids = |ps: &Vec<ProcessPtr>| ps.iter().map(|p| p.identifier().clone()).collect::<Vec<Identifier>>();
+        jvalue!({
+            "order": self.order().map(|p| p.identifier().clone()).collect::<Vec<Identifier>>(),
+            "levels": self.levels.iter().map(ids).collect::<Vec<Vec<Identifier>>>(),
+        })
+    }
+}
+
+/// 接続を調べられるこのシステムのプロセスか
+fn is_local(identifier: &Identifier) -> bool {
+    IdentifierStruct::try_from(identifier.clone())
+        .is_ok_and(|s| s.broker_type_name == "core" && s.broker_name == "core")
+}
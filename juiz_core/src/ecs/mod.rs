@@ -2,14 +2,22 @@
 
 pub mod execution_context;
 pub mod execution_context_core;
+pub mod execution_context_statistics;
+pub mod execution_context_worker_pool;
+pub mod execution_schedule;
 pub mod execution_context_holder;
 pub mod execution_context_factory;
 pub mod execution_context_proxy;
 pub mod execution_context_function;
 pub mod execution_context_holder_factory;
 pub mod one_shot_ec;
+pub mod event_ec;
+pub mod simulated_clock;
+pub mod step_ec;
 
 pub use execution_context::ExecutionContext;
 pub use execution_context_core::ExecutionContextCore;
+pub use execution_context_statistics::ExecutionContextStatistics;
+pub use execution_schedule::ExecutionSchedule;
 pub use execution_context_factory::ExecutionContextFactory;
 //pub use execution_context::ECServiceFunction;
\ No newline at end of file
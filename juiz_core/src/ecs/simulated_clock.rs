@@ -0,0 +1,60 @@
+//! 実時間の代わりにプロセスが読むシミュレーション時刻
+//!
+//! StepECが一サイクル進めるごとにstep_sizeだけ進む。プロセスは時刻を出力するクロックプロセスに
+//! Pull型で接続して読む。
+
+use std::sync::{Arc, Mutex};
+
+use crate::{connections::ConnectionFactoryImpl, prelude::*, processes::process_from_clousure_new_with_class_name};
+
+pub const SIMULATED_CLOCK_TYPE_NAME: &str = "simulated_clock";
+
+#[derive(Debug, Clone)]
+pub struct SimulatedClock {
+    start_time: f64,
+    step_size: f64,
+    num_steps: u64,
+}
+
+impl SimulatedClock {
+
+    pub fn new(start_time: f64, step_size: f64) -> Self {
+        Self { start_time, step_size, num_steps: 0 }
+    }
+
+    /// 現在の時刻 [sec]。誤差が積もらないようにステップ数から計算する
+    pub fn now(&self) -> f64 {
+        self.start_time + self.step_size * self.num_steps as f64
+    }
+
+    pub fn num_steps(&self) -> u64 {
+        self.num_steps
+    }
+
+    pub fn advance(&mut self) {
+        self.num_steps += 1;
+    }
+
+    pub fn profile(&self) -> Value {
+        jvalue!({
+            "time": self.now(),
+            "start_time": self.start_time,
+            "step_size": self.step_size,
+            "num_steps": self.num_steps,
+        })
+    }
+}
+
+/// clockの現在時刻を出力するプロセスを作る。毎回読み直すようにメモ化はしない
+pub fn simulated_clock_process(name: &str, clock: Arc<Mutex<SimulatedClock>>) -> JuizResult<ProcessPtr> {
+    let manifest: ProcessManifest = jvalue!({
+        "type_name": SIMULATED_CLOCK_TYPE_NAME,
+        "name": name,
+        "use_memo": false,
+        "arguments": [],
+    }).try_into()?;
+    let func = move |_args: CapsuleMap| -> JuizResult<Capsule> {
+        Ok(jvalue!(juiz_lock(&clock)?.now()).into())
+    };
+    Ok(ProcessPtr::new(process_from_clousure_new_with_class_name(JuizObjectClass::Process("SimulatedClock"), manifest, func, Box::new(ConnectionFactoryImpl::new()))?))
+}
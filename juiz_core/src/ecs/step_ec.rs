@@ -0,0 +1,118 @@
+//! 自分では走らず、ec_stepで頼まれた回数だけサイクルを進めるExecutionContext
+//!
+//! ```yaml
+//! ecs:
+//!   - type_name: StepEC
+//!     name: sim_ec0
+//!     step_size: 0.01
+//!     start_time: 0.0
+//! ```
+//! 作ると"{name}_clock"という名前のsimulated_clockプロセスができる。バインドしたプロセスは
+//! これにPull型で接続すると実時間の代わりにシミュレーション時刻を読める。このプロセスはECを消すと一緒に消える。
+
+use std::sync::{Arc, Mutex, RwLock};
+use juiz_sdk::anyhow::anyhow;
+
+use crate::prelude::*;
+
+use super::{simulated_clock::{simulated_clock_process, SimulatedClock}, ExecutionContext, ExecutionContextCore, ExecutionContextFactory};
+
+pub const STEP_EC_TYPE_NAME: &str = "StepEC";
+
+pub struct StepEC {
+    name: String,
+    clock: Arc<Mutex<SimulatedClock>>,
+    clock_process_id: Option<Identifier>,
+}
+
+impl StepEC {
+
+    pub fn new(name: &str, manifest: &Value) -> JuizResult<Arc<RwLock<StepEC>>> {
+        let step_size = match obj_get_f64(manifest, "step_size") {
+            Ok(step_size) if step_size > 0.0 => step_size,
+            Ok(step_size) => return Err(anyhow!(JuizError::InvalidSettingError{message: format!("StepEC({name}) step_size must be positive but {step_size}.")})),
+            Err(_) => 1.0,
+        };
+        let start_time = obj_get_f64(manifest, "start_time").unwrap_or(0.0);
+        Ok(Arc::new(RwLock::new(StepEC {
+            name: name.to_owned(),
+            clock: Arc::new(Mutex::new(SimulatedClock::new(start_time, step_size))),
+            clock_process_id: None,
+        })))
+    }
+
+    fn clock_process_name(&self) -> String {
+        format!("{}_clock", self.name)
+    }
+}
+
+impl ExecutionContext for StepEC {
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn type_name(&self) -> &str {
+        STEP_EC_TYPE_NAME
+    }
+
+    fn profile(&self) -> JuizResult<Value> {
+        Ok(jvalue!({
+            "clock": juiz_lock(&self.clock)?.profile(),
+            "clock_process": self.clock_process_name(),
+        }))
+    }
+
+    fn on_create(&mut self, worker: &mut CoreWorker, _core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<()> {
+        let p = simulated_clock_process(self.clock_process_name().as_str(), self.clock.clone())?;
+        let id = p.identifier().clone();
+        worker.store_mut().processes.register(&id, p)?;
+        self.clock_process_id = Some(id);
+        Ok(())
+    }
+
+    fn on_destroy(&mut self, worker: &mut CoreWorker, _core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<()> {
+        if let Some(id) = self.clock_process_id.take() {
+            worker.destroy_process_ref(&id)?;
+        }
+        Ok(())
+    }
+
+    /// ec_stepで進めるので、startされても何もしない
+    fn execute(&self, _core: &Arc<Mutex<ExecutionContextCore>>) -> JuizResult<bool> {
+        Ok(false)
+    }
+
+    fn step(&self, core: &Arc<Mutex<ExecutionContextCore>>, n: usize) -> JuizResult<Value> {
+        log::trace!("StepEC({})::step(n={n}) called", self.name);
+        let mut steps = Vec::with_capacity(n);
+        for _ in 0..n {
+            let (step, time) = {
+                let clock = juiz_lock(&self.clock)?;
+                (clock.num_steps(), clock.now())
+            };
+            let outputs = juiz_lock(core)?.svc_with_outputs()?;
+            steps.push(jvalue!({"step": step, "time": time, "outputs": outputs}));
+            juiz_lock(&self.clock)?.advance();
+        }
+        Ok(steps.into())
+    }
+
+    fn is_periodic(&self) -> bool {
+        false
+    }
+}
+
+pub struct StepECFactory {}
+
+impl ExecutionContextFactory for StepECFactory {
+
+    fn type_name(&self) -> &str {
+        STEP_EC_TYPE_NAME
+    }
+
+    fn create(&self, manifest: Value) -> JuizResult<Arc<RwLock<dyn ExecutionContext>>> {
+        let name = obj_get_str(&manifest, "name")?;
+        Ok(StepEC::new(name, &manifest)?)
+    }
+}
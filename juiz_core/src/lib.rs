@@ -10,14 +10,18 @@ mod containers;
 mod brokers;
 mod topics;
 mod ecs;
+mod recording;
 
 pub mod prelude;
 
 // pub use crate::utils::yaml_conf_load;
-pub use core::{SystemStore, SystemStorePtr};
+pub use core::{SystemStore, SystemStorePtr, SubSystemHeartbeat, SubSystemState};
 pub use brokers::{create_broker_factory_impl, create_broker_proxy_factory_impl, CRUDBroker, CRUDBrokerHolder};
 pub use brokers::{CRUDBrokerProxy, CRUDBrokerProxyHolder};
-pub use ecs::{ExecutionContext, ExecutionContextCore, ExecutionContextFactory, execution_context_core::ExecutionContextState};
+pub use brokers::PayloadCodec;
+pub use brokers::websocket::WebSocketBrokerProxy;
+pub use plugin::PluginPolicy;
+pub use ecs::{ExecutionContext, ExecutionContextCore, ExecutionContextFactory, ExecutionContextStatistics, ExecutionSchedule, execution_context_core::ExecutionContextState};
 
 // Re export 
 
@@ -5,7 +5,9 @@ mod plugin;
 mod python;
 mod cpp;
 mod rust;
+mod plugin_policy;
 
 
 pub use plugin::{Plugin, JuizObjectPlugin, concat_dirname, plugin_name_to_file_name};
 pub(crate) use rust::RustPlugin;
+pub use plugin_policy::PluginPolicy;
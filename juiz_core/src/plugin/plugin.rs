@@ -4,7 +4,7 @@ use juiz_sdk::anyhow;
 use crate::prelude::*;
 use crate::{containers::{ContainerFactoryPtr, ContainerProcessFactoryPtr}, prelude::ProcessFactoryPtr};
 
-use super::{cpp::CppPlugin, python::PythonPlugin, rust::RustPlugin};
+use super::{cpp::CppPlugin, python::PythonPlugin, rust::RustPlugin, PluginPolicy};
 
 
 #[derive(Clone)]
@@ -59,10 +59,11 @@ fn cpp_plugin_path(name: &str, v: &Value) -> JuizResult<std::path::PathBuf> {
 
 impl JuizObjectPlugin {
 
-    pub fn new(language: &str, name: &str, v: &Value, manifest_entry_point: &str, option: &Value) -> JuizResult<JuizObjectPlugin> {
+    /// 読み込む前にpolicyでプラグインのパスを確かめる
+    pub fn new(language: &str, name: &str, v: &Value, manifest_entry_point: &str, option: &Value, policy: &PluginPolicy) -> JuizResult<JuizObjectPlugin> {
         //let manifest_entry_point = "manifest_entry_point";
         match language {
-            "rust" => Ok(JuizObjectPlugin::Rust(Rc::new(RustPlugin::load(plugin_path(name, v)?)?))),
+            "rust" => Ok(JuizObjectPlugin::Rust(Rc::new(RustPlugin::load(policy.resolve(&plugin_path(name, v)?)?)?.allow_missing_abi(policy.allow_missing_abi())))),
             "python" => {
                 let pythonpaths = match obj_get_array(option, "pythonpath") {
                     Ok(arr_value) => {
@@ -70,9 +71,9 @@ impl JuizObjectPlugin {
                     },
                     Err(_) => None,
                 };
-                Ok( JuizObjectPlugin::Python(Rc::new(PythonPlugin::load(python_plugin_path(name, v)?, pythonpaths)?)))
+                Ok( JuizObjectPlugin::Python(Rc::new(PythonPlugin::load(policy.resolve(&python_plugin_path(name, v)?)?, pythonpaths)?)))
             },
-            "c++" => Ok(JuizObjectPlugin::Cpp(Rc::new(CppPlugin::new(cpp_plugin_path(name, v)?, manifest_entry_point)?))),
+            "c++" => Ok(JuizObjectPlugin::Cpp(Rc::new(CppPlugin::new(policy.resolve(&cpp_plugin_path(name, v)?)?, manifest_entry_point)?))),
             _ => {
                 log::error!("In setup_container_factories() function, unknown language option ({:}) detected", language);
                 Err(anyhow::Error::from(JuizError::InvalidSettingError{message: format!("In setup_container_factories() function, unknown language option ({:}) detected", language)}))
@@ -80,16 +81,16 @@ impl JuizObjectPlugin {
         }
     }
 
-    pub fn new_rust(filepath: PathBuf) -> JuizResult<JuizObjectPlugin> {
-        Ok(JuizObjectPlugin::Rust(Rc::new(RustPlugin::load(filepath)?)))
+    pub fn new_rust(filepath: PathBuf, policy: &PluginPolicy) -> JuizResult<JuizObjectPlugin> {
+        Ok(JuizObjectPlugin::Rust(Rc::new(RustPlugin::load(policy.resolve(&filepath)?)?.allow_missing_abi(policy.allow_missing_abi()))))
     }
 
-    pub fn new_python(filepath: PathBuf) -> JuizResult<JuizObjectPlugin> {
-        Ok(JuizObjectPlugin::Python(Rc::new(PythonPlugin::load(filepath, None)?)))
+    pub fn new_python(filepath: PathBuf, policy: &PluginPolicy) -> JuizResult<JuizObjectPlugin> {
+        Ok(JuizObjectPlugin::Python(Rc::new(PythonPlugin::load(policy.resolve(&filepath)?, None)?)))
     }
 
-    pub fn new_cpp(filepath: PathBuf, manifest_entry_point: &str) -> JuizResult<JuizObjectPlugin> {
-        Ok(JuizObjectPlugin::Cpp(Rc::new(CppPlugin::new(filepath, manifest_entry_point)?)))
+    pub fn new_cpp(filepath: PathBuf, manifest_entry_point: &str, policy: &PluginPolicy) -> JuizResult<JuizObjectPlugin> {
+        Ok(JuizObjectPlugin::Cpp(Rc::new(CppPlugin::new(policy.resolve(&filepath)?, manifest_entry_point)?)))
     }
 
     pub fn profile_full(&self) -> JuizResult<Value> {
@@ -0,0 +1,207 @@
+//! プラグインの読み込みを制限するポリシー
+//!
+//! システムのマニフェストの"plugin_policy"で、読み込んでよいプラグインの場所と名前、中身のSHA-256を決める。
+//!
+//! ```yaml
+//! plugin_policy:
+//!   search_paths: ["./target/debug", "./python"]
+//!   allow: ["libincrement_process.*", "*.py"]
+//!   checksums:
+//!     libincrement_process.so: "3b4c...e1"
+//!   checksum_file: "./plugins.sha256"
+//!   require_checksum: true
+//!   allow_missing_abi: false
+//! ```
+//!
+//! "search_paths"があれば、プラグインはそのどれかのディレクトリの下になければならない。
+//! ディレクトリを含まないファイル名だけが渡されたときは"search_paths"を順に探す。
+//! "allow"のパターンはファイル名と照合し、'*'は任意の文字列にマッチする。
+//! "checksum_file"は`sha256sum`の出力と同じ`<16進数>  <ファイル名>`の行を並べたファイル。
+//! チェックサムが登録されたプラグインは中身が一致しなければ読み込まない。
+//! "require_checksum"がtrueならチェックサムが登録されていないプラグインも読み込まない。
+//! "allow_missing_abi"がtrueなら、ABI記述子を公開していない(古いjuiz_sdkで作った)Rustのプラグインも確かめずに読み込む。
+//! 既定では記述子の無いプラグインは読み込まない。
+//! "plugin_policy"が無ければ、これまで通りどのパスのプラグインも読み込む。
+
+use std::{collections::HashMap, path::{Path, PathBuf}};
+use juiz_sdk::anyhow::anyhow;
+use regex::Regex;
+
+use crate::prelude::*;
+
+fn policy_setting_error(message: String) -> juiz_sdk::anyhow::Error {
+    anyhow!(JuizError::InvalidSettingError{message: format!("plugin_policy: {message}")})
+}
+
+fn rejected_error(path: &Path, reason: String) -> juiz_sdk::anyhow::Error {
+    log::error!("Plugin ({}) is rejected. {reason}", path.display());
+    anyhow!(JuizError::PluginRejectedError{plugin_path: path.display().to_string(), reason})
+}
+
+fn pattern_to_regex(pattern: &str) -> JuizResult<Regex> {
+    let escaped = regex::escape(pattern).replace("\\*", ".*");
+    Ok(Regex::new(format!("^{escaped}$").as_str())?)
+}
+
+fn resolve_dir(path: &Path, working_dir: &Path) -> PathBuf {
+    if path.is_absolute() { path.to_path_buf() } else { working_dir.join(path) }
+}
+
+/// SHA-256の小文字16進数
+fn sha256_hex(path: &Path) -> JuizResult<String> {
+    let bytes = std::fs::read(path)?;
+    Ok(ring::digest::digest(&ring::digest::SHA256, &bytes).as_ref().iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>())
+}
+
+/// `sha256sum`の形式のファイルを読む。バイナリモードの'*'は読み飛ばす
+fn load_checksum_file(path: &Path) -> JuizResult<HashMap<String, String>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| policy_setting_error(format!("Reading checksum_file ({}) failed. {e}", path.display())))?;
+    let mut checksums = HashMap::new();
+    for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let (hash, file_name) = line.split_once(char::is_whitespace)
+            .ok_or_else(|| policy_setting_error(format!("checksum_file ({}) has invalid line '{line}'.", path.display())))?;
+        let file_name = file_name.trim_start().trim_start_matches('*');
+        checksums.insert(file_name_of(Path::new(file_name)), hash.to_ascii_lowercase());
+    }
+    Ok(checksums)
+}
+
+fn file_name_of(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// システムのマニフェストの"plugin_policy"
+#[derive(Debug, Clone, Default)]
+pub struct PluginPolicy {
+    /// Noneならどのパスも許す
+    search_paths: Option<Vec<PathBuf>>,
+    /// Noneならどのファイル名も許す
+    allow: Option<Vec<Regex>>,
+    /// ファイル名ごとのSHA-256
+    checksums: HashMap<String, String>,
+    require_checksum: bool,
+    /// ABI記述子の無いRustのプラグインを読み込むか
+    allow_missing_abi: bool,
+    /// 相対パスの基準。Noneならカレントディレクトリ
+    working_dir: Option<PathBuf>,
+}
+
+impl PluginPolicy {
+
+    /// 制限の無いポリシー
+    pub fn unrestricted() -> Self {
+        Self::default()
+    }
+
+    /// システムのマニフェストから"plugin_policy"を読む。相対パスはworking_dirを基準にする
+    pub fn from_system_manifest(manifest: &Value, working_dir: Option<PathBuf>) -> JuizResult<Self> {
+        let policy = match manifest.get("plugin_policy") {
+            None => return Ok(Self::unrestricted()),
+            Some(policy) => policy,
+        };
+        if !policy.is_object() {
+            return Err(policy_setting_error(format!("must be object. ({policy})")));
+        }
+        let base_dir = match working_dir.as_ref() {
+            Some(wd) => wd.clone(),
+            None => std::env::current_dir()?,
+        };
+        let search_paths = match policy.get("search_paths") {
+            None => None,
+            Some(v) => Some(get_array(v)?.iter().map(|p| {
+                let p = p.as_str().ok_or_else(|| policy_setting_error(format!("search_paths must be array of string. ({v})")))?;
+                // 無いディレクトリはそのまま残す。照合では何にもマッチしない
+                let dir = resolve_dir(Path::new(p), &base_dir);
+                Ok(dir.canonicalize().unwrap_or(dir))
+            }).collect::<JuizResult<Vec<PathBuf>>>()?),
+        };
+        let allow = match policy.get("allow") {
+            None => None,
+            Some(v) => Some(get_array(v)?.iter().map(|p| {
+                pattern_to_regex(p.as_str().ok_or_else(|| policy_setting_error(format!("allow must be array of string. ({v})")))?)
+            }).collect::<JuizResult<Vec<Regex>>>()?),
+        };
+        let mut checksums = match policy.get("checksum_file") {
+            None => HashMap::new(),
+            Some(v) => {
+                let p = v.as_str().ok_or_else(|| policy_setting_error(format!("checksum_file must be string. ({v})")))?;
+                load_checksum_file(&resolve_dir(Path::new(p), &base_dir))?
+            }
+        };
+        if let Some(v) = policy.get("checksums") {
+            for (file_name, hash) in get_hashmap(v)?.iter() {
+                let hash = hash.as_str().ok_or_else(|| policy_setting_error(format!("checksum of '{file_name}' must be string.")))?;
+                checksums.insert(file_name.clone(), hash.to_ascii_lowercase());
+            }
+        }
+        let require_checksum = match policy.get("require_checksum") {
+            None => false,
+            Some(v) => v.as_bool().ok_or_else(|| policy_setting_error(format!("require_checksum must be bool. ({v})")))?,
+        };
+        let allow_missing_abi = match policy.get("allow_missing_abi") {
+            None => false,
+            Some(v) => v.as_bool().ok_or_else(|| policy_setting_error(format!("allow_missing_abi must be bool. ({v})")))?,
+        };
+        Ok(PluginPolicy{search_paths, allow, checksums, require_checksum, allow_missing_abi, working_dir})
+    }
+
+    pub fn allow_missing_abi(&self) -> bool {
+        self.allow_missing_abi
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        self.search_paths.is_none() && self.allow.is_none() && self.checksums.is_empty() && !self.require_checksum
+    }
+
+    /// 読み込むプラグインのパスを決めてポリシーを確かめる。
+    ///
+    /// 制限が無ければpathをそのまま返す。制限があれば正規化したパスを返すので、そのパスを読み込むこと。
+    pub fn resolve(&self, path: &Path) -> JuizResult<PathBuf> {
+        if self.is_unrestricted() {
+            return Ok(path.to_path_buf());
+        }
+        let working_dir = match self.working_dir.as_ref() {
+            Some(wd) => wd.clone(),
+            None => std::env::current_dir()?,
+        };
+        let candidate = match self.search_paths.as_ref() {
+            // ファイル名だけなら検索パスから探す
+            Some(dirs) if path.parent().map_or(true, |p| p.as_os_str().is_empty()) => {
+                dirs.iter().map(|d| d.join(path)).find(|p| p.is_file())
+                    .ok_or_else(|| rejected_error(path, format!("not found in search_paths {dirs:?}.")))?
+            },
+            _ => resolve_dir(path, &working_dir),
+        };
+        let canonical = candidate.canonicalize()
+            .map_err(|e| rejected_error(path, format!("can not be resolved. {e}")))?;
+
+        if let Some(dirs) = self.search_paths.as_ref() {
+            if !dirs.iter().any(|d| canonical.starts_with(d)) {
+                return Err(rejected_error(&canonical, format!("is not under search_paths {dirs:?}.")));
+            }
+        }
+        let file_name = file_name_of(&canonical);
+        if let Some(allow) = self.allow.as_ref() {
+            if !allow.iter().any(|r| r.is_match(file_name.as_str())) {
+                return Err(rejected_error(&canonical, format!("file name '{file_name}' is not in allow list.")));
+            }
+        }
+        match self.checksums.get(&file_name) {
+            Some(expected) => {
+                let actual = sha256_hex(&canonical)?;
+                if &actual != expected {
+                    return Err(rejected_error(&canonical, format!("SHA-256 ({actual}) does not match the registered checksum ({expected}).")));
+                }
+            },
+            None if self.require_checksum => {
+                return Err(rejected_error(&canonical, format!("no checksum is registered for '{file_name}'.")));
+            },
+            None => {},
+        }
+        log::debug!("Plugin ({}) is accepted by plugin_policy", canonical.display());
+        Ok(canonical)
+    }
+}
@@ -1,7 +1,7 @@
 
 use std::{collections::HashMap, fs, io::{BufWriter, Cursor}, path::PathBuf, sync::Arc};
 use image::ImageFormat;
-use pyo3::{prelude::*, types::{PyBytes, PyDict, PyFloat, PyInt, PyList, PyNone, PySet, PyString, PyTuple}};
+use pyo3::{prelude::*, exceptions::PyValueError, types::{PyByteArray, PyBytes, PyDict, PyFloat, PyInt, PyList, PyNone, PySet, PyString, PyTuple}};
 use juiz_sdk::serde_json::Map;
 use juiz_sdk::anyhow::{self, anyhow};
 use crate::{containers::{bind_container_function, container_factory_create, container_process_factory_create_from_trait}, prelude::*, processes::process_factory_create_from_trait};
@@ -372,6 +372,14 @@ fn capsuleptr_to_pyany(py: Python, value: &CapsulePtr) -> Py<PyAny> {
         return value.lock_as_image(|img| {
             image_to_pyany(py, img)
         }).unwrap()
+    } else if value.is_bytes().unwrap() {
+        return value.lock_as_bytes(|bytes| {
+            PyBytes::new_bound(py, bytes).into_py(py)
+        }).unwrap()
+    } else if value.is_numeric_array().unwrap() {
+        return value.lock_as_numeric_array(|array| {
+            numeric_array_to_pyany(py, array)
+        }).unwrap()
     }
     todo!("capsuleptr_to_pyany failed. CapsulePtr type is not available yet. Value is {value:?}")
 }
@@ -397,6 +405,42 @@ def image_from_bytes(w, h, image_data):
     }
 }
 
+/// 数値の配列はnumpy.ndarrayにする。numpyが無ければ形を持ったmemoryviewにする
+fn numeric_array_to_pyany(py: Python, array: &NumericArray) -> Py<PyAny> {
+    let py_app = r"
+FORMATS = {'f32': ('<f4', 'f'), 'f64': ('<f8', 'd'), 'i32': ('<i4', 'i'), 'u8': ('u1', 'B')}
+def numeric_array_from_bytes(dtype, shape, data):
+    np_dtype, fmt = FORMATS[dtype]
+    try:
+        import numpy
+    except ImportError:
+        return memoryview(data).cast(fmt, shape)
+    return numpy.frombuffer(data, dtype=np_dtype).reshape(shape)
+";
+    let b = PyBytes::new_bound(py, &array.to_le_bytes());
+    let module = PyModule::from_code_bound(py, py_app, "", "").unwrap();
+    module.getattr("numeric_array_from_bytes").unwrap().call1((array.numeric_type().as_str(), array.shape().to_vec(), b)).unwrap().unbind()
+}
+
+fn ndarray_to_capsule(value: &PyAny) -> PyResult<Capsule> {
+    Python::with_gil(|py| -> PyResult<Capsule> {
+        let app_code = r"
+NAMES = {'float32': 'f32', 'float64': 'f64', 'int32': 'i32', 'uint8': 'u8'}
+def numeric_array_to_bytes(array):
+    name = NAMES.get(array.dtype.name)
+    if name is None:
+        raise TypeError('numpy.ndarray of ' + array.dtype.name + ' is not available in juiz. Use float32, float64, int32 or uint8.')
+    return (name, list(array.shape), array.astype(array.dtype.newbyteorder('<'), copy=False).tobytes(order='C'))
+";
+        let module = PyModule::from_code_bound(py, app_code, "", "")?;
+        let (dtype, shape, data) = module.getattr("numeric_array_to_bytes")?.call1((value,))?.extract::<(String, Vec<usize>, Bound<PyBytes>)>()?;
+        NumericType::try_from(dtype.as_str())
+            .and_then(|numeric_type| NumericArray::from_le_bytes(numeric_type, shape, data.as_bytes()))
+            .map(|array| array.into())
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    })
+}
+
 // pub fn python_process_call(py: Python, entry_point: &Py<PyAny>, arguments: Vec<Py<PyAny>>) -> JuizResult<Capsule> {
 //     match entry_point.call1(py, PyTuple::new_bound(py, arguments)) {
 //         Ok(result) => {
@@ -436,8 +480,7 @@ pub fn python_process_call(py: Python, entry_point: &Py<PyAny>, pytuple: pyo3::B
         Ok(v) => {
             let object = v.extract::<&PyAny>(py)?;
             Ok(if check_object_is_ndarray(&py, object) {
-                //pyany_to_mat(py, object).unwrap()
-                todo!()
+                ndarray_to_capsule(object)?
             } else {
                 // println!("pyany_to_value: {object:?}");
                 pyany_to_capsule(object)?.into()
@@ -509,7 +552,13 @@ pub fn pyany_to_value(value: &PyAny) -> PyResult<Value> {
 
 
 pub fn pyany_to_capsule(value: &PyAny) -> PyResult<Capsule> {
-    if value.is_instance_of::<PyString>() {
+    if value.is_instance_of::<PyBytes>() {
+        Ok(value.extract::<&PyBytes>()?.as_bytes().to_vec().into())
+    } else if value.is_instance_of::<PyByteArray>() {
+        Ok(value.extract::<&PyByteArray>()?.to_vec().into())
+    } else if Python::with_gil(|py| check_object_is_ndarray(&py, value)) {
+        ndarray_to_capsule(value)
+    } else if value.is_instance_of::<PyString>() {
         Ok(Value::from(value.extract::<String>()?).into())
     } else if value.is_instance_of::<PyFloat>() {
         Ok(Value::from(value.extract::<f64>()?).into())
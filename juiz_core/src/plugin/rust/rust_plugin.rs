@@ -16,6 +16,7 @@ pub struct RustPlugin {
     path: PathBuf,
     plugin_manager: Option<Arc<Mutex<dyn PluginManager>>>,
     lib: Option<Library>,
+    allow_missing_abi: bool,
 }
 
 
@@ -38,7 +39,8 @@ impl RustPlugin {
                     log::info!("RustPlugin::load({:?}) loaded", path);
                     Ok(RustPlugin{lib:Some(lib), 
                         plugin_manager,
-                        path})
+                        path,
+                        allow_missing_abi: false})
                 },
                 Err(_) => {
                     log::error!("RustPlugin::load({:?}) failed.", path);
@@ -48,6 +50,12 @@ impl RustPlugin {
         }
     }
 
+    /// ABI記述子の無いプラグインも読み込むか。PluginPolicyのallow_missing_abiを渡す
+    pub fn allow_missing_abi(mut self, allow_missing_abi: bool) -> Self {
+        self.allow_missing_abi = allow_missing_abi;
+        self
+    }
+
     pub fn load_symbol<T>(&self, symbol_name: &[u8]) -> JuizResult<libloading::Symbol<T>> {
         log::trace!("RustPlugin::load_symbol({:?}) called", std::str::from_utf8(symbol_name));
         unsafe {
@@ -61,11 +69,48 @@ impl RustPlugin {
         }
     }
 
+    /// ファクトリのシンボルに対応するABI記述子を自分のものと比べる。
+    ///
+    /// 記述子の無いプラグインは古いjuiz_sdkで作られたもので、互換性を確かめられないので読み込まない。
+    /// allow_missing_abiが指定されていれば警告だけ出して通す。
+    pub fn check_abi(&self, symbol_name: &str) -> JuizResult<()> {
+        log::trace!("RustPlugin::check_abi({symbol_name}) called");
+        type AbiSymbolType<'a> = libloading::Symbol<'a, unsafe extern "C" fn() -> AbiDescriptor>;
+        let abi_symbol_name = abi_symbol_name(symbol_name);
+        let plugin_abi = unsafe {
+            match self.lib.as_ref().unwrap().get::<AbiSymbolType>(abi_symbol_name.as_bytes()) {
+                Ok(func) => AbiInfo::from_descriptor(&(func)()),
+                Err(_) if self.allow_missing_abi => {
+                    log::warn!("RustPlugin({}) does not export ABI descriptor '{abi_symbol_name}'. ABI compatibility is not checked.", self.path.display());
+                    return Ok(());
+                }
+                Err(_) => {
+                    log::error!("RustPlugin({}) is refused. ABI descriptor '{abi_symbol_name}' is not exported.", self.path.display());
+                    return Err(anyhow::Error::from(JuizError::PluginRejectedError{plugin_path: self.path.display().to_string(), reason: format!("ABI descriptor '{abi_symbol_name}' is not exported. Rebuild the plugin with the current juiz_sdk, or set plugin_policy.allow_missing_abi to load it without the check.")}));
+                }
+            }
+        };
+        let expected = AbiInfo::current();
+        match expected.incompatibility(&plugin_abi) {
+            None => Ok(()),
+            Some(reason) => {
+                log::error!("RustPlugin({}) is refused. {reason}. expected ({expected}), plugin ({plugin_abi})", self.path.display());
+                Err(anyhow::Error::from(JuizError::PluginAbiMismatchError{plugin_path: self.path.display().to_string(), symbol_name: symbol_name.to_owned(), reason}))
+            }
+        }
+    }
+
+    /// ABIを確かめてからファクトリのシンボルを読む
+    pub fn load_factory_symbol<T>(&self, symbol_name: &str) -> JuizResult<libloading::Symbol<T>> {
+        self.check_abi(symbol_name)?;
+        self.load_symbol::<T>(symbol_name.as_bytes())
+    }
+
     pub fn load_process_factory(&self, _working_dir: Option<PathBuf>, symbol_name: &str) -> JuizResult<ProcessFactoryPtr> {
         log::trace!("load_process_factory({_working_dir:?}, symbol_name={symbol_name}) called");
         type SymbolType = libloading::Symbol<'static, unsafe extern "Rust" fn() -> JuizResult<ProcessFactoryStruct>>;
         unsafe {
-            let symbol = self.load_symbol::<SymbolType>(symbol_name.as_bytes())?;
+            let symbol = self.load_factory_symbol::<SymbolType>(symbol_name)?;
             let ProcessFactoryStruct(manifest, proc_function) = (symbol)().with_context(||format!("calling symbol '{symbol_name}'"))?;
             process_factory_create(manifest, proc_function)
         }
@@ -75,7 +120,7 @@ impl RustPlugin {
         log::trace!("load_container_factory({_working_dir:?}, symbol_name={symbol_name}) called");
         type SymbolType = libloading::Symbol<'static, unsafe extern "Rust" fn() -> JuizResult<ContainerFactoryStruct>>;
         unsafe {
-            let symbol = self.load_symbol::<SymbolType>(symbol_name.as_bytes())?;
+            let symbol = self.load_factory_symbol::<SymbolType>(symbol_name)?;
             let ContainerFactoryStruct(manifest, factory_function) = (symbol)()?;
             container_factory_create(manifest, factory_function)
         }
@@ -85,7 +130,7 @@ impl RustPlugin {
         log::trace!("load_container_process_factory({_working_dir:?}, symbol_name={symbol_name}) called");
         type SymbolType = libloading::Symbol<'static, unsafe extern "Rust" fn() -> JuizResult<ContainerProcessFactoryStruct>>;
         unsafe {
-            let symbol = self.load_symbol::<SymbolType>(symbol_name.as_bytes())?;
+            let symbol = self.load_factory_symbol::<SymbolType>(symbol_name)?;
             let ContainerProcessFactoryStruct(manifest, factory_function) = (symbol)()?;
             container_process_factory_create(manifest, factory_function)
         }
@@ -94,7 +139,7 @@ impl RustPlugin {
     pub fn load_component_manifest(&self) -> JuizResult<ComponentManifest> {
         log::trace!("load_component_manifest() for RustPlugin(path={:?}) called", self.path);
         type ComponentProfileFunctionSymbolType<'a> = libloading::Symbol<'a, unsafe extern "Rust" fn() -> ComponentManifest>;
-        let symbol = self.load_symbol::<ComponentProfileFunctionSymbolType>(b"component_manifest")?;
+        let symbol = self.load_factory_symbol::<ComponentProfileFunctionSymbolType>("component_manifest")?;
         Ok(unsafe {
              (symbol)()//.with_context(||format!("calling symbol 'container_factory'. arg is {manifest:}"))?;
         })
@@ -104,7 +149,7 @@ impl RustPlugin {
         log::trace!("load_broker_factory() for RustPlugin(path={:?}) called", self.path);
         //log::trace!("BrokerFactory (type_name={:?}) created.", juiz_lock(&bf)?.type_name());
         type BrokerFactorySymbolType<'a> = libloading::Symbol<'a, unsafe extern "Rust" fn(CoreBrokerPtr) -> JuizResult<Arc<Mutex<dyn BrokerFactory>>>>;
-        let symbol_bf = self.load_symbol::<BrokerFactorySymbolType>(b"broker_factory")?;
+        let symbol_bf = self.load_factory_symbol::<BrokerFactorySymbolType>("broker_factory")?;
         unsafe {
             (symbol_bf)(system.core_broker().clone())
         }
@@ -113,7 +158,7 @@ impl RustPlugin {
     pub fn load_broker_proxy_factory(&self, _system: &mut System,) -> JuizResult<Arc<Mutex<dyn BrokerProxyFactory>>> {
         log::trace!("load_broker_proxy_factory() for RustPlugin(path={:?}) called", self.path);
         type BrokerProxyFactorySymbolType<'a> = libloading::Symbol<'a, unsafe extern "Rust" fn() -> JuizResult<Arc<Mutex<dyn BrokerProxyFactory>>>>;
-        let symbol_bpf = self.load_symbol::<BrokerProxyFactorySymbolType>(b"broker_proxy_factory")?;
+        let symbol_bpf = self.load_factory_symbol::<BrokerProxyFactorySymbolType>("broker_proxy_factory")?;
         unsafe {
             (symbol_bpf)()
         }
@@ -133,4 +178,20 @@ impl Drop for RustPlugin {
     fn drop(&mut self) {
         log::info!("RustPlugin({})::drop() called", self.path.display());
     }
-}
\ No newline at end of file
+}
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    /// ABI記述子を公開していない共有ライブラリとしてlibcを使う
+    #[test]
+    fn rust_plugin_without_abi_descriptor_test() -> JuizResult<()> {
+        let plugin = RustPlugin::load(PathBuf::from("libc.so.6"))?;
+        let err = plugin.check_abi("process_factory").unwrap_err();
+        assert!(matches!(err.downcast_ref::<JuizError>(), Some(JuizError::PluginRejectedError{..})), "unexpected error {err:?}");
+
+        let plugin = plugin.allow_missing_abi(true);
+        assert!(plugin.check_abi("process_factory").is_ok());
+        Ok(())
+    }
+}
@@ -33,6 +33,7 @@ pub use crate::{
         ExecutionContextBrokerProxy,
         BrokerBrokerProxy,
         ConnectionBrokerProxy,     
+        TopicBrokerProxy,
         CoreBrokerPtr,
         CoreBroker
     },
@@ -44,4 +45,15 @@ pub use crate::{
     connections::{
         connect
     },
+    recording::{
+        Recorder,
+        Player,
+        PlaybackSpeed,
+        PlaybackTarget,
+        BagReader,
+        BagWriter,
+        BagChannel,
+        BagChannelKind,
+        BagRecord,
+    },
 };
@@ -0,0 +1,128 @@
+//! 内部プロセスのサブグラフを一つのプロセスとして見せる合成プロセス
+//!
+//! 合成プロセス自体は通常のProcessImplで、その関数が内部のグラフを評価する。
+//! そのためcall, execute, p_applyや接続はProcessImplと同じように使える。
+//!
+//! 内部プロセスどうしは接続しない。合成プロセスがconnectionsの定義に従って上流から順に内部プロセスをcallする。
+//! そのため内部プロセスの出力のmemoや、内部プロセスに直接p_applyした値は評価に使わない。
+
+use std::collections::{HashMap, VecDeque};
+use juiz_sdk::anyhow::anyhow;
+
+use crate::connections::ConnectionFactoryImpl;
+use crate::prelude::*;
+use super::process_impl::ProcessImpl;
+
+/// 内部プロセス一つ分の評価の仕方
+struct CompositeNode {
+    process: ProcessPtr,
+    /// (引数名, 上流のノードの番号)
+    links: Vec<(String, usize)>,
+    /// (引数名, 合成プロセスの引数名)
+    exposed: Vec<(String, String)>,
+}
+
+/// 内部のグラフをトポロジカル順に並べたもの
+struct CompositeGraph {
+    nodes: Vec<CompositeNode>,
+    order: Vec<usize>,
+    outlet: usize,
+}
+
+impl CompositeGraph {
+
+    fn new(definition: &CompositeProcessManifest, processes: &HashMap<String, ProcessPtr>) -> JuizResult<Self> {
+        let names = definition.processes.iter().map(|p| p.name.clone().unwrap_or_default()).collect::<Vec<String>>();
+        let index_of = |name: &str| names.iter().position(|n| n == name)
+            .ok_or_else(|| anyhow!(JuizError::InvalidSettingError{message: format!("CompositeProcess({}) does not have inner process '{name}'.", definition.type_name)}));
+        let mut nodes = Vec::new();
+        for name in names.iter() {
+            let process = processes.get(name)
+                .ok_or_else(|| anyhow!(JuizError::CanNotFindError{target: format!("CompositeProcess({})::{name}", definition.type_name)}))?;
+            nodes.push(CompositeNode { process: process.clone(), links: Vec::new(), exposed: Vec::new() });
+        }
+        for c in definition.connections.iter() {
+            let source = index_of(c.source.as_str())?;
+            nodes[index_of(c.destination.as_str())?].links.push((c.arg_name.clone(), source));
+        }
+        for i in definition.inlets.iter() {
+            nodes[index_of(i.process.as_str())?].exposed.push((i.arg_name.clone(), i.name.clone()));
+        }
+        let order = topological_order(&nodes).ok_or_else(|| anyhow!(JuizError::InvalidSettingError{message: format!("Connections in CompositeProcess({}) have a cycle.", definition.type_name)}))?;
+        Ok(Self { nodes, order, outlet: index_of(definition.outlet.as_str())? })
+    }
+
+    /// 上流から順に内部プロセスをcallし、公開している出力を返す
+    ///
+    /// 内部プロセスの引数は、接続された上流の出力、合成プロセスの引数、内部プロセスのデフォルト値の順に優先する。
+    fn evaluate(&self, args: &CapsuleMap) -> JuizResult<CapsulePtr> {
+        let mut outputs: Vec<Option<CapsulePtr>> = vec![None; self.nodes.len()];
+        for &index in self.order.iter() {
+            let node = &self.nodes[index];
+            let process = node.process.lock()?;
+            let mut inputs: CapsuleMap = process.manifest().arguments.iter()
+                .map(|a| (a.name.clone(), a.default.clone().into()))
+                .collect::<Vec<(String, CapsulePtr)>>().into();
+            for (arg_name, name) in node.exposed.iter() {
+                inputs.insert(arg_name.clone(), args.get(name.as_str())?);
+            }
+            for (arg_name, source) in node.links.iter() {
+                if let Some(output) = outputs[*source].as_ref() {
+                    inputs.insert(arg_name.clone(), output.clone());
+                }
+            }
+            outputs[index] = Some(process.call(inputs)?);
+        }
+        outputs[self.outlet].take().ok_or_else(|| anyhow!(JuizError::InvalidValueError{message: "CompositeProcess output is not evaluated.".to_owned()}))
+    }
+}
+
+/// Kahnの方法で並べる。閉路があればNone
+fn topological_order(nodes: &[CompositeNode]) -> Option<Vec<usize>> {
+    let mut in_degrees = nodes.iter().map(|n| n.links.len()).collect::<Vec<usize>>();
+    let mut queue = (0..nodes.len()).filter(|i| in_degrees[*i] == 0).collect::<VecDeque<usize>>();
+    let mut order = Vec::new();
+    while let Some(index) = queue.pop_front() {
+        order.push(index);
+        for (destination, node) in nodes.iter().enumerate() {
+            for _ in node.links.iter().filter(|(_, source)| *source == index) {
+                in_degrees[destination] -= 1;
+                if in_degrees[destination] == 0 {
+                    queue.push_back(destination);
+                }
+            }
+        }
+    }
+    (order.len() == nodes.len()).then_some(order)
+}
+
+/// 公開する入力の引数マニフェストを内部プロセスから写し、インスタンスのマニフェストのデフォルト値で上書きする
+fn facade_manifest(definition: &CompositeProcessManifest, mut manifest: ProcessManifest, processes: &HashMap<String, ProcessPtr>) -> JuizResult<ProcessManifest> {
+    let mut arguments = Vec::new();
+    for inlet in definition.inlets.iter() {
+        let process = processes.get(&inlet.process)
+            .ok_or_else(|| anyhow!(JuizError::CanNotFindError{target: format!("CompositeProcess({})::{}", definition.type_name, inlet.process)}))?;
+        let mut argument = process.lock()?.manifest().arguments.iter().find(|a| a.name == inlet.arg_name).cloned()
+            .ok_or_else(|| anyhow!(JuizError::CanNotFindError{target: format!("CompositeProcess({})::{}::{}", definition.type_name, inlet.process, inlet.arg_name)}))?;
+        argument.name = inlet.name.clone();
+        if let Some(instance_argument) = manifest.arguments.iter().find(|a| a.name == inlet.name) {
+            argument.default = instance_argument.default.clone();
+        }
+        arguments.push(argument);
+    }
+    manifest.type_name = definition.type_name.clone();
+    manifest.description = definition.description.clone();
+    manifest.arguments = arguments;
+    Ok(manifest)
+}
+
+/// 作成済みの内部プロセス (合成プロセスの中での名前がキー) から合成プロセスを作る
+pub(crate) fn composite_process_new(definition: &CompositeProcessManifest, manifest: ProcessManifest, processes: &HashMap<String, ProcessPtr>) -> JuizResult<ProcessPtr> {
+    log::trace!("composite_process_new(type_name={}, manifest={manifest}) called", definition.type_name);
+    let graph = CompositeGraph::new(definition, processes)?;
+    let manifest = facade_manifest(definition, manifest, processes)?;
+    let func = move |args: CapsuleMap| -> JuizResult<Capsule> {
+        graph.evaluate(&args)?.clone_capsule()
+    };
+    Ok(ProcessPtr::new(ProcessImpl::new_from_clousure_and_class_name(JuizObjectClass::Process("CompositeProcess"), manifest, func, Box::new(ConnectionFactoryImpl::new()))?))
+}
@@ -95,8 +95,12 @@ impl Inlet {
         }
     }
 
+    /// 同じidentifierの接続があれば置き換える (サブシステムとのつなぎ直しで同じ接続が届く)
     pub(crate) fn insert(&mut self, con: Box<dyn SourceConnection + 'static>) {
-        self.source_connections.push(con);
+        match self.source_connections.iter().position(|c| c.identifier() == con.identifier()) {
+            Some(index) => self.source_connections[index] = con,
+            None => self.source_connections.push(con),
+        }
     }
 
     pub fn bind(&mut self, value: CapsulePtr) -> JuizResult<CapsulePtr> {
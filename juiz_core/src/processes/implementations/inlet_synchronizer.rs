@@ -0,0 +1,140 @@
+//! 複数のinletへのpushの入力をそろえる
+//!
+//! ProcessManifestのsyncで指定されたinletごとに届いた入力を保持しておき、
+//! ポリシーに合う組がそろった時だけその組を返す。
+
+use std::{collections::{HashMap, VecDeque}, sync::Mutex, time::Duration};
+
+use juiz_sdk::anyhow::anyhow;
+use crate::prelude::*;
+
+struct Sample {
+    value: CapsulePtr,
+    stamp: Option<String>,
+}
+
+pub(crate) struct InletSynchronizer {
+    policy: InletSyncPolicy,
+    queue_size: usize,
+    inlet_names: Vec<String>,
+    queues: Mutex<HashMap<String, VecDeque<Sample>>>,
+}
+
+fn stamp_as_secs(sample: &Sample) -> Option<f64> {
+    sample.stamp.as_ref().and_then(|s| s.parse::<f64>().ok())
+}
+
+impl InletSynchronizer {
+
+    /// 同期しないポリシーの時はNoneを返す。syncのinletsに引数に無い名前があればエラーにする
+    pub(crate) fn new(manifest: &ProcessManifest) -> JuizResult<Option<Self>> {
+        if manifest.sync.is_none() {
+            return Ok(None);
+        }
+        if let Some(name) = manifest.sync.inlets.iter().find(|n| !manifest.arguments.iter().any(|a| &a.name == *n)) {
+            return Err(anyhow!(JuizError::ProcessManifestInvalidError{message: format!("sync.inlets of Process({}) has '{name}', but it is not an argument of the process.", manifest.type_name)}));
+        }
+        let inlet_names = if manifest.sync.inlets.is_empty() {
+            manifest.arguments.iter().map(|a| a.name.clone()).collect()
+        } else {
+            manifest.sync.inlets.clone()
+        };
+        Ok(Some(Self {
+            policy: manifest.sync.policy.clone(),
+            queue_size: manifest.sync.queue_size,
+            queues: Mutex::new(inlet_names.iter().map(|n| (n.clone(), VecDeque::new())).collect()),
+            inlet_names,
+        }))
+    }
+
+    pub(crate) fn contains(&self, inlet_name: &str) -> bool {
+        self.inlet_names.iter().any(|n| n == inlet_name)
+    }
+
+    fn stamp_key(&self) -> Option<&str> {
+        match &self.policy {
+            InletSyncPolicy::ExactTime { key } => Some(key.as_str()),
+            InletSyncPolicy::ApproximateTime { key, .. } => Some(key.as_str()),
+            _ => None,
+        }
+    }
+
+    /// inletに届いた値を保持し、ポリシーに合う入力の組がそろったらそれを返す
+    ///
+    /// 返した組に使った値と、それより古い値は捨てられる。
+    pub(crate) fn push(&self, inlet_name: &str, value: CapsulePtr) -> JuizResult<Option<HashMap<String, CapsulePtr>>> {
+        let stamp = match self.stamp_key() {
+            Some(key) => match value.get_option(key) {
+                Ok(stamp) => Some(stamp),
+                Err(_) => {
+                    log::warn!("InletSynchronizer::push({inlet_name}) ignored input because it does not have option '{key}'.");
+                    return Ok(None);
+                }
+            },
+            None => None,
+        };
+        let mut queues = self.queues.lock().map_err(|e| anyhow!(JuizError::MutexLockFailedError{error: e.to_string()}))?;
+        let queue = queues.get_mut(inlet_name).ok_or_else(|| anyhow!(JuizError::CanNotFindError{target: format!("InletSynchronizer::Inlet({inlet_name})")}))?;
+        queue.push_back(Sample { value, stamp });
+        while queue.len() > self.queue_size {
+            queue.pop_front();
+        }
+
+        let selected = match &self.policy {
+            InletSyncPolicy::AllUpdated => select_all_updated(&queues),
+            InletSyncPolicy::ExactTime { .. } => select_exact_time(&queues, inlet_name),
+            InletSyncPolicy::ApproximateTime { slop, .. } => select_approximate_time(&queues, inlet_name, *slop),
+            InletSyncPolicy::None => None,
+        };
+        Ok(selected.map(|indices| {
+            indices.into_iter().map(|(name, index)| {
+                let queue = queues.get_mut(&name).unwrap();
+                let sample = queue.drain(..=index).next_back().unwrap();
+                (name, sample.value)
+            }).collect()
+        }))
+    }
+
+    /// 前回の実行から新しい入力が届いていないinletの名前
+    pub(crate) fn stale_inlets(&self) -> JuizResult<Vec<String>> {
+        let queues = self.queues.lock().map_err(|e| anyhow!(JuizError::MutexLockFailedError{error: e.to_string()}))?;
+        Ok(self.inlet_names.iter().filter(|n| queues.get(*n).is_none_or(|q| q.is_empty())).cloned().collect())
+    }
+}
+
+/// 全てのinletに値があれば、それぞれ最新のものを選ぶ
+fn select_all_updated(queues: &HashMap<String, VecDeque<Sample>>) -> Option<Vec<(String, usize)>> {
+    queues.iter().map(|(name, q)| {
+        if q.is_empty() { None } else { Some((name.clone(), q.len() - 1)) }
+    }).collect()
+}
+
+/// 今届いた値とスタンプが一致する値が全てのinletにあればそれを選ぶ
+fn select_exact_time(queues: &HashMap<String, VecDeque<Sample>>, pivot_name: &str) -> Option<Vec<(String, usize)>> {
+    let pivot = queues.get(pivot_name)?.back()?.stamp.clone()?;
+    queues.iter().map(|(name, q)| {
+        q.iter().rposition(|s| s.stamp.as_ref() == Some(&pivot)).map(|i| (name.clone(), i))
+    }).collect()
+}
+
+/// 今届いた値を含み、時刻の幅がslop以内に収まる組を探す
+///
+/// 今届いた値の時刻tを含む幅slopの窓 [lower, lower+slop] を、lowerをtから下げながら試し、
+/// 全てのinletが窓の中に値を持つ最初の窓で、それぞれtに一番近い値を選ぶ。
+fn select_approximate_time(queues: &HashMap<String, VecDeque<Sample>>, pivot_name: &str, slop: Duration) -> Option<Vec<(String, usize)>> {
+    let slop = slop.as_secs_f64();
+    let pivot = stamp_as_secs(queues.get(pivot_name)?.back()?)?;
+    let mut lowers: Vec<f64> = queues.values().flat_map(|q| q.iter().filter_map(stamp_as_secs))
+        .filter(|t| *t <= pivot && pivot - *t <= slop)
+        .collect();
+    lowers.sort_by(|a, b| b.total_cmp(a));
+    lowers.into_iter().find_map(|lower| {
+        queues.iter().map(|(name, q)| {
+            q.iter().enumerate()
+                .filter_map(|(i, s)| stamp_as_secs(s).map(|t| (i, t)))
+                .filter(|(_, t)| *t >= lower && *t <= lower + slop)
+                .min_by(|(_, a), (_, b)| (a - pivot).abs().total_cmp(&(b - pivot).abs()))
+                .map(|(i, _)| (name.clone(), i))
+        }).collect::<Option<Vec<(String, usize)>>>()
+    })
+}
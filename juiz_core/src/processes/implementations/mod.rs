@@ -6,10 +6,13 @@ mod process_factory_wrapper;
 mod process_factory_impl;
 mod inlet;
 mod outlet;
+mod inlet_synchronizer;
+mod composite_process;
 
 pub use process_factory_wrapper::ProcessFactoryWrapper;
 pub use process_proxy::ProcessProxy;
 
 pub use process_impl::process_from_clousure_new_with_class_name;
 pub use process_impl::process_new;
-pub use process_factory_impl::ProcessFactoryImpl;
\ No newline at end of file
+pub use process_factory_impl::ProcessFactoryImpl;
+pub(crate) use composite_process::composite_process_new;
\ No newline at end of file
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::atomic::{AtomicU64, Ordering}};
 
 use crate::prelude::*;
 
@@ -9,6 +9,7 @@ pub struct Outlet {
     destination_connections: HashMap<String, Box<dyn DestinationConnection>>,
     output_memo: CapsulePtr,
     use_memo: bool,
+    sequence: AtomicU64,
 }
 
 
@@ -21,6 +22,7 @@ impl Outlet {
             destination_connections: HashMap::new(),
             output_memo: Capsule::empty().into(),
             use_memo: use_memo,
+            sequence: AtomicU64::new(0),
         }
     }
 
@@ -37,6 +39,10 @@ impl Outlet {
         self.destination_connections.insert(name, con);
     }
 
+    pub(crate) fn remove(&mut self, name: &str) -> Option<Box<dyn DestinationConnection>> {
+        self.destination_connections.remove(name)
+    }
+
     pub(crate) fn destination_connections(&self) -> JuizResult<Vec<&Box<dyn DestinationConnection>>> {
         let mut v: Vec<&Box<dyn DestinationConnection>> = Vec::new();
         for c in self.destination_connections.values() {
@@ -67,16 +73,22 @@ impl Outlet {
     }
     */
 
-    /// 出力バッファー (memo) にデータを書き込む
-    pub(crate) fn set_value(&self, capsule: CapsulePtr) -> CapsulePtr {
+    /// 出力に付ける通し番号を払い出す。0から始まる
+    fn next_sequence(&self) -> u64 {
+        self.sequence.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// 出力バッファー (memo) にデータを書き込み、出力ごとの通し番号を付ける
+    pub(crate) fn set_value(&self, capsule: CapsulePtr) -> JuizResult<CapsulePtr> {
         log::trace!("Outlet({})::set_value() called", self.name);
+        capsule.set_sequence(self.next_sequence())?;
         if self.use_memo {
             self.output_memo.replace(capsule);
         } else {
             log::trace!("Outlet({})::set_value() called but 'use_memo' property is set to false so value is spoiled.", self.name);
-            return capsule;
+            return Ok(capsule);
         }
-        self.memo()
+        Ok(self.memo())
     }
 
     /// 出力を出力接続 (DestinationConnection) に投げる
@@ -4,17 +4,19 @@
 
 
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use juiz_sdk::anyhow;
 
 use crate::connections::{ConnectionFactory, ConnectionFactoryImpl};
 use crate::prelude::*;
 
-use juiz_sdk::utils::check_manifest_before_call;
+use juiz_sdk::utils::{check_argument_before_apply, check_manifest_before_call};
 use juiz_sdk::connections::{ConnectionManifest, DestinationConnection, SourceConnection};
 
 //use crate::value::CapsuleMap;
 use super::inlet::Inlet;
+use super::inlet_synchronizer::InletSynchronizer;
 use super::outlet::Outlet;
 use crate::processes::{ProcessBodyFunctionTrait, ProcessBodyFunctionType};
 //use crate::manifests::ProcessManifest;
@@ -26,6 +28,7 @@ pub struct ProcessImpl {
     identifier: Identifier,
     outlet: Outlet,
     inlets: Vec<Inlet>,
+    synchronizer: Option<InletSynchronizer>,
     connection_factory: Box<dyn ConnectionFactory + 'static>,
 }
 
@@ -66,6 +69,7 @@ impl ProcessImpl {
             identifier: manifest.identifier()?, //identifier_from_manifest("core", "core", "Process", &manifest)?,
             outlet: Outlet::new(manifest.name.as_ref().unwrap().as_str(), manifest.use_memo),
             inlets: Self::create_inlets(&manifest),
+            synchronizer: InletSynchronizer::new(&manifest)?,
             manifest,
             connection_factory,
         })
@@ -117,6 +121,16 @@ impl ProcessImpl {
         }).collect::<Vec<(String, CapsulePtr)>>().into()
     }
 
+    /// 同期してそろった入力の組を使い、残りのinletからはデータ収集を行う。
+    fn collect_values_with(&self, mut values: HashMap<String, CapsulePtr>) -> CapsuleMap {
+        self.inlets.iter().map(|inlet| {
+            match values.remove(inlet.name()) {
+                Some(v) => (inlet.name().clone(), v),
+                None => (inlet.name().clone(), inlet.collect_value()),
+            }
+        }).collect::<Vec<(String, CapsulePtr)>>().into()
+    }
+
 }
 
 impl JuizObjectCoreHolder for ProcessImpl {
@@ -134,6 +148,7 @@ impl JuizObject for ProcessImpl {
             "inlets": self.inlets.iter().map(|inlet| { inlet.profile_full().unwrap() }).collect::<Vec<Value>>(),
             "outlet": self.outlet.profile_full()?,
             "arguments": self.manifest.arguments.iter().map(|v| { v.clone().into() }).collect::<Vec<Value>>(),
+            "stale_inlets": self.stale_inlets()?,
         }))?;
         Ok(v.into())
     }
@@ -148,7 +163,10 @@ impl Process for ProcessImpl {
     fn call(&self, args: CapsuleMap) -> JuizResult<CapsulePtr> {
         log::trace!("ProcessImpl({})::call(args=**) called", self.identifier());
         check_manifest_before_call(&(self.manifest), &args)?;
-        Ok( (self.function)(args)?.into() )
+        let output: CapsulePtr = (self.function)(args)?.into();
+        // 遅延を後から追えるように、作成時刻と作成元を出力に書いておく。通し番号は出力側 (Outlet) に渡ったときに付ける
+        output.stamp(&self.identifier)?;
+        Ok(output)
     }
 
     fn is_updated(&self) -> JuizResult<bool> {
@@ -173,7 +191,7 @@ impl Process for ProcessImpl {
     fn invoke<'b>(&'b self) -> JuizResult<CapsulePtr> {
         log::trace!("Processimpl({})::invoke() called", self.identifier());
         if self.outlet.memo().is_empty()? || self.is_updated()? {
-            return Ok(self.outlet.set_value(self.call(self.collect_values())?));
+            return self.outlet.set_value(self.call(self.collect_values())?);
         }
         return Ok(self.outlet.memo().clone());
     }
@@ -193,9 +211,22 @@ impl Process for ProcessImpl {
         self.outlet.push(self.invoke()?)
     }
 
+    /// 後段からpushされた時に呼ばれる。
+    /// 
+    /// 同期ポリシーの対象のinletへのpushでは、入力の組がそろった時だけcallする。
+    /// そろっていなければmemoを返す。
     fn push_by(&self, arg_name: &str, value: CapsulePtr) -> JuizResult<CapsulePtr> {
         log::trace!("ProcessImpl::push_by({}) called", self.identifier());
-        let v = self.outlet.set_value(self.call(self.collect_values_exclude(arg_name, value))?);
+        if let Some(synchronizer) = self.synchronizer.as_ref().filter(|s| s.contains(arg_name)) {
+            return match synchronizer.push(arg_name, value)? {
+                Some(values) => {
+                    let v = self.outlet.set_value(self.call(self.collect_values_with(values))?)?;
+                    self.outlet.push(v)
+                },
+                None => Ok(self.outlet.memo()),
+            };
+        }
+        let v = self.outlet.set_value(self.call(self.collect_values_exclude(arg_name, value))?)?;
         self.outlet.push(v)
     }
     
@@ -227,6 +258,12 @@ impl Process for ProcessImpl {
         Ok(connection_manifest)
     }
 
+    fn disconnect_to(&mut self, arg_name: &str) -> JuizResult<()> {
+        log::trace!("ProcessImpl(id={:?}).disconnect_to(arg_name={arg_name}) called", self.identifier());
+        self.outlet.remove(arg_name)
+            .ok_or_else(|| anyhow::Error::from(JuizError::ConnectionCanNotBeFoundError { identifier: arg_name.to_owned() }))
+            .and(Ok(()))
+    }
     
     fn source_connections(&self) -> JuizResult<Vec<&Box<dyn SourceConnection>>> {
         Ok(self.inlets.iter().map(|inlet| { inlet.source_connections() } ).flatten().collect::<Vec<&Box<dyn SourceConnection>>>())
@@ -238,8 +275,16 @@ impl Process for ProcessImpl {
     }
     
     fn p_apply(&mut self, arg_name: &str, value: CapsulePtr) -> JuizResult<CapsulePtr> {
+        check_argument_before_apply(&self.manifest, arg_name, &value)?;
         self.inlet_mut(arg_name)?.bind(value)
     }
+
+    fn stale_inlets(&self) -> JuizResult<Vec<String>> {
+        match self.synchronizer.as_ref() {
+            Some(synchronizer) => synchronizer.stale_inlets(),
+            None => Ok(Vec::new()),
+        }
+    }
     
     fn purge(&mut self) -> JuizResult<()> {
         log::trace!("ProcessImpl({})::purge() called", self.identifier());
@@ -72,7 +72,8 @@ impl Process for ProcessProxy {
     }
 
     fn execute(&self) -> JuizResult<CapsulePtr> {
-        todo!()
+        log::trace!("ProcessProxy({})::execute() called", self.identifier());
+        juiz_lock(&self.broker_proxy)?.any_process_execute(&self.identifier())
     }
 
     fn push_by(&self, arg_name: &str, value: CapsulePtr) -> JuizResult<CapsulePtr> {
@@ -105,6 +106,11 @@ impl Process for ProcessProxy {
     fn p_apply(&mut self, arg_name: &str, value: CapsulePtr) -> JuizResult<CapsulePtr> {
         juiz_lock(&self.broker_proxy)?.process_p_apply(&self.identifier(), arg_name, value)
     }
+
+    fn stale_inlets(&self) -> JuizResult<Vec<String>> {
+        let profile = self.profile_full()?;
+        Ok(obj_get_array(&profile, "stale_inlets")?.iter().filter_map(|v| v.as_str().map(|s| s.to_owned())).collect())
+    }
     
     fn purge(&mut self) -> JuizResult<()> {
 
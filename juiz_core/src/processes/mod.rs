@@ -11,6 +11,7 @@ pub(crate) use implementations::{
     //process_from_clousure,
     ProcessFactoryWrapper,
     ProcessFactoryImpl,
+    composite_process_new,
 };
 
 pub use implementations::{
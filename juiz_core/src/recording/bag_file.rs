@@ -0,0 +1,266 @@
+//! 記録ファイル (bagファイル) の読み書き
+//!
+//! ファイルは `MAGIC` とフォーマットのバージョン(u32)で始まり、以降は長さ(u32)を先頭につけたフレームが並ぶ。
+//! フレームの先頭1バイトが種類で、ヘッダ(JSON)、チャンネルの定義(JSON)、記録の3種類がある。
+//! 記録はチャンネル番号(u32)、記録時刻(UNIX時刻の秒, f64)、Capsuleの順に並ぶ。
+//! CapsuleはIPCブローカーと同じバイナリ表現で、画像も画素をそのまま含むのでファイルだけで再生できる。
+
+use std::{fs::File, io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom}, path::Path, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use juiz_sdk::anyhow::{self, anyhow};
+
+use crate::brokers::ipc::ipc_codec::{check_frame_size, decode_capsule, encode_capsule, read_payload, write_frame};
+use crate::prelude::*;
+
+pub(crate) const MAGIC: &[u8; 4] = b"JBAG";
+pub(crate) const FORMAT_VERSION: u32 = 1;
+
+const FRAME_HEADER: u8 = 0;
+const FRAME_CHANNEL: u8 = 1;
+const FRAME_RECORD: u8 = 2;
+
+fn invalid_file(message: &str) -> anyhow::Error {
+    anyhow!(JuizError::RecordFileError{ message: message.to_owned() })
+}
+
+/// 記録したデータの出どころ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BagChannelKind {
+    Topic,
+    Connection,
+}
+
+impl BagChannelKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BagChannelKind::Topic => "topic",
+            BagChannelKind::Connection => "connection",
+        }
+    }
+}
+
+impl TryFrom<&str> for BagChannelKind {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "topic" => Ok(BagChannelKind::Topic),
+            "connection" => Ok(BagChannelKind::Connection),
+            _ => Err(invalid_file(format!("unknown channel kind '{value}'.").as_str())),
+        }
+    }
+}
+
+/// 記録ファイルのチャンネル
+///
+/// nameはTopicならTopic名、接続なら接続のIdentifier。
+#[derive(Debug, Clone, PartialEq)]
+pub struct BagChannel {
+    pub id: u32,
+    pub name: String,
+    pub kind: BagChannelKind,
+}
+
+impl From<&BagChannel> for Value {
+    fn from(channel: &BagChannel) -> Self {
+        jvalue!({
+            "id": channel.id,
+            "name": channel.name,
+            "kind": channel.kind.as_str(),
+        })
+    }
+}
+
+impl TryFrom<&Value> for BagChannel {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> JuizResult<Self> {
+        Ok(BagChannel {
+            id: obj_get_i64(value, "id")? as u32,
+            name: obj_get_str(value, "name")?.to_owned(),
+            kind: obj_get_str(value, "kind")?.try_into()?,
+        })
+    }
+}
+
+/// 記録ファイルの1件の記録
+pub struct BagRecord {
+    pub channel_id: u32,
+    /// 記録した時刻 (UNIX時刻の秒)
+    pub stamp: f64,
+    pub capsule: CapsulePtr,
+}
+
+pub(crate) fn system_time_to_secs(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_secs_f64()
+}
+
+pub struct BagWriter {
+    writer: BufWriter<File>,
+    channels: Vec<BagChannel>,
+    num_records: u64,
+}
+
+impl BagWriter {
+
+    pub fn create(path: &Path) -> JuizResult<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        std::io::Write::write_all(&mut writer, MAGIC)?;
+        std::io::Write::write_all(&mut writer, &FORMAT_VERSION.to_be_bytes())?;
+        let header = jvalue!({
+            "format": "juiz_bag",
+            "version": FORMAT_VERSION,
+            "created": system_time_to_secs(SystemTime::now()),
+        });
+        let mut frame = vec![FRAME_HEADER];
+        frame.extend(juiz_sdk::serde_json::to_vec(&header)?);
+        write_frame(&mut writer, &frame)?;
+        Ok(Self { writer, channels: Vec::new(), num_records: 0 })
+    }
+
+    pub fn channels(&self) -> &[BagChannel] {
+        &self.channels
+    }
+
+    pub fn num_records(&self) -> u64 {
+        self.num_records
+    }
+
+    /// チャンネルを追加して番号を返す。同じチャンネルがあればその番号を返す
+    pub fn add_channel(&mut self, name: &str, kind: BagChannelKind) -> JuizResult<u32> {
+        if let Some(c) = self.channels.iter().find(|c| c.name == name && c.kind == kind) {
+            return Ok(c.id);
+        }
+        let channel = BagChannel { id: self.channels.len() as u32, name: name.to_owned(), kind };
+        let mut frame = vec![FRAME_CHANNEL];
+        frame.extend(juiz_sdk::serde_json::to_vec(&Value::from(&channel))?);
+        write_frame(&mut self.writer, &frame)?;
+        self.channels.push(channel);
+        Ok(self.channels.len() as u32 - 1)
+    }
+
+    pub fn write(&mut self, channel_id: u32, stamp: SystemTime, capsule: &CapsulePtr) -> JuizResult<()> {
+        if channel_id as usize >= self.channels.len() {
+            return Err(invalid_file(format!("channel {channel_id} is not defined.").as_str()));
+        }
+        let mut frame = vec![FRAME_RECORD];
+        frame.extend_from_slice(&channel_id.to_be_bytes());
+        frame.extend_from_slice(&system_time_to_secs(stamp).to_be_bytes());
+        frame.extend(encode_capsule(capsule)?);
+        write_frame(&mut self.writer, &frame)?;
+        self.num_records += 1;
+        Ok(())
+    }
+
+    /// バッファに残っている記録をファイルに書き出す
+    pub fn flush(&mut self) -> JuizResult<()> {
+        std::io::Write::flush(&mut self.writer)?;
+        Ok(())
+    }
+}
+
+pub struct BagReader {
+    reader: BufReader<File>,
+    header: Value,
+    channels: Vec<BagChannel>,
+}
+
+impl BagReader {
+
+    pub fn open(path: &Path) -> JuizResult<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic[0..4] != MAGIC {
+            return Err(invalid_file(format!("{path:?} is not a juiz record file.").as_str()));
+        }
+        let version = u32::from_be_bytes([magic[4], magic[5], magic[6], magic[7]]);
+        if version != FORMAT_VERSION {
+            return Err(invalid_file(format!("record file version {version} is not supported.").as_str()));
+        }
+        let frame = read_frame_or_eof(&mut reader)?.ok_or_else(|| invalid_file("record file does not have header."))?;
+        if frame.first() != Some(&FRAME_HEADER) {
+            return Err(invalid_file("record file does not start with header."));
+        }
+        let header = juiz_sdk::serde_json::from_slice::<Value>(&frame[1..])?;
+        Ok(Self { reader, header, channels: Vec::new() })
+    }
+
+    pub fn header(&self) -> &Value {
+        &self.header
+    }
+
+    /// これまでに読んだチャンネルの定義
+    pub fn channels(&self) -> &[BagChannel] {
+        &self.channels
+    }
+
+    pub fn channel(&self, id: u32) -> Option<&BagChannel> {
+        self.channels.iter().find(|c| c.id == id)
+    }
+
+    /// 次の記録を読む。ファイルの終わりではNoneを返す
+    pub fn next_record(&mut self) -> JuizResult<Option<BagRecord>> {
+        loop {
+            let frame = match read_frame_or_eof(&mut self.reader)? {
+                Some(f) => f,
+                None => return Ok(None),
+            };
+            match frame.first() {
+                Some(&FRAME_CHANNEL) => {
+                    let v = juiz_sdk::serde_json::from_slice::<Value>(&frame[1..])?;
+                    self.channels.push((&v).try_into()?);
+                },
+                Some(&FRAME_RECORD) => {
+                    if frame.len() < 13 {
+                        return Err(invalid_file("record frame is too short."));
+                    }
+                    let channel_id = u32::from_be_bytes(frame[1..5].try_into()?);
+                    let stamp = f64::from_be_bytes(frame[5..13].try_into()?);
+                    let capsule = decode_capsule(&frame[13..])?;
+                    return Ok(Some(BagRecord { channel_id, stamp, capsule }));
+                },
+                _ => return Err(invalid_file("unknown frame type.")),
+            }
+        }
+    }
+
+    /// ファイル全体のチャンネルの定義を読む。記録の中身は読み飛ばす
+    pub fn scan_channels(path: &Path) -> JuizResult<Vec<BagChannel>> {
+        let mut bag = BagReader::open(path)?;
+        loop {
+            let mut size = [0u8; 4];
+            match bag.reader.read_exact(&mut size) {
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                r => r?,
+            }
+            let size = check_frame_size(u32::from_be_bytes(size) as usize)?;
+            if size == 0 {
+                return Err(invalid_file("empty frame."));
+            }
+            let mut kind = [0u8; 1];
+            bag.reader.read_exact(&mut kind)?;
+            if kind[0] == FRAME_CHANNEL {
+                let body = read_payload(&mut bag.reader, size - 1)?;
+                let v = juiz_sdk::serde_json::from_slice::<Value>(&body)?;
+                bag.channels.push((&v).try_into()?);
+            } else {
+                bag.reader.seek(SeekFrom::Current(size as i64 - 1))?;
+            }
+        }
+        Ok(bag.channels)
+    }
+}
+
+fn read_frame_or_eof<R: Read>(reader: &mut R) -> JuizResult<Option<Vec<u8>>> {
+    let mut size = [0u8; 4];
+    match reader.read_exact(&mut size) {
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        r => r?,
+    }
+    let payload = read_payload(reader, check_frame_size(u32::from_be_bytes(size) as usize)?)?;
+    if payload.is_empty() {
+        return Err(invalid_file("empty frame."));
+    }
+    Ok(Some(payload))
+}
@@ -0,0 +1,9 @@
+//! TopicやConnectionを流れるデータの記録と再生
+
+mod bag_file;
+mod recorder;
+mod player;
+
+pub use bag_file::{BagChannel, BagChannelKind, BagReader, BagRecord, BagWriter};
+pub use recorder::Recorder;
+pub use player::{Player, PlaybackSpeed, PlaybackTarget};
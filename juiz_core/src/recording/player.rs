@@ -0,0 +1,270 @@
+//! 記録ファイルのデータをTopicやプロセスのinletに流し直す
+
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, thread::JoinHandle, time::{Duration, Instant}};
+
+use juiz_sdk::anyhow::{self, anyhow};
+
+use crate::{prelude::*, topics::TopicPtr};
+
+use super::bag_file::{BagChannel, BagReader, BagRecord};
+
+/// 停止要求を確かめる間隔
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 再生の速さ
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PlaybackSpeed {
+    /// 記録した時と同じ間隔で流す
+    #[default]
+    Original,
+    /// 記録した時の間隔をこの倍率で縮める (2.0なら2倍速)
+    Scaled(f64),
+    /// step()を呼ぶたびに1件ずつ流す
+    Stepped,
+}
+
+impl TryFrom<&Value> for PlaybackSpeed {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> JuizResult<Self> {
+        let err = || anyhow!(JuizError::InvalidSettingError{message: format!("Conversion failed Value({value:?}) -> PlaybackSpeed. Use a positive number, 'original' or 'step'.")});
+        match value {
+            Value::String(s) => match s.as_str() {
+                "original" => Ok(PlaybackSpeed::Original),
+                "step" => Ok(PlaybackSpeed::Stepped),
+                _ => Err(err()),
+            },
+            Value::Number(n) => match n.as_f64() {
+                Some(1.0) => Ok(PlaybackSpeed::Original),
+                Some(r) if r > 0.0 => Ok(PlaybackSpeed::Scaled(r)),
+                _ => Err(err()),
+            },
+            _ => Err(err()),
+        }
+    }
+}
+
+impl From<PlaybackSpeed> for Value {
+    fn from(speed: PlaybackSpeed) -> Self {
+        match speed {
+            PlaybackSpeed::Original => jvalue!("original"),
+            PlaybackSpeed::Scaled(r) => jvalue!(r),
+            PlaybackSpeed::Stepped => jvalue!("step"),
+        }
+    }
+}
+
+/// 再生したデータを流す先
+#[derive(Clone)]
+pub enum PlaybackTarget {
+    Topic(TopicPtr),
+    Inlet(ProcessPtr, String),
+}
+
+impl PlaybackTarget {
+    fn push(&self, capsule: CapsulePtr) -> JuizResult<()> {
+        match self {
+            PlaybackTarget::Topic(topic) => topic.push(capsule, None),
+            PlaybackTarget::Inlet(process, arg_name) => {
+                process.lock()?.push_by(arg_name.as_str(), capsule)?;
+                Ok(())
+            },
+        }
+    }
+}
+
+pub struct Player {
+    name: String,
+    path: PathBuf,
+    speed: PlaybackSpeed,
+    looping: bool,
+    reader: BagReader,
+    targets: HashMap<String, PlaybackTarget>,
+    pending: Option<BagRecord>,
+    first_stamp: Option<f64>,
+    started: Option<Instant>,
+    num_played: u64,
+    stop_flag: Arc<AtomicBool>,
+}
+
+impl Player {
+
+    pub fn open(name: &str, path: &Path, speed: PlaybackSpeed) -> JuizResult<Self> {
+        log::trace!("Player::open(name={name}, path={path:?}, speed={speed:?}) called");
+        Ok(Self {
+            name: name.to_owned(),
+            path: path.to_path_buf(),
+            speed,
+            looping: false,
+            reader: BagReader::open(path)?,
+            targets: HashMap::new(),
+            pending: None,
+            first_stamp: None,
+            started: None,
+            num_played: 0,
+            stop_flag: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn speed(&self) -> PlaybackSpeed {
+        self.speed
+    }
+
+    pub fn set_looping(&mut self, looping: bool) -> &mut Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn num_played(&self) -> u64 {
+        self.num_played
+    }
+
+    /// 記録ファイルに含まれるチャンネル
+    pub fn channels(&self) -> JuizResult<Vec<BagChannel>> {
+        BagReader::scan_channels(&self.path)
+    }
+
+    /// チャンネル (TopicならTopic名、接続なら接続のIdentifier) のデータを流す先を決める
+    pub fn set_target(&mut self, channel_name: &str, target: PlaybackTarget) -> &mut Self {
+        self.targets.insert(channel_name.to_owned(), target);
+        self
+    }
+
+    /// 先頭から再生し直す
+    pub fn rewind(&mut self) -> JuizResult<()> {
+        self.reader = BagReader::open(&self.path)?;
+        self.pending = None;
+        self.first_stamp = None;
+        self.started = None;
+        Ok(())
+    }
+
+    /// 次の記録を読んでおき、それを流すまでの待ち時間を返す。最後まで再生したらNone
+    fn prefetch(&mut self) -> JuizResult<Option<Duration>> {
+        if self.pending.is_none() {
+            self.pending = match self.reader.next_record()? {
+                Some(r) => Some(r),
+                None if self.looping && self.num_played > 0 => {
+                    self.rewind()?;
+                    self.reader.next_record()?
+                },
+                None => None,
+            };
+        }
+        let stamp = match &self.pending {
+            Some(r) => r.stamp,
+            None => return Ok(None),
+        };
+        let first_stamp = *self.first_stamp.get_or_insert(stamp);
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let offset = (stamp - first_stamp).max(0.0);
+        let due = match self.speed {
+            PlaybackSpeed::Original => offset,
+            PlaybackSpeed::Scaled(r) => offset / r,
+            PlaybackSpeed::Stepped => return Ok(Some(Duration::ZERO)),
+        };
+        Ok(Some(Duration::from_secs_f64(due).saturating_sub(started.elapsed())))
+    }
+
+    fn push_pending(&mut self) -> JuizResult<()> {
+        let record = match self.pending.take() {
+            Some(r) => r,
+            None => return Ok(()),
+        };
+        let channel_name = self.reader.channel(record.channel_id).map(|c| c.name.clone())
+            .ok_or_else(|| anyhow!(JuizError::RecordFileError{message: format!("channel {} is not defined.", record.channel_id)}))?;
+        match self.targets.get(&channel_name) {
+            Some(target) => target.push(record.capsule)?,
+            None => log::trace!("Player({})::push_pending() skipped channel({channel_name}) because it has no target.", self.name),
+        }
+        self.num_played += 1;
+        Ok(())
+    }
+
+    /// 待たずに1件だけ流す。最後まで再生していたらfalseを返す
+    pub fn step(&mut self) -> JuizResult<bool> {
+        if self.prefetch()?.is_none() {
+            return Ok(false);
+        }
+        self.push_pending()?;
+        Ok(true)
+    }
+
+    /// 最後まで (ループする時はstop()まで) 再生する。流した件数を返す
+    pub fn play(&mut self) -> JuizResult<u64> {
+        if self.speed == PlaybackSpeed::Stepped {
+            return Err(anyhow!(JuizError::InvalidSettingError{message: format!("Player({}) is in step mode. Use step() instead.", self.name)}));
+        }
+        self.stop_flag.store(false, Ordering::SeqCst);
+        while let Some(wait) = self.prefetch()? {
+            if !sleep_unless_stopped(&self.stop_flag, wait) {
+                break;
+            }
+            self.push_pending()?;
+        }
+        Ok(self.num_played)
+    }
+
+    /// 別スレッドで再生を始める。再生中もプレイヤーの状態を見られるように、待つ間はロックを外す
+    pub fn start(player: &Arc<Mutex<Player>>) -> JuizResult<JoinHandle<JuizResult<u64>>> {
+        let (name, stop_flag) = {
+            let p = juiz_lock(player)?;
+            if p.speed == PlaybackSpeed::Stepped {
+                return Err(anyhow!(JuizError::InvalidSettingError{message: format!("Player({}) is in step mode. Use step() instead.", p.name)}));
+            }
+            p.stop_flag.store(false, Ordering::SeqCst);
+            (p.name.clone(), p.stop_flag.clone())
+        };
+        let player = player.clone();
+        Ok(std::thread::Builder::new().name(format!("juiz_player_{name}")).spawn(move || {
+            loop {
+                let wait = match juiz_lock(&player)?.prefetch()? {
+                    Some(w) => w,
+                    None => break,
+                };
+                if !sleep_unless_stopped(&stop_flag, wait) {
+                    break;
+                }
+                juiz_lock(&player)?.push_pending()?;
+            }
+            let num_played = juiz_lock(&player)?.num_played;
+            log::info!("Player({name}) finished. {num_played} records played.");
+            Ok(num_played)
+        })?)
+    }
+
+    /// start()やplay()の再生を止める
+    pub fn stop(&self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn profile_full(&self) -> JuizResult<Value> {
+        Ok(jvalue!({
+            "name": self.name,
+            "file": self.path.to_string_lossy(),
+            "speed": Value::from(self.speed),
+            "loop": self.looping,
+            "targets": self.targets.keys().collect::<Vec<&String>>(),
+            "num_played": self.num_played,
+        }))
+    }
+}
+
+/// 停止要求が来たらfalseを返す
+fn sleep_unless_stopped(stop_flag: &AtomicBool, wait: Duration) -> bool {
+    let deadline = Instant::now() + wait;
+    loop {
+        if stop_flag.load(Ordering::SeqCst) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        std::thread::sleep((deadline - now).min(STOP_CHECK_INTERVAL));
+    }
+}
@@ -0,0 +1,117 @@
+//! TopicやConnectionを流れるデータを記録ファイルに書き出す
+//!
+//! 記録したいTopicや接続の送り手のプロセスに、記録用のプロセスをPush型で接続する。
+//! 送り手がpushしたCapsuleはそのtimestampのオプションの時刻と一緒にファイルに書かれる。
+//! timestampの無いCapsuleは受け取った時刻で書かれる。
+
+use std::{path::{Path, PathBuf}, sync::{Arc, Mutex}, time::SystemTime};
+
+use crate::{connections::ConnectionFactoryImpl, prelude::*, processes::process_from_clousure_new_with_class_name, topics::TopicPtr};
+
+use super::bag_file::{BagChannelKind, BagWriter};
+
+struct RecorderTap {
+    source: ProcessPtr,
+    arg_name: String,
+}
+
+pub struct Recorder {
+    name: String,
+    path: PathBuf,
+    writer: Arc<Mutex<BagWriter>>,
+    taps: Vec<RecorderTap>,
+}
+
+impl Recorder {
+
+    pub fn create(name: &str, path: &Path) -> JuizResult<Self> {
+        log::trace!("Recorder::create(name={name}, path={path:?}) called");
+        Ok(Self {
+            name: name.to_owned(),
+            path: path.to_path_buf(),
+            writer: Arc::new(Mutex::new(BagWriter::create(path)?)),
+            taps: Vec::new(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    pub fn num_records(&self) -> JuizResult<u64> {
+        Ok(juiz_lock(&self.writer)?.num_records())
+    }
+
+    pub fn record_topic(&mut self, topic: &TopicPtr) -> JuizResult<u32> {
+        let channel_id = juiz_lock(&self.writer)?.add_channel(topic.name(), BagChannelKind::Topic)?;
+        self.tap(topic.process_ptr(), channel_id)?;
+        Ok(channel_id)
+    }
+
+    /// 接続を流れるデータを記録する。sourceは接続の送り手のプロセス
+    pub fn record_connection(&mut self, connection_id: &Identifier, source: ProcessPtr) -> JuizResult<u32> {
+        let channel_id = juiz_lock(&self.writer)?.add_channel(connection_id.as_str(), BagChannelKind::Connection)?;
+        self.tap(source, channel_id)?;
+        Ok(channel_id)
+    }
+
+    /// 送り手の出力を受け取ってファイルに書くプロセスを作り、送り手から接続する
+    fn tap(&mut self, source: ProcessPtr, channel_id: u32) -> JuizResult<()> {
+        // 送り手の出力接続は引数名で区別されるので、レコーダーとチャンネルごとに別の名前にする
+        let arg_name = format!("{}_{}", self.name, channel_id);
+        let manifest: ProcessManifest = jvalue!({
+            "type_name": "recorder",
+            "name": arg_name,
+            "use_memo": false,
+            "arguments": [
+                {"name": arg_name, "type": "object", "default": {}}
+            ]
+        }).try_into()?;
+        let writer = self.writer.clone();
+        let input_name = arg_name.clone();
+        let func = move |args: CapsuleMap| -> JuizResult<Capsule> {
+            let value = args.get(input_name.as_str())?;
+            let stamp = value.timestamp()?.unwrap_or_else(SystemTime::now);
+            juiz_lock(&writer)?.write(channel_id, stamp, &value)?;
+            Ok(Capsule::empty())
+        };
+        let sink = ProcessPtr::new(process_from_clousure_new_with_class_name(JuizObjectClass::Process("Recorder"), manifest, func, Box::new(ConnectionFactoryImpl::new()))?);
+        let source_id = source.identifier().clone();
+        let sink_id = sink.identifier().clone();
+        connect(source.clone(), sink, ConnectionManifest::new(ConnectionType::Push, source_id, arg_name.clone(), sink_id, None))?;
+        self.taps.push(RecorderTap { source, arg_name });
+        Ok(())
+    }
+
+    /// 記録をやめて送り手から切り離し、記録をファイルに書き出す
+    pub fn stop(&mut self) -> JuizResult<()> {
+        log::trace!("Recorder({})::stop() called", self.name);
+        for tap in self.taps.drain(..) {
+            tap.source.lock_mut()?.disconnect_to(tap.arg_name.as_str())?;
+        }
+        juiz_lock(&self.writer)?.flush()
+    }
+
+    pub fn profile_full(&self) -> JuizResult<Value> {
+        let writer = juiz_lock(&self.writer)?;
+        Ok(jvalue!({
+            "name": self.name,
+            "file": self.path.to_string_lossy(),
+            "channels": writer.channels().iter().map(Value::from).collect::<Vec<Value>>(),
+            "num_records": writer.num_records(),
+            "recording": !self.taps.is_empty(),
+        }))
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            log::error!("Recorder({})::drop() failed to stop. Error({e})", self.name);
+        }
+    }
+}
@@ -3,6 +3,11 @@ use std::sync::{Arc, RwLock};
 use uuid::Uuid;
 use juiz_sdk::anyhow::anyhow;
 use crate::{connections::ConnectionFactoryImpl, core::SubSystemProxy, prelude::*, processes::process_from_clousure_new_with_class_name};
+
+mod topic_encoding;
+use topic_encoding::{decode_from_wire, encode_for_wire};
+pub(crate) use topic_encoding::{image_to_raw, raw_to_image};
+
 pub type TopicName = String;
 
 #[derive(Clone)]
@@ -10,13 +15,15 @@ pub type TopicName = String;
 pub struct Topic {
     name: TopicName,
     subsystem_proxies: Vec<SubSystemProxy>,
+    /// 他のシステムへ画像を転送するときの符号化
+    encoding: TopicEncoding,
 }
 
 #[allow(unused)]
 impl Topic {
 
     pub fn new(name: &str) -> Self {
-        Self{name: name.to_owned(), subsystem_proxies: Vec::new()}
+        Self{name: name.to_owned(), subsystem_proxies: Vec::new(), encoding: TopicEncoding::default()}
     }
 
     pub fn name(&self) -> &str {
@@ -50,12 +57,14 @@ pub struct TopicPtr {
 fn capsule_ptr_to_capsule(v: &CapsulePtr) -> JuizResult<Capsule> {
     if v.is_value()? {
         v.lock_as_value(|v| -> Capsule { Capsule::from(v.clone()) } )
+    } else if v.is_image()? {
+        // 共有メモリの画像も、出版したプロセスが手放したあとに読めるように複製する
+        v.lock_as_image(|image| -> Capsule { Capsule::from(image.clone()) })
+    } else if v.is_bytes()? || v.is_numeric_array()? {
+        v.clone_capsule()
     } else {
         Err(anyhow!(JuizError::ArgumentError { message: "CapsulePtr is not available for Topic".to_owned() }))
     }
-    /* else if v.is_image()? {
-        v.lock_as_mat(|m| -> Capsule { Capsule::from(m.clone()) })
-    }; */
 }
 
 impl TopicPtr {
@@ -89,9 +98,20 @@ impl TopicPtr {
             match my_topic.read() {
                 Ok(t) => {
                     log::trace!(" - my_topic.read() OK.");
+                    let mut wire: Option<CapsulePtr> = None;
                     for subsystem in t.subsystem_proxies.iter() {
                         log::trace!("- broker_proxy: subsystem={:?}", subsystem.uuid());
-                        match juiz_lock(&subsystem.broker_proxy())?.topic_push(my_topic_name.as_str(), v.clone(), Some(my_uuid)) {
+                        if !subsystem.is_alive()? {
+                            // ハートビートが戻るまで転送を止める
+                            log::trace!("- subsystem({}) is not alive. skipped.", subsystem.uuid());
+                            continue;
+                        }
+                        // 画像は転送先があるときに一度だけ符号化する
+                        let w = match wire.as_ref() {
+                            Some(w) => w.clone(),
+                            None => wire.insert(encode_for_wire(&v, t.encoding)?).clone(),
+                        };
+                        match juiz_lock(&subsystem.broker_proxy())?.topic_push(my_topic_name.as_str(), w, Some(my_uuid)) {
                             Ok(_) => {
                                 log::trace!("SubsystemProxy.topic_push() success");
                                 Ok(())
@@ -134,13 +154,34 @@ impl TopicPtr {
         self.ptr.lock()?.profile_full()
     }
 
+    /// 他のシステムから転送されてきた値を流す。符号化された画像は画像に戻す
     pub fn push(&self, capsule: CapsulePtr, pushed_system_uuid: Option<Uuid>) -> JuizResult<()> {
         log::trace!("push(uuid={pushed_system_uuid:?}) called");
+        let capsule = decode_from_wire(capsule)?;
         let r = self.ptr.lock()?.push_by("input", capsule).and_then(|_|{Ok(())});
         log::trace!("push(uuid={pushed_system_uuid:?}) exit");
         r
     }
 
+    pub fn encoding(&self) -> JuizResult<TopicEncoding> {
+        match self.topic.read() {
+            Ok(t) => Ok(t.encoding),
+            Err(_e) => Err(anyhow!(JuizError::ObjectLockError { target: "Topic".to_owned() })),
+        }
+    }
+
+    /// 他のシステムへ画像を転送するときの符号化を決める
+    pub fn set_encoding(&self, encoding: TopicEncoding) -> JuizResult<()> {
+        log::trace!("set_encoding(name={}, encoding={encoding}) called", self.name());
+        match self.topic.write() {
+            Ok(mut t) => {
+                t.encoding = encoding;
+                Ok(())
+            },
+            Err(_e) => Err(anyhow!(JuizError::ObjectLockError { target: "Topic".to_owned() })),
+        }
+    }
+
     pub fn num_local_publishers(&self) -> JuizResult<usize> {
         Ok(self.ptr.lock()?.source_connections()?.len())
     }
@@ -153,6 +194,9 @@ impl TopicPtr {
         log::trace!("register_subscriber_subsystem(name={}, subsystem_proxy={}) called", self.name(), subsystem_proxy.uuid());
         match self.topic.write() {
             Ok(mut t) => {
+                if t.subsystem_proxies.iter().any(|s| s.uuid() == subsystem_proxy.uuid()) { // つなぎ直しのときはもう登録している
+                    return Ok(())
+                }
                 t.subsystem_proxies.push(subsystem_proxy);
                 Ok(())
             },  
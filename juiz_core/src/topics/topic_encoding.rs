@@ -0,0 +1,100 @@
+//! トピックが他のシステムへ画像を転送するときの符号化
+//!
+//! 画像はどのブローカーでも運べるバイト列 (PNG, JPEG) か数値の配列 (raw) にしてから`topic_push`で送る。
+//! どの符号化で送ったかはCapsuleのオプション`TOPIC_ENCODING_KEY`に載せ、受け取ったトピックが画像に戻す。
+
+use std::io::Cursor;
+
+use juiz_sdk::anyhow::anyhow;
+use juiz_sdk::image::{self, codecs::jpeg::JpegEncoder, ColorType, DynamicImage, ImageBuffer, ImageFormat};
+
+use crate::prelude::*;
+
+fn encoding_error(message: String) -> juiz_sdk::anyhow::Error {
+    anyhow!(JuizError::ValueTypeError{message})
+}
+
+fn image_to_png(image: &DynamicImage) -> JuizResult<Vec<u8>> {
+    let mut buf = Cursor::new(Vec::new());
+    image.write_to(&mut buf, ImageFormat::Png)?;
+    Ok(buf.into_inner())
+}
+
+fn image_to_jpeg(image: &DynamicImage, quality: u8) -> JuizResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut encoder = JpegEncoder::new_with_quality(&mut buf, quality);
+    // JPEGはアルファを持てないので、グレースケール以外はRGBにする
+    match image {
+        DynamicImage::ImageLuma8(gray) => encoder.encode(gray.as_raw(), image.width(), image.height(), ColorType::L8)?,
+        _ => encoder.encode(image.to_rgb8().as_raw(), image.width(), image.height(), ColorType::Rgb8)?,
+    };
+    drop(encoder);
+    Ok(buf)
+}
+
+/// [高さ, 幅, チャンネル]のu8の数値の配列にする。8bit以外の画像はRGBA8に変換する
+pub(crate) fn image_to_raw(image: &DynamicImage) -> JuizResult<NumericArray> {
+    let (channels, bytes) = match image {
+        DynamicImage::ImageLuma8(_) => (1, image.as_bytes().to_vec()),
+        DynamicImage::ImageLumaA8(_) => (2, image.as_bytes().to_vec()),
+        DynamicImage::ImageRgb8(_) => (3, image.as_bytes().to_vec()),
+        DynamicImage::ImageRgba8(_) => (4, image.as_bytes().to_vec()),
+        _ => (4, image.to_rgba8().into_raw()),
+    };
+    NumericArray::new(vec![image.height() as usize, image.width() as usize, channels], bytes)
+}
+
+pub(crate) fn raw_to_image(array: &NumericArray) -> JuizResult<DynamicImage> {
+    let (height, width, channels) = match array.shape() {
+        [h, w, c] => (*h as u32, *w as u32, *c),
+        shape => return Err(encoding_error(format!("raw topic image must have shape [height, width, channels], but {shape:?}."))),
+    };
+    let bytes = array.as_u8().ok_or_else(|| encoding_error("raw topic image must be uint8 array.".to_owned()))?.to_vec();
+    let image = match channels {
+        1 => ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageLuma8),
+        2 => ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageLumaA8),
+        3 => ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageRgb8),
+        4 => ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageRgba8),
+        _ => None,
+    };
+    image.ok_or_else(|| encoding_error(format!("raw topic image ({width}x{height}x{channels}) can not be converted to image.")))
+}
+
+fn with_options(mut capsule: CapsulePtr, options: impl Iterator<Item = (String, String)>) -> JuizResult<CapsulePtr> {
+    for (k, v) in options {
+        capsule.set_option(k.as_str(), v.as_str())?;
+    }
+    Ok(capsule)
+}
+
+/// 送る前に画像を符号化する。画像でなければそのまま返す。オプション (時刻など) は引き継ぐ
+pub(crate) fn encode_for_wire(capsule: &CapsulePtr, encoding: TopicEncoding) -> JuizResult<CapsulePtr> {
+    if !capsule.is_image()? {
+        return Ok(capsule.clone());
+    }
+    let encoded: CapsulePtr = capsule.lock_as_image(|image| -> JuizResult<CapsulePtr> {
+        Ok(match encoding {
+            TopicEncoding::Png => image_to_png(image)?.into(),
+            TopicEncoding::Jpeg{quality} => image_to_jpeg(image, quality)?.into(),
+            TopicEncoding::Raw => image_to_raw(image)?.into(),
+        })
+    })??;
+    let mut encoded = with_options(encoded, capsule.get_options()?.into_iter())?;
+    encoded.set_option(TOPIC_ENCODING_KEY, encoding.as_str())?;
+    Ok(encoded)
+}
+
+/// 受け取ったCapsuleが符号化された画像なら画像に戻す
+pub(crate) fn decode_from_wire(capsule: CapsulePtr) -> JuizResult<CapsulePtr> {
+    let options = capsule.get_options()?;
+    let encoding = match options.get(TOPIC_ENCODING_KEY) {
+        None => return Ok(capsule),
+        Some(encoding) => encoding.clone(),
+    };
+    let image = match encoding.as_str() {
+        "png" | "jpeg" => capsule.lock_as_bytes(|bytes| image::load_from_memory(bytes))??,
+        "raw" => capsule.lock_as_numeric_array(raw_to_image)??,
+        _ => return Err(encoding_error(format!("topic encoding '{encoding}' is not supported."))),
+    };
+    with_options(image.into(), options.into_iter().filter(|(k, _)| k != TOPIC_ENCODING_KEY))
+}
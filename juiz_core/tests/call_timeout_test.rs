@@ -0,0 +1,92 @@
+extern crate juiz_core;
+use std::time::{Duration, Instant};
+
+use juiz_core::prelude::*;
+
+mod common;
+
+const NAMESPACE: &str = "juiz_call_timeout_test.sock";
+
+fn slow_function(args: CapsuleMap) -> JuizResult<Capsule> {
+    let msec = args.get("msec")?.lock_as_value(|v| v.as_i64().unwrap_or(0))?;
+    std::thread::sleep(Duration::from_millis(msec as u64));
+    Ok(jvalue!(msec).into())
+}
+
+fn setup_system() -> JuizResult<(System, Identifier)> {
+    let system = common::setup_system(None)?;
+    let manifest = jvalue!({
+        "type_name": "slow",
+        "arguments": [{"name": "msec", "type": "int", "description": "sleep time", "default": 300}],
+    });
+    let id = common::register_process(&system, manifest, slow_function, "slow0")?;
+    Ok((system, id))
+}
+
+fn args(msec: i64, timeout: Option<Duration>) -> CapsuleMap {
+    let mut args = CapsuleMap::new();
+    args.insert("msec".to_owned(), jvalue!(msec).into());
+    if let Some(timeout) = timeout {
+        args.set_timeout(timeout);
+    }
+    args
+}
+
+fn is_timeout_error(e: &juiz_core::anyhow::Error) -> bool {
+    matches!(e.downcast_ref::<JuizError>(), Some(JuizError::RemoteCallTimeoutError{..}))
+}
+
+fn is_still_running_error(e: &juiz_core::anyhow::Error) -> bool {
+    matches!(e.downcast_ref::<JuizError>(), Some(JuizError::RemoteCallStillRunningError{..}))
+}
+
+#[test]
+fn call_timeout_local_test() -> JuizResult<()> {
+    let (system, id) = setup_system()?;
+
+    let started = Instant::now();
+    let err = system.core_broker().lock()?.process_call(&id, args(300, Some(Duration::from_millis(50)))).unwrap_err();
+    assert!(is_timeout_error(&err), "unexpected error {err:?}");
+    assert!(started.elapsed() < Duration::from_millis(250));
+
+    // 打ち切った呼び出しがプロセスのロックを持っている間は、待たずに断る
+    let started = Instant::now();
+    let err = system.core_broker().lock()?.process_execute_with_timeout(&id, Some(Duration::from_millis(50))).unwrap_err();
+    assert!(is_still_running_error(&err), "unexpected error {err:?}");
+    assert!(started.elapsed() < Duration::from_millis(50));
+    let err = system.core_broker().lock()?.process_call(&id, args(10, None)).unwrap_err();
+    assert!(is_still_running_error(&err), "unexpected error {err:?}");
+
+    std::thread::sleep(Duration::from_millis(350));
+    let err = system.core_broker().lock()?.process_execute_with_timeout(&id, Some(Duration::from_millis(50))).unwrap_err();
+    assert!(is_timeout_error(&err), "unexpected error {err:?}");
+    std::thread::sleep(Duration::from_millis(350));
+
+    // 間に合えば普通に値が返る
+    let output = system.core_broker().lock()?.process_call(&id, args(10, Some(Duration::from_secs(2))))?;
+    assert_eq!(output.extract_value()?, jvalue!(10));
+    Ok(())
+}
+
+#[test]
+fn call_timeout_ipc_test() -> JuizResult<()> {
+    let (mut system, id) = setup_system()?;
+
+    common::remove_socket_file(NAMESPACE);
+    let broker = system.create_broker(&jvalue!({"type_name": "ipc", "name": NAMESPACE, "namespace": NAMESPACE}))?;
+    broker.lock_mut()?.start()?;
+    broker.lock_mut()?.wait_until_started(Duration::from_secs(3))?;
+
+    // マニフェストのtimeoutは呼び出しでの指定が無いときに使う
+    let proxy = system.create_broker_proxy(&jvalue!({"type_name": "ipc", "name": NAMESPACE, "timeout": 0.05}))?;
+    let started = Instant::now();
+    let err = juiz_lock(&proxy)?.process_call(&id, args(300, None)).unwrap_err();
+    assert!(is_timeout_error(&err), "unexpected error {err:?}");
+    assert!(started.elapsed() < Duration::from_millis(250));
+
+    // 時間切れのあとはつなぎ直して呼べる。打ち切った呼び出しが終わるのを待つ
+    std::thread::sleep(Duration::from_millis(350));
+    let output = juiz_lock(&proxy)?.process_call(&id, args(10, Some(Duration::from_secs(2))))?;
+    assert_eq!(output.extract_value()?, jvalue!(10));
+    Ok(())
+}
@@ -1,5 +1,7 @@
 
 
+use std::time::Duration;
+
 use juiz_core::prelude::*;
 
 
@@ -125,3 +127,56 @@ pub fn new_execution_process<'a> (name: &str) -> JuizResult<impl Process> {
     assert!(p.is_ok(), "ProcessImpl::new() failed. Error is {:?}", p.err());
     p
 }
+
+
+/// 前回のテストが残したソケットファイルがあるとIPCのブローカーが起動できないので消しておく
+#[allow(dead_code)]
+pub fn remove_socket_file(namespace: &str) {
+    let _ = std::fs::remove_file(format!("/tmp/{namespace}"));
+}
+
+/// テスト用のシステムを作る。broker_manifestがあればそのブローカーを起動して待つ
+#[allow(dead_code)]
+pub fn setup_system(broker_manifest: Option<Value>) -> JuizResult<System> {
+    let mut system = System::new(jvalue!({"name": "juiz_core_test"}))?.start_http_broker(false).setup()?;
+    if let Some(broker_manifest) = broker_manifest {
+        if broker_manifest["type_name"] == "ipc" {
+            remove_socket_file(obj_get_str(&broker_manifest, "namespace")?);
+        }
+        let broker = system.create_broker(&broker_manifest)?;
+        broker.lock_mut()?.start()?;
+        broker.lock_mut()?.wait_until_started(Duration::from_secs(3))?;
+    }
+    Ok(system)
+}
+
+/// ProcessFactoryを登録して、nameのプロセスを作る
+#[allow(dead_code)]
+pub fn register_process(system: &System, manifest: Value, function: fn(CapsuleMap) -> JuizResult<Capsule>, name: &str) -> JuizResult<Identifier> {
+    let type_name = obj_get_str(&manifest, "type_name")?.to_owned();
+    let pf = process_factory_create(manifest.try_into()?, function)?;
+    system.core_broker().lock_mut().and_then(|mut cb| {
+        cb.worker_mut().store_mut().processes.register_factory(&type_name, pf)?;
+        let p = cb.worker_mut().create_process_ref(jvalue!({"name": name, "type_name": type_name}).try_into()?)?;
+        Ok(p.identifier().clone())
+    })
+}
+
+/// broker_manifestのブローカーとincrement0のプロセスを持つシステムを作る
+#[allow(dead_code)]
+pub fn setup_increment_system(broker_manifest: Value) -> JuizResult<(System, Identifier)> {
+    let system = setup_system(Some(broker_manifest))?;
+    let manifest = jvalue!({
+        "type_name" : "increment",
+        "arguments" : [
+            {
+                "name": "arg1",
+                "type": "int",
+                "description": "test_argument",
+                "default": 1,
+            },
+        ],
+    });
+    let id = register_process(&system, manifest, increment_function, "increment0")?;
+    Ok((system, id))
+}
@@ -0,0 +1,119 @@
+extern crate juiz_core;
+use juiz_core::prelude::*;
+
+mod common;
+
+fn setup_system() -> JuizResult<System> {
+    let system = common::setup_system(None)?;
+    let increment_manifest = jvalue!({
+        "type_name": "increment",
+        "arguments": [{"name": "arg1", "type": "int", "description": "", "default": 1}],
+    });
+    let add_manifest = jvalue!({
+        "type_name": "add",
+        "arguments": [
+            {"name": "arg1", "type": "int", "description": "", "default": 1},
+            {"name": "arg2", "type": "int", "description": "", "default": 1},
+        ],
+    });
+    system.core_broker().lock_mut().and_then(|mut cb| {
+        let store = cb.worker_mut().store_mut();
+        store.processes.register_factory(&"increment".to_owned(), process_factory_create(increment_manifest.try_into()?, common::increment_function)?)?;
+        store.processes.register_factory(&"add".to_owned(), process_factory_create(add_manifest.try_into()?, common::add_function)?)?;
+        // (a + b) + 1 を計算する合成プロセス
+        cb.worker_mut().register_composite_process(jvalue!({
+            "type_name": "add_and_increment",
+            "processes": [
+                {"type_name": "add", "name": "add0"},
+                {"type_name": "increment", "name": "inc0"},
+            ],
+            "connections": [
+                {"source": "add0", "destination": "inc0", "arg_name": "arg1"},
+            ],
+            "inlets": [
+                {"name": "a", "process": "add0", "arg_name": "arg1"},
+                {"name": "b", "process": "add0", "arg_name": "arg2"},
+            ],
+            "outlet": "inc0",
+        }).try_into()?)
+    })?;
+    Ok(system)
+}
+
+fn create_process(system: &System, type_name: &str, name: &str) -> JuizResult<ProcessPtr> {
+    system.core_broker().lock_mut()?.process_create(jvalue!({"type_name": type_name, "name": name}).try_into()?)?;
+    system.core_broker().lock()?.worker().process_from_typename_and_name(type_name, name)
+}
+
+#[test]
+fn composite_process_call_test() -> JuizResult<()> {
+    let system = setup_system()?;
+    let p = create_process(&system, "add_and_increment", "ai0")?;
+
+    let names = p.lock()?.manifest().arguments.iter().map(|a| a.name.clone()).collect::<Vec<String>>();
+    assert_eq!(names, vec!["a", "b"]);
+    let output = p.lock()?.call(vec![("a", jvalue!(2)), ("b", jvalue!(3))].into())?;
+    assert_eq!(output.lock_as_value(|v| v.as_i64())?, Some(6));
+    assert_eq!(output.producer()?, Some(p.identifier().clone()));
+
+    // 内部プロセスも通常のプロセスとして登録されている
+    let inner = system.core_broker().lock()?.worker().process_from_typename_and_name("increment", "ai0.inc0")?;
+    assert_eq!(inner.lock()?.call(vec![("arg1", jvalue!(10))].into())?.lock_as_value(|v| v.as_i64())?, Some(11));
+    Ok(())
+}
+
+#[test]
+fn composite_process_execute_and_connect_test() -> JuizResult<()> {
+    let system = setup_system()?;
+    let p = create_process(&system, "add_and_increment", "ai1")?;
+    let next = ProcessPtr::new(common::new_increment_process_use_memo("next0")?);
+    connect(p.clone(), next.clone(), ConnectionManifest::new(ConnectionType::Push, p.identifier().clone(), "arg1".to_owned(), next.identifier().clone(), None))?;
+
+    p.lock_mut()?.p_apply("a", jvalue!(10).into())?;
+    // bはadd0のarg2のデフォルト値1になる
+    assert_eq!(p.lock()?.execute()?.lock_as_value(|v| v.as_i64())?, Some(12));
+    assert_eq!(next.lock()?.get_output().lock_as_value(|v| v.as_i64())?, Some(13));
+
+    assert_eq!(p.lock()?.push_by("b", jvalue!(5).into())?.lock_as_value(|v| v.as_i64())?, Some(16));
+    assert_eq!(next.lock()?.get_output().lock_as_value(|v| v.as_i64())?, Some(17));
+    Ok(())
+}
+
+#[test]
+fn composite_process_destroy_test() -> JuizResult<()> {
+    let system = setup_system()?;
+    let p = create_process(&system, "add_and_increment", "ai2")?;
+    system.core_broker().lock_mut()?.process_destroy(p.identifier())?;
+    assert!(system.core_broker().lock()?.worker().process_from_typename_and_name("add", "ai2.add0").is_err());
+    Ok(())
+}
+
+#[test]
+fn composite_process_cycle_test() -> JuizResult<()> {
+    let system = setup_system()?;
+    system.core_broker().lock_mut()?.worker_mut().register_composite_process(jvalue!({
+        "type_name": "cyclic",
+        "processes": [
+            {"type_name": "increment", "name": "inc0"},
+            {"type_name": "increment", "name": "inc1"},
+        ],
+        "connections": [
+            {"source": "inc0", "destination": "inc1", "arg_name": "arg1"},
+            {"source": "inc1", "destination": "inc0", "arg_name": "arg1"},
+        ],
+        "outlet": "inc1",
+    }).try_into()?)?;
+    assert!(system.core_broker().lock_mut()?.process_create(jvalue!({"type_name": "cyclic", "name": "c0"}).try_into()?).is_err());
+    // 作りかけの内部プロセスは残らない
+    assert!(system.core_broker().lock()?.worker().process_from_typename_and_name("increment", "c0.inc0").is_err());
+    // 内部の接続は合成プロセスが評価するので、接続の型は書けない
+    assert!(CompositeProcessManifest::try_from(jvalue!({
+        "type_name": "typed",
+        "processes": [{"type_name": "increment", "name": "inc0"}, {"type_name": "increment", "name": "inc1"}],
+        "connections": [{"source": "inc0", "destination": "inc1", "arg_name": "arg1", "type": "pull"}],
+        "outlet": "inc1",
+    })).is_err());
+    // ProcessFactoryと同じ型名は使えない
+    assert!(system.core_broker().lock_mut()?.worker_mut().register_composite_process(CompositeProcessManifest::new("add", "x")).is_err());
+    Ok(())
+}
@@ -164,4 +164,124 @@ fn simple_connection_builder_invoke_test() -> JuizResult<()> {
     let iv = arc.lock_as_value(|value| { value.as_i64().unwrap() }).unwrap();
     assert_eq!(iv, 3);
     Ok(())
-}
\ No newline at end of file
+}
+
+fn slow_increment_function(v: CapsuleMap) -> JuizResult<Capsule> {
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    common::increment_function(v)
+}
+
+fn connect_with_qos(qos: Value) -> JuizResult<(ProcessPtr, ProcessPtr)> {
+    let rp1 = ProcessPtr::new(common::new_increment_process_use_memo("process1")?);
+    let rp2 = ProcessPtr::new(process_new(jvalue!({
+        "name": "process2",
+        "type_name": "slow_increment",
+        "use_memo": true,
+        "arguments" : [{"name": "arg1", "type": "int", "description": "test_argument", "default": 1}],
+    }).try_into()?, slow_increment_function)?);
+    let manifest: ConnectionManifest = jvalue!({
+        "identifier": "con1",
+        "type": "push",
+        "source": rp1.identifier(),
+        "destination": rp2.identifier(),
+        "arg_name": "arg1",
+        "qos": qos,
+    }).try_into()?;
+    connect(rp1.clone(), rp2.clone(), manifest)?;
+    Ok((rp1, rp2))
+}
+
+fn statistics(rp1: &ProcessPtr) -> JuizResult<Value> {
+    let proc = rp1.lock()?;
+    let cons = proc.destination_connections()?;
+    Ok(cons[0].profile_full()?["statistics"].clone())
+}
+
+fn wait_delivered(rp1: &ProcessPtr, count: u64) -> JuizResult<Value> {
+    for _ in 0..100 {
+        let stat = statistics(rp1)?;
+        if stat["delivered"].as_u64() == Some(count) {
+            return Ok(stat);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    statistics(rp1)
+}
+
+#[test]
+fn connection_manifest_qos_test() -> JuizResult<()> {
+    let manifest: ConnectionManifest = jvalue!({
+        "type": "push",
+        "source": "core://core/Process/process1::increment",
+        "destination": "core://core/Process/process2::increment",
+        "arg_name": "arg1",
+        "qos": {"queue_depth": 3, "policy": "block", "min_period": 0.5},
+    }).try_into()?;
+    assert_eq!(manifest.qos, ConnectionQoS::new(3, QueuePolicy::Block, Some(std::time::Duration::from_millis(500))));
+
+    let value: Value = manifest.into();
+    assert_eq!(value["qos"], jvalue!({"queue_depth": 3, "policy": "block", "min_period": 0.5}));
+    let roundtrip: ConnectionManifest = value.try_into()?;
+    assert_eq!(roundtrip.qos.policy, QueuePolicy::Block);
+
+    let invalid: JuizResult<ConnectionManifest> = jvalue!({
+        "source": "core://core/Process/process1::increment",
+        "destination": "core://core/Process/process2::increment",
+        "arg_name": "arg1",
+        "qos": {"policy": "unknown"},
+    }).try_into();
+    assert!(invalid.is_err());
+    Ok(())
+}
+
+#[test]
+fn connection_qos_drop_newest_test() -> JuizResult<()> {
+    let (rp1, rp2) = connect_with_qos(jvalue!({"queue_depth": 1, "policy": "drop_newest"}))?;
+
+    // 受け手は遅いが送り手は待たされない
+    let start = std::time::Instant::now();
+    for _ in 0..5 {
+        rp1.lock()?.execute()?;
+    }
+    assert!(start.elapsed() < std::time::Duration::from_millis(200));
+
+    let stat = statistics(&rp1)?;
+    assert_eq!(stat["queued"].as_u64().unwrap() + stat["dropped"].as_u64().unwrap(), 5, "statistics={stat}");
+    assert!(stat["dropped"].as_u64().unwrap() >= 3, "statistics={stat}");
+
+    let queued = stat["queued"].as_u64().unwrap();
+    let stat = wait_delivered(&rp1, queued)?;
+    assert_eq!(stat["delivered"].as_u64(), Some(queued), "statistics={stat}");
+    assert_eq!(stat["queue_length"].as_u64(), Some(0));
+    assert_eq!(rp2.lock()?.get_output().lock_as_value(|v| v.as_i64())?, Some(3));
+    Ok(())
+}
+
+#[test]
+fn connection_qos_block_test() -> JuizResult<()> {
+    let (rp1, _rp2) = connect_with_qos(jvalue!({"queue_depth": 1, "policy": "block"}))?;
+
+    for _ in 0..4 {
+        rp1.lock()?.execute()?;
+    }
+    let stat = wait_delivered(&rp1, 4)?;
+    assert_eq!(stat["queued"].as_u64(), Some(4), "statistics={stat}");
+    assert_eq!(stat["dropped"].as_u64(), Some(0), "statistics={stat}");
+    assert_eq!(stat["delivered"].as_u64(), Some(4), "statistics={stat}");
+    Ok(())
+}
+
+#[test]
+fn connection_qos_min_period_test() -> JuizResult<()> {
+    let (rp1, _rp2) = connect_with_qos(jvalue!({"min_period": 10.0}))?;
+
+    // キューを使わない場合は周期に満たないデータは捨てられる
+    for _ in 0..3 {
+        rp1.lock()?.execute()?;
+    }
+    let stat = statistics(&rp1)?;
+    assert_eq!(stat["delivered"].as_u64(), Some(1), "statistics={stat}");
+    assert_eq!(stat["dropped"].as_u64(), Some(2), "statistics={stat}");
+    assert_eq!(stat["queued"].as_u64(), Some(0), "statistics={stat}");
+    Ok(())
+}
@@ -0,0 +1,116 @@
+extern crate juiz_core;
+use std::time::{Duration, Instant};
+
+use juiz_core::prelude::*;
+
+mod common;
+
+fn new_system() -> JuizResult<System> {
+    let system = System::new(jvalue!({"name": "ec_schedule_test"}))?.start_http_broker(false);
+    let increment_manifest = jvalue!({
+        "type_name": "increment",
+        "arguments": [{"name": "arg1", "type": "int", "description": "", "default": 1}],
+    });
+    let sleep_manifest = jvalue!({
+        "type_name": "sleep",
+        "arguments": [{"name": "arg1", "type": "int", "description": "", "default": 0}],
+    });
+    let sleep_function = |_args: CapsuleMap| -> JuizResult<Capsule> {
+        std::thread::sleep(Duration::from_millis(100));
+        Ok(jvalue!(0).into())
+    };
+    {
+        let mut broker = system.core_broker().lock_mut()?;
+        broker.worker_mut().store_mut().processes.register_factory(&"increment".to_owned(), process_factory_create(increment_manifest.try_into()?, common::increment_function)?)?;
+        broker.worker_mut().store_mut().processes.register_factory(&"sleep".to_owned(), process_factory_create(sleep_manifest.try_into()?, sleep_function)?)?;
+    }
+    system.setup()
+}
+
+fn create_process(system: &System, type_name: &str, name: &str) -> JuizResult<ProcessPtr> {
+    system.core_broker().lock_mut()?.worker_mut().create_process_ref(jvalue!({"type_name": type_name, "name": name}).try_into()?)
+}
+
+fn pull(src: &ProcessPtr, dst: &ProcessPtr) -> JuizResult<()> {
+    connect(src.clone(), dst.clone(), ConnectionManifest::new(ConnectionType::Pull, src.identifier().clone(), "arg1".to_owned(), dst.identifier().clone(), None))?;
+    Ok(())
+}
+
+fn create_step_ec(system: &System, manifest: Value, targets: &[&ProcessPtr]) -> JuizResult<Identifier> {
+    let profile = system.core_broker().lock_mut()?.ec_create(&manifest)?;
+    let ec_id = obj_get_str(&profile, "identifier")?.to_owned();
+    let ec = system.core_broker().lock()?.worker().ec_from_id(&ec_id)?;
+    for target in targets.iter() {
+        ec.lock().unwrap().bind((*target).clone())?;
+    }
+    Ok(ec_id)
+}
+
+fn schedule(system: &System, ec_id: &Identifier) -> JuizResult<Value> {
+    Ok(obj_get(&system.core_broker().lock()?.ec_profile_full(ec_id)?, "schedule")?.clone())
+}
+
+#[test]
+fn ec_schedule_topological_order_test() -> JuizResult<()> {
+    let mut system = new_system()?;
+    system.run_and_do_once(|system| {
+        let a = create_process(system, "increment", "a")?;
+        let b = create_process(system, "increment", "b")?;
+        let c = create_process(system, "increment", "c")?;
+        pull(&a, &b)?;
+        pull(&b, &c)?;
+        // 接続と逆の順にバインドしても接続元から実行する
+        let ec_id = create_step_ec(system, jvalue!({"type_name": "StepEC", "name": "order0"}), &[&c, &b, &a])?;
+
+        let schedule = schedule(system, &ec_id)?;
+        let order = obj_get_array(&schedule, "order")?.iter().map(|v| v.as_str().unwrap().to_owned()).collect::<Vec<String>>();
+        assert_eq!(order, vec![a.identifier().clone(), b.identifier().clone(), c.identifier().clone()]);
+        assert_eq!(obj_get_array(&schedule, "levels")?.len(), 3);
+        assert!(obj_get(&schedule, "error")?.is_null());
+
+        let steps = system.core_broker().lock_mut()?.ec_step(&ec_id, 1)?;
+        let outputs = obj_get(&steps[0], "outputs")?;
+        assert_eq!(obj_get_i64(outputs, a.identifier())?, 2);
+        assert_eq!(obj_get_i64(outputs, c.identifier())?, 4);
+        Ok(())
+    })
+}
+
+#[test]
+fn ec_schedule_cycle_test() -> JuizResult<()> {
+    let mut system = new_system()?;
+    system.run_and_do_once(|system| {
+        let a = create_process(system, "increment", "a")?;
+        let b = create_process(system, "increment", "b")?;
+        pull(&a, &b)?;
+        pull(&b, &a)?;
+        let ec_id = create_step_ec(system, jvalue!({"type_name": "StepEC", "name": "cycle0"}), &[&a, &b])?;
+
+        let err = system.core_broker().lock_mut()?.ec_step(&ec_id, 1).unwrap_err();
+        assert!(err.to_string().contains(a.identifier()));
+        assert!(obj_get_str(&schedule(system, &ec_id)?, "error")?.contains(b.identifier()));
+        Ok(())
+    })
+}
+
+#[test]
+fn ec_schedule_parallel_test() -> JuizResult<()> {
+    let mut system = new_system()?;
+    system.run_and_do_once(|system| {
+        let s0 = create_process(system, "sleep", "s0")?;
+        let s1 = create_process(system, "sleep", "s1")?;
+        let ec_id = create_step_ec(system, jvalue!({"type_name": "StepEC", "name": "parallel0", "parallel": true, "num_workers": 2}), &[&s0, &s1])?;
+
+        let schedule = schedule(system, &ec_id)?;
+        assert_eq!(obj_get_bool(&schedule, "parallel")?, true);
+        assert_eq!(obj_get_i64(&schedule, "num_workers")?, 2);
+        assert_eq!(obj_get_array(&schedule, "levels")?.len(), 1);
+
+        // 依存しない二つのプロセスは同時に走る
+        let started = Instant::now();
+        let steps = system.core_broker().lock_mut()?.ec_step(&ec_id, 1)?;
+        assert!(started.elapsed() < Duration::from_millis(190));
+        assert_eq!(obj_get(&steps[0], "outputs")?.as_object().unwrap().len(), 2);
+        Ok(())
+    })
+}
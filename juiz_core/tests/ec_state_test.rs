@@ -0,0 +1,114 @@
+extern crate juiz_core;
+use std::time::Duration;
+
+use juiz_core::prelude::*;
+use juiz_sdk::anyhow::anyhow;
+
+mod common;
+
+fn fail_function(_v: CapsuleMap) -> JuizResult<Capsule> {
+    Err(anyhow!(JuizError::ArgumentError{message: "always fails".to_owned()}))
+}
+
+fn create_event_ec(system: &System, name: &str, process: ProcessPtr, error_threshold: Option<i64>) -> JuizResult<Identifier> {
+    let mut manifest = jvalue!({
+        "type_name": "EventEC",
+        "name": name,
+        "triggers": [{"topic": format!("{name}_topic")}],
+    });
+    if let Some(n) = error_threshold {
+        manifest.as_object_mut().unwrap().insert("error_threshold".to_owned(), jvalue!(n));
+    }
+    let profile = system.core_broker().lock_mut()?.ec_create(&manifest)?;
+    let ec_id = obj_get_str(&profile, "identifier")?.to_owned();
+    system.core_broker().lock()?.worker().ec_from_id(&ec_id)?.lock().unwrap().bind(process)?;
+    Ok(ec_id)
+}
+
+fn push(system: &System, name: &str) -> JuizResult<()> {
+    let topic = system.core_broker().lock_mut()?.worker_mut().create_topic(format!("{name}_topic"))?;
+    topic.push(jvalue!({}).into(), None)
+}
+
+fn state(system: &System, ec_id: &Identifier) -> JuizResult<String> {
+    Ok(system.core_broker().lock()?.ec_get_state(ec_id)?.as_str().unwrap().to_owned())
+}
+
+fn wait_state(system: &System, ec_id: &Identifier, expected: &str) -> JuizResult<String> {
+    for _ in 0..100 {
+        if state(system, ec_id)? == expected {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    state(system, ec_id)
+}
+
+#[test]
+fn ec_pause_and_resume_test() -> JuizResult<()> {
+    let mut system = System::new(jvalue!({"name": "ec_state_test"}))?.start_http_broker(false).setup()?;
+    system.run_and_do_once(|system| {
+        let p = ProcessPtr::new(common::new_increment_process_use_memo("inc0")?);
+        let ec_id = create_event_ec(system, "ev0", p.clone(), None)?;
+        // 動いていないECはpauseもresumeもできない
+        assert!(system.core_broker().lock_mut()?.ec_pause(&ec_id).is_err());
+        system.core_broker().lock_mut()?.ec_start(&ec_id)?;
+        assert!(system.core_broker().lock_mut()?.ec_resume(&ec_id).is_err());
+        std::thread::sleep(Duration::from_millis(50));
+
+        system.core_broker().lock_mut()?.ec_pause(&ec_id)?;
+        assert_eq!(state(system, &ec_id)?, "PAUSED");
+        push(system, "ev0")?;
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(p.lock()?.get_output().is_empty()?);
+
+        // 止まっている間に来たトリガーでresume後に実行する
+        system.core_broker().lock_mut()?.ec_resume(&ec_id)?;
+        assert_eq!(state(system, &ec_id)?, "STARTED");
+        for _ in 0..100 {
+            if !p.lock()?.get_output().is_empty()? {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(p.lock()?.get_output().lock_as_value(|v| v.as_i64())?, Some(2));
+
+        system.core_broker().lock_mut()?.ec_pause(&ec_id)?;
+        system.core_broker().lock_mut()?.ec_stop(&ec_id)?;
+        assert_eq!(state(system, &ec_id)?, "STOPPED");
+        Ok(())
+    })
+}
+
+#[test]
+fn ec_error_threshold_and_reset_test() -> JuizResult<()> {
+    let mut system = System::new(jvalue!({"name": "ec_state_test"}))?.start_http_broker(false).setup()?;
+    system.run_and_do_once(|system| {
+        let manifest = jvalue!({"name": "fail0", "type_name": "fail", "arguments": []});
+        let p = ProcessPtr::new(process_new(manifest.try_into()?, fail_function)?);
+        let ec_id = create_event_ec(system, "ev1", p, Some(2))?;
+        system.core_broker().lock_mut()?.ec_start(&ec_id)?;
+        std::thread::sleep(Duration::from_millis(50));
+
+        push(system, "ev1")?;
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(state(system, &ec_id)?, "STARTED");
+        push(system, "ev1")?;
+        assert_eq!(wait_state(system, &ec_id, "ERROR")?, "ERROR");
+        let profile = system.core_broker().lock()?.ec_profile_full(&ec_id)?;
+        assert_eq!(obj_get_i64(&profile, "num_consecutive_errors")?, 2);
+
+        // ERRORからはresetしないとstartできない
+        assert!(system.core_broker().lock_mut()?.ec_start(&ec_id).is_err());
+        system.core_broker().lock_mut()?.ec_reset(&ec_id)?;
+        assert_eq!(state(system, &ec_id)?, "STOPPED");
+        let profile = system.core_broker().lock()?.ec_profile_full(&ec_id)?;
+        assert_eq!(obj_get_i64(&profile, "num_consecutive_errors")?, 0);
+        assert!(system.core_broker().lock_mut()?.ec_reset(&ec_id).is_err());
+
+        system.core_broker().lock_mut()?.ec_start(&ec_id)?;
+        assert_eq!(state(system, &ec_id)?, "STARTED");
+        system.core_broker().lock_mut()?.ec_stop(&ec_id)?;
+        Ok(())
+    })
+}
@@ -0,0 +1,62 @@
+extern crate juiz_core;
+use std::time::{Duration, Instant};
+
+use juiz_core::prelude::*;
+use juiz_core::{ExecutionContextCore, ExecutionContextStatistics};
+
+mod common;
+
+#[test]
+fn ec_statistics_record_test() -> JuizResult<()> {
+    let mut statistics = ExecutionContextStatistics::new();
+    statistics.set_period(Some(Duration::from_millis(100)));
+    let t0 = Instant::now();
+    assert!(!statistics.record(t0, Duration::from_millis(10)));
+    assert!(!statistics.record(t0 + Duration::from_millis(110), Duration::from_millis(30)));
+    assert!(statistics.record(t0 + Duration::from_millis(200), Duration::from_millis(120)));
+
+    let profile = statistics.profile();
+    assert_eq!(obj_get_i64(&profile, "num_cycles")?, 3);
+    assert_eq!(obj_get_i64(&profile, "num_deadline_misses")?, 1);
+
+    // ECが決めたデッドラインがあれば、実行時間が周期より短くても終わった時刻で判断する
+    let mut by_deadline = ExecutionContextStatistics::new();
+    by_deadline.set_period(Some(Duration::from_millis(100)));
+    by_deadline.set_deadline(Some(t0 + Duration::from_millis(100)));
+    assert!(by_deadline.record(t0 + Duration::from_millis(60), Duration::from_millis(50)));
+    assert!(by_deadline.last_missed());
+    by_deadline.set_deadline(Some(t0 + Duration::from_millis(200)));
+    assert!(!by_deadline.last_missed());
+    assert!(!by_deadline.record(t0 + Duration::from_millis(110), Duration::from_millis(50)));
+    assert_eq!(by_deadline.num_deadline_misses(), 1);
+    let execution_time = obj_get(&profile, "execution_time")?;
+    assert!((obj_get_f64(execution_time, "min")? - 0.01).abs() < 1e-9);
+    assert!((obj_get_f64(execution_time, "avg")? - 0.16 / 3.0).abs() < 1e-9);
+    assert!((obj_get_f64(execution_time, "max")? - 0.12).abs() < 1e-9);
+    // 周期100msに対して間隔は110msと90ms
+    let jitter = obj_get(&profile, "jitter")?;
+    assert!((obj_get_f64(jitter, "avg")? - 0.01).abs() < 1e-9);
+    assert!((obj_get_f64(jitter, "max")? - 0.01).abs() < 1e-9);
+
+    statistics.reset();
+    assert_eq!(statistics.num_cycles(), 0);
+    assert_eq!(statistics.period(), Some(Duration::from_millis(100)));
+    Ok(())
+}
+
+#[test]
+fn ec_core_svc_statistics_test() -> JuizResult<()> {
+    let core = ExecutionContextCore::new();
+    let p = ProcessPtr::new(common::new_increment_process("inc0")?);
+    juiz_lock(&core)?.bind(p)?;
+    for _ in 0..3 {
+        juiz_lock(&core)?.svc()?;
+    }
+    let profile = juiz_lock(&core)?.profile()?;
+    let statistics = obj_get(&profile, "statistics")?;
+    assert_eq!(obj_get_i64(statistics, "num_cycles")?, 3);
+    // 周期を持たなければデッドラインミスは数えない
+    assert_eq!(obj_get_i64(statistics, "num_deadline_misses")?, 0);
+    assert!(obj_get(statistics, "period")?.is_null());
+    Ok(())
+}
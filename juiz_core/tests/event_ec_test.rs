@@ -0,0 +1,147 @@
+extern crate juiz_core;
+use std::time::Duration;
+
+use juiz_core::prelude::*;
+
+mod common;
+
+fn new_system(manifest: Value) -> JuizResult<System> {
+    let system = System::new(manifest)?.start_http_broker(false);
+    let increment_manifest = jvalue!({
+        "type_name": "increment",
+        "use_memo": true,
+        "arguments": [{"name": "arg1", "type": "int", "description": "", "default": 1}],
+    });
+    system.core_broker().lock_mut()?.worker_mut().store_mut().processes.register_factory(&"increment".to_owned(), process_factory_create(increment_manifest.try_into()?, common::increment_function)?)?;
+    system.setup()
+}
+
+fn wait_num_executed(system: &System, ec_id: &Identifier, num: i64) -> JuizResult<i64> {
+    let mut n = 0;
+    for _ in 0..100 {
+        n = obj_get_i64(&system.core_broker().lock()?.ec_profile_full(ec_id)?, "num_executed")?;
+        if n >= num {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    Ok(n)
+}
+
+#[test]
+fn event_ec_topic_trigger_test() -> JuizResult<()> {
+    let mut system = new_system(jvalue!({
+        "name": "event_ec_test",
+        "processes": [
+            {"type_name": "increment", "name": "inc0"},
+        ],
+        "ecs": [{
+            "type_name": "EventEC",
+            "name": "ev0",
+            "triggers": [{"topic": "trigger_topic"}],
+            "bind": [{"type_name": "increment", "name": "inc0"}],
+            "auto_start": true,
+        }],
+    }))?;
+    system.run_and_do_once(|system| {
+        let ec_id = system.core_broker().lock()?.ec_list(false)?[0].as_str().unwrap().to_owned();
+        let inc0 = system.core_broker().lock()?.worker().process_from_typename_and_name("increment", "inc0")?;
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(wait_num_executed(system, &ec_id, 0)?, 0);
+        assert!(inc0.lock()?.get_output().is_empty()?);
+
+        let topic = system.core_broker().lock_mut()?.worker_mut().create_topic("trigger_topic".to_owned())?;
+        topic.push(jvalue!({}).into(), None)?;
+        assert_eq!(wait_num_executed(system, &ec_id, 1)?, 1);
+        assert_eq!(inc0.lock()?.get_output().lock_as_value(|v| v.as_i64())?, Some(2));
+        Ok(())
+    })
+}
+
+#[test]
+fn event_ec_inlet_trigger_and_max_rate_test() -> JuizResult<()> {
+    let mut system = new_system(jvalue!({"name": "event_ec_test"}))?;
+    system.run_and_do_once(|system| {
+        let src = ProcessPtr::new(common::new_increment_process("src0")?);
+        let dst = system.core_broker().lock_mut()?.worker_mut().create_process_ref(jvalue!({"type_name": "increment", "name": "dst0"}).try_into()?)?;
+        connect(src.clone(), dst.clone(), ConnectionManifest::new(ConnectionType::Push, src.identifier().clone(), "arg1".to_owned(), dst.identifier().clone(), None))?;
+
+        let profile = system.core_broker().lock_mut()?.ec_create(&jvalue!({
+            "type_name": "EventEC",
+            "name": "ev1",
+            "triggers": [{"inlet": "arg1"}],
+            "max_rate": 10.0,
+        }))?;
+        let ec_id = obj_get_str(&profile, "identifier")?.to_owned();
+        assert_eq!(obj_get_f64(&profile, "max_rate")?, 10.0);
+        system.core_broker().lock()?.worker().ec_from_id(&ec_id)?.lock().unwrap().bind(dst.clone())?;
+        system.core_broker().lock_mut()?.ec_start(&ec_id)?;
+        std::thread::sleep(Duration::from_millis(50));
+
+        // 0.1秒の間隔を空けて実行する
+        let started = std::time::Instant::now();
+        src.lock()?.execute()?;
+        assert_eq!(wait_num_executed(system, &ec_id, 1)?, 1);
+        src.lock()?.execute()?;
+        assert_eq!(wait_num_executed(system, &ec_id, 2)?, 2);
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        system.core_broker().lock_mut()?.ec_stop(&ec_id)?;
+        src.lock()?.execute()?;
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(wait_num_executed(system, &ec_id, 3)?, 2);
+        Ok(())
+    })
+}
+
+#[test]
+fn event_ec_debounce_max_latency_test() -> JuizResult<()> {
+    let mut system = new_system(jvalue!({
+        "name": "event_ec_test",
+        "processes": [
+            {"type_name": "increment", "name": "inc0"},
+        ],
+        "ecs": [{
+            "type_name": "EventEC",
+            "name": "ev4",
+            "triggers": [{"topic": "busy_topic"}],
+            "debounce": 0.05,
+            "max_latency": 0.2,
+            "bind": [{"type_name": "increment", "name": "inc0"}],
+            "auto_start": true,
+        }],
+    }))?;
+    system.run_and_do_once(|system| {
+        let ec_id = system.core_broker().lock()?.ec_list(false)?[0].as_str().unwrap().to_owned();
+        let topic = system.core_broker().lock_mut()?.worker_mut().create_topic("busy_topic".to_owned())?;
+        // debounceより短い間隔でトリガーし続ける
+        let pushing = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let pusher = {
+            let pushing = pushing.clone();
+            std::thread::spawn(move || {
+                while pushing.load(std::sync::atomic::Ordering::SeqCst) {
+                    let _ = topic.push(jvalue!({}).into(), None);
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            })
+        };
+        // 最初のトリガーからmax_latencyが過ぎたら実行する
+        assert!(wait_num_executed(system, &ec_id, 1)? >= 1);
+
+        // debounceで待っている間でもstopで抜ける
+        let started = std::time::Instant::now();
+        system.core_broker().lock_mut()?.ec_stop(&ec_id)?;
+        assert!(started.elapsed() < Duration::from_millis(200), "elapsed={:?}", started.elapsed());
+        pushing.store(false, std::sync::atomic::Ordering::SeqCst);
+        pusher.join().unwrap();
+        Ok(())
+    })
+}
+
+#[test]
+fn event_ec_invalid_manifest_test() -> JuizResult<()> {
+    let system = new_system(jvalue!({"name": "event_ec_test"}))?;
+    assert!(system.core_broker().lock_mut()?.ec_create(&jvalue!({"type_name": "EventEC", "name": "ev2"})).is_err());
+    assert!(system.core_broker().lock_mut()?.ec_create(&jvalue!({"type_name": "EventEC", "name": "ev3", "triggers": [{"topic": "t"}], "max_rate": 0.0})).is_err());
+    Ok(())
+}
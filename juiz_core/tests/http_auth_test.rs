@@ -0,0 +1,101 @@
+extern crate juiz_core;
+use std::sync::{Arc, Mutex};
+use juiz_core::prelude::*;
+
+mod common;
+
+const PORT: i64 = 18290;
+
+fn auth_manifest() -> Value {
+    jvalue!({
+        "tokens": [
+            {"token": "admin_secret"},
+            {"token": "viewer_secret", "allow": [
+                {"method": "read"},
+                {"method": "update", "class_name": "process", "function_name": "call", "identifier": "*increment1*"},
+            ]},
+        ]
+    })
+}
+
+fn setup_system() -> JuizResult<(System, Identifier)> {
+    common::setup_increment_system(jvalue!({
+        "type_name": "http",
+        "name": format!("127.0.0.1:{PORT}"),
+        "host": "127.0.0.1",
+        "port": PORT,
+        "auth": auth_manifest(),
+    }))
+}
+
+/// トークンごとに別のシステムからつなぐ
+fn client_proxy(token: Option<&str>) -> JuizResult<(System, Arc<Mutex<dyn BrokerProxy>>)> {
+    let mut client = System::new(jvalue!({"name": "http_auth_test_client"}))?.start_http_broker(false).setup()?;
+    let mut manifest = jvalue!({"type_name": "http", "name": format!("127.0.0.1:{PORT}")});
+    if let Some(token) = token {
+        manifest["token"] = jvalue!(token);
+    }
+    let proxy = client.create_broker_proxy(&manifest)?;
+    Ok((client, proxy))
+}
+
+fn args(v: i64) -> CapsuleMap {
+    let mut args = CapsuleMap::new();
+    args.insert("arg1".to_owned(), jvalue!(v).into());
+    args
+}
+
+fn status(path: &str, token: Option<&str>) -> JuizResult<u16> {
+    let mut request = reqwest::blocking::Client::new().get(format!("http://127.0.0.1:{PORT}/api/{path}"));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    Ok(request.send()?.status().as_u16())
+}
+
+#[test]
+fn http_auth_test() -> JuizResult<()> {
+    let (_system, id) = setup_system()?;
+
+    assert_eq!(status("system/profile_full", None)?, 401);
+    assert_eq!(status("system/profile_full", Some("wrong_secret"))?, 401);
+    assert_eq!(status("system/profile_full", Some("viewer_secret"))?, 200);
+    assert_eq!(status("system/filesystem_list?path=.", Some("admin_secret"))?, 200);
+
+    // トークンが無ければ何もできない
+    let (_client, proxy) = client_proxy(None)?;
+    let err = juiz_lock(&proxy)?.process_list(false).unwrap_err();
+    assert!(matches!(err.downcast_ref::<JuizError>(), Some(JuizError::BrokerUnauthorizedError{..})), "unexpected error {err:?}");
+
+    // 読むことはできるがincrement0は呼べない
+    let (_client, proxy) = client_proxy(Some("viewer_secret"))?;
+    let list = juiz_lock(&proxy)?.process_list(false)?;
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    let err = juiz_lock(&proxy)?.process_call(&id, args(2)).unwrap_err();
+    assert!(matches!(err.downcast_ref::<JuizError>(), Some(JuizError::BrokerForbiddenError{..})), "unexpected error {err:?}");
+
+    let (_client, proxy) = client_proxy(Some("admin_secret"))?;
+    let output = juiz_lock(&proxy)?.process_call(&id, args(2))?;
+    assert_eq!(output.extract_value()?, jvalue!(3));
+    Ok(())
+}
+
+#[test]
+fn http_auth_invalid_manifest_test() -> JuizResult<()> {
+    let mut system = System::new(jvalue!({"name": "http_auth_invalid_test"}))?.start_http_broker(false).setup()?;
+    for auth in [
+        jvalue!({"tokens": [{"token": ""}]}),
+        jvalue!({"tokens": [{"token": "a"}, {"token": "a"}]}),
+        jvalue!({"tokens": [{"token": "a", "allow": [{"method": "execute"}]}]}),
+        jvalue!({"tokens": "a"}),
+    ] {
+        assert!(system.create_broker(&jvalue!({
+            "type_name": "http",
+            "name": "127.0.0.1:18291",
+            "host": "127.0.0.1",
+            "port": 18291,
+            "auth": auth,
+        })).is_err(), "auth={auth} must be rejected.");
+    }
+    Ok(())
+}
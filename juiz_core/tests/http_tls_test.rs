@@ -0,0 +1,116 @@
+extern crate juiz_core;
+use std::{path::PathBuf, sync::{Arc, Mutex}};
+use juiz_core::prelude::*;
+use rustls::pki_types::{pem::PemObject, CertificateDer};
+
+mod common;
+
+const PORT: i64 = 18292;
+
+fn work_dir(name: &str) -> JuizResult<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("juiz_http_tls_test_{name}_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn setup_system(dir: &PathBuf) -> JuizResult<(System, Identifier)> {
+    common::setup_increment_system(jvalue!({
+        "type_name": "http",
+        "name": format!("127.0.0.1:{PORT}"),
+        "host": "127.0.0.1",
+        "port": PORT,
+        "tls": {
+            "self_signed": true,
+            "cert_path": dir.join("cert.pem"),
+            "key_path": dir.join("key.pem"),
+        },
+    }))
+}
+
+/// 確かめ方ごとに別のシステムからつなぐ
+fn client_proxy(tls: Value) -> JuizResult<(System, Arc<Mutex<dyn BrokerProxy>>)> {
+    let mut client = System::new(jvalue!({"name": "http_tls_test_client"}))?.start_http_broker(false).setup()?;
+    let proxy = client.create_broker_proxy(&jvalue!({"type_name": "http", "name": format!("127.0.0.1:{PORT}"), "tls": tls}))?;
+    Ok((client, proxy))
+}
+
+fn call(proxy: &Arc<Mutex<dyn BrokerProxy>>, id: &Identifier) -> JuizResult<Value> {
+    let mut args = CapsuleMap::new();
+    args.insert("arg1".to_owned(), jvalue!(2).into());
+    juiz_lock(proxy)?.process_call(id, args)?.extract_value()
+}
+
+fn cert_fingerprint(dir: &PathBuf) -> JuizResult<String> {
+    let der = CertificateDer::from_pem_file(dir.join("cert.pem"))?;
+    Ok(ring::digest::digest(&ring::digest::SHA256, &der).as_ref().iter().map(|b| format!("{b:02X}")).collect::<Vec<String>>().join(":"))
+}
+
+#[test]
+fn http_tls_test() -> JuizResult<()> {
+    let dir = work_dir("server")?;
+    let (_system, id) = setup_system(&dir)?;
+    let fingerprint = cert_fingerprint(&dir)?;
+
+    // 自己署名の証明書をそのままCAとして固定する
+    let (_client, proxy) = client_proxy(jvalue!({"ca_path": dir.join("cert.pem")}))?;
+    assert_eq!(call(&proxy, &id)?, jvalue!(3));
+
+    let (_client, proxy) = client_proxy(jvalue!({"fingerprint": fingerprint.to_lowercase()}))?;
+    assert_eq!(call(&proxy, &id)?, jvalue!(3));
+
+    let (_client, proxy) = client_proxy(jvalue!({"fingerprint": "00:11:22"}))?;
+    assert!(call(&proxy, &id).is_err());
+
+    // 初回は記録し、以降は記録した指紋と比べる
+    let known_hosts = dir.join("known_hosts");
+    let (_client, proxy) = client_proxy(jvalue!({"trust_on_first_use": true, "known_hosts_path": known_hosts}))?;
+    assert_eq!(call(&proxy, &id)?, jvalue!(3));
+    assert_eq!(std::fs::read_to_string(&known_hosts)?, format!("127.0.0.1:{PORT} {fingerprint}\n"));
+    let (_client, proxy) = client_proxy(jvalue!({"trust_on_first_use": true, "known_hosts_path": known_hosts}))?;
+    assert_eq!(call(&proxy, &id)?, jvalue!(3));
+
+    std::fs::write(&known_hosts, format!("127.0.0.1:{PORT} 00:11:22\n"))?;
+    let (_client, proxy) = client_proxy(jvalue!({"trust_on_first_use": true, "known_hosts_path": known_hosts}))?;
+    assert!(call(&proxy, &id).is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+    Ok(())
+}
+
+#[test]
+fn http_tls_invalid_manifest_test() -> JuizResult<()> {
+    let dir = work_dir("invalid")?;
+    let mut system = System::new(jvalue!({"name": "http_tls_invalid_test"}))?.start_http_broker(false).setup()?;
+    for tls in [
+        jvalue!({}),
+        jvalue!({"cert_path": dir.join("cert.pem")}),
+        jvalue!({"cert_path": dir.join("no_cert.pem"), "key_path": dir.join("no_key.pem")}),
+    ] {
+        assert!(system.create_broker(&jvalue!({
+            "type_name": "http",
+            "name": "127.0.0.1:18293",
+            "host": "127.0.0.1",
+            "port": 18293,
+            "tls": tls,
+        })).is_err(), "tls={tls} must be rejected.");
+    }
+    // 作るときは確かめるだけで、自己署名の証明書は始めるまで作らない
+    system.create_broker(&jvalue!({
+        "type_name": "http",
+        "name": "127.0.0.1:18294",
+        "host": "127.0.0.1",
+        "port": 18294,
+        "tls": {"self_signed": true, "cert_path": dir.join("cert.pem"), "key_path": dir.join("key.pem")},
+    }))?;
+    assert!(!dir.join("cert.pem").exists() && !dir.join("key.pem").exists());
+    for tls in [
+        jvalue!({}),
+        jvalue!({"ca_path": dir.join("cert.pem"), "fingerprint": "00:11"}),
+        jvalue!({"fingerprint": "00:11", "trust_on_first_use": true}),
+    ] {
+        assert!(client_proxy(tls.clone()).is_err(), "tls={tls} must be rejected.");
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+    Ok(())
+}
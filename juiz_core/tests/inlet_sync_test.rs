@@ -0,0 +1,106 @@
+extern crate juiz_core;
+use juiz_core::prelude::*;
+
+mod common;
+
+fn new_synchronized_add_process(sync: Value) -> JuizResult<ProcessPtr> {
+    let manifest = jvalue!({
+        "name": "fusion0",
+        "type_name": "add",
+        "use_memo": true,
+        "arguments" : [
+            {"name": "arg1", "type": "int", "description": "test_argument_1", "default": 0},
+            {"name": "arg2", "type": "int", "description": "test_argument_2", "default": 0},
+        ],
+        "sync": sync,
+    });
+    Ok(ProcessPtr::new(process_new(manifest.try_into()?, common::add_function)?))
+}
+
+fn stamped(value: i64, stamp: &str) -> JuizResult<CapsulePtr> {
+    let mut capsule = CapsulePtr::from(jvalue!(value));
+    capsule.set_option("timestamp", stamp)?;
+    Ok(capsule)
+}
+
+#[test]
+fn inlet_sync_manifest_test() -> JuizResult<()> {
+    let manifest: ProcessManifest = jvalue!({
+        "type_name": "add",
+        "arguments" : [],
+        "sync": {"policy": "approximate_time", "slop": 0.05, "inlets": ["arg1", "arg2"]},
+    }).try_into()?;
+    assert_eq!(manifest.sync.policy, InletSyncPolicy::ApproximateTime { key: "timestamp".to_owned(), slop: std::time::Duration::from_millis(50) });
+    assert_eq!(manifest.sync.inlets, vec!["arg1".to_owned(), "arg2".to_owned()]);
+
+    let value: Value = manifest.into();
+    let roundtrip: ProcessManifest = value.try_into()?;
+    assert_eq!(roundtrip.sync.policy.as_str(), "approximate_time");
+
+    let short: ProcessManifest = jvalue!({"type_name": "add", "sync": "all_updated"}).try_into()?;
+    assert_eq!(short.sync.policy, InletSyncPolicy::AllUpdated);
+
+    let invalid: JuizResult<ProcessManifest> = jvalue!({"type_name": "add", "sync": {"policy": "approximate_time"}}).try_into();
+    assert!(invalid.is_err());
+
+    // 引数に無いinletの名前はプロセスを作る時にエラーになる
+    let err = new_synchronized_add_process(jvalue!({"policy": "all_updated", "inlets": ["arg1", "arg3"]})).err().unwrap();
+    assert!(matches!(err.downcast_ref::<JuizError>(), Some(JuizError::ProcessManifestInvalidError{..})), "unexpected error {err:?}");
+    Ok(())
+}
+
+#[test]
+fn inlet_sync_all_updated_test() -> JuizResult<()> {
+    let p = new_synchronized_add_process(jvalue!("all_updated"))?;
+    assert_eq!(p.lock()?.stale_inlets()?.len(), 2);
+
+    // 片方だけでは実行されない
+    let output = p.lock()?.push_by("arg1", jvalue!(1).into())?;
+    assert!(output.is_empty()?);
+    assert_eq!(p.lock()?.stale_inlets()?, vec!["arg2".to_owned()]);
+
+    let output = p.lock()?.push_by("arg1", jvalue!(2).into())?;
+    assert!(output.is_empty()?);
+
+    // 両方そろうと最新の値で実行される
+    let output = p.lock()?.push_by("arg2", jvalue!(10).into())?;
+    assert_eq!(output.lock_as_value(|v| v.as_i64())?, Some(12));
+    assert_eq!(p.lock()?.stale_inlets()?.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn inlet_sync_exact_time_test() -> JuizResult<()> {
+    let p = new_synchronized_add_process(jvalue!({"policy": "exact_time"}))?;
+
+    p.lock()?.push_by("arg1", stamped(1, "100")?)?;
+    p.lock()?.push_by("arg1", stamped(2, "101")?)?;
+    let output = p.lock()?.push_by("arg2", stamped(10, "99")?)?;
+    assert!(output.is_empty()?);
+
+    let output = p.lock()?.push_by("arg2", stamped(20, "101")?)?;
+    assert_eq!(output.lock_as_value(|v| v.as_i64())?, Some(22));
+    assert_eq!(p.lock()?.stale_inlets()?.len(), 2);
+
+    p.lock()?.push_by("arg1", stamped(3, "102")?)?;
+    assert_eq!(p.lock()?.stale_inlets()?, vec!["arg2".to_owned()]);
+
+    // スタンプの無い値は無視される
+    let output = p.lock()?.push_by("arg1", jvalue!(3).into())?;
+    assert_eq!(output.lock_as_value(|v| v.as_i64())?, Some(22));
+    Ok(())
+}
+
+#[test]
+fn inlet_sync_approximate_time_test() -> JuizResult<()> {
+    let p = new_synchronized_add_process(jvalue!({"policy": "approximate_time", "slop": 0.05}))?;
+
+    p.lock()?.push_by("arg1", stamped(1, "10.00")?)?;
+    p.lock()?.push_by("arg1", stamped(2, "10.10")?)?;
+    let output = p.lock()?.push_by("arg2", stamped(10, "10.30")?)?;
+    assert!(output.is_empty()?);
+
+    let output = p.lock()?.push_by("arg2", stamped(20, "10.13")?)?;
+    assert_eq!(output.lock_as_value(|v| v.as_i64())?, Some(22));
+    Ok(())
+}
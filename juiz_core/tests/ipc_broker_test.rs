@@ -0,0 +1,168 @@
+extern crate juiz_core;
+use std::{io::{BufRead, BufReader, Write}, os::unix::net::UnixStream, sync::Mutex, time::{Duration, Instant}};
+use juiz_core::prelude::*;
+use juiz_core::prelude::image::{DynamicImage, Rgb, RgbImage};
+
+mod common;
+
+const NAMESPACE: &str = "juiz_ipc_broker_test.sock";
+const SHM_NAMESPACE: &str = "juiz_ipc_broker_shm_test.sock";
+
+fn grayscale_function(v: CapsuleMap) -> JuizResult<Capsule> {
+    let gray = v.get("img")?.lock_as_image(|img| img.to_luma8())?;
+    Ok(DynamicImage::ImageLuma8(gray).into())
+}
+
+fn setup_system(namespace: &str, shared_memory: bool) -> JuizResult<(System, Identifier)> {
+    let system = common::setup_system(Some(jvalue!({
+        "type_name": "ipc",
+        "name": namespace,
+        "namespace": namespace,
+        "shared_memory": shared_memory,
+    })))?;
+    let manifest = jvalue!({
+        "type_name" : "grayscale",
+        "arguments" : [
+            {
+                "name": "img",
+                "type": "image",
+                "description": "input image",
+                "default": {},
+            },
+        ],
+    });
+    let id = common::register_process(&system, manifest, grayscale_function, "grayscale0")?;
+    Ok((system, id))
+}
+
+#[test]
+fn ipc_broker_test() -> JuizResult<()> {
+    let (mut system, id) = setup_system(NAMESPACE, false)?;
+
+    // バイナリのフレームでのやりとり
+    let proxy = system.create_broker_proxy(&jvalue!({"type_name": "ipc", "name": NAMESPACE}))?;
+    let list = juiz_lock(&proxy)?.process_list(false)?;
+    assert!(list.as_array().unwrap().iter().any(|v| v.as_str() == Some(id.as_str())), "process list does not contain {id}. list={list}");
+
+    let image = RgbImage::from_fn(4, 3, |x, y| Rgb([(x * 60) as u8, (y * 80) as u8, 200]));
+    let output = juiz_lock(&proxy)?.process_push_by(&id, "img".to_owned(), DynamicImage::ImageRgb8(image.clone()).into())?;
+    assert!(!output.is_shared_image()?);
+    assert_eq!(output.producer()?, Some(id.clone()));
+    assert!(output.sequence()?.is_some() && output.timestamp()?.is_some());
+    let gray = output.extract_image()?;
+    assert_eq!(gray, DynamicImage::ImageLuma8(DynamicImage::ImageRgb8(image).to_luma8()));
+
+    // 従来の改行区切りJSONのクライアントもつながる
+    let mut stream = UnixStream::connect(format!("/tmp/{NAMESPACE}"))?;
+    for _ in 0..2 {
+        let request = jvalue!({
+            "__map__": {},
+            "__param__": {"method_name": "READ", "class_name": "process", "function_name": "list", "recursive": "false"},
+        });
+        stream.write_all((request.to_string() + "\n").as_bytes())?;
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        let response = juiz_core::prelude::serde_json::from_str::<Value>(line.as_str())?;
+        assert!(response["__value__"].as_array().unwrap().iter().any(|v| v.as_str() == Some(id.as_str())), "legacy response={response}");
+    }
+    Ok(())
+}
+
+#[test]
+fn ipc_broker_shared_memory_test() -> JuizResult<()> {
+    let (mut system, id) = setup_system(SHM_NAMESPACE, true)?;
+    let proxy = system.create_broker_proxy(&jvalue!({"type_name": "ipc", "name": SHM_NAMESPACE}))?;
+
+    // 送り手が画像を共有メモリに置いてハンドルだけを渡す
+    let image = RgbImage::from_fn(64, 48, |x, y| Rgb([x as u8, y as u8, 100]));
+    let output = juiz_lock(&proxy)?.process_push_by(&id, "img".to_owned(), DynamicImage::ImageRgb8(image.clone()).into())?;
+    assert!(output.is_shared_image()?);
+    let name = output.lock_as_shared_image(|img| img.name().to_owned())?;
+    // 受け手は画素を複製せずに共有メモリを参照する
+    let mapped = output.lock_as_shared_image(|img| img.as_bytes().as_ptr())?;
+    let viewed = output.lock_as_image_view(|img| img.as_bytes().as_ptr())?;
+    assert_eq!(viewed, mapped);
+    assert!(output.is_shared_image()?);
+    let gray = output.lock_as_shared_image(|img| img.to_image())??;
+    assert_eq!(gray, DynamicImage::ImageLuma8(DynamicImage::ImageRgb8(image).to_luma8()));
+
+    // 最後の参照がなくなるとセグメントは消える
+    drop(output);
+    if cfg!(target_os = "linux") {
+        assert!(!std::path::Path::new(&format!("/dev/shm{name}")).exists());
+    }
+    Ok(())
+}
+
+/// 最後に作った共有メモリの画像の名前
+static SLOW_IMAGE_NAME: Mutex<Option<String>> = Mutex::new(None);
+
+fn slow_image_function(v: CapsuleMap) -> JuizResult<Capsule> {
+    let msec = v.get("msec")?.lock_as_value(|v| v.as_i64().unwrap_or(0))?;
+    std::thread::sleep(Duration::from_millis(msec as u64));
+    let image = SharedImage::new(8, 8, SharedImageColor::L8)?;
+    *SLOW_IMAGE_NAME.lock().unwrap() = Some(image.name().to_owned());
+    Ok(image.into())
+}
+
+#[test]
+fn ipc_broker_shared_memory_dropped_connection_test() -> JuizResult<()> {
+    let namespace = "juiz_ipc_broker_shm_drop_test.sock";
+    let (mut system, _) = setup_system(namespace, true)?;
+    let manifest = jvalue!({
+        "type_name": "slow_image",
+        "arguments": [{"name": "msec", "type": "int", "description": "sleep time", "default": 0}],
+    });
+    let id = common::register_process(&system, manifest, slow_image_function, "slow_image0")?;
+    // タイムアウトしたプロキシは接続を捨てるので、サーバーが送った画像のハンドルは誰にも開かれない
+    let proxy = system.create_broker_proxy(&jvalue!({"type_name": "ipc", "name": namespace, "timeout": 0.05}))?;
+    let mut args = CapsuleMap::new();
+    args.insert("msec".to_owned(), jvalue!(200).into());
+    assert!(juiz_lock(&proxy)?.process_call(&id, args).is_err());
+    std::thread::sleep(Duration::from_millis(300));
+    let name = SLOW_IMAGE_NAME.lock().unwrap().clone().expect("slow_image was not called.");
+
+    // 次の呼び出しで出力が入れ替わり、サーバーの持っていた参照もなくなる
+    let mut args = CapsuleMap::new();
+    args.insert("msec".to_owned(), jvalue!(0).into());
+    args.set_timeout(Duration::from_secs(2));
+    let output = juiz_lock(&proxy)?.process_call(&id, args)?;
+    assert!(output.is_shared_image()?);
+
+    let path = format!("/dev/shm{name}");
+    let started = Instant::now();
+    while std::path::Path::new(&path).exists() {
+        assert!(started.elapsed() < Duration::from_secs(2), "{path} was not unlinked.");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    Ok(())
+}
+
+fn scale_function(v: CapsuleMap) -> JuizResult<Capsule> {
+    let array = v.get("points")?.extract_numeric_array()?;
+    let scaled = array.as_f32().unwrap().iter().map(|x| x * 2.0).collect::<Vec<f32>>();
+    Ok(NumericArray::new(array.shape().to_vec(), scaled)?.into())
+}
+
+#[test]
+fn ipc_broker_numeric_array_test() -> JuizResult<()> {
+    // 共有メモリを使うかどうかに関わらず、バージョン3のフレームでJSONに包まずにそのまま送る
+    for (namespace, shared_memory) in [("juiz_ipc_broker_array_test.sock", true), ("juiz_ipc_broker_array_no_shm_test.sock", false)] {
+        let (mut system, _) = setup_system(namespace, shared_memory)?;
+        let manifest = jvalue!({
+            "type_name" : "scale",
+            "arguments" : [
+                {"name": "points", "type": "numeric_array", "description": "input points"},
+            ],
+        });
+        let id = common::register_process(&system, manifest, scale_function, "scale0")?;
+        let proxy = system.create_broker_proxy(&jvalue!({"type_name": "ipc", "name": namespace}))?;
+        let input = NumericArray::new(vec![2, 3], vec![0.5f32, 1.0, 1.5, 2.0, 2.5, 3.0])?;
+        let output = juiz_lock(&proxy)?.process_push_by(&id, "points".to_owned(), input.into())?;
+        assert!(output.is_numeric_array()?, "shared_memory={shared_memory}");
+        let array = output.extract_numeric_array()?;
+        assert_eq!(array.shape(), &[2, 3]);
+        assert_eq!(array.as_f32().unwrap(), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    }
+    Ok(())
+}
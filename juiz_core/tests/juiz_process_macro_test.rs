@@ -0,0 +1,68 @@
+extern crate juiz_core;
+use juiz_core::prelude::*;
+use serde::{Deserialize, Serialize};
+
+mod scale {
+    use super::*;
+
+    #[derive(Debug, Deserialize, Serialize, JuizSchema)]
+    pub struct Point {
+        pub x: f64,
+        pub y: f64,
+        pub label: Option<String>,
+    }
+
+    /// JuizSchemaをつけていない型
+    #[derive(Debug, Deserialize, Serialize)]
+    pub struct Offset {
+        pub dx: f64,
+        pub dy: f64,
+    }
+
+    #[juiz_process]
+    pub fn scale(point: Point, offsets: Vec<Offset>, factor: f64) -> JuizResult<Point> {
+        let (dx, dy) = offsets.iter().fold((0.0, 0.0), |(x, y), o| (x + o.dx, y + o.dy));
+        Ok(Point{x: point.x * factor + dx, y: point.y * factor + dy, label: point.label})
+    }
+}
+
+fn args(point: Value, offsets: Value) -> CapsuleMap {
+    let mut args = CapsuleMap::new();
+    args.insert("point".to_owned(), point.into());
+    args.insert("offsets".to_owned(), offsets.into());
+    args.insert("factor".to_owned(), jvalue!(2.0).into());
+    args
+}
+
+#[test]
+fn juiz_process_macro_serde_argument_test() -> JuizResult<()> {
+    // 引数はserdeで読み、戻り値はserdeでCapsuleにする
+    let output = scale::scale(args(jvalue!({"x": 1.0, "y": 2.0, "label": "p"}), jvalue!([{"dx": 0.5, "dy": 0.0}, {"dx": 0.5, "dy": 1.0}])))?;
+    assert_eq!(output.as_value().unwrap(), &jvalue!({"x": 3.0, "y": 5.0, "label": "p"}));
+
+    // labelがNoneの出力はnullになり、それをもう一度引数に渡せる
+    let output = scale::scale(args(jvalue!({"x": 1.0, "y": 2.0}), jvalue!([])))?;
+    assert_eq!(output.as_value().unwrap(), &jvalue!({"x": 2.0, "y": 4.0, "label": null}));
+    let point = scale::manifest().arguments.into_iter().find(|a| a.name == "point").unwrap();
+    point.check_value(output.as_value().unwrap())?;
+    let output = scale::scale(args(output.as_value().unwrap().clone(), jvalue!([])))?;
+    assert_eq!(output.as_value().unwrap(), &jvalue!({"x": 4.0, "y": 8.0, "label": null}));
+    assert!(point.check_value(&jvalue!({"x": 1.0, "y": 2.0, "label": 3})).is_err());
+
+    let err = scale::scale(args(jvalue!({"x": "one", "y": 2.0}), jvalue!([]))).unwrap_err();
+    assert!(matches!(err.downcast_ref::<JuizError>(), Some(JuizError::ValueTypeError{..})), "unexpected error {err:?}");
+    Ok(())
+}
+
+#[test]
+fn juiz_process_macro_manifest_test() -> JuizResult<()> {
+    let manifest = scale::manifest();
+    let point = manifest.arguments.iter().find(|a| a.name == "point").unwrap();
+    assert_eq!(point.type_name, ArgumentType::Object);
+    assert_eq!(point.schema.required, vec!["x".to_owned(), "y".to_owned()]);
+    // JuizSchemaの無い型は制約の無いarrayになる
+    let offsets = manifest.arguments.iter().find(|a| a.name == "offsets").unwrap();
+    assert_eq!(offsets.type_name, ArgumentType::Array);
+    assert!(offsets.schema.is_empty());
+    Ok(())
+}
@@ -0,0 +1,181 @@
+extern crate juiz_core;
+use std::time::Instant;
+
+use juiz_core::{prelude::*, PayloadCodec};
+use juiz_sdk::value::CapsuleValue;
+use juiz_core::prelude::image::{DynamicImage, Rgb, RgbImage};
+
+mod common;
+
+const PORT: i64 = 18310;
+
+const CODECS: [PayloadCodec; 3] = [PayloadCodec::Json, PayloadCodec::MessagePack, PayloadCodec::Cbor];
+
+fn sample_map() -> CapsuleMap {
+    let mut map = CapsuleMap::new();
+    map.insert("arg1".to_owned(), jvalue!(1).into());
+    map.insert("name".to_owned(), jvalue!("increment0").into());
+    map.insert("points".to_owned(), jvalue!((0..256).map(|i| jvalue!({"x": i as f64 * 0.5, "y": -i, "valid": i % 2 == 0})).collect::<Vec<Value>>()).into());
+    map.set_param("class_name", "process");
+    map
+}
+
+#[test]
+fn payload_codec_round_trip_test() -> JuizResult<()> {
+    for codec in CODECS {
+        let expected: Value = sample_map().into();
+        let decoded: Value = codec.decode_capsule_map(&codec.encode_capsule_map(sample_map())?)?.into();
+        assert_eq!(decoded, expected, "codec={codec}");
+
+        let mut capsule: CapsulePtr = jvalue!({"x": 1.5, "list": [1, 2, 3]}).into();
+        capsule.set_option("seq", "3")?;
+        let decoded = codec.decode_capsule_ptr(&codec.encode_capsule_ptr(capsule)?)?;
+        assert_eq!(decoded.get_option("seq")?, "3", "codec={codec}");
+        assert_eq!(decoded.extract_value()?, jvalue!({"x": 1.5, "list": [1, 2, 3]}), "codec={codec}");
+
+        let decoded = codec.decode_capsule_ptr(&codec.encode_capsule_ptr(vec![7u8; 16].into())?)?;
+        assert_eq!(decoded.lock_as_bytes(|b| b.to_vec())?, vec![7u8; 16], "codec={codec}");
+
+        let array = NumericArray::new(vec![2, 3], vec![0.5f32, 1.0, 1.5, 2.0, 2.5, 3.0])?;
+        let decoded = codec.decode_capsule_ptr(&codec.encode_capsule_ptr(array.clone().into())?)?;
+        assert_eq!(decoded.extract_numeric_array()?, array, "codec={codec}");
+    }
+
+    // MessagePackとCBORではバイナリをBase64にせずそのまま載せ、画像も送れる
+    let image = DynamicImage::ImageRgb8(RgbImage::from_fn(4, 3, |x, y| Rgb([(x * 60) as u8, (y * 80) as u8, 200])));
+    let base64_size = PayloadCodec::Json.encode_capsule_ptr(vec![7u8; 3000].into())?.len();
+    for codec in [PayloadCodec::MessagePack, PayloadCodec::Cbor] {
+        assert!(codec.encode_capsule_ptr(vec![7u8; 3000].into())?.len() < 3100, "codec={codec} base64={base64_size}");
+
+        let mut map = sample_map();
+        map.insert("img".to_owned(), image.clone().into());
+        map.insert("raw".to_owned(), vec![1u8, 2, 3].into());
+        let decoded = codec.decode_capsule_map(&codec.encode_capsule_map(map)?)?;
+        assert_eq!(decoded.get("img")?.extract_image()?, image, "codec={codec}");
+        assert_eq!(decoded.get("raw")?.lock_as_bytes(|b| b.to_vec())?, vec![1u8, 2, 3], "codec={codec}");
+        assert_eq!(decoded.get_param("class_name"), Some(&"process".to_owned()), "codec={codec}");
+
+        let decoded = codec.decode_capsule_ptr(&codec.encode_capsule_ptr(image.clone().into())?)?;
+        assert_eq!(decoded.extract_image()?, image, "codec={codec}");
+    }
+    assert!(PayloadCodec::Json.encode_capsule_ptr(image.into()).is_err());
+
+    // 利用者の値が__value__を持っていても包みと取り違えず、空のCapsuleは{}と区別する
+    for codec in [PayloadCodec::MessagePack, PayloadCodec::Cbor] {
+        let user = CapsulePtr::from(Capsule::from(CapsuleValue::from(jvalue!({"__value__": 1, "x": 2}))));
+        let decoded = codec.decode_capsule_ptr(&codec.encode_capsule_ptr(user)?)?;
+        assert_eq!(decoded.extract_value()?, jvalue!({"__value__": 1, "x": 2}), "codec={codec}");
+        assert!(codec.decode_capsule_ptr(&codec.encode_capsule_ptr(CapsulePtr::new())?)?.is_empty()?, "codec={codec}");
+        assert!(codec.decode_capsule_ptr(&codec.encode_capsule_ptr(jvalue!({}).into())?)?.is_value()?, "codec={codec}");
+        assert!(codec.decode_capsule_ptr(&codec.encode(&jvalue!({"x": 2}))?).is_err(), "codec={codec}");
+    }
+
+    // 通信路の外で作ったJSONはタグがあってもそのまま値として扱う
+    let user: CapsulePtr = jvalue!({"__bytes__": "AQID"}).into();
+    assert!(user.is_value()?);
+    assert!(CapsulePtr::from_json_value(jvalue!({"__bytes__": "AQID"}))?.is_bytes()?);
+
+    // 受け取った形の要素数の積があふれたら、空のデータでも受け付けない
+    let header = jvalue!({"dtype": "f32", "shape": [1u64 << 32, 1u64 << 32, 16]});
+    assert!(NumericArray::from_header_and_bytes(&header, &[]).is_err());
+    assert_eq!(PayloadCodec::from_accept("text/html, application/cbor;q=0.9, application/json"), PayloadCodec::Cbor);
+    assert_eq!(PayloadCodec::from_content_type("application/msgpack"), Some(PayloadCodec::MessagePack));
+    assert!(PayloadCodec::try_from("yaml").is_err());
+    Ok(())
+}
+
+/// JSONと比べた大きさと符号化・復号の時間を表示する (`cargo test -- --nocapture`)
+#[test]
+fn payload_codec_benchmark_test() -> JuizResult<()> {
+    const N: u32 = 200;
+    let value: Value = sample_map().into();
+    let mut sizes = Vec::new();
+    for codec in CODECS {
+        let bytes = codec.encode(&value)?;
+        let start = Instant::now();
+        for _ in 0..N {
+            codec.encode(&value)?;
+        }
+        let encode_time = start.elapsed() / N;
+        let start = Instant::now();
+        for _ in 0..N {
+            codec.decode(&bytes)?;
+        }
+        let decode_time = start.elapsed() / N;
+        println!("{codec:>8}: size={:>6} bytes, encode={encode_time:?}, decode={decode_time:?}", bytes.len());
+        sizes.push(bytes.len());
+    }
+    assert!(sizes[1] < sizes[0], "MessagePack must be smaller than JSON");
+    assert!(sizes[2] < sizes[0], "CBOR must be smaller than JSON");
+
+    // 数値の配列はMessagePackとCBORではBase64にしないぶん小さい
+    let array = NumericArray::new(vec![256, 256], (0..256 * 256).map(|i| i as f32 * 0.25).collect::<Vec<f32>>())?;
+    let points: CapsulePtr = array.into();
+    let array_map = || {
+        let mut map = CapsuleMap::new();
+        map.insert("points".to_owned(), points.clone());
+        map
+    };
+    let mut sizes = Vec::new();
+    for codec in CODECS {
+        let bytes = codec.encode_capsule_map(array_map())?;
+        let start = Instant::now();
+        for _ in 0..N {
+            codec.encode_capsule_map(array_map())?;
+        }
+        let encode_time = start.elapsed() / N;
+        let start = Instant::now();
+        for _ in 0..N {
+            codec.decode_capsule_map(&bytes)?;
+        }
+        let decode_time = start.elapsed() / N;
+        println!("{codec:>8}: numeric array size={:>7} bytes, encode={encode_time:?}, decode={decode_time:?}", bytes.len());
+        sizes.push(bytes.len());
+    }
+    let raw_size = 256 * 256 * 4;
+    assert!(sizes[0] > raw_size * 4 / 3, "JSON encodes numeric array in base64");
+    assert!(sizes[1] < raw_size + 256, "MessagePack must carry numeric array as bin");
+    assert!(sizes[2] < raw_size + 256, "CBOR must carry numeric array as byte string");
+    Ok(())
+}
+
+fn setup_system() -> JuizResult<(System, Identifier)> {
+    common::setup_increment_system(jvalue!({
+        "type_name": "http",
+        "name": format!("127.0.0.1:{PORT}"),
+        "host": "127.0.0.1",
+        "port": PORT,
+    }))
+}
+
+#[test]
+fn http_payload_codec_test() -> JuizResult<()> {
+    let (_system, id) = setup_system()?;
+
+    for codec in CODECS {
+        let mut client = System::new(jvalue!({"name": "payload_codec_test_client"}))?.start_http_broker(false).setup()?;
+        let proxy = client.create_broker_proxy(&jvalue!({"type_name": "http", "name": format!("127.0.0.1:{PORT}"), "codec": codec.as_str()}))?;
+        let mut args = CapsuleMap::new();
+        args.insert("arg1".to_owned(), jvalue!(2).into());
+        let output = juiz_lock(&proxy)?.process_call(&id, args)?;
+        assert_eq!(output.extract_value()?, jvalue!(3), "codec={codec}");
+        assert_eq!(juiz_lock(&proxy)?.process_list(false)?.as_array().unwrap().len(), 1, "codec={codec}");
+    }
+
+    // Acceptで応答の符号化が選べる
+    let response = reqwest::blocking::Client::new()
+        .get(format!("http://127.0.0.1:{PORT}/api/process/list"))
+        .header("Accept", "application/cbor")
+        .send()?;
+    assert_eq!(response.headers().get("content-type").unwrap(), "application/cbor");
+    assert_eq!(PayloadCodec::Cbor.decode_capsule_ptr(&response.bytes()?)?.extract_value()?.as_array().unwrap().len(), 1);
+
+    // 知らないContent-Typeは断る
+    let response = reqwest::blocking::Client::new()
+        .patch(format!("http://127.0.0.1:{PORT}/api/process/call"))
+        .header("Content-Type", "application/yaml")
+        .body("arg1: 2")
+        .send()?;
+    assert_eq!(response.status().as_u16(), 415);
+    Ok(())
+}
@@ -0,0 +1,123 @@
+extern crate juiz_core;
+use std::{path::PathBuf, time::Duration};
+use juiz_core::{prelude::*, PluginPolicy};
+
+const PORT: i64 = 18300;
+
+/// 許可するディレクトリと許可しないディレクトリにプラグインのファイルを置く
+fn plugin_dirs(name: &str) -> JuizResult<(PathBuf, PathBuf)> {
+    let dir = std::env::temp_dir().join(format!("juiz_plugin_policy_test_{name}_{}", std::process::id()));
+    let allowed = dir.join("allowed");
+    let other = dir.join("other");
+    std::fs::create_dir_all(&allowed)?;
+    std::fs::create_dir_all(&other)?;
+    for d in [&allowed, &other] {
+        std::fs::write(d.join("talker.py"), "def manifest():\n    return {}\n")?;
+        std::fs::write(d.join("listener.py"), "def manifest():\n    return {}\n")?;
+    }
+    Ok((allowed.canonicalize()?, other.canonicalize()?))
+}
+
+fn is_rejected(err: &juiz_core::anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<JuizError>(), Some(JuizError::PluginRejectedError{..}))
+}
+
+#[test]
+fn plugin_policy_resolve_test() -> JuizResult<()> {
+    let (allowed, other) = plugin_dirs("resolve")?;
+    let policy = PluginPolicy::from_system_manifest(&jvalue!({
+        "plugin_policy": {
+            "search_paths": [allowed.to_str().unwrap()],
+            "allow": ["talker*"],
+        }
+    }), None)?;
+
+    // ファイル名だけなら検索パスから探す
+    assert_eq!(policy.resolve(&PathBuf::from("talker.py"))?, allowed.join("talker.py"));
+    assert_eq!(policy.resolve(&allowed.join("talker.py"))?, allowed.join("talker.py"));
+    // 検索パスの外
+    assert!(is_rejected(&policy.resolve(&other.join("talker.py")).unwrap_err()));
+    // ..で検索パスの外に出る
+    assert!(is_rejected(&policy.resolve(&allowed.join("..").join("other").join("talker.py")).unwrap_err()));
+    // allowに無いファイル名
+    assert!(is_rejected(&policy.resolve(&allowed.join("listener.py")).unwrap_err()));
+
+    // 制限が無ければパスはそのまま
+    let unrestricted = PluginPolicy::from_system_manifest(&jvalue!({}), None)?;
+    assert_eq!(unrestricted.resolve(&other.join("listener.py"))?, other.join("listener.py"));
+    // ABI記述子の無いプラグインは明示しないと読み込まない
+    assert!(!unrestricted.allow_missing_abi());
+    assert!(PluginPolicy::from_system_manifest(&jvalue!({"plugin_policy": {"allow_missing_abi": true}}), None)?.allow_missing_abi());
+    Ok(())
+}
+
+#[test]
+fn plugin_policy_checksum_test() -> JuizResult<()> {
+    let (allowed, _other) = plugin_dirs("checksum")?;
+    let talker_sha256 = "a1a7e7c0ad4d1a4ae4a1a0e1ba8cb8a6d39b1b1a4f9f1c7c5d3f04f0a6f1b2c3";
+    std::fs::write(allowed.join("plugins.sha256"), format!("{talker_sha256}  talker.py\n"))?;
+    let policy = PluginPolicy::from_system_manifest(&jvalue!({
+        "plugin_policy": {
+            "checksum_file": "plugins.sha256",
+            "require_checksum": true,
+        }
+    }), Some(allowed.clone()))?;
+    // 中身が一致しない
+    assert!(is_rejected(&policy.resolve(&allowed.join("talker.py")).unwrap_err()));
+    // チェックサムが登録されていない
+    assert!(is_rejected(&policy.resolve(&allowed.join("listener.py")).unwrap_err()));
+
+    // 正しいチェックサムなら読み込める
+    let contents = std::fs::read(allowed.join("listener.py"))?;
+    let listener_sha256 = ring::digest::digest(&ring::digest::SHA256, &contents).as_ref().iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    let policy = PluginPolicy::from_system_manifest(&jvalue!({
+        "plugin_policy": {
+            "checksums": {"listener.py": listener_sha256},
+            "require_checksum": true,
+        }
+    }), Some(allowed.clone()))?;
+    assert_eq!(policy.resolve(&allowed.join("listener.py"))?, allowed.join("listener.py"));
+    Ok(())
+}
+
+#[test]
+fn plugin_policy_over_broker_test() -> JuizResult<()> {
+    let (allowed, other) = plugin_dirs("broker")?;
+    let mut system = System::new(jvalue!({
+        "name": "plugin_policy_test",
+        "plugin_policy": {
+            "search_paths": [allowed.to_str().unwrap()],
+        }
+    }))?.start_http_broker(false).setup()?;
+
+    // ローカルの呼び出し
+    let err = system.core_broker().lock_mut()?.system_load_process("python".to_owned(), other.join("talker.py").display().to_string()).unwrap_err();
+    assert!(is_rejected(&err), "unexpected error {err:?}");
+    // 知らない言語はpanicせずにエラーを返す
+    let err = system.core_broker().lock_mut()?.system_load_component("fortran".to_owned(), allowed.join("talker.py").display().to_string()).unwrap_err();
+    assert!(matches!(err.downcast_ref::<JuizError>(), Some(JuizError::InvalidSettingError{..})), "unexpected error {err:?}");
+
+    // HTTPBroker越しの呼び出しでも同じエラーになる
+    let broker = system.create_broker(&jvalue!({
+        "type_name": "http",
+        "name": format!("127.0.0.1:{PORT}"),
+        "host": "127.0.0.1",
+        "port": PORT,
+    }))?;
+    broker.lock_mut()?.start()?;
+    broker.lock_mut()?.wait_until_started(Duration::from_secs(3))?;
+
+    let mut client = System::new(jvalue!({"name": "plugin_policy_test_client"}))?.start_http_broker(false).setup()?;
+    let proxy = client.create_broker_proxy(&jvalue!({"type_name": "http", "name": format!("127.0.0.1:{PORT}")}))?;
+    let err = juiz_lock(&proxy)?.system_load_container("python".to_owned(), other.join("talker.py").display().to_string()).unwrap_err();
+    match err.downcast_ref::<JuizError>() {
+        Some(JuizError::PluginRejectedError{plugin_path, reason}) => {
+            assert_eq!(plugin_path, &other.join("talker.py").display().to_string());
+            assert!(reason.contains("search_paths"), "unexpected reason {reason}");
+        },
+        _ => panic!("unexpected error {err:?}"),
+    }
+    Ok(())
+}
@@ -156,4 +156,115 @@ fn bind_and_invoke_add_process_test()->JuizResult<()> {
     let iv = vv.lock_as_value(|value| { value.as_i64().unwrap() }).unwrap();
     assert_eq!(iv, 3);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn process_output_metadata_test() -> JuizResult<()> {
+    let p = common::new_increment_process_use_memo("increment_meta")?;
+    let before = std::time::SystemTime::now();
+    let mut args = CapsuleMap::new();
+    args.insert("arg1".to_owned(), jvalue!(1).into());
+    let first = p.call(args)?;
+    assert_eq!(first.producer()?, Some(p.identifier()));
+    let stamp = first.timestamp()?.expect("output must have timestamp.");
+    assert!(stamp >= before - std::time::Duration::from_millis(1) && stamp <= std::time::SystemTime::now());
+    // 出力側に渡らない呼び出しは通し番号を使わない
+    assert_eq!(first.sequence()?, None);
+
+    // invokeの出力 (memo) にも書かれ、通し番号は出力ごとに増える
+    let second = p.invoke()?;
+    assert_eq!(second.sequence()?, Some(0));
+    assert_eq!(p.get_output().sequence()?, Some(0));
+    assert_eq!(p.push_by("arg1", jvalue!(2).into())?.sequence()?, Some(1));
+
+    // __value__と__option__に包んだValueを経由しても残る
+    let restored: CapsulePtr = capsule_to_value(p.get_output())?.into();
+    assert_eq!(restored.sequence()?, Some(1));
+    assert_eq!(restored.producer()?, Some(p.identifier()));
+    Ok(())
+}
+
+/// 撮影時刻のようにtimestampを自分で書く処理
+fn captured_function(_args: CapsuleMap) -> JuizResult<Capsule> {
+    let mut capsule: Capsule = jvalue!(1).into();
+    capsule.set_timestamp(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1000));
+    Ok(capsule)
+}
+
+#[test]
+fn process_output_timestamp_kept_test() -> JuizResult<()> {
+    let p = process_new(jvalue!({"name": "camera0", "type_name": "camera", "arguments": []}).try_into()?, captured_function)?;
+    let output = p.call(CapsuleMap::new())?;
+    assert_eq!(output.timestamp()?, Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1000)));
+    assert_eq!(output.producer()?, Some(p.identifier()));
+    Ok(())
+}
+
+#[test]
+fn argument_schema_process_test() -> JuizResult<()> {
+    let manifest = jvalue!({
+        "name": "increment_schema",
+        "type_name": "increment",
+        "arguments" : [
+            {
+                "name": "arg1",
+                "type": "int",
+                "description": "test_argument",
+                "default": 1,
+                "minimum": 0,
+                "maximum": 10,
+            },
+        ],
+    });
+    let mut p = process_new(manifest.try_into()?, common::increment_function)?;
+
+    // 制約はprofile_fullでも見える
+    let profile = p.profile_full()?;
+    assert_eq!(profile["arguments"][0]["maximum"], jvalue!(10.0));
+
+    assert_eq!(p.call(vec!(("arg1", jvalue!(3))).into())?.lock_as_value(|v| v.as_i64())?, Some(4));
+    let e = p.call(vec!(("arg1", jvalue!(11))).into()).expect_err("Process must reject value out of range.");
+    assert!(matches!(e.downcast_ref::<JuizError>(), Some(JuizError::ArgumentConstraintViolationError{name, ..}) if name == "arg1"), "error={e}");
+    assert!(p.p_apply("arg1", jvalue!(-1).into()).is_err());
+
+    // 既定値が制約を満たさないマニフェストは読めない
+    let invalid: JuizResult<ProcessManifest> = jvalue!({
+        "name": "hoge",
+        "type_name": "increment",
+        "arguments": [
+            {"name": "mode", "type": "string", "default": "slow", "enum": ["fast", "accurate"]},
+        ]
+    }).try_into();
+    assert!(invalid.is_err());
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize, JuizSchema)]
+struct Point {
+    x: f64,
+    y: f64,
+    label: Option<String>,
+}
+
+fn scale_point_function(v: CapsuleMap) -> JuizResult<Capsule> {
+    let point: Point = capsule_ptr_to_deserialize(v.get("point")?)?;
+    serialize_to_capsule(&Point{x: point.x * 2.0, y: point.y * 2.0, label: point.label})
+}
+
+#[test]
+fn serde_struct_argument_process_test() -> JuizResult<()> {
+    let manifest = ProcessManifest::new("scale_point")
+        .name("scale_point0")
+        .add_arg(ArgumentManifest::new_with_schema::<Point>("point", "point to scale", None));
+    // 構造体のフィールドが制約になる
+    let arg: Value = manifest.arguments[0].clone().into();
+    assert_eq!(arg["type"], jvalue!("object"));
+    assert_eq!(arg["required"], jvalue!(["x", "y"]));
+    assert_eq!(arg["properties"]["x"]["type"], jvalue!("float"));
+
+    let p = process_new(manifest, scale_point_function)?;
+    let output = p.call(vec!(("point", jvalue!({"x": 1.0, "y": 2.5}))).into())?;
+    assert_eq!(capsule_ptr_to_deserialize::<Point>(output)?, Point{x: 2.0, y: 5.0, label: None});
+    assert!(p.call(vec!(("point", jvalue!({"x": 1.0}))).into()).is_err());
+    Ok(())
+}
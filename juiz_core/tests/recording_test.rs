@@ -0,0 +1,152 @@
+extern crate juiz_core;
+use std::{path::PathBuf, time::{Duration, SystemTime}};
+use juiz_core::prelude::*;
+use juiz_core::prelude::image::{DynamicImage, Rgb, RgbImage};
+
+mod common;
+
+fn temp_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn bag_file_roundtrip_test() -> JuizResult<()> {
+    let path = temp_file("juiz_bag_file_roundtrip_test.jbag");
+    let image = RgbImage::from_fn(4, 3, |x, y| Rgb([(x * 60) as u8, (y * 80) as u8, 200]));
+    {
+        let mut writer = BagWriter::create(&path)?;
+        let value_channel = writer.add_channel("pose", BagChannelKind::Topic)?;
+        let image_channel = writer.add_channel("image", BagChannelKind::Topic)?;
+        let array_channel = writer.add_channel("points", BagChannelKind::Topic)?;
+        assert_eq!(writer.add_channel("pose", BagChannelKind::Topic)?, value_channel);
+
+        let mut value: CapsulePtr = jvalue!({"x": 1.5}).into();
+        value.set_option("seq", "7")?;
+        writer.write(value_channel, SystemTime::now(), &value)?;
+        writer.write(image_channel, SystemTime::now(), &DynamicImage::ImageRgb8(image.clone()).into())?;
+        writer.write(array_channel, SystemTime::now(), &NumericArray::new(vec![3], vec![1.0f32, 2.0, 3.0])?.into())?;
+        assert!(writer.write(5, SystemTime::now(), &value).is_err());
+        // u32に収まらない次元はフレームに書けない
+        assert!(writer.write(array_channel, SystemTime::now(), &NumericArray::new(vec![1usize << 32, 0], Vec::<f32>::new())?.into()).is_err());
+    }
+
+    let channels = BagReader::scan_channels(&path)?;
+    assert_eq!(channels.iter().map(|c| c.name.as_str()).collect::<Vec<&str>>(), vec!["pose", "image", "points"]);
+
+    let mut reader = BagReader::open(&path)?;
+    assert_eq!(reader.header()["version"], jvalue!(1));
+    let first = reader.next_record()?.expect("record file must have first record.");
+    assert_eq!(reader.channel(first.channel_id).unwrap().name, "pose");
+    assert_eq!(first.capsule.lock_as_value(|v| v["x"].as_f64())?, Some(1.5));
+    assert_eq!(first.capsule.sequence()?, Some(7));
+    let second = reader.next_record()?.expect("record file must have second record.");
+    assert_eq!(second.capsule.extract_image()?, DynamicImage::ImageRgb8(image));
+    assert!(second.stamp >= first.stamp);
+    // 数値の配列はJSONに包まずに書かれるので、読んだときも数値の配列のまま
+    let third = reader.next_record()?.expect("record file must have third record.");
+    assert!(third.capsule.is_numeric_array()?);
+    assert_eq!(third.capsule.extract_numeric_array()?.as_f32().unwrap(), &[1.0, 2.0, 3.0]);
+    assert!(reader.next_record()?.is_none());
+
+    // 長さだけ大きく申告して中身の無いフレームは、その大きさのバッファを確保せずにエラーにする
+    let truncated = temp_file("juiz_bag_file_truncated_test.jbag");
+    let mut bytes = std::fs::read(&path)?[0..8].to_vec();
+    bytes.extend_from_slice(&(1u32 << 29).to_be_bytes());
+    bytes.push(0);
+    std::fs::write(&truncated, bytes)?;
+    assert!(BagReader::open(&truncated).is_err());
+    Ok(())
+}
+
+#[test]
+fn record_and_replay_connection_test() -> JuizResult<()> {
+    let path = temp_file("juiz_record_and_replay_connection_test.jbag");
+    let source = ProcessPtr::new(common::new_increment_process_use_memo("source0")?);
+    let destination = ProcessPtr::new(common::new_increment_process_use_memo("destination0")?);
+    let manifest = ConnectionManifest::new(ConnectionType::Push, source.identifier().clone(), "arg1".to_owned(), destination.identifier().clone(), None);
+    connect(source.clone(), destination.clone(), manifest)?;
+    let connection_id = connection_identifier_new(source.identifier(), destination.identifier(), "arg1");
+
+    let mut recorder = Recorder::create("rec0", &path)?;
+    recorder.record_connection(&connection_id, source.clone())?;
+    for i in 0..3 {
+        source.lock()?.push_by("arg1", jvalue!(i * 10).into())?;
+    }
+    assert_eq!(recorder.num_records()?, 3);
+    let produced = source.lock()?.get_output().timestamp()?.expect("output must have timestamp.");
+    recorder.stop()?;
+    source.lock()?.push_by("arg1", jvalue!(100).into())?;
+    assert_eq!(recorder.num_records()?, 3);
+
+    // stopで書き出されていて、出力のtimestampの時刻で記録されている
+    let mut reader = BagReader::open(&path)?;
+    let mut last = None;
+    while let Some(record) = reader.next_record()? {
+        last = Some(record);
+    }
+    let produced = produced.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs_f64();
+    assert!((last.expect("record file must have records.").stamp - produced).abs() < 1e-5);
+
+    // 記録した接続の先と同じ引数に1件ずつ流す
+    let replayed = ProcessPtr::new(common::new_increment_process_use_memo("replayed0")?);
+    let mut player = Player::open("play0", &path, PlaybackSpeed::Stepped)?;
+    assert_eq!(player.channels()?[0].kind, BagChannelKind::Connection);
+    player.set_target(connection_id.as_str(), PlaybackTarget::Inlet(replayed.clone(), "arg1".to_owned()));
+    assert!(player.play().is_err());
+    for expected in [2, 12, 22] {
+        assert!(player.step()?);
+        assert_eq!(replayed.lock()?.get_output().lock_as_value(|v| v.as_i64())?, Some(expected));
+    }
+    assert!(!player.step()?);
+    assert_eq!(player.num_played(), 3);
+    Ok(())
+}
+
+#[test]
+fn record_and_replay_topic_in_system_test() -> JuizResult<()> {
+    let recorded = temp_file("juiz_record_topic_test.jbag");
+    let replayed = temp_file("juiz_replay_topic_test.jbag");
+
+    let mut system = System::new(jvalue!({
+        "name": "recording_test",
+        "recorders": [{"name": "rec0", "file": recorded.to_string_lossy(), "topics": ["pose"]}],
+    }))?.start_http_broker(false).setup()?;
+    system.run_and_do_once(|system| {
+        let topic = system.core_broker().lock_mut()?.worker_mut().create_topic("pose".to_owned())?;
+        for i in 0..4 {
+            topic.push(jvalue!({"i": i}).into(), None)?;
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        let recorder = system.core_broker().lock()?.worker().store().recorders.get("rec0").cloned().unwrap();
+        assert_eq!(juiz_lock(&recorder)?.num_records()?, 4);
+        Ok(())
+    })?;
+
+    // 別のTopicに10倍速で流し直し、それを記録して確かめる
+    let mut system = System::new(jvalue!({
+        "name": "replay_test",
+        "recorders": [{"name": "rec1", "file": replayed.to_string_lossy(), "topics": ["pose_replay"]}],
+        "players": [{"name": "play0", "file": recorded.to_string_lossy(), "speed": 10.0, "remap": {"pose": "pose_replay"}}],
+    }))?.start_http_broker(false).setup()?;
+    system.run_and_do_once(|system| {
+        let recorder = system.core_broker().lock()?.worker().store().recorders.get("rec1").cloned().unwrap();
+        for _ in 0..100 {
+            if juiz_lock(&recorder)?.num_records()? == 4 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(juiz_lock(&recorder)?.num_records()?, 4);
+        Ok(())
+    })?;
+
+    let mut reader = BagReader::open(&replayed)?;
+    let mut values = Vec::new();
+    while let Some(record) = reader.next_record()? {
+        values.push(record.capsule.lock_as_value(|v| v["i"].as_i64())?.unwrap());
+    }
+    assert_eq!(values, vec![0, 1, 2, 3]);
+    Ok(())
+}
@@ -0,0 +1,105 @@
+extern crate juiz_core;
+
+use juiz_core::prelude::*;
+
+mod common;
+
+fn new_system() -> JuizResult<System> {
+    let system = System::new(jvalue!({"name": "step_ec_test"}))?.start_http_broker(false);
+    let double_manifest = jvalue!({
+        "type_name": "double",
+        "arguments": [{"name": "arg1", "type": "float", "description": "", "default": 0.0}],
+    });
+    let double_function = |args: CapsuleMap| -> JuizResult<Capsule> {
+        let v = args.get("arg1")?.lock_as_value(|v| v.as_f64().unwrap())?;
+        Ok(jvalue!(v * 2.0).into())
+    };
+    system.core_broker().lock_mut()?.worker_mut().store_mut().processes.register_factory(&"double".to_owned(), process_factory_create(double_manifest.try_into()?, double_function)?)?;
+    system.setup()
+}
+
+#[test]
+fn step_ec_simulated_clock_test() -> JuizResult<()> {
+    let mut system = new_system()?;
+    system.run_and_do_once(|system| {
+        let profile = system.core_broker().lock_mut()?.ec_create(&jvalue!({
+            "type_name": "StepEC",
+            "name": "sim0",
+            "step_size": 0.5,
+        }))?;
+        let ec_id = obj_get_str(&profile, "identifier")?.to_owned();
+        let clock = system.core_broker().lock()?.worker().process_from_typename_and_name("simulated_clock", "sim0_clock")?;
+        let dst = system.core_broker().lock_mut()?.worker_mut().create_process_ref(jvalue!({"type_name": "double", "name": "dst0"}).try_into()?)?;
+        connect(clock.clone(), dst.clone(), ConnectionManifest::new(ConnectionType::Pull, clock.identifier().clone(), "arg1".to_owned(), dst.identifier().clone(), None))?;
+        system.core_broker().lock()?.worker().ec_from_id(&ec_id)?.lock().unwrap().bind(dst.clone())?;
+
+        // startしなくても自分では走らない
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(dst.lock()?.get_output().is_empty()?);
+
+        let steps = system.core_broker().lock_mut()?.ec_step(&ec_id, 3)?;
+        let steps = steps.as_array().unwrap();
+        assert_eq!(steps.len(), 3);
+        for (k, step) in steps.iter().enumerate() {
+            let time = 0.5 * k as f64;
+            assert_eq!(obj_get_i64(step, "step")?, k as i64);
+            assert_eq!(obj_get_f64(step, "time")?, time);
+            assert_eq!(obj_get_f64(obj_get(step, "outputs")?, dst.identifier())?, time * 2.0);
+        }
+
+        let profile = system.core_broker().lock()?.ec_profile_full(&ec_id)?;
+        assert_eq!(obj_get_f64(obj_get(&profile, "clock")?, "time")?, 1.5);
+        assert_eq!(obj_get_i64(obj_get(&profile, "clock")?, "num_steps")?, 3);
+        Ok(())
+    })
+}
+
+#[test]
+fn step_ec_state_and_unsupported_test() -> JuizResult<()> {
+    let mut system = new_system()?;
+    system.run_and_do_once(|system| {
+        let profile = system.core_broker().lock_mut()?.ec_create(&jvalue!({"type_name": "StepEC", "name": "sim1"}))?;
+        let ec_id = obj_get_str(&profile, "identifier")?.to_owned();
+        system.core_broker().lock_mut()?.ec_start(&ec_id)?;
+        system.core_broker().lock_mut()?.ec_pause(&ec_id)?;
+        assert!(system.core_broker().lock_mut()?.ec_step(&ec_id, 1).is_err());
+        system.core_broker().lock_mut()?.ec_resume(&ec_id)?;
+        assert_eq!(system.core_broker().lock_mut()?.ec_step(&ec_id, 2)?.as_array().unwrap().len(), 2);
+
+        assert!(system.core_broker().lock_mut()?.ec_create(&jvalue!({"type_name": "StepEC", "name": "sim2", "step_size": 0.0})).is_err());
+
+        // 手動で進められないECはstepを断る
+        let profile = system.core_broker().lock_mut()?.ec_create(&jvalue!({"type_name": "EventEC", "name": "ev0", "triggers": [{"topic": "t"}]}))?;
+        let ev_id = obj_get_str(&profile, "identifier")?.to_owned();
+        assert!(system.core_broker().lock_mut()?.ec_step(&ev_id, 1).is_err());
+        Ok(())
+    })
+}
+
+#[test]
+fn step_ec_destroy_and_bytes_output_test() -> JuizResult<()> {
+    let mut system = new_system()?;
+    let bytes_manifest = jvalue!({"type_name": "bytes", "arguments": []});
+    let bytes_function = |_args: CapsuleMap| -> JuizResult<Capsule> {
+        Ok(vec![1u8, 2, 3].into())
+    };
+    system.core_broker().lock_mut()?.worker_mut().store_mut().processes.register_factory(&"bytes".to_owned(), process_factory_create(bytes_manifest.try_into()?, bytes_function)?)?;
+    system.run_and_do_once(|system| {
+        let profile = system.core_broker().lock_mut()?.ec_create(&jvalue!({"type_name": "StepEC", "name": "sim3"}))?;
+        let ec_id = obj_get_str(&profile, "identifier")?.to_owned();
+        let p = system.core_broker().lock_mut()?.worker_mut().create_process_ref(jvalue!({"type_name": "bytes", "name": "bytes0"}).try_into()?)?;
+        system.core_broker().lock()?.worker().ec_from_id(&ec_id)?.lock().unwrap().bind(p.clone())?;
+
+        // バイト列の出力はnullにせずBase64で包んで返す
+        let steps = system.core_broker().lock_mut()?.ec_step(&ec_id, 1)?;
+        assert_eq!(obj_get(obj_get(&steps.as_array().unwrap()[0], "outputs")?, p.identifier())?, &jvalue!({"__bytes__": "AQID"}));
+
+        // 消すと時計のプロセスも消え、同じ名前で作り直せる
+        system.core_broker().lock_mut()?.ec_destroy(&ec_id)?;
+        assert!(system.core_broker().lock()?.worker().ec_from_id(&ec_id).is_err());
+        assert!(system.core_broker().lock()?.worker().process_from_typename_and_name("simulated_clock", "sim3_clock").is_err());
+        system.core_broker().lock_mut()?.ec_create(&jvalue!({"type_name": "StepEC", "name": "sim3"}))?;
+        assert!(system.core_broker().lock()?.worker().process_from_typename_and_name("simulated_clock", "sim3_clock").is_ok());
+        Ok(())
+    })
+}
@@ -0,0 +1,104 @@
+extern crate juiz_core;
+use std::{sync::atomic::{AtomicI64, Ordering}, time::Duration};
+
+use juiz_core::prelude::*;
+
+mod common;
+
+const MASTER_NAMESPACE: &str = "juiz_heartbeat_master_test.sock";
+const SUB_NAMESPACE: &str = "juiz_heartbeat_sub_test.sock";
+const SUB_UUID: &str = "5f0c8d2e-1b7a-4c3e-9a51-2d6f8e4b7c10";
+const TOPIC_NAME: &str = "heartbeat_topic";
+
+static NUM_RECEIVED: AtomicI64 = AtomicI64::new(0);
+
+fn receiver_function(args: CapsuleMap) -> JuizResult<Capsule> {
+    let _ = args.get("input")?;
+    Ok(jvalue!(NUM_RECEIVED.fetch_add(1, Ordering::SeqCst) + 1).into())
+}
+
+fn new_system_with_ipc_broker(mut manifest: Value, namespace: &str) -> JuizResult<(System, BrokerPtr)> {
+    common::remove_socket_file(namespace);
+    manifest["brokers"] = jvalue!([{"type_name": "ipc", "name": namespace, "namespace": namespace}]);
+    let system = System::new(manifest)?.start_http_broker(false).setup()?;
+    let broker = system.core_broker().lock()?.system_store().lock()?.brokers.get("ipc").unwrap().clone();
+    Ok((system, broker))
+}
+
+fn setup_sub_system() -> JuizResult<(System, BrokerPtr)> {
+    let (system, broker) = new_system_with_ipc_broker(jvalue!({"name": "heartbeat_sub", "uuid": SUB_UUID}), SUB_NAMESPACE)?;
+    let manifest = jvalue!({
+        "type_name": "receiver",
+        "arguments": [{"name": "input", "type": "object", "description": "received value", "default": {}}],
+    });
+    let pf = process_factory_create(manifest.try_into()?, receiver_function)?;
+    system.core_broker().lock_mut().and_then(|mut cb| {
+        cb.worker_mut().store_mut().processes.register_factory(&"receiver".to_owned(), pf)?;
+        let p = cb.worker_mut().create_process_ref(jvalue!({"type_name": "receiver", "name": "receiver0"}).try_into()?)?;
+        cb.worker_mut().process_subscribe_topic(p, &"input".to_owned(), TopicManifest::new(TOPIC_NAME))
+    })?;
+    Ok((system, broker))
+}
+
+fn subsystem_state(system: &System) -> JuizResult<String> {
+    let profile = system.core_broker().lock()?.profile_full()?;
+    Ok(profile["subsystems"][0]["liveness"]["state"].as_str().unwrap_or_default().to_owned())
+}
+
+fn push_topic(system: &System, value: i64) -> JuizResult<()> {
+    let topic = system.core_broker().lock_mut()?.worker_mut().create_topic(TOPIC_NAME.to_owned())?;
+    topic.push(jvalue!({"value": value}).into(), None)
+}
+
+#[test]
+fn subsystem_heartbeat_test() -> JuizResult<()> {
+    let (_sub_system, sub_broker) = setup_sub_system()?;
+    let (master_system, _master_broker) = new_system_with_ipc_broker(jvalue!({
+        "name": "heartbeat_master",
+        "heartbeat": {"interval": 0.1, "timeout": 0.5, "max_missed": 2},
+    }), MASTER_NAMESPACE)?;
+    // つなぎ直すときはローカルの出版者がいるトピックを相手に購読してもらう
+    let pf = process_factory_create(jvalue!({"type_name": "publisher", "arguments": []}).try_into()?, |_| Ok(jvalue!({}).into()))?;
+    master_system.core_broker().lock_mut().and_then(|mut cb| {
+        cb.worker_mut().store_mut().processes.register_factory(&"publisher".to_owned(), pf)?;
+        let p = cb.worker_mut().create_process_ref(jvalue!({"type_name": "publisher", "name": "publisher0"}).try_into()?)?;
+        cb.worker_mut().process_publish_topic(p, TopicManifest::new(TOPIC_NAME))
+    })?;
+
+    master_system.core_broker().lock_mut()?.system_add_subsystem(jvalue!({"type_name": "ipc", "name": SUB_NAMESPACE}))?;
+    assert_eq!(subsystem_state(&master_system)?, "ALIVE");
+
+    // サブシステムの購読者にトピックを中継させる
+    let master_uuid = master_system.core_broker().lock()?.system_store().uuid()?;
+    let result = master_system.core_broker().lock_mut()?.topic_request_subscribe(TOPIC_NAME, Some(master_uuid))?;
+    assert_eq!(result["subscribe"], jvalue!(true));
+    push_topic(&master_system, 1)?;
+    assert_eq!(NUM_RECEIVED.load(Ordering::SeqCst), 1);
+
+    // サブシステムが応答しなくなるとmax_missed回でDEADになり、トピックは転送されない
+    sub_broker.lock_mut()?.stop()?;
+    std::thread::sleep(Duration::from_millis(100));
+    master_system.heartbeat()?;
+    assert_eq!(subsystem_state(&master_system)?, "ALIVE");
+    master_system.heartbeat()?;
+    assert_eq!(subsystem_state(&master_system)?, "DEAD");
+    push_topic(&master_system, 2)?;
+    assert_eq!(NUM_RECEIVED.load(Ordering::SeqCst), 1);
+
+    // 同じUUIDのまま戻ってくればALIVEに戻り、トピックの転送も再開する (購読し直しても二重には届かない)
+    sub_broker.lock_mut()?.start()?;
+    sub_broker.lock_mut()?.wait_until_started(Duration::from_secs(3))?;
+    master_system.heartbeat()?;
+    assert_eq!(subsystem_state(&master_system)?, "ALIVE");
+    push_topic(&master_system, 3)?;
+    assert_eq!(NUM_RECEIVED.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+#[test]
+fn subsystem_heartbeat_invalid_manifest_test() -> JuizResult<()> {
+    assert!(System::new(jvalue!({"name": "heartbeat_test", "uuid": "not-a-uuid"})).is_err());
+    let system = System::new(jvalue!({"name": "heartbeat_test", "heartbeat": {"max_missed": 0}}))?.start_http_broker(false);
+    assert!(system.setup().is_err());
+    Ok(())
+}
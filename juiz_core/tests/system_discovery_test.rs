@@ -0,0 +1,75 @@
+extern crate juiz_core;
+use std::time::{Duration, Instant};
+
+use juiz_core::prelude::*;
+
+mod common;
+
+const MASTER_NAMESPACE: &str = "juiz_discovery_master_test.sock";
+const SUB_NAMESPACE: &str = "juiz_discovery_sub_test.sock";
+// ほかのテストやLAN上のjuizと混ざらないように専用のポートを使う
+const DISCOVERY_PORT: i64 = 18575;
+
+fn new_system(name: &str, namespace: &str) -> JuizResult<System> {
+    common::remove_socket_file(namespace);
+    System::new(jvalue!({
+        "name": name,
+        "brokers": [{"type_name": "ipc", "name": namespace, "namespace": namespace}],
+        "discovery": {"port": DISCOVERY_PORT, "loopback": true, "interval": 0.1, "expire": 1.0},
+    }))?.start_http_broker(false).setup()
+}
+
+fn wait_peer_names(system: &System, expected: &[&str]) -> JuizResult<Vec<String>> {
+    let start = Instant::now();
+    loop {
+        let peers = system.core_broker().lock()?.system_discovered_peers()?;
+        let names = get_array(&peers)?.iter().map(|p| Ok(obj_get_str(p, "name")?.to_owned())).collect::<JuizResult<Vec<String>>>()?;
+        if expected.iter().all(|n| names.iter().any(|m| m == n)) || start.elapsed() > Duration::from_secs(3) {
+            return Ok(names);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn system_discovery_test() -> JuizResult<()> {
+    let sub_system = new_system("discovery_sub", SUB_NAMESPACE)?;
+    let master_system = new_system("discovery_master", MASTER_NAMESPACE)?;
+    let sub_uuid = sub_system.core_broker().lock()?.system_store().uuid()?;
+
+    // 互いに相手を見つけ、自分自身はリストに入らない
+    assert_eq!(wait_peer_names(&master_system, &["discovery_sub"])?, vec!["discovery_sub".to_owned()]);
+    assert_eq!(wait_peer_names(&sub_system, &["discovery_master"])?, vec!["discovery_master".to_owned()]);
+    let peers = master_system.core_broker().lock()?.system_discovered_peers()?;
+    let peer = &get_array(&peers)?[0];
+    assert_eq!(obj_get_str(peer, "uuid")?, sub_uuid.to_string());
+    assert_eq!(peer["brokers"], jvalue!([{"type_name": "ipc", "name": SUB_NAMESPACE}]));
+
+    // 見つけた相手を名前でサブシステムに加える
+    let master_system = master_system.add_subsystem_by_peer_name(Some("discovery_sub".to_owned()), Duration::from_secs(3))?;
+    let profile = master_system.core_broker().lock()?.profile_full()?;
+    assert_eq!(profile["subsystems"].as_array().unwrap().len(), 1);
+    assert_eq!(profile["subsystems"][0]["name"], jvalue!(SUB_NAMESPACE));
+    assert_eq!(profile["subsystems"][0]["liveness"]["state"], jvalue!("ALIVE"));
+
+    // サブシステムからも見つけたシステムのリストを問い合わせられる
+    let remote_peers = master_system.core_broker().lock()?.worker().broker_proxy("ipc", SUB_NAMESPACE, false)
+        .and_then(|bp| juiz_lock(&bp)?.system_discovered_peers())?;
+    assert_eq!(get_array(&remote_peers)?.iter().map(|p| obj_get_str(p, "name").unwrap().to_owned()).collect::<Vec<String>>(), vec!["discovery_master".to_owned()]);
+
+    // 見つかっていない名前はtimeoutでエラーになる
+    assert!(master_system.add_subsystem_by_peer_name(Some("no_such_system".to_owned()), Duration::from_millis(200)).is_err());
+    Ok(())
+}
+
+#[test]
+fn system_discovery_invalid_manifest_test() -> JuizResult<()> {
+    let system = System::new(jvalue!({"name": "discovery_test", "discovery": {"group": "192.168.0.1"}}))?.start_http_broker(false);
+    assert!(system.setup().is_err());
+    let system = System::new(jvalue!({"name": "discovery_test", "discovery": {"port": 70000}}))?.start_http_broker(false);
+    assert!(system.setup().is_err());
+    // discoveryが無ければ見つけたシステムのリストは空
+    let system = System::new(jvalue!({"name": "discovery_test"}))?.start_http_broker(false).setup()?;
+    assert_eq!(system.core_broker().lock()?.system_discovered_peers()?, jvalue!([]));
+    Ok(())
+}
@@ -0,0 +1,17 @@
+extern crate juiz_core;
+
+use juiz_core::prelude::*;
+
+/// System::newに渡したマニフェストはCoreWorkerに残り、setup()はそれを見てオブジェクトを作る
+#[test]
+fn system_keeps_manifest_test() -> JuizResult<()> {
+    let system = System::new(jvalue!({"name": "system_manifest_test", "option": {"custom": 1}}))?;
+    let manifest = system.core_broker().lock()?.worker().manifest();
+    assert_eq!(manifest["name"], jvalue!("system_manifest_test"));
+    assert_eq!(manifest["option"]["custom"], jvalue!(1));
+
+    let system = system.start_http_broker(false).setup()?;
+    let manifest = system.core_broker().lock()?.worker().manifest();
+    assert_eq!(manifest["name"], jvalue!("system_manifest_test"));
+    Ok(())
+}
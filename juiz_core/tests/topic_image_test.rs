@@ -0,0 +1,103 @@
+extern crate juiz_core;
+
+use juiz_core::prelude::*;
+use juiz_core::prelude::image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+
+mod common;
+
+const MASTER_NAMESPACE: &str = "juiz_topic_image_master_test.sock";
+const SUB_NAMESPACE: &str = "juiz_topic_image_sub_test.sock";
+const SUB_UUID: &str = "8a1e4f6c-3d2b-4e7a-b5c9-0f1d2e3a4b5c";
+
+fn receiver_function(args: CapsuleMap) -> JuizResult<Capsule> {
+    let v = args.get("input")?.lock_as_image_view(|image| {
+        let p = image.get_pixel(1, 1);
+        jvalue!({"width": image.width(), "height": image.height(), "pixel": [p[0], p[1], p[2]]})
+    })?;
+    Ok(v.into())
+}
+
+fn new_system_with_ipc_broker(mut manifest: Value, namespace: &str) -> JuizResult<System> {
+    common::remove_socket_file(namespace);
+    manifest["brokers"] = jvalue!([{"type_name": "ipc", "name": namespace, "namespace": namespace}]);
+    System::new(manifest)?.start_http_broker(false).setup()
+}
+
+fn topic_name(encoding: &TopicEncoding) -> String {
+    format!("image_{}", encoding.as_str())
+}
+
+#[test]
+fn topic_image_forwarding_test() -> JuizResult<()> {
+    let encodings = [TopicEncoding::Png, TopicEncoding::Jpeg{quality: 80}, TopicEncoding::Raw];
+
+    let sub_system = new_system_with_ipc_broker(jvalue!({"name": "topic_image_sub", "uuid": SUB_UUID}), SUB_NAMESPACE)?;
+    let manifest = jvalue!({
+        "type_name": "image_receiver",
+        // 受け取った画像から作った出力をあとでget_output()で読むので残しておく
+        "use_memo": true,
+        "arguments": [{"name": "input", "type": "image", "description": "received image", "default": {}}],
+    });
+    let pf = process_factory_create(manifest.try_into()?, receiver_function)?;
+    sub_system.core_broker().lock_mut().and_then(|mut cb| {
+        cb.worker_mut().store_mut().processes.register_factory(&"image_receiver".to_owned(), pf)?;
+        for encoding in encodings.iter() {
+            let p = cb.worker_mut().create_process_ref(jvalue!({"type_name": "image_receiver", "name": topic_name(encoding)}).try_into()?)?;
+            cb.worker_mut().process_subscribe_topic(p, &"input".to_owned(), TopicManifest::new(topic_name(encoding).as_str()))?;
+        }
+        Ok(())
+    })?;
+
+    let master_system = new_system_with_ipc_broker(jvalue!({"name": "topic_image_master"}), MASTER_NAMESPACE)?;
+    let pf = process_factory_create(jvalue!({"type_name": "publisher", "arguments": []}).try_into()?, |_| Ok(jvalue!({}).into()))?;
+    master_system.core_broker().lock_mut().and_then(|mut cb| {
+        cb.worker_mut().store_mut().processes.register_factory(&"publisher".to_owned(), pf)?;
+        for encoding in encodings.iter() {
+            let p = cb.worker_mut().create_process_ref(jvalue!({"type_name": "publisher", "name": topic_name(encoding)}).try_into()?)?;
+            cb.worker_mut().process_publish_topic(p, TopicManifest::new(topic_name(encoding).as_str()).encoding(*encoding))?;
+        }
+        Ok(())
+    })?;
+    master_system.core_broker().lock_mut()?.system_add_subsystem(jvalue!({"type_name": "ipc", "name": SUB_NAMESPACE}))?;
+    let master_uuid = master_system.core_broker().lock()?.system_store().uuid()?;
+
+    let image = DynamicImage::ImageRgb8(RgbImage::from_fn(16, 12, |_x, _y| Rgb([200, 120, 40])));
+    for encoding in encodings.iter() {
+        let name = topic_name(encoding);
+        let result = master_system.core_broker().lock_mut()?.topic_request_subscribe(name.as_str(), Some(master_uuid))?;
+        assert_eq!(result["subscribe"], jvalue!(true));
+        let topic = master_system.core_broker().lock_mut()?.worker_mut().create_topic(name.clone())?;
+        assert_eq!(topic.encoding()?, *encoding);
+        topic.push(image.clone().into(), None)?;
+
+        let receiver = sub_system.core_broker().lock()?.worker().process_from_typename_and_name("image_receiver", name.as_str())?;
+        let output = receiver.lock()?.get_output().extract_value()?;
+        assert_eq!(output["width"], jvalue!(16), "encoding={encoding}");
+        assert_eq!(output["height"], jvalue!(12), "encoding={encoding}");
+        let pixel = output["pixel"].as_array().unwrap().iter().map(|v| v.as_i64().unwrap()).collect::<Vec<i64>>();
+        // JPEGは非可逆なので少しずれてよい
+        let tolerance = if matches!(encoding, TopicEncoding::Jpeg{..}) { 8 } else { 0 };
+        for (received, sent) in pixel.iter().zip([200, 120, 40]) {
+            assert!((received - sent).abs() <= tolerance, "encoding={encoding}, pixel={pixel:?}");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn topic_encoding_manifest_test() -> JuizResult<()> {
+    let manifest: ProcessManifest = jvalue!({
+        "type_name": "camera",
+        "arguments": [],
+        "publishes": ["raw_image", {"name": "jpeg_image", "encoding": "jpeg", "quality": 70}, {"name": "png_image", "encoding": "png"}],
+    }).try_into()?;
+    assert_eq!(manifest.publishes[0].encoding, TopicEncoding::Png);
+    assert_eq!(manifest.publishes[1].encoding, TopicEncoding::Jpeg{quality: 70});
+    assert_eq!(manifest.publishes[2].encoding, TopicEncoding::Png);
+    let v: Value = manifest.publishes[1].clone().into();
+    assert_eq!(v, jvalue!({"name": "jpeg_image", "encoding": "jpeg", "quality": 70}));
+
+    assert!(TopicManifest::try_from(jvalue!({"name": "t", "encoding": "gif"})).is_err());
+    assert!(TopicManifest::try_from(jvalue!({"name": "t", "encoding": "jpeg", "quality": 0})).is_err());
+    Ok(())
+}
@@ -0,0 +1,53 @@
+extern crate juiz_core;
+use std::{collections::HashMap, sync::mpsc, time::Duration};
+use juiz_core::{prelude::*, CRUDBrokerProxy, WebSocketBrokerProxy};
+
+mod common;
+
+
+fn setup_system(port: i64) -> JuizResult<(System, Identifier)> {
+    common::setup_increment_system(jvalue!({
+        "type_name": "websocket",
+        "name": format!("127.0.0.1:{port}"),
+        "host": "127.0.0.1",
+        "port": port,
+    }))
+}
+
+#[test]
+fn websocket_broker_crud_and_subscribe_test() -> JuizResult<()> {
+    let (system, id) = setup_system(18090)?;
+    let proxy = WebSocketBrokerProxy::new(&jvalue!({"name": "127.0.0.1:18090"}))?;
+
+    let list = proxy.read("process", "list", HashMap::new())?.extract_value()?;
+    assert!(list.as_array().unwrap().iter().any(|v| v.as_str() == Some(id.as_str())), "process list does not contain {id}. list={list}");
+
+    let (sender, receiver) = mpsc::channel();
+    let subscription_id = proxy.subscribe_process(&id, move |capsule| {
+        let _ = sender.send(capsule.extract_value().unwrap());
+    })?;
+    let process = system.core_broker().lock()?.worker().any_process_from_identifier(&id, false)?;
+    assert_eq!(process.lock()?.destination_connections()?.len(), 1);
+
+    system.core_broker().lock()?.process_execute(&id)?;
+    let pushed = receiver.recv_timeout(Duration::from_secs(3)).expect("output was not pushed via websocket.");
+    assert_eq!(pushed.as_i64(), Some(2));
+
+    proxy.unsubscribe(subscription_id.as_str())?;
+    assert_eq!(process.lock()?.destination_connections()?.len(), 0);
+    Ok(())
+}
+
+#[test]
+fn websocket_broker_invalid_identifier_test() -> JuizResult<()> {
+    let (_system, id) = setup_system(18091)?;
+    let proxy = WebSocketBrokerProxy::new(&jvalue!({"name": "127.0.0.1:18091"}))?;
+
+    let err = proxy.read("process", "profile_full", HashMap::from([("identifier".to_owned(), "not_an_identifier".to_owned())])).unwrap_err();
+    assert!(matches!(err.downcast_ref::<JuizError>(), Some(JuizError::InvalidIdentifierError{..})), "unexpected error {err:?}");
+
+    // 壊れたリクエストのあとも同じ接続で呼べる
+    let profile = proxy.read("process", "profile_full", HashMap::from([("identifier".to_owned(), id.clone())]))?.extract_value()?;
+    assert_eq!(profile["identifier"], jvalue!(id));
+    Ok(())
+}
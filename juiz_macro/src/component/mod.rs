@@ -3,7 +3,7 @@ use serde_json::{Map, Value};
 use quote::{format_ident, quote};
 use syn::{parse_macro_input, ItemFn, Stmt};
 
-use crate::util::parse_attr;
+use crate::util::{parse_attr, abi_descriptor_tokenstream};
 
 
 pub(crate) fn juiz_component_manifest_inner(attr: TokenStream) -> TokenStream {
@@ -132,9 +132,13 @@ pub(crate) fn juiz_component_manifest_inner(attr: TokenStream) -> TokenStream {
     
     item_fn.block.stmts.push(parse_macro_input!(return_token_stream as Stmt));
 
+    // コンポーネントのマニフェストのABI記述子を自動生成する
+    let abi_descriptor = abi_descriptor_tokenstream("component_manifest");
+
     // 最後の最後に全部の関数を並べる。
     let ts = quote! { 
         #item_fn
+        #abi_descriptor
     };
 
     // println!("{}", ts.to_string());
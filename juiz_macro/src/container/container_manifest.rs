@@ -89,6 +89,12 @@ pub(crate) fn construct_manif_tokenstream(function_name: String, manifest_attr:
                     manif = manif.add_image_arg(#arg_name, #description);
                 }
             },
+            "NumericArray" => {
+                construct_manif = quote!{
+                    #construct_manif
+                    manif = manif.add_numeric_array_arg(#arg_name, #description);
+                }
+            },
             _ => {
                 panic!("[process_manifest.rs]自動マニフェスト生成に失敗。対応するデータ型ではありません。 ({type_name:})")
             }
@@ -179,6 +185,12 @@ pub(crate) fn component_construct_manif_tokenstream(function_name: String, manif
                     manif = manif.add_image_arg(#arg_name, #description);
                 }
             },
+            "NumericArray" => {
+                construct_manif = quote!{
+                    #construct_manif
+                    manif = manif.add_numeric_array_arg(#arg_name, #description);
+                }
+            },
             _ => {
                 panic!("[process_manifest.rs]自動マニフェスト生成に失敗。対応するデータ型ではありません。 ({type_name:})")
             }
@@ -6,7 +6,7 @@ use crate::proc_macro::TokenStream;
 use syn::{parse_macro_input, ItemFn, Stmt};
 use crate::util::{get_body_tokenstream, change_argument_to_capsule_map};
 
-use crate::util::parse_attr;
+use crate::util::{parse_attr, abi_descriptor_tokenstream};
 // #use super::process_manifest::{construct_manif_tokenstream, manifest_tokenstream};
 // #use super::decorate_function_arg::{get_body_tokenstream, change_argument_to_capsule_map};
 //use super::gen_process_factory::factory_tokenstream;
@@ -49,12 +49,16 @@ pub(crate) fn juiz_component_container_inner(attr: TokenStream, item: TokenStrea
 
 
     // factoryを自動生成する
-    let fts = component_factory_tokenstream(ast.sig.ident.clone(), factory_name);
+    let fts = component_factory_tokenstream(ast.sig.ident.clone(), factory_name.clone());
     let factory_item_fn : ItemFn = syn::parse_macro_input!(fts as ItemFn);
     
+    // ファクトリのABI記述子を自動生成する
+    let abi_descriptor = abi_descriptor_tokenstream(&factory_name);
+
     // 最後の最後に全部の関数を並べる。
     quote! { 
         #factory_item_fn
+        #abi_descriptor
         #ast
         #manifest2_item_fn
     }.into()
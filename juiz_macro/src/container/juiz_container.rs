@@ -6,7 +6,7 @@ use crate::proc_macro::TokenStream;
 use syn::{parse_macro_input, ItemFn, Stmt};
 use crate::util::{get_body_tokenstream, change_argument_to_capsule_map};
 
-use crate::util::parse_attr;
+use crate::util::{parse_attr, abi_descriptor_tokenstream};
 // #use super::process_manifest::{construct_manif_tokenstream, manifest_tokenstream};
 // #use super::decorate_function_arg::{get_body_tokenstream, change_argument_to_capsule_map};
 //use super::gen_process_factory::factory_tokenstream;
@@ -51,9 +51,13 @@ pub(crate) fn juiz_container_inner(attr: TokenStream, item: TokenStream) -> Toke
     let fts = factory_tokenstream(ast.sig.ident.clone());
     let factory_item_fn : ItemFn = syn::parse_macro_input!(fts as ItemFn);
     
+    // ファクトリのABI記述子を自動生成する
+    let abi_descriptor = abi_descriptor_tokenstream("container_factory");
+
     // 最後の最後に全部の関数を並べる。
     quote! { 
         #factory_item_fn
+        #abi_descriptor
         #ast
         #manifest2_item_fn
     }.into()
@@ -85,6 +85,12 @@ fn construct_manif_inner(_container_type_ident: syn::Ident, _function_name: Stri
                     manif = manif.add_image_arg(#arg_name, #description);
                 }
             }
+            "NumericArray" => {
+                construct_manif = quote!{
+                    #construct_manif
+                    manif = manif.add_numeric_array_arg(#arg_name, #description);
+                }
+            }
             _ => {
                 panic!("[container_process_manifest.rs]自動マニフェスト生成に失敗。対応するデータ型ではありません。 ({type_name:})")
             }
@@ -4,7 +4,7 @@ use quote::{format_ident, quote};
 use crate::proc_macro::TokenStream;
 use syn::{parse_macro_input, ItemFn, Stmt};
 
-use crate::util::parse_attr;
+use crate::util::{parse_attr, abi_descriptor_tokenstream};
 use super::{container_process_manifest::{component_construct_manif_tokenstream, component_manifest_tokenstream}, gen_process_factory::component_factory_tokenstream};
 use crate::util::{get_body_tokenstream, change_container_process_argument_to_capsule_map};
 
@@ -62,14 +62,18 @@ pub(crate) fn juiz_component_container_process_inner(attr: TokenStream, item: To
 
 
     // factoryを自動生成する
-    let fts = component_factory_tokenstream(ast.sig.ident.clone(), factory_name);
+    let fts = component_factory_tokenstream(ast.sig.ident.clone(), factory_name.clone());
     let factory_item_fn : ItemFn = syn::parse_macro_input!(fts as ItemFn);
     
     // println!("{}", manifest2_item_fn.to_token_stream().to_string());
     //println!("ast: {}", ast.to_token_stream().to_string());
+    // ファクトリのABI記述子を自動生成する
+    let abi_descriptor = abi_descriptor_tokenstream(&factory_name);
+
     // 最後の最後に全部の関数を並べる。
     quote! { 
         #factory_item_fn 
+        #abi_descriptor
         #ast
         #manifest2_item_fn
         //#factory_item_fn
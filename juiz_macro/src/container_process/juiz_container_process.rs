@@ -4,7 +4,7 @@ use quote::{format_ident, quote};
 use crate::proc_macro::TokenStream;
 use syn::{parse_macro_input, ItemFn, Stmt};
 
-use crate::util::parse_attr;
+use crate::util::{parse_attr, abi_descriptor_tokenstream};
 use super::container_process_manifest::{construct_manif_tokenstream, manifest_tokenstream};
 use crate::util::{get_body_tokenstream, change_container_process_argument_to_capsule_map};
 use super::gen_process_factory::factory_tokenstream;
@@ -65,9 +65,13 @@ pub(crate) fn juiz_container_process_inner(attr: TokenStream, item: TokenStream)
     let factory_item_fn : ItemFn = syn::parse_macro_input!(fts as ItemFn);
     
     //println!("ast: {}", ast.to_token_stream().to_string());
+    // ファクトリのABI記述子を自動生成する
+    let abi_descriptor = abi_descriptor_tokenstream("container_process_factory");
+
     // 最後の最後に全部の関数を並べる。
     quote! { 
         #factory_item_fn 
+        #abi_descriptor
         #ast
         #manifest2_item_fn
         //#factory_item_fn
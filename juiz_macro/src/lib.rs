@@ -145,6 +145,7 @@ mod process;
 mod container;
 mod container_process;
 mod component;
+mod schema;
 
 use crate::proc_macro::TokenStream;
 
@@ -179,6 +180,35 @@ pub fn juiz_process(attr: TokenStream, item: TokenStream) -> TokenStream {
 }
 
 
+/// プロセスの引数にするユーザー定義の構造体のためのderiveマクロ
+///
+/// `juiz_process`の引数にはserdeで読める構造体を、戻り値にはserdeで書ける型を使えます。
+/// 引数の構造体にこのマクロをつけると、フィールドの型と必須のキーがマニフェストの制約になります。
+/// つけなければ制約の無いobjectの引数 (`Vec`ならarrayの引数) になり、値はserdeで読めるかどうかだけが検査されます。
+///
+/// # Examples
+///
+/// ```ignore
+/// use juiz_sdk::prelude::*;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize, Serialize, JuizSchema)]
+/// struct Point {
+///     x: f64,
+///     y: f64,
+///     label: Option<String>,
+/// }
+///
+/// #[juiz_process]
+/// fn scale(point: Point, factor: f64) -> JuizResult<Point> {
+///     Ok(Point{x: point.x * factor, y: point.y * factor, label: point.label})
+/// }
+/// ```
+#[proc_macro_derive(JuizSchema)]
+pub fn juiz_schema(item: TokenStream) -> TokenStream {
+    schema::juiz_schema_inner(item)
+}
+
 /// コンテナ定義のためのマクロ
 /// 
 /// マクロの引数は省略できます。
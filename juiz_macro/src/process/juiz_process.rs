@@ -4,9 +4,9 @@ use quote::quote;
 use crate::proc_macro::TokenStream;
 use syn::{parse_macro_input, ItemFn, Stmt};
 
-use crate::util::parse_attr;
+use crate::util::{parse_attr, abi_descriptor_tokenstream};
 use super::process_manifest::{construct_manif_tokenstream, manifest_tokenstream};
-use crate::util::{get_body_tokenstream, change_argument_to_capsule_map};
+use crate::util::{get_body_tokenstream, get_body_tokenstream_returning, change_argument_to_capsule_map, change_return_type_to_capsule, argument_statement, exit_tokenstream};
 use super::gen_process_factory::factory_tokenstream;
 
 pub(crate) fn juiz_process_inner(attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -17,25 +17,29 @@ pub(crate) fn juiz_process_inner(attr: TokenStream, item: TokenStream) -> TokenS
     
     // ここで全部 CapsuleMapにしてしまう。元の引数のデータはarg_mapで受け取る
     let arg_map = change_argument_to_capsule_map(&mut ast);
+    // 戻り値がCapsuleでなければserdeでCapsuleにするので、戻り値の型もJuizResult<Capsule>にしてしまう
+    let output_type = change_return_type_to_capsule(&mut ast);
 
     // println!("arg_map!: {arg_map:?}");
     // このあとclearしちゃうのでまずbodyを保存。
-    let body: TokenStream = get_body_tokenstream(&ast);
+    let body: TokenStream = match output_type.as_ref() {
+        Some(t) => get_body_tokenstream_returning(&ast, t),
+        None => get_body_tokenstream(&ast),
+    };
 
     ast.block.stmts.clear();
 
     // 最初に取得した引数のリストから、CapsuleMapから引数それぞれにtry_intoするコードを生成する。
+    // try_intoできない型はserdeで読む。
     for (type_name, arg_name) in arg_map.iter() {
-        let set_original_argument_statement: TokenStream = quote!(
-            let #arg_name : #type_name = __the_only_argument_modified_by_juiz_process_macro__.get(stringify!(#arg_name))?.try_into()?;
-        ).into();
+        let set_original_argument_statement: TokenStream = argument_statement(type_name, arg_name);
         ast.block.stmts.push(parse_macro_input!(set_original_argument_statement as Stmt));
     }
     
     // bodyを返す
     ast.block.stmts.push(parse_macro_input!(body as Stmt));
     // 値を返す部分を生成
-    let exit: TokenStream = quote! { { body() } }.into();
+    let exit: TokenStream = exit_tokenstream(&output_type);
     ast.block.stmts.push(parse_macro_input!(exit as Stmt));
 
 
@@ -52,9 +56,13 @@ pub(crate) fn juiz_process_inner(attr: TokenStream, item: TokenStream) -> TokenS
     let fts = factory_tokenstream(ast.sig.ident.clone());
     let factory_item_fn : ItemFn = syn::parse_macro_input!(fts as ItemFn);
     
+    // ファクトリのABI記述子を自動生成する
+    let abi_descriptor = abi_descriptor_tokenstream("process_factory");
+
     // 最後の最後に全部の関数を並べる。
     quote! { 
         #factory_item_fn 
+        #abi_descriptor
         #ast
         #manifest2_item_fn
         //#factory_item_fn
@@ -1,7 +1,8 @@
-use quote::{format_ident, quote, ToTokens};
+use quote::{format_ident, quote};
 use serde_json::json;
 use crate::proc_macro::TokenStream;
 use syn::TypePath;
+use crate::util::vec_element_typename;
 
 
 pub(crate) fn manifest_tokenstream() -> TokenStream {
@@ -21,6 +22,26 @@ pub(crate) fn component_manifest_tokenstream(proc_type_str: String) -> TokenStre
 }
 
 
+/// serdeで読むユーザー定義の型の引数。
+///
+/// 型と制約はJuizSchemaから作る。JuizSchemaを実装していない型は制約の無いfallbackの型 (objectかarray) の引数にする。
+fn serde_argument_manifest(type_path: &TypePath, arg_name: &str, description: &str, default_value: Option<&serde_json::Value>, fallback: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let default_value = match default_value {
+        Some(v) => {
+            let value_str = serde_json::to_string(v).unwrap();
+            quote!{ Some(serde_json::from_str(#value_str).unwrap()) }
+        },
+        None => quote!{ None },
+    };
+    quote!{
+        juiz_sdk::prelude::ArgumentManifest::new_with_element_schema({
+            #[allow(unused_imports)]
+            use juiz_sdk::prelude::{ProbeJuizSchema, ProbeFallbackSchema};
+            (&&juiz_sdk::prelude::SchemaProbe::<#type_path>::new()).probe_element_schema(#fallback)
+        }, #arg_name, #description, #default_value)
+    }
+}
+
 fn construct_manif_inner(function_name: String, manifest_attr: &serde_json::Value, arg_map: &Vec<(TypePath, syn::Ident)>) -> proc_macro2::TokenStream {
     // attr変数から読み取った値からdescriptionを取得
     let description = manifest_attr.as_object().unwrap().get("description").and_then(|v| { Some(v.clone()) }).or(Some(json!(format!("Default description of Process({function_name})")))).unwrap().as_str().unwrap().to_owned();
@@ -74,12 +95,7 @@ fn construct_manif_inner(function_name: String, manifest_attr: &serde_json::Valu
                 }
             },
             "Vec" => {
-                let arguments = type_path.path.segments.first().unwrap().arguments.clone();
-                // println!("args: {arguments:?}");
-                let vec_element_typename = match arguments.into_token_stream().into_iter().find_map(|v|{ match v {
-                    proc_macro2::TokenTree::Ident(ident) => Some(ident.to_string()),
-                    _ => None,
-                }}) {
+                let vec_element_typename = match vec_element_typename(type_path) {
                     None => panic!("Vecの引数が見つかりません"),
                     Some(v) => v,
                 };
@@ -102,8 +118,18 @@ fn construct_manif_inner(function_name: String, manifest_attr: &serde_json::Valu
                             manif = manif.add_array_arg(#arg_name, #description, serde_json::from_str("[]").unwrap());
                         }
                     }
-                    _ => {
-                        panic!("対応していないVecのElement({vec_element_typename}) です。")
+                    "u8" => { // Vec<u8>はバイト列
+                        construct_manif = quote!{
+                            #construct_manif
+                            manif = manif.add_bytes_arg(#arg_name, #description);
+                        }
+                    }
+                    _ => { // それ以外のVec<T>はserdeで読む
+                        let serde_arg = serde_argument_manifest(type_path, arg_name.as_str(), description, argument_default_value.get(arg_name.as_str()), quote!{ juiz_sdk::prelude::ArgumentType::Array });
+                        construct_manif = quote!{
+                            #construct_manif
+                            manif = manif.add_arg(#serde_arg);
+                        }
                     }
                 }
             }
@@ -122,8 +148,18 @@ fn construct_manif_inner(function_name: String, manifest_attr: &serde_json::Valu
                     manif = manif.add_image_arg(#arg_name, #description);
                 }
             },
-            _ => {
-                panic!("[process_manifest.rs]自動マニフェスト生成に失敗。対応するデータ型ではありません。 ({type_name:}/(type_path={type_path:?}))")
+            "NumericArray" => {
+                construct_manif = quote!{
+                    #construct_manif
+                    manif = manif.add_numeric_array_arg(#arg_name, #description);
+                }
+            },
+            _ => { // それ以外の型はserdeで読むユーザー定義の型として扱う
+                let serde_arg = serde_argument_manifest(type_path, arg_name.as_str(), description, argument_default_value.get(arg_name.as_str()), quote!{ juiz_sdk::prelude::ArgumentType::Object });
+                construct_manif = quote!{
+                    #construct_manif
+                    manif = manif.add_arg(#serde_arg);
+                }
             }
         }
     }
@@ -0,0 +1,42 @@
+use quote::quote;
+use crate::proc_macro::TokenStream;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+pub(crate) fn juiz_schema_inner(item: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(item as DeriveInput);
+    let ident = ast.ident.clone();
+    let fields = match &ast.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => named.named.iter().collect::<Vec<_>>(),
+            _ => panic!("JuizSchemaは名前付きのフィールドを持つ構造体にだけ使えます。({ident})"),
+        },
+        _ => panic!("JuizSchemaは構造体にだけ使えます。({ident})"),
+    };
+
+    // フィールドごとに型と制約を足していく。Option以外のフィールドは必須のキーにする
+    let mut properties = quote!{};
+    for field in fields {
+        let field_name = field.ident.as_ref().unwrap().to_string();
+        let field_type = &field.ty;
+        properties = quote!{
+            #properties
+            if !<#field_type as juiz_sdk::prelude::JuizSchema>::is_optional() {
+                required.push(#field_name);
+            }
+            schema = schema.property(#field_name, <#field_type as juiz_sdk::prelude::JuizSchema>::element_schema());
+        };
+    }
+
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    quote!{
+        impl #impl_generics juiz_sdk::prelude::JuizSchema for #ident #ty_generics #where_clause {
+            #[allow(unused_mut)]
+            fn element_schema() -> juiz_sdk::prelude::ElementSchema {
+                let mut schema = juiz_sdk::prelude::ArgumentSchema::new();
+                let mut required: Vec<&str> = Vec::new();
+                #properties
+                juiz_sdk::prelude::ElementSchema::new(juiz_sdk::prelude::ArgumentType::Object).schema(schema.required(&required))
+            }
+        }
+    }.into()
+}
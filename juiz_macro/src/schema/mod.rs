@@ -0,0 +1,3 @@
+mod juiz_schema;
+
+pub(crate) use juiz_schema::juiz_schema_inner;
@@ -0,0 +1,10 @@
+use quote::{format_ident, quote};
+
+/// ファクトリ関数(factory_name)に対応するABI記述子の関数(factory_name + "_abi")を生成する。
+/// 読み込む側はファクトリを呼ぶ前にこの記述子でrustcやjuiz_sdkの版を確かめる。
+pub(crate) fn abi_descriptor_tokenstream(factory_name: &str) -> proc_macro2::TokenStream {
+    let abi_ident = format_ident!("{}_abi", factory_name);
+    quote!{
+        juiz_sdk::export_abi_descriptor!(#abi_ident);
+    }
+}
@@ -5,7 +5,7 @@ use std::collections::HashMap;
 
 use quote::quote;
 use crate::proc_macro::TokenStream;
-use syn::{parse_quote, ItemFn, TypePath};
+use syn::{parse_quote, GenericArgument, ItemFn, PathArguments, ReturnType, Type, TypePath};
 use crate::util::parse_arg_map;
 
 use super::parse_arg_map_skip_first;
@@ -53,3 +53,74 @@ pub(crate) fn get_body_tokenstream(ast: &ItemFn) -> TokenStream {
         let mut body = || { #body };
     }.into()
 }
+
+/// Vec<T>のTの名前。Tがパスならその最後のセグメントの名前を返す (`Vec<serde_json::Value>`なら"Value")
+pub(crate) fn vec_element_typename(type_path: &TypePath) -> Option<String> {
+    let PathArguments::AngleBracketed(arguments) = &type_path.path.segments.last()?.arguments else {
+        return None;
+    };
+    match arguments.args.first()? {
+        GenericArgument::Type(Type::Path(element)) => element.path.segments.last().map(|seg| seg.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// CapsulePtrからtry_intoで変換できる型か。そうでなければserdeで変換する
+pub(crate) fn is_builtin_argument_type(type_path: &TypePath) -> bool {
+    let type_name = type_path.path.segments.iter().map(|seg| {seg.ident.to_string()}).collect::<Vec<String>>().join("::");
+    match type_name.as_str() {
+        "bool" | "i64" | "f64" | "String" | "Value" | "DynamicImage" | "NumericArray" => true,
+        "Vec" => matches!(vec_element_typename(type_path).as_deref(), Some("Value" | "f64" | "i64" | "u8")),
+        _ => false,
+    }
+}
+
+/// CapsuleMapから引数を1つ取り出す文
+pub(crate) fn argument_statement(type_path: &TypePath, arg_name: &syn::Ident) -> TokenStream {
+    if is_builtin_argument_type(type_path) {
+        quote!(
+            let #arg_name : #type_path = __the_only_argument_modified_by_juiz_process_macro__.get(stringify!(#arg_name))?.try_into()?;
+        ).into()
+    } else {
+        quote!(
+            let #arg_name : #type_path = juiz_sdk::prelude::capsule_ptr_to_deserialize(__the_only_argument_modified_by_juiz_process_macro__.get(stringify!(#arg_name))?)?;
+        ).into()
+    }
+}
+
+/// 戻り値がJuizResult<Capsule>でなければJuizResult<Capsule>に書き換えて、元のJuizResult<T>の型を返す
+pub(crate) fn change_return_type_to_capsule(ast: &mut ItemFn) -> Option<Type> {
+    let ReturnType::Type(_, output_type) = &ast.sig.output else { return None; };
+    let Type::Path(output_path) = output_type.as_ref() else { return None; };
+    let last_segment = output_path.path.segments.last()?;
+    let PathArguments::AngleBracketed(arguments) = &last_segment.arguments else { return None; };
+    let Some(GenericArgument::Type(Type::Path(value_path))) = arguments.args.first() else { return None; };
+    if value_path.path.segments.last().map(|seg| seg.ident == "Capsule").unwrap_or(true) {
+        return None;
+    }
+    let original = output_type.as_ref().clone();
+    ast.sig.output = parse_quote!( -> juiz_sdk::prelude::JuizResult<juiz_sdk::prelude::Capsule> );
+    Some(original)
+}
+
+/// get_body_tokenstreamと同じだが、クロージャの戻り値の型を書いておく
+pub(crate) fn get_body_tokenstream_returning(ast: &ItemFn, output_type: &Type) -> TokenStream {
+    let mut body = quote! {};
+    for s in &ast.block.stmts {
+        body = quote! {
+            #body
+            #s
+        };
+    }
+    quote! {
+        let mut body = || -> #output_type { #body };
+    }.into()
+}
+
+/// 本体を呼んで値を返す式。戻り値を書き換えたときはserdeでCapsuleにする
+pub(crate) fn exit_tokenstream(output_type: &Option<Type>) -> TokenStream {
+    match output_type {
+        None => quote! { { body() } }.into(),
+        Some(_) => quote! { { juiz_sdk::prelude::serialize_to_capsule(&body()?) } }.into(),
+    }
+}
@@ -3,7 +3,10 @@
 mod attr_parser;
 mod arg_parser;
 mod decorate_function_arg;
+mod abi_descriptor;
 
 pub(crate) use attr_parser::parse_attr;
 pub(crate) use arg_parser::{parse_arg_map, parse_arg_map_skip_first};
-pub(crate) use decorate_function_arg::{change_argument_to_capsule_map, change_container_process_argument_to_capsule_map, get_body_tokenstream};
\ No newline at end of file
+pub(crate) use decorate_function_arg::{change_argument_to_capsule_map, change_container_process_argument_to_capsule_map, get_body_tokenstream};
+pub(crate) use decorate_function_arg::{argument_statement, change_return_type_to_capsule, exit_tokenstream, get_body_tokenstream_returning, vec_element_typename};
+pub(crate) use abi_descriptor::abi_descriptor_tokenstream;
@@ -1,5 +1,6 @@
 use crate::prelude::*;
 use super::connection_manifest::ConnectionManifest;
+use super::connection_qos::ConnectionQoS;
 
 
 #[derive(Clone)]
@@ -86,6 +87,10 @@ impl ConnectionCore {
         self.manifest.connection_type.clone()
     }   
 
+    pub fn qos(&self) -> &ConnectionQoS {
+        &self.manifest.qos
+    }
+
     pub fn profile_full(&self) -> JuizResult<Value> {
         Ok(jvalue!({
             //"identifier": self.core.identifier(),
@@ -94,6 +99,7 @@ impl ConnectionCore {
             "arg_name": self.arg_name().to_owned(),
             "destination_identifier": self.destination_identifier().to_owned(),
             "source_process_identifier": self.source_identifier().to_owned(),
+            "qos": Value::from(self.qos().clone()),
         }).into())
     }
 }
@@ -4,6 +4,7 @@ use serde_json::{json, Map};
 use crate::{prelude::Identifier, result::{JuizError, JuizResult}, value::{CapsuleMap, Value}};
 
 use super::connection_type::ConnectionType;
+use super::connection_qos::ConnectionQoS;
 
 
 #[derive(Clone, Debug)]
@@ -13,6 +14,7 @@ pub struct ConnectionManifest {
     pub source_process_id: Identifier,
     pub destination_process_id: Identifier,
     pub arg_name: String,
+    pub qos: ConnectionQoS,
 }
 
 impl Into<Value> for ConnectionManifest {
@@ -25,6 +27,9 @@ impl Into<Value> for ConnectionManifest {
         if self.identifier.is_some() {
             map.insert("identifier".to_owned(), self.identifier.unwrap().into());
         }
+        if !self.qos.is_default() {
+            map.insert("qos".to_owned(), self.qos.into());
+        }
         map.into()
     }
 }
@@ -38,7 +43,11 @@ impl TryFrom<CapsuleMap> for ConnectionManifest {
             identifier: value.get_str("identifier").ok(),
             source_process_id: value.get_str("source")?,
             destination_process_id: value.get_str("destination")?,
-            arg_name:  value.get_str("arg_name")?
+            arg_name:  value.get_str("arg_name")?,
+            qos: match value.get("qos") {
+                Ok(qos) => qos.lock_as_value(|v| ConnectionQoS::try_from(v))??,
+                Err(_) => ConnectionQoS::default(),
+            },
         })
     }
 }
@@ -84,6 +93,10 @@ impl TryFrom<Value> for ConnectionManifest {
                 let source_process_id = value_to_identifier(vobj.get("source"))?;
                 // = vobj.get("source").ok_or_else(err_handle)?.as_str().ok_or_else(err_handle)?.to_owned();
                 let destination_process_id = value_to_identifier(vobj.get("destination"))?;
+                let qos = match vobj.get("qos") {
+                    Some(v) => ConnectionQoS::try_from(v)?,
+                    None => ConnectionQoS::default(),
+                };
                 //let connection_type = vobj.get("type").ok_or_else(err_handle)?.as_str().ok_or_else(err_handle)?.to_owned();
                 Ok( ConnectionManifest{
                     identifier,
@@ -91,6 +104,7 @@ impl TryFrom<Value> for ConnectionManifest {
                     source_process_id,
                     destination_process_id,
                     arg_name,
+                    qos,
                 } )
             }
             None => todo!(),
@@ -106,9 +120,15 @@ impl ConnectionManifest {
             connection_type,
             source_process_id,
             destination_process_id,
-            arg_name
+            arg_name,
+            qos: ConnectionQoS::default(),
         }
     }
+
+    pub fn with_qos(mut self, qos: ConnectionQoS) -> Self {
+        self.qos = qos;
+        self
+    }
 }
 
 impl Display for ConnectionManifest {
@@ -0,0 +1,108 @@
+
+use std::{fmt::Display, time::Duration};
+use anyhow::anyhow;
+use serde_json::Map;
+use crate::{result::{JuizError, JuizResult}, value::Value};
+
+
+/// キューがいっぱいの時の振る舞い
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum QueuePolicy {
+    /// 一番古いデータを捨てて新しいデータを入れる
+    #[default]
+    DropOldest,
+    /// 新しいデータを捨てる
+    DropNewest,
+    /// 空きができるまで送り手を待たせる
+    Block,
+}
+
+impl QueuePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueuePolicy::DropOldest => "drop_oldest",
+            QueuePolicy::DropNewest => "drop_newest",
+            QueuePolicy::Block => "block",
+        }
+    }
+}
+
+impl TryFrom<&str> for QueuePolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "drop_oldest" => Ok(QueuePolicy::DropOldest),
+            "drop_newest" => Ok(QueuePolicy::DropNewest),
+            "block" => Ok(QueuePolicy::Block),
+            _ => Err(anyhow!(JuizError::InvalidArgumentError{message: format!("Unknown queue policy '{value}'. Use 'drop_oldest', 'drop_newest' or 'block'.")}))
+        }
+    }
+}
+
+impl Display for QueuePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Push型接続のQoS設定
+///
+/// queue_depthが0の時は従来通り送り手のスレッドで受け手に直接pushする。
+/// 1以上の時は接続ごとのキューに積んで、別スレッドから受け手に配送する。
+/// min_periodを指定すると受け手へのpushの間隔がその時間以上になる。
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ConnectionQoS {
+    pub queue_depth: usize,
+    pub policy: QueuePolicy,
+    pub min_period: Option<Duration>,
+}
+
+impl ConnectionQoS {
+
+    pub fn new(queue_depth: usize, policy: QueuePolicy, min_period: Option<Duration>) -> Self {
+        Self { queue_depth, policy, min_period }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == ConnectionQoS::default()
+    }
+
+    pub fn is_queued(&self) -> bool {
+        self.queue_depth > 0
+    }
+}
+
+impl From<ConnectionQoS> for Value {
+    fn from(qos: ConnectionQoS) -> Self {
+        let mut map: Map<String, Value> = Map::new();
+        map.insert("queue_depth".to_owned(), qos.queue_depth.into());
+        map.insert("policy".to_owned(), qos.policy.as_str().into());
+        if let Some(period) = qos.min_period {
+            map.insert("min_period".to_owned(), period.as_secs_f64().into());
+        }
+        map.into()
+    }
+}
+
+impl TryFrom<&Value> for ConnectionQoS {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> JuizResult<Self> {
+        let err = || anyhow!(JuizError::InvalidArgumentError{message: format!("Conversion faild Value({value:?}) -> ConnectionQoS.")});
+        let obj = value.as_object().ok_or_else(err)?;
+        let queue_depth = match obj.get("queue_depth") {
+            Some(v) => v.as_u64().ok_or_else(err)? as usize,
+            None => 0,
+        };
+        let policy = match obj.get("policy") {
+            Some(v) => v.as_str().ok_or_else(err)?.try_into()?,
+            None => QueuePolicy::default(),
+        };
+        let min_period = match obj.get("min_period") {
+            Some(v) => Some(Duration::try_from_secs_f64(v.as_f64().ok_or_else(err)?).map_err(|_| err())?),
+            None => None,
+        };
+        Ok(ConnectionQoS { queue_depth, policy, min_period })
+    }
+}
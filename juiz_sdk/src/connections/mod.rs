@@ -5,6 +5,7 @@ mod connection_core;
 pub mod connection;
 mod connection_type;
 mod connection_manifest;
+mod connection_qos;
 
 pub mod destination_connection;
 pub mod source_connection;
@@ -13,5 +14,6 @@ pub use connection_core::ConnectionCore;
 pub use connection::Connection;
 pub use connection_manifest::ConnectionManifest;
 pub use connection_type::ConnectionType;
+pub use connection_qos::{ConnectionQoS, QueuePolicy};
 pub use source_connection::SourceConnection;
 pub use destination_connection::DestinationConnection;
@@ -4,6 +4,7 @@
 use crate::prelude::*;
 
 use super::connection::Connection;
+use crate::processes::ProcessPtr;
 
 
 pub trait SourceConnection : Connection {
@@ -15,5 +16,8 @@ pub trait SourceConnection : Connection {
     // fn source_process_id(&self) -> &Identifier;
 
     fn pull(&self) -> JuizResult<CapsulePtr>;
+
+    /// 接続元のプロセス
+    fn source_process(&self) -> ProcessPtr;
 }
 
@@ -0,0 +1,176 @@
+//! プラグインのABIの互換性の確認
+//!
+//! `extern "Rust"`のファクトリ関数は`Arc<Mutex<dyn ...>>`などRustの型をそのまま受け渡すので、
+//! 違うrustcや違うjuiz_sdkでビルドしたプラグインを呼ぶと壊れる。
+//! そこでファクトリのシンボル`xxx`ごとに`xxx_abi`という`extern "C"`の関数でABI記述子を公開し、
+//! 読み込む側は自分の記述子と比べてからファクトリを呼ぶ。
+//! `juiz_macro`のマクロは記述子の関数も生成する。手で書いたファクトリには[`export_abi_descriptor!`]を使う。
+//!
+//! ```ignore
+//! #[no_mangle]
+//! pub unsafe extern "Rust" fn execution_context_factory() -> JuizResult<Arc<Mutex<dyn ExecutionContextFactory>>> {
+//!     ...
+//! }
+//! export_abi_descriptor!(execution_context_factory_abi);
+//! ```
+
+use std::{ffi::{c_char, CStr}, fmt::Display, mem::{align_of, size_of}};
+
+use crate::prelude::*;
+
+/// 記述子そのものの形式の版。AbiDescriptorのフィールドを変えたら上げる
+pub const ABI_DESCRIPTOR_VERSION: u32 = 1;
+
+/// ファクトリ関数の名前からABI記述子の関数の名前を作る
+pub fn abi_symbol_name(symbol_name: &str) -> String {
+    symbol_name.to_owned() + "_abi"
+}
+
+/// プラグインが公開するABI記述子。rustcが違っても読めるようにCの表現にする
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct AbiDescriptor {
+    pub descriptor_version: u32,
+    /// `rustc --version`の出力。ヌル終端
+    pub rustc_version: *const c_char,
+    /// juiz_sdkのバージョン。ヌル終端
+    pub sdk_version: *const c_char,
+    /// プラグインとやり取りする型の大きさとアラインメントのハッシュ
+    pub layout_hash: u64,
+}
+
+const RUSTC_VERSION: &str = concat!(env!("JUIZ_RUSTC_VERSION"), "\0");
+const SDK_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+/// FNV-1aで(大きさ, アラインメント)の列をハッシュする
+const fn layout_hash(layouts: &[(usize, usize)]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut i = 0;
+    while i < layouts.len() {
+        let words = [layouts[i].0 as u64, layouts[i].1 as u64];
+        let mut w = 0;
+        while w < words.len() {
+            let bytes = words[w].to_le_bytes();
+            let mut b = 0;
+            while b < bytes.len() {
+                hash ^= bytes[b] as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+                b += 1;
+            }
+            w += 1;
+        }
+        i += 1;
+    }
+    hash
+}
+
+macro_rules! layouts {
+    ($($t:ty),* $(,)?) => { [$((size_of::<$t>(), align_of::<$t>())),*] };
+}
+
+/// ファクトリ関数の引数や戻り値に現れる型
+const LAYOUT_HASH: u64 = layout_hash(&layouts!(
+    Value,
+    Capsule,
+    CapsulePtr,
+    CapsuleMap,
+    ProcessManifest,
+    ContainerManifest,
+    ComponentManifest,
+    ContainerPtr,
+    JuizResult<Capsule>,
+    JuizResult<ProcessFactoryStruct>,
+    JuizResult<ContainerFactoryStruct>,
+    JuizResult<ContainerProcessFactoryStruct>,
+    JuizResult<ContainerStackFactoryStruct>,
+));
+
+/// このjuiz_sdkをビルドしたときのABI記述子
+pub fn abi_descriptor() -> AbiDescriptor {
+    AbiDescriptor {
+        descriptor_version: ABI_DESCRIPTOR_VERSION,
+        rustc_version: RUSTC_VERSION.as_ptr() as *const c_char,
+        sdk_version: SDK_VERSION.as_ptr() as *const c_char,
+        layout_hash: LAYOUT_HASH,
+    }
+}
+
+/// 比べやすいように所有した文字列にしたABI記述子
+#[derive(Clone, Debug, PartialEq)]
+pub struct AbiInfo {
+    pub descriptor_version: u32,
+    pub rustc_version: String,
+    pub sdk_version: String,
+    pub layout_hash: u64,
+}
+
+impl AbiInfo {
+
+    /// このjuiz_sdkのABI
+    pub fn current() -> Self {
+        // 自分で作った記述子なので文字列は正しい
+        unsafe { Self::from_descriptor(&abi_descriptor()) }
+    }
+
+    /// # Safety
+    /// 文字列のポインタはヌルか、ヌル終端の文字列を指していること
+    pub unsafe fn from_descriptor(descriptor: &AbiDescriptor) -> Self {
+        let to_string = |p: *const c_char| if p.is_null() { String::new() } else { CStr::from_ptr(p).to_string_lossy().into_owned() };
+        AbiInfo {
+            descriptor_version: descriptor.descriptor_version,
+            rustc_version: to_string(descriptor.rustc_version),
+            sdk_version: to_string(descriptor.sdk_version),
+            layout_hash: descriptor.layout_hash,
+        }
+    }
+
+    /// 互換でなければその理由
+    pub fn incompatibility(&self, plugin: &AbiInfo) -> Option<String> {
+        if self.descriptor_version != plugin.descriptor_version {
+            Some(format!("ABI descriptor version differs (expected {}, plugin {})", self.descriptor_version, plugin.descriptor_version))
+        } else if self.rustc_version != plugin.rustc_version {
+            Some(format!("rustc differs (expected '{}', plugin '{}')", self.rustc_version, plugin.rustc_version))
+        } else if self.sdk_version != plugin.sdk_version {
+            Some(format!("juiz_sdk version differs (expected {}, plugin {})", self.sdk_version, plugin.sdk_version))
+        } else if self.layout_hash != plugin.layout_hash {
+            Some(format!("type layout hash differs (expected {:016x}, plugin {:016x})", self.layout_hash, plugin.layout_hash))
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for AbiInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rustc='{}', juiz_sdk={}, layout_hash={:016x}", self.rustc_version, self.sdk_version, self.layout_hash)
+    }
+}
+
+/// ファクトリ関数`xxx`に対応するABI記述子の関数`xxx_abi`を公開する
+#[macro_export]
+macro_rules! export_abi_descriptor {
+    ($name:ident) => {
+        #[no_mangle]
+        pub extern "C" fn $name() -> $crate::factory::abi::AbiDescriptor {
+            $crate::factory::abi::abi_descriptor()
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abi_info_test() {
+        let current = AbiInfo::current();
+        assert_eq!(current.sdk_version, env!("CARGO_PKG_VERSION"));
+        assert!(!current.rustc_version.is_empty());
+        assert_eq!(current.incompatibility(&current.clone()), None);
+
+        let other_rustc = AbiInfo{rustc_version: "rustc 0.0.0".to_owned(), ..current.clone()};
+        assert!(current.incompatibility(&other_rustc).unwrap().contains("rustc"));
+        let other_layout = AbiInfo{layout_hash: current.layout_hash ^ 1, ..current.clone()};
+        assert!(current.incompatibility(&other_layout).unwrap().contains("layout"));
+    }
+}
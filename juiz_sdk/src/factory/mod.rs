@@ -2,6 +2,8 @@
 //! 
 //! 
 
+pub mod abi;
+
 use std::sync::Arc;
 use anyhow::anyhow;
 use crate::{containers::ContainerImpl, prelude::*};
@@ -3,6 +3,7 @@ use std::fmt::Display;
 
 use crate::prelude::*;
 use super::manifest_description::Description;
+use super::argument_schema::{ArgumentSchema, JuizSchema};
 use anyhow::anyhow;
 
 
@@ -15,6 +16,8 @@ pub enum ArgumentType {
     Array,
     Object,
     Image,
+    Bytes,
+    NumericArray,
 }
 
 impl ArgumentType {
@@ -28,6 +31,8 @@ impl ArgumentType {
             ArgumentType::Array => "array",
             ArgumentType::Object => "object",
             ArgumentType::Image => "image",
+            ArgumentType::Bytes => "bytes",
+            ArgumentType::NumericArray => "numeric_array",
         }
     }
 }
@@ -50,6 +55,8 @@ impl TryFrom<&str> for ArgumentType {
             "array" => Ok(ArgumentType::Array),
             "object" => Ok(ArgumentType::Object),
             "image" => Ok(ArgumentType::Image),
+            "bytes" => Ok(ArgumentType::Bytes),
+            "numeric_array" => Ok(ArgumentType::NumericArray),
             _ => Err(anyhow!(JuizError::ProcessManifestInvalidError{message: "Argument type is invalid in ArgumentManifest in ProcessManifest.".to_owned()}))
         }
     }
@@ -60,7 +67,8 @@ pub struct ArgumentManifest {
     pub type_name: ArgumentType,
     pub name: String,
     pub description: Description,
-    pub default: Value
+    pub default: Value,
+    pub schema: ArgumentSchema,
 }
 
 impl Display for ArgumentManifest {
@@ -81,6 +89,8 @@ fn type_check(arg_type: &ArgumentType, value: &Value) -> JuizResult<()> {
         ArgumentType::Array => if value.is_array() {Ok(())} else { ret_err() },
         ArgumentType::Object => if value.is_object() {Ok(())} else { ret_err() },
         ArgumentType::Image => if value.is_null() || value.is_object() {Ok(())} else { ret_err() },
+        // バイト列と数値の配列の既定値は無いか、Base64で包んだJSON
+        ArgumentType::Bytes | ArgumentType::NumericArray => if value.is_null() || value.is_object() {Ok(())} else { ret_err() },
     }
 }
 impl ArgumentManifest {
@@ -90,7 +100,8 @@ impl ArgumentManifest {
             type_name,
             name: name.to_owned(),
             description,
-            default
+            default,
+            schema: ArgumentSchema::new(),
         }
     }
 
@@ -100,15 +111,57 @@ impl ArgumentManifest {
             type_name,
             name: name.to_owned(),
             description,
-            default
+            default,
+            schema: ArgumentSchema::new(),
         })
     }
 
+    /// JuizSchemaを実装した型から作る。既定値が無ければ型ごとの空の値にする
+    pub fn new_with_schema<T: JuizSchema>(name: &str, description: &str, default: Option<Value>) -> Self {
+        Self::new_with_element_schema(T::element_schema(), name, description, default)
+    }
+
+    /// 要素の型と制約から作る。既定値が無ければ型ごとの空の値 (nullを通すならnull) にする。#[juiz_process]で使う
+    pub fn new_with_element_schema(element: ElementSchema, name: &str, description: &str, default: Option<Value>) -> Self {
+        let default = default.unwrap_or_else(|| if element.nullable {
+            Value::Null
+        } else {
+            match element.type_name {
+                ArgumentType::Bool => jvalue!(false),
+                ArgumentType::Int => jvalue!(0),
+                ArgumentType::Float => jvalue!(0.0),
+                ArgumentType::String => jvalue!(""),
+                ArgumentType::Array => jvalue!([]),
+                _ => jvalue!({}),
+            }
+        });
+        Self::new(element.type_name, name, description.into(), default).schema(element.schema)
+    }
+
     pub fn description(mut self, description: &str) -> Self {
         self.description = description.into();
         self
     }
 
+    pub fn schema(mut self, schema: ArgumentSchema) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// 値がスキーマの制約を満たすか調べる。JSONの値でなければ調べない
+    pub fn check_value(&self, value: &Value) -> JuizResult<()> {
+        self.schema.check(self.name.as_str(), value).map_err(|message| {
+            anyhow!(JuizError::ArgumentConstraintViolationError{name: self.name.clone(), message})
+        })
+    }
+
+    pub fn check(&self, value: &CapsulePtr) -> JuizResult<()> {
+        if self.schema.is_empty() || !value.is_value()? {
+            return Ok(());
+        }
+        value.lock_as_value(|v| self.check_value(v))?
+    }
+
     pub fn new_bool(name: &str, default: bool) -> Self {
         Self::new(ArgumentType::Bool, name, "".into(), default.into())
     }
@@ -136,17 +189,29 @@ impl ArgumentManifest {
     pub fn new_image(name: &str) -> Self {
         Self::new(ArgumentType::String, name, "".into(), jvalue!({}))
     }
+
+    pub fn new_bytes(name: &str) -> Self {
+        Self::new(ArgumentType::Bytes, name, "".into(), Value::Null)
+    }
+
+    pub fn new_numeric_array(name: &str) -> Self {
+        Self::new(ArgumentType::NumericArray, name, "".into(), Value::Null)
+    }
     
 }
 
 impl Into<Value> for ArgumentManifest {
     fn into(self) -> Value {
-        jvalue!({
+        let mut v = jvalue!({
             "name": self.name,
             "type": self.type_name.as_str(),
             "description": self.description.to_str(),
             "default": self.default,
-        })
+        });
+        if let Some(map) = v.as_object_mut() {
+            self.schema.write_to(map);
+        }
+        v
     }
 }
 
@@ -160,8 +225,16 @@ impl Into<Value> for ArgumentManifest {
 ///   "default": 1
 /// });
 /// let arg: ArgumentManifest = arg_value.try_into()?;
-/// 
-/// 
+///
+/// // 取りうる値や範囲などの制約も書ける
+/// let mode: ArgumentManifest = jvalue!({
+///   "type": "string",
+///   "name": "mode",
+///   "default": "fast",
+///   "enum": ["fast", "accurate"]
+/// }).try_into()?;
+/// assert!(mode.check_value(&jvalue!("accurate")).is_ok());
+/// assert!(mode.check_value(&jvalue!("slow")).is_err());
 /// Ok(())}
 /// ```
 impl TryFrom<Value> for ArgumentManifest {
@@ -175,12 +248,18 @@ impl TryFrom<Value> for ArgumentManifest {
             Err(e)
         })?;
         let default_value = obj_get(&value, "default").or_else(|e| {
-            if type_name == ArgumentType::Image {
+            if matches!(type_name, ArgumentType::Image | ArgumentType::Bytes | ArgumentType::NumericArray) {
                 return Ok(&Value::Null);
             }
             log::error!("TryFrom<Value> for ArgumentManifest::try_from({value:?}) failed. {e}");
             Err(e)
         })?;
-        ArgumentManifest::new_with_check(type_name, name, description.into(), default_value.clone())
+        let manifest = ArgumentManifest::new_with_check(type_name, name, description.into(), default_value.clone())?
+            .schema(ArgumentSchema::try_from(&value)?);
+        // 既定値も制約を満たしていなければならない。nullや{}は既定値が無い印なので調べない
+        if !default_value.is_null() && default_value != &jvalue!({}) {
+            manifest.check_value(default_value)?;
+        }
+        Ok(manifest)
     }
 }
@@ -0,0 +1,384 @@
+//! 引数の値に課す制約 (スキーマ)
+//!
+//! ArgumentManifestの型だけでは表せない制約を書く。マニフェストのJSONでは引数と同じ階層に次のキーで書く。
+//!
+//! - `"enum"`: 取りうる値の一覧
+//! - `"minimum"`, `"maximum"`: 数値の下限と上限 (両端を含む)
+//! - `"items"`: 配列の要素の型と制約。`{"type": "float", "minimum": 0.0}` のように書く
+//! - `"min_items"`, `"max_items"`: 配列の長さの下限と上限
+//! - `"required"`: オブジェクトが必ず持つべきキーの一覧
+//! - `"properties"`: オブジェクトの各キーの型と制約。`{"x": {"type": "float"}}` のように書く
+//!
+//! `"items"` と `"properties"` の要素には `"nullable": true` を書ける。書くと型に関わらずnullも通す。
+//!
+//! 制約はプロセスの呼び出し前 (check_manifest_before_call) と p_apply で検査される。
+//! JSONの値以外 (画像など) は検査しない。
+
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use serde_json::Map;
+use crate::prelude::*;
+
+/// 配列の要素やオブジェクトのキーの型と制約
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElementSchema {
+    pub type_name: ArgumentType,
+    pub nullable: bool,
+    pub schema: ArgumentSchema,
+}
+
+impl ElementSchema {
+
+    pub fn new(type_name: ArgumentType) -> Self {
+        ElementSchema { type_name, nullable: false, schema: ArgumentSchema::new() }
+    }
+
+    /// nullも通すか。OptionのJuizSchemaで使う
+    pub fn nullable(mut self, nullable: bool) -> Self {
+        self.nullable = nullable;
+        self
+    }
+
+    pub fn schema(mut self, schema: ArgumentSchema) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    fn check(&self, path: &str, value: &Value) -> Result<(), String> {
+        if self.nullable && value.is_null() {
+            return Ok(());
+        }
+        if !kind_matches(&self.type_name, value) {
+            return Err(format!("{path} must be {}, but {value} is given", self.type_name));
+        }
+        self.schema.check(path, value)
+    }
+}
+
+impl From<ElementSchema> for Value {
+    fn from(element: ElementSchema) -> Self {
+        let mut v = jvalue!({"type": element.type_name.as_str()});
+        if let Some(map) = v.as_object_mut() {
+            if element.nullable {
+                map.insert("nullable".to_owned(), true.into());
+            }
+            element.schema.write_to(map);
+        }
+        v
+    }
+}
+
+impl TryFrom<&Value> for ElementSchema {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let type_name: ArgumentType = obj_get_str(value, "type")?.try_into()?;
+        let nullable = match value.get("nullable") {
+            None => false,
+            Some(v) => v.as_bool().ok_or_else(|| invalid_schema("nullable", value))?,
+        };
+        Ok(ElementSchema { type_name, nullable, schema: value.try_into()? })
+    }
+}
+
+/// 引数の値に課す制約。何も書かれていなければ検査しない
+///
+/// ```
+/// use juiz_sdk::prelude::*;
+/// let schema = ArgumentSchema::new()
+///     .items(ElementSchema::new(ArgumentType::Float).schema(ArgumentSchema::new().range(Some(0.0), Some(1.0))))
+///     .length(Some(3), Some(3));
+/// assert!(schema.check("color", &jvalue!([0.0, 0.5, 1.0])).is_ok());
+/// assert!(schema.check("color", &jvalue!([0.0, 0.5])).is_err());
+/// assert!(schema.check("color", &jvalue!([0.0, 0.5, 2.0])).is_err());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ArgumentSchema {
+    pub enumeration: Option<Vec<Value>>,
+    pub minimum: Option<f64>,
+    pub maximum: Option<f64>,
+    pub items: Option<Box<ElementSchema>>,
+    pub min_items: Option<usize>,
+    pub max_items: Option<usize>,
+    pub required: Vec<String>,
+    pub properties: BTreeMap<String, ElementSchema>,
+}
+
+impl ArgumentSchema {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn enumeration(mut self, values: Vec<Value>) -> Self {
+        self.enumeration = Some(values);
+        self
+    }
+
+    pub fn range(mut self, minimum: Option<f64>, maximum: Option<f64>) -> Self {
+        self.minimum = minimum;
+        self.maximum = maximum;
+        self
+    }
+
+    pub fn items(mut self, items: ElementSchema) -> Self {
+        self.items = Some(Box::new(items));
+        self
+    }
+
+    pub fn length(mut self, min_items: Option<usize>, max_items: Option<usize>) -> Self {
+        self.min_items = min_items;
+        self.max_items = max_items;
+        self
+    }
+
+    pub fn required(mut self, keys: &[&str]) -> Self {
+        self.required = keys.iter().map(|k| k.to_string()).collect();
+        self
+    }
+
+    pub fn property(mut self, key: &str, element: ElementSchema) -> Self {
+        self.properties.insert(key.to_owned(), element);
+        self
+    }
+
+    /// 値が制約を満たすか調べる。満たさなければpath (引数名や要素の位置) を含むメッセージを返す
+    pub fn check(&self, path: &str, value: &Value) -> Result<(), String> {
+        if let Some(values) = self.enumeration.as_ref() {
+            if !values.contains(value) {
+                return Err(format!("{path} must be one of {}, but {value} is given", Value::from(values.clone())));
+            }
+        }
+        if let Some(x) = value.as_f64() {
+            if let Some(minimum) = self.minimum {
+                if x < minimum {
+                    return Err(format!("{path} must be >= {minimum}, but {value} is given"));
+                }
+            }
+            if let Some(maximum) = self.maximum {
+                if x > maximum {
+                    return Err(format!("{path} must be <= {maximum}, but {value} is given"));
+                }
+            }
+        }
+        if let Some(array) = value.as_array() {
+            if let Some(min_items) = self.min_items {
+                if array.len() < min_items {
+                    return Err(format!("{path} must have at least {min_items} items, but {} items are given", array.len()));
+                }
+            }
+            if let Some(max_items) = self.max_items {
+                if array.len() > max_items {
+                    return Err(format!("{path} must have at most {max_items} items, but {} items are given", array.len()));
+                }
+            }
+            if let Some(items) = self.items.as_ref() {
+                for (i, v) in array.iter().enumerate() {
+                    items.check(format!("{path}[{i}]").as_str(), v)?;
+                }
+            }
+        }
+        if let Some(object) = value.as_object() {
+            for key in self.required.iter() {
+                if !object.contains_key(key) {
+                    return Err(format!("{path} must have key '{key}'"));
+                }
+            }
+            for (key, element) in self.properties.iter() {
+                if let Some(v) = object.get(key) {
+                    element.check(format!("{path}.{key}").as_str(), v)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 書かれている制約だけを引数のマニフェストのJSONに足す
+    pub fn write_to(self, map: &mut Map<String, Value>) {
+        if let Some(values) = self.enumeration {
+            map.insert("enum".to_owned(), values.into());
+        }
+        if let Some(minimum) = self.minimum {
+            map.insert("minimum".to_owned(), minimum.into());
+        }
+        if let Some(maximum) = self.maximum {
+            map.insert("maximum".to_owned(), maximum.into());
+        }
+        if let Some(items) = self.items {
+            map.insert("items".to_owned(), (*items).into());
+        }
+        if let Some(min_items) = self.min_items {
+            map.insert("min_items".to_owned(), min_items.into());
+        }
+        if let Some(max_items) = self.max_items {
+            map.insert("max_items".to_owned(), max_items.into());
+        }
+        if !self.required.is_empty() {
+            map.insert("required".to_owned(), self.required.into());
+        }
+        if !self.properties.is_empty() {
+            map.insert("properties".to_owned(), self.properties.into_iter().map(|(k, v)| (k, v.into())).collect::<Map<String, Value>>().into());
+        }
+    }
+}
+
+fn invalid_schema(key: &str, value: &Value) -> anyhow::Error {
+    anyhow!(JuizError::ProcessManifestInvalidError{message: format!("Argument schema '{key}' is invalid in ArgumentManifest ({value}).")})
+}
+
+impl TryFrom<&Value> for ArgumentSchema {
+    type Error = anyhow::Error;
+
+    /// 引数のマニフェストのJSONから制約のキーだけを読む
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let number = |key: &str| -> JuizResult<Option<f64>> {
+            match value.get(key) {
+                None => Ok(None),
+                Some(v) => v.as_f64().map(Some).ok_or_else(|| invalid_schema(key, value)),
+            }
+        };
+        let length = |key: &str| -> JuizResult<Option<usize>> {
+            match value.get(key) {
+                None => Ok(None),
+                Some(v) => v.as_u64().map(|n| Some(n as usize)).ok_or_else(|| invalid_schema(key, value)),
+            }
+        };
+        let enumeration = match value.get("enum") {
+            None => None,
+            Some(v) => Some(v.as_array().ok_or_else(|| invalid_schema("enum", value))?.clone()),
+        };
+        let items = match value.get("items") {
+            None => None,
+            Some(v) => Some(Box::new(ElementSchema::try_from(v)?)),
+        };
+        let required = match value.get("required") {
+            None => Vec::new(),
+            Some(v) => v.as_array().ok_or_else(|| invalid_schema("required", value))?.iter().map(|k| {
+                k.as_str().map(|s| s.to_owned()).ok_or_else(|| invalid_schema("required", value))
+            }).collect::<JuizResult<Vec<String>>>()?,
+        };
+        let properties = match value.get("properties") {
+            None => BTreeMap::new(),
+            Some(v) => v.as_object().ok_or_else(|| invalid_schema("properties", value))?.iter().map(|(k, p)| {
+                Ok((k.clone(), ElementSchema::try_from(p)?))
+            }).collect::<JuizResult<BTreeMap<String, ElementSchema>>>()?,
+        };
+        Ok(ArgumentSchema {
+            enumeration,
+            minimum: number("minimum")?,
+            maximum: number("maximum")?,
+            items,
+            min_items: length("min_items")?,
+            max_items: length("max_items")?,
+            required,
+            properties,
+        })
+    }
+}
+
+/// 要素の型が合うか。JSONでは整数と浮動小数点が区別できないことがあるので、floatには数値なら何でも通す
+fn kind_matches(type_name: &ArgumentType, value: &Value) -> bool {
+    match type_name {
+        ArgumentType::Bool => value.is_boolean(),
+        ArgumentType::Int => value.is_i64() || value.is_u64(),
+        ArgumentType::Float => value.is_number(),
+        ArgumentType::String => value.is_string(),
+        ArgumentType::Array => value.is_array(),
+        ArgumentType::Object => value.is_object(),
+        ArgumentType::Image | ArgumentType::Bytes | ArgumentType::NumericArray => true,
+    }
+}
+
+/// 型から引数の型と制約を作る
+///
+/// `#[juiz_process]` の引数にserdeで読めるユーザー定義の構造体を使うときに、マニフェストの制約を作るために使う。
+/// 構造体には `#[derive(JuizSchema)]` で実装できる。`Option` のフィールド以外は必須のキーになる。
+/// `Option` のフィールドはキーが無くても、値がnullでもよい。
+///
+/// ```
+/// use juiz_sdk::prelude::*;
+/// let schema = <Vec<f64> as JuizSchema>::element_schema();
+/// assert_eq!(schema.type_name, ArgumentType::Array);
+/// assert_eq!(schema.schema.items.unwrap().type_name, ArgumentType::Float);
+/// ```
+pub trait JuizSchema {
+    fn element_schema() -> ElementSchema;
+
+    /// Optionのフィールドは必須にしない
+    fn is_optional() -> bool {
+        false
+    }
+}
+
+macro_rules! impl_juiz_schema {
+    ($argument_type:expr, $($t:ty),*) => {
+        $(
+            impl JuizSchema for $t {
+                fn element_schema() -> ElementSchema {
+                    ElementSchema::new($argument_type)
+                }
+            }
+        )*
+    };
+}
+
+impl_juiz_schema!(ArgumentType::Bool, bool);
+impl_juiz_schema!(ArgumentType::Int, i8, i16, i32, i64, u8, u16, u32, u64, usize);
+impl_juiz_schema!(ArgumentType::Float, f32, f64);
+impl_juiz_schema!(ArgumentType::String, String);
+
+/// `#[juiz_process]`が引数の型から制約を作るときに使う
+///
+/// `(&&SchemaProbe::<T>::new()).probe_element_schema(fallback)` と書くと、
+/// TがJuizSchemaを実装していればその制約を、実装していなければ制約の無いfallbackの型を返す。
+#[doc(hidden)]
+pub struct SchemaProbe<T>(std::marker::PhantomData<T>);
+
+impl<T> SchemaProbe<T> {
+    pub fn new() -> Self {
+        SchemaProbe(std::marker::PhantomData)
+    }
+}
+
+#[doc(hidden)]
+pub trait ProbeJuizSchema {
+    fn probe_element_schema(&self, fallback: ArgumentType) -> ElementSchema;
+}
+
+impl<T: JuizSchema> ProbeJuizSchema for &SchemaProbe<T> {
+    fn probe_element_schema(&self, _fallback: ArgumentType) -> ElementSchema {
+        T::element_schema()
+    }
+}
+
+#[doc(hidden)]
+pub trait ProbeFallbackSchema {
+    fn probe_element_schema(&self, fallback: ArgumentType) -> ElementSchema;
+}
+
+impl<T> ProbeFallbackSchema for SchemaProbe<T> {
+    fn probe_element_schema(&self, fallback: ArgumentType) -> ElementSchema {
+        ElementSchema::new(fallback)
+    }
+}
+
+impl<T: JuizSchema> JuizSchema for Vec<T> {
+    fn element_schema() -> ElementSchema {
+        ElementSchema::new(ArgumentType::Array).schema(ArgumentSchema::new().items(T::element_schema()))
+    }
+}
+
+impl<T: JuizSchema> JuizSchema for Option<T> {
+    fn element_schema() -> ElementSchema {
+        T::element_schema().nullable(true)
+    }
+
+    fn is_optional() -> bool {
+        true
+    }
+}
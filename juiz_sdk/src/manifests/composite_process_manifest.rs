@@ -0,0 +1,170 @@
+use anyhow::anyhow;
+use crate::prelude::*;
+
+/// 合成プロセスの入力として公開する内部プロセスの引数
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompositeInletManifest {
+    /// 合成プロセスでの引数名
+    pub name: String,
+    /// 内部プロセスの名前
+    pub process: String,
+    /// 内部プロセスの引数名
+    pub arg_name: String,
+}
+
+/// 内部プロセスどうしの接続。プロセスは合成プロセスの中での名前で書く
+///
+/// 合成プロセスが上流から順に評価するので、Push/Pullの接続の型は持たない
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompositeConnectionManifest {
+    pub source: String,
+    pub destination: String,
+    pub arg_name: String,
+}
+
+/// 内部プロセスと接続からなるサブグラフを一つのプロセスの型として定義するマニフェスト
+///
+/// 内部プロセスのインスタンス名は"合成プロセスの名前.内部プロセスの名前"になる。
+#[derive(Clone, Debug)]
+pub struct CompositeProcessManifest {
+    pub type_name: String,
+    pub description: Description,
+    pub processes: Vec<ProcessManifest>,
+    pub connections: Vec<CompositeConnectionManifest>,
+    pub inlets: Vec<CompositeInletManifest>,
+    /// 出力を公開する内部プロセスの名前
+    pub outlet: String,
+}
+
+impl CompositeProcessManifest {
+
+    /// ```
+    /// use juiz_sdk::prelude::*;
+    /// let manifest = CompositeProcessManifest::new("increment_twice", "inc1")
+    ///   .add_process(ProcessManifest::new("increment").name("inc0"))
+    ///   .add_process(ProcessManifest::new("increment").name("inc1"))
+    ///   .connect("inc0", "inc1", "arg1")
+    ///   .expose("arg1", "inc0", "arg1");
+    /// assert_eq!(manifest.inner_name("twice0", "inc0"), "twice0.inc0");
+    /// assert_eq!(manifest.inlets[0].process, "inc0");
+    /// ```
+    pub fn new(type_name: &str, outlet: &str) -> Self {
+        Self {
+            type_name: type_name.to_owned(),
+            description: "".into(),
+            processes: Vec::new(),
+            connections: Vec::new(),
+            inlets: Vec::new(),
+            outlet: outlet.to_owned(),
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn add_process(mut self, process: ProcessManifest) -> Self {
+        self.processes.push(process);
+        self
+    }
+
+    pub fn connect(mut self, source: &str, destination: &str, arg_name: &str) -> Self {
+        self.connections.push(CompositeConnectionManifest {
+            source: source.to_owned(),
+            destination: destination.to_owned(),
+            arg_name: arg_name.to_owned(),
+        });
+        self
+    }
+
+    pub fn expose(mut self, name: &str, process: &str, arg_name: &str) -> Self {
+        self.inlets.push(CompositeInletManifest {
+            name: name.to_owned(),
+            process: process.to_owned(),
+            arg_name: arg_name.to_owned(),
+        });
+        self
+    }
+
+    /// 合成プロセスのインスタンスの中での内部プロセスのインスタンス名
+    pub fn inner_name(&self, instance_name: &str, process_name: &str) -> String {
+        format!("{instance_name}.{process_name}")
+    }
+}
+
+impl From<CompositeProcessManifest> for Value {
+    fn from(manifest: CompositeProcessManifest) -> Self {
+        jvalue!({
+            "type_name": manifest.type_name,
+            "description": manifest.description.to_str(),
+            "processes": manifest.processes.into_iter().map(|p| p.into()).collect::<Vec<Value>>(),
+            "connections": manifest.connections.iter().map(|c| jvalue!({
+                "source": c.source,
+                "destination": c.destination,
+                "arg_name": c.arg_name,
+            })).collect::<Vec<Value>>(),
+            "inlets": manifest.inlets.iter().map(|i| jvalue!({
+                "name": i.name,
+                "process": i.process,
+                "arg_name": i.arg_name,
+            })).collect::<Vec<Value>>(),
+            "outlet": manifest.outlet,
+        })
+    }
+}
+
+/// ```
+/// use juiz_sdk::prelude::*;
+/// fn main() -> JuizResult<()> {
+/// let manifest: CompositeProcessManifest = jvalue!({
+///   "type_name": "increment_twice",
+///   "processes": [
+///     {"type_name": "increment", "name": "inc0"},
+///     {"type_name": "increment", "name": "inc1"}
+///   ],
+///   "connections": [
+///     {"source": "inc0", "destination": "inc1", "arg_name": "arg1"}
+///   ],
+///   "inlets": [
+///     {"name": "value", "process": "inc0", "arg_name": "arg1"}
+///   ],
+///   "outlet": "inc1"
+/// }).try_into()?;
+/// assert_eq!(manifest.processes.len(), 2);
+/// assert_eq!(manifest.connections[0].source, "inc0");
+/// Ok(())}
+/// ```
+impl TryFrom<Value> for CompositeProcessManifest {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Value) -> JuizResult<Self> {
+        let err = |message: String| anyhow!(JuizError::ProcessManifestInvalidError{message: format!("Conversion failed Value({value}) -> CompositeProcessManifest. {message}")});
+        let mut manifest = CompositeProcessManifest::new(obj_get_str(&value, "type_name")?, obj_get_str(&value, "outlet")?)
+            .description(obj_get_str(&value, "description").unwrap_or(""));
+        for p in obj_get_array(&value, "processes")?.iter() {
+            let process: ProcessManifest = p.clone().try_into()?;
+            if process.name.is_none() {
+                return Err(err("Inner process must have 'name'.".to_owned()));
+            }
+            manifest = manifest.add_process(process);
+        }
+        if let Ok(connections) = obj_get_array(&value, "connections") {
+            for c in connections.iter() {
+                if c.get("type").is_some() {
+                    return Err(err(format!("Connection {c} in CompositeProcess can not have 'type'. Inner processes are evaluated in order by the CompositeProcess.")));
+                }
+                manifest = manifest.connect(obj_get_str(c, "source")?, obj_get_str(c, "destination")?, obj_get_str(c, "arg_name")?);
+            }
+        }
+        if let Ok(inlets) = obj_get_array(&value, "inlets") {
+            for i in inlets.iter() {
+                let arg_name = obj_get_str(i, "arg_name")?;
+                // nameを省略したら内部プロセスの引数名をそのまま使う
+                let name = obj_get_str(i, "name").unwrap_or(arg_name);
+                manifest = manifest.expose(name, obj_get_str(i, "process")?, arg_name);
+            }
+        }
+        Ok(manifest)
+    }
+}
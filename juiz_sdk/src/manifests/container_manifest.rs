@@ -156,6 +156,14 @@ impl ContainerManifest {
         self.add_arg(ArgumentManifest::new_image(name).description(description))
     }
 
+    pub fn add_bytes_arg(self, name: &str, description: &str) -> Self {
+        self.add_arg(ArgumentManifest::new_bytes(name).description(description))
+    }
+
+    pub fn add_numeric_array_arg(self, name: &str, description: &str) -> Self {
+        self.add_arg(ArgumentManifest::new_numeric_array(name).description(description))
+    }
+
 }
 
 impl TryFrom<Value> for ContainerManifest {
@@ -0,0 +1,129 @@
+
+use std::time::Duration;
+use anyhow::anyhow;
+use serde_json::Map;
+use crate::prelude::*;
+
+/// 時刻同期に使うCapsuleのオプションのデフォルトのキー
+pub const DEFAULT_SYNC_KEY: &str = TIMESTAMP_OPTION_KEY;
+
+/// 同期したinletごとに保持する入力の数のデフォルト
+pub const DEFAULT_SYNC_QUEUE_SIZE: usize = 10;
+
+/// 複数のinletの入力をそろえる方法
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum InletSyncPolicy {
+    /// 同期しない。各inletが持っている値をそのまま使う
+    #[default]
+    None,
+    /// Capsuleのオプションkeyの値が全てのinletで一致したら実行する
+    ExactTime { key: String },
+    /// オプションkeyの時刻 (秒) の差がslop以内の組がそろったら実行する
+    ApproximateTime { key: String, slop: Duration },
+    /// 全てのinletが前回の実行から更新されたら実行する
+    AllUpdated,
+}
+
+impl InletSyncPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InletSyncPolicy::None => "none",
+            InletSyncPolicy::ExactTime { .. } => "exact_time",
+            InletSyncPolicy::ApproximateTime { .. } => "approximate_time",
+            InletSyncPolicy::AllUpdated => "all_updated",
+        }
+    }
+}
+
+/// ProcessManifestに書くinletの同期の設定
+///
+/// inletsが空の時は全ての引数を同期する。
+#[derive(Clone, Debug, PartialEq)]
+pub struct InletSyncManifest {
+    pub policy: InletSyncPolicy,
+    pub inlets: Vec<String>,
+    pub queue_size: usize,
+}
+
+impl Default for InletSyncManifest {
+    fn default() -> Self {
+        Self { policy: InletSyncPolicy::None, inlets: Vec::new(), queue_size: DEFAULT_SYNC_QUEUE_SIZE }
+    }
+}
+
+impl InletSyncManifest {
+
+    pub fn new(policy: InletSyncPolicy) -> Self {
+        Self { policy, ..Default::default() }
+    }
+
+    pub fn inlets(mut self, inlets: &[&str]) -> Self {
+        self.inlets = inlets.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size;
+        self
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.policy == InletSyncPolicy::None
+    }
+}
+
+impl From<InletSyncManifest> for Value {
+    fn from(manifest: InletSyncManifest) -> Self {
+        let mut map: Map<String, Value> = Map::new();
+        map.insert("policy".to_owned(), manifest.policy.as_str().into());
+        match manifest.policy {
+            InletSyncPolicy::ExactTime { key } => {
+                map.insert("key".to_owned(), key.into());
+            },
+            InletSyncPolicy::ApproximateTime { key, slop } => {
+                map.insert("key".to_owned(), key.into());
+                map.insert("slop".to_owned(), slop.as_secs_f64().into());
+            },
+            _ => {}
+        }
+        map.insert("inlets".to_owned(), manifest.inlets.into());
+        map.insert("queue_size".to_owned(), manifest.queue_size.into());
+        map.into()
+    }
+}
+
+impl TryFrom<&Value> for InletSyncManifest {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> JuizResult<Self> {
+        let err = || anyhow!(JuizError::ProcessManifestInvalidError{message: format!("Conversion failed Value({value:?}) -> InletSyncManifest.")});
+        // "sync": "all_updated" のように方法だけを書いても良い
+        let (policy_name, obj) = match value {
+            Value::String(s) => (s.as_str(), None),
+            Value::Object(obj) => (obj.get("policy").and_then(|v| v.as_str()).ok_or_else(err)?, Some(obj)),
+            _ => return Err(err()),
+        };
+        let key = obj.and_then(|o| o.get("key")).and_then(|v| v.as_str()).unwrap_or(DEFAULT_SYNC_KEY).to_owned();
+        let policy = match policy_name {
+            "none" => InletSyncPolicy::None,
+            "exact_time" => InletSyncPolicy::ExactTime { key },
+            "approximate_time" => {
+                let slop = obj.and_then(|o| o.get("slop")).and_then(|v| v.as_f64()).ok_or_else(err)?;
+                InletSyncPolicy::ApproximateTime { key, slop: Duration::try_from_secs_f64(slop).map_err(|_| err())? }
+            },
+            "all_updated" => InletSyncPolicy::AllUpdated,
+            _ => return Err(err()),
+        };
+        let inlets = match obj.and_then(|o| o.get("inlets")) {
+            Some(v) => v.as_array().ok_or_else(err)?.iter()
+                .map(|n| n.as_str().map(|s| s.to_owned()).ok_or_else(err))
+                .collect::<JuizResult<Vec<String>>>()?,
+            None => Vec::new(),
+        };
+        let queue_size = match obj.and_then(|o| o.get("queue_size")) {
+            Some(v) => v.as_u64().filter(|n| *n > 0).ok_or_else(err)? as usize,
+            None => DEFAULT_SYNC_QUEUE_SIZE,
+        };
+        Ok(InletSyncManifest { policy, inlets, queue_size })
+    }
+}
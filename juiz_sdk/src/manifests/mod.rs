@@ -4,14 +4,20 @@
 mod process_manifest;
 mod container_manifest;
 mod argument_manifest;
+mod argument_schema;
 mod manifest_description;
 // pub mod container_process_manifest;
 mod component_manifest;
 mod topic_manifest;
+mod inlet_sync_manifest;
+mod composite_process_manifest;
 
 pub use container_manifest::ContainerManifest;
 pub use process_manifest::ProcessManifest;
 pub use component_manifest::ComponentManifest;
-pub use topic_manifest::TopicManifest;
+pub use topic_manifest::{TopicManifest, TopicEncoding, TOPIC_ENCODING_KEY, DEFAULT_JPEG_QUALITY};
 pub use argument_manifest::{ArgumentManifest, ArgumentType};
-pub use manifest_description::Description;
\ No newline at end of file
+pub use argument_schema::{ArgumentSchema, ElementSchema, JuizSchema, SchemaProbe, ProbeJuizSchema, ProbeFallbackSchema};
+pub use manifest_description::Description;
+pub use inlet_sync_manifest::{InletSyncManifest, InletSyncPolicy, DEFAULT_SYNC_KEY};
+pub use composite_process_manifest::{CompositeProcessManifest, CompositeInletManifest, CompositeConnectionManifest};
\ No newline at end of file
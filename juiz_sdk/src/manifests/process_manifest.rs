@@ -3,7 +3,7 @@ use std::{collections::HashMap, fmt::Display};
 use anyhow::anyhow;
 use serde_json::Map;
 use crate::{identifier::identifier_new, prelude::*};
-use super::{argument_manifest::ArgumentManifest, manifest_description::Description, topic_manifest::TopicManifest};
+use super::{argument_manifest::ArgumentManifest, inlet_sync_manifest::InletSyncManifest, manifest_description::Description, topic_manifest::TopicManifest};
 
 #[derive(Clone, Debug)]
 pub struct ProcessManifest {
@@ -20,6 +20,7 @@ pub struct ProcessManifest {
     pub subscribes: HashMap<String, TopicManifest>,
     pub container_name: Option<String>,
     pub container_type: Option<String>,
+    pub sync: InletSyncManifest,
 }
 
 impl Display for ProcessManifest {
@@ -58,6 +59,9 @@ impl ProcessManifest {
             .description(self.description.as_str())
             .use_memo(self.use_memo)
             .container_type(self.container_type.as_ref().map(|v| { v.clone() }));
+        if partial_instance_manifest.sync.is_none() {
+            partial_instance_manifest.sync = self.sync.clone();
+        }
 
         let mut new_argument_manif: Vec<ArgumentManifest> = Vec::new();
         for arg_manif in self.arguments.iter() {
@@ -71,7 +75,7 @@ impl ProcessManifest {
                     default_value = instance_arg_manif.default.clone();
                 }
             }
-            new_argument_manif.push(ArgumentManifest::new(arg_type.clone(), arg_name.as_str(), description, default_value));
+            new_argument_manif.push(ArgumentManifest::new(arg_type.clone(), arg_name.as_str(), description, default_value).schema(arg_manif.schema.clone()));
         }
         partial_instance_manifest.arguments.clear();
         partial_instance_manifest.arguments = new_argument_manif;
@@ -108,6 +112,7 @@ impl ProcessManifest {
             container_name: None,
             container_type: None,
             language: "rust".to_owned(),
+            sync: InletSyncManifest::default(),
         }
     }
 
@@ -188,6 +193,14 @@ impl ProcessManifest {
         self.add_arg(ArgumentManifest::new_image(name).description(description))
     }
 
+    pub fn add_bytes_arg(self, name: &str, description: &str) -> Self {
+        self.add_arg(ArgumentManifest::new_bytes(name).description(description))
+    }
+
+    pub fn add_numeric_array_arg(self, name: &str, description: &str) -> Self {
+        self.add_arg(ArgumentManifest::new_numeric_array(name).description(description))
+    }
+
     /// ```
     /// use juiz_core::prelude::*;
     /// let manifest = ProcessManifest::new("hoge_type")
@@ -228,6 +241,19 @@ impl ProcessManifest {
         self
     }
 
+    /// 他のシステムへ画像を送るときの符号化も決めて出版する
+    ///
+    /// ```
+    /// use juiz_sdk::prelude::*;
+    /// let manifest = ProcessManifest::new("camera")
+    ///   .publishes_topic(TopicManifest::new("image").encoding(TopicEncoding::Jpeg{quality: 80}));
+    /// assert_eq!(manifest.publishes[0].encoding, TopicEncoding::Jpeg{quality: 80});
+    /// ```
+    pub fn publishes_topic(mut self, topic: TopicManifest) -> Self {
+        self.publishes.push(topic);
+        self
+    }
+
         /// ```
     /// use juiz_core::prelude::*;
     /// let manifest = ProcessManifest::new("hoge_type")
@@ -241,6 +267,19 @@ impl ProcessManifest {
         self
     }
 
+    /// ```
+    /// use juiz_sdk::prelude::*;
+    /// let manifest = ProcessManifest::new("hoge_type")
+    ///   .add_image_arg("image", "image_arg")
+    ///   .add_object_arg("odom", "odometry_arg", jvalue!({}))
+    ///   .sync(InletSyncManifest::new(InletSyncPolicy::AllUpdated));
+    /// assert_eq!(manifest.sync.policy, InletSyncPolicy::AllUpdated);
+    /// ```
+    pub fn sync(mut self, sync: InletSyncManifest) -> Self {
+        self.sync = sync;
+        self
+    }
+
     pub fn identifier(&self) -> JuizResult<Identifier> { 
         if let Some(name) = self.name.as_ref() {
             Ok(identifier_new(
@@ -309,6 +348,9 @@ impl Into<Value> for ProcessManifest {
         if let Some(name) = self.name {
             map.insert("name".to_owned(), name.into());
         }
+        if !self.sync.is_none() {
+            map.insert("sync".to_owned(), self.sync.into());
+        }
         
         v
     }
@@ -394,7 +436,7 @@ impl TryFrom<Value> for ProcessManifest {
         match obj_get_array(&value, "publishes") {
             Ok(value_array) => {
                 for arg_obj in value_array.into_iter() {
-                    p = p.publishes(arg_obj.as_str().unwrap());
+                    p = p.publishes_topic(arg_obj.clone().try_into()?);
                 }
             }
             Err(_) => {},
@@ -402,11 +444,14 @@ impl TryFrom<Value> for ProcessManifest {
         match obj_get_obj(&value, "subscribes") {
             Ok(value_map) => {
                 for (arg_name, arg_obj) in value_map.into_iter() {
-                    p = p.subscribes(arg_name.as_str(), arg_obj.as_str().unwrap());
+                    p.subscribes.insert(arg_name.clone(), arg_obj.clone().try_into()?);
                 }
             }
             Err(_) => {},
         };
+        if let Some(sync_value) = value.get("sync") {
+            p = p.sync(sync_value.try_into()?);
+        }
         Ok(p)
     }    
     type Error = anyhow::Error;
@@ -4,39 +4,118 @@ use crate::prelude::*;
 
 use anyhow::anyhow;
 
+/// 他のシステムのトピックへ画像を転送するときに、どの符号化で送ったかを載せるオプション (とパラメータ) のキー
+pub const TOPIC_ENCODING_KEY: &str = "topic_encoding";
+
+/// JPEGの品質を書かなかったときの値
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// トピックが他のシステムへ画像を転送するときの符号化
+///
+/// マニフェストでは `"encoding"` に `"png"`, `"jpeg"`, `"raw"` のどれかを書く。JPEGの品質は `"quality"` (1から100) で書く。
+/// 画像以外の値は符号化しない。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TopicEncoding {
+    /// 可逆圧縮。何も書かなければこれを使う
+    #[default]
+    Png,
+    /// 非可逆圧縮。帯域が狭いときに使う
+    Jpeg{quality: u8},
+    /// 圧縮しない。画素を[高さ, 幅, チャンネル]のu8の数値の配列として送る
+    Raw,
+}
+
+impl TopicEncoding {
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TopicEncoding::Png => "png",
+            TopicEncoding::Jpeg{..} => "jpeg",
+            TopicEncoding::Raw => "raw",
+        }
+    }
+}
+
+impl Display for TopicEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TopicEncoding::Jpeg{quality} => f.write_fmt(format_args!("jpeg(quality={quality})")),
+            _ => f.write_str(self.as_str()),
+        }
+    }
+}
+
+impl TryFrom<&Value> for TopicEncoding {
+    type Error = anyhow::Error;
+
+    /// トピックのマニフェストの `"encoding"` と `"quality"` を読む
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let encoding = match value.get("encoding") {
+            None => return Ok(TopicEncoding::default()),
+            Some(v) => v.as_str().ok_or_else(|| anyhow!(JuizError::TopicManifestInvalidError{message: format!("Topic encoding must be string ({value}).")}))?,
+        };
+        match encoding {
+            "png" => Ok(TopicEncoding::Png),
+            "raw" => Ok(TopicEncoding::Raw),
+            "jpeg" | "jpg" => {
+                let quality = match value.get("quality") {
+                    None => DEFAULT_JPEG_QUALITY,
+                    Some(q) => match q.as_u64() {
+                        Some(q) if (1..=100).contains(&q) => q as u8,
+                        _ => return Err(anyhow!(JuizError::TopicManifestInvalidError{message: format!("Topic JPEG quality must be 1 to 100 ({value}).")})),
+                    },
+                };
+                Ok(TopicEncoding::Jpeg{quality})
+            },
+            _ => Err(anyhow!(JuizError::TopicManifestInvalidError{message: format!("Topic encoding '{encoding}' is not supported. Use png, jpeg or raw.")})),
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct TopicManifest {
-    pub name: String
+    pub name: String,
+    pub encoding: TopicEncoding,
 }
 
 impl TopicManifest {
     pub fn new(name: &str) -> Self {
-        TopicManifest{name: name.to_owned()}
+        TopicManifest{name: name.to_owned(), encoding: TopicEncoding::default()}
+    }
+
+    pub fn encoding(mut self, encoding: TopicEncoding) -> Self {
+        self.encoding = encoding;
+        self
     }
 }
 
 impl Display for TopicManifest {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("TopicManifest({})", self.name))
+        f.write_fmt(format_args!("TopicManifest({}, encoding={})", self.name, self.encoding))
     }
 }
 
 impl TryFrom<Value> for TopicManifest {
+    /// トピックの名前の文字列か、`{"name": "camera", "encoding": "jpeg", "quality": 80}` のようなオブジェクトから作る
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        match value.as_str() {
-            Some(v_str) => {
-                Ok(TopicManifest{name: v_str.to_owned()})
-            },
+        if let Some(v_str) = value.as_str() {
+            return Ok(TopicManifest::new(v_str));
+        }
+        match value.get("name").and_then(|v| v.as_str()) {
+            Some(name) => Ok(TopicManifest::new(name).encoding((&value).try_into()?)),
             None => Err(anyhow!(JuizError::TopicManifestInvalidError{message: "Topic manifest can not convert to Value.".to_owned()})),
         }
     }
-    
+
     type Error = anyhow::Error;
 }
 
 impl Into<Value> for TopicManifest {
     fn into(self) -> Value {
-        self.name.into()
+        match self.encoding {
+            TopicEncoding::Png => self.name.into(),
+            TopicEncoding::Jpeg{quality} => jvalue!({"name": self.name, "encoding": "jpeg", "quality": quality}),
+            TopicEncoding::Raw => jvalue!({"name": self.name, "encoding": "raw"}),
+        }
     }
-}
\ No newline at end of file
+}
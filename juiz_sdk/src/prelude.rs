@@ -10,17 +10,31 @@ pub use crate::{
     },
     manifests::{
         ArgumentManifest, ArgumentType, 
+        ArgumentSchema, ElementSchema, JuizSchema, SchemaProbe, ProbeJuizSchema, ProbeFallbackSchema,
         ProcessManifest,
         Description,
         ContainerManifest,
         ComponentManifest,
         TopicManifest,
+        TopicEncoding,
+        TOPIC_ENCODING_KEY,
+        InletSyncManifest,
+        InletSyncPolicy,
+        CompositeProcessManifest,
     },
     value::{
         jvalue, Value, 
         Capsule, 
         CapsuleMap,
         CapsulePtr,
+        SharedImage,
+        SharedImageColor,
+        SharedImageHandle,
+        SharedImageLease,
+        ImageView,
+        NumericArray,
+        NumericData,
+        NumericType,
         load_str,
         obj_get_str,
         obj_get_bool,
@@ -36,6 +50,13 @@ pub use crate::{
         obj_insert,
         as_obj,
         capsule_to_value,
+        capsule_ptr_to_deserialize,
+        serialize_to_capsule,
+        TIMESTAMP_OPTION_KEY,
+        SEQUENCE_OPTION_KEY,
+        PRODUCER_OPTION_KEY,
+        TIMEOUT_PARAM_KEY,
+        parse_timeout,
         value_to_capsule,
         value_merge,
     }, 
@@ -67,6 +88,7 @@ pub use crate::{
         ContainerProcessFactoryStruct,
         container_stack_factory,
         ContainerStackFactoryStruct,
+        abi::{AbiDescriptor, AbiInfo, abi_descriptor, abi_symbol_name},
     },
     containers::{
         Container,
@@ -79,6 +101,8 @@ pub use crate::{
         SourceConnection,
         DestinationConnection,
         ConnectionManifest,
+        ConnectionQoS,
+        QueuePolicy,
     },
     object::{
         JuizObject,
@@ -90,7 +114,8 @@ pub use crate::{
 };
 
 pub use image;
+pub use crate::export_abi_descriptor;
 
-pub use juiz_macro::{juiz_process, juiz_container, juiz_container_process, juiz_component_process, juiz_component_container, juiz_component_container_process, juiz_component_manifest};
+pub use juiz_macro::{JuizSchema, juiz_process, juiz_container, juiz_container_process, juiz_component_process, juiz_component_container, juiz_component_container_process, juiz_component_manifest};
 pub use serde_json;
 pub use env_logger;
\ No newline at end of file
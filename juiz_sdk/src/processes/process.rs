@@ -46,6 +46,11 @@ pub trait Process : Send + Sync + mopa::Any + JuizObject + 'static {
     fn notify_connected_from<'b>(&'b mut self, source: ProcessPtr, connection_manifest: ConnectionManifest) -> JuizResult<ConnectionManifest>;
 
     fn try_connect_to(&mut self, target: ProcessPtr, connection_manifest: ConnectionManifest) -> JuizResult<ConnectionManifest>;
+
+    /// 出力側接続のうちarg_nameで登録されたものを取り外す。取り外せないプロセスはエラーを返す
+    fn disconnect_to(&mut self, arg_name: &str) -> JuizResult<()> {
+        Err(anyhow::Error::from(JuizError::ProcessDisconnectNotSupportedError{identifier: self.identifier(), arg_name: arg_name.to_owned()}))
+    }
     
     fn source_connections(&self) -> JuizResult<Vec<&Box<dyn SourceConnection>>>;
 
@@ -53,6 +58,9 @@ pub trait Process : Send + Sync + mopa::Any + JuizObject + 'static {
 
     fn p_apply(&mut self, arg_name: &str, value: CapsulePtr) -> JuizResult<CapsulePtr>;
 
+    /// 同期ポリシーの対象のinletのうち、前回の実行から新しい入力が届いていないものの名前
+    fn stale_inlets(&self) -> JuizResult<Vec<String>>;
+
     fn purge(&mut self) -> JuizResult<()>;
 }
 
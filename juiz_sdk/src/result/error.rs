@@ -19,6 +19,10 @@ pub enum JuizError {
     PluginLoadFailedError{plugin_path: String},
     #[error("Plugin({plugin_path:}) tried to load symbol({symbol_name:}) but failed.")]
     PluginLoadSymbolFailedError {plugin_path: String, symbol_name: String},
+    #[error("Plugin({plugin_path}) is rejected by plugin_policy. {reason}")]
+    PluginRejectedError { plugin_path: String, reason: String },
+    #[error("Plugin({plugin_path}) is not ABI compatible with this juiz ({symbol_name}). {reason}")]
+    PluginAbiMismatchError { plugin_path: String, symbol_name: String, reason: String },
     #[error("ProcessFactory({type_name:}) is already loaded.")]
     ProcessFactoryOfSameTypeNameAlreadyExistsError { type_name: String },
     #[error("ProcessFactory({type_name:}) can not be found.")]
@@ -43,6 +47,8 @@ pub enum JuizError {
     ProcessOutputMemoIsNotInitializedError { id: String },
     #[error("Process checked given argument but the argument does not contain the predefined specification ({process_manifest:})")]
     ArgumentMissingWhenCallingError { process_manifest: Value, missing_arg_name: String },
+    #[error("Argument '{name}' does not satisfy its schema. ({message})")]
+    ArgumentConstraintViolationError { name: String, message: String },
     #[error("Process manifest includes invalid value type string. Manifest is ({manifest:}), type string is ({type_string:}")]
     ManifestArgumentDefaultValueIsInvalidTypeError {manifest: Value, type_string: String  },
     #[error("Process manifest includes invalid value type in default arugment value.Value is ({value:}")]
@@ -118,12 +124,36 @@ pub enum JuizError {
     BrokerNameCanNotResolveToURLError { given_name: String },
     #[error("CoreStore can not find broker (by id= {id:})")]
     BrokerProfileNotFoundError { id : String },
+    #[error("Process({identifier}) does not support disconnecting its output (arg_name={arg_name})")]
+    ProcessDisconnectNotSupportedError { identifier: String, arg_name: String },
     #[error("ProcessProxy construct can not accept class ({class_name:?})")]
     ProcessProxyCanNotAcceptClassError { class_name: String },
     #[error("ExecutionContextProxy construct can not accept class ({class_name:?})")]
     ExecutionContextProxyCanNotAcceptClassError { class_name: String },
     #[error("Execution Context Can not Lock its State")]
     ExecutionContextCanNotLockStateError {  },
+    #[error("ExecutionContext({name}) missed its deadline (elapsed={elapsed_sec} sec, period={period_sec} sec)")]
+    ExecutionContextDeadlineMissedError { name: String, elapsed_sec: f64, period_sec: f64 },
+    #[error("ExecutionContext({id}) can not {operation} in {state} state")]
+    ExecutionContextInvalidStateTransitionError { id: String, state: String, operation: String },
+    #[error("ExecutionContext({type_name}) does not support step")]
+    ExecutionContextStepNotSupportedError { type_name: String },
+    #[error("Processes bound to ExecutionContext form a cycle ({processes:?})")]
+    ExecutionContextScheduleCycleError { processes: Vec<String> },
+    #[error("ExecutionContext worker failed to execute Process({id})")]
+    ExecutionContextWorkerError { id: String },
+    #[error("Call to {target} timed out after {timeout_sec} sec")]
+    RemoteCallTimeoutError { target: String, timeout_sec: f64 },
+    #[error("Call to {target} is refused because the previous call that timed out is still running")]
+    RemoteCallStillRunningError { target: String },
+    #[error("Call to {target} panicked")]
+    RemoteCallPanickedError { target: String },
+    #[error("System named {name} can not be discovered")]
+    DiscoveredPeerCanNotFoundError { name: String },
+    #[error("Broker ({broker_name}) refused the request because the token is missing or unknown")]
+    BrokerUnauthorizedError { broker_name: String },
+    #[error("Broker ({broker_name}) does not permit the token to {method} {class_name}/{function_name}")]
+    BrokerForbiddenError { broker_name: String, method: String, class_name: String, function_name: String },
     
     #[error("Process Argument can not found by name ({name})")]
     ArgumentCanNotFoundByNameError{ name: String },
@@ -149,6 +179,8 @@ pub enum JuizError {
     InvalidConnectionIdentifierError { identifier: String },
     #[error("Connection ID ({identifier}) can not be found.")]
     ConnectionCanNotBeFoundError { identifier: String },
+    #[error("Connection ({identifier}) is closed.")]
+    ConnectionClosedError { identifier: String },
     #[error("CapuleMap does not contain value with key=({key}).")]
     CapsuleMapDoesNotContainValueError { key: String },
     #[error("Cofig file is invalid format.")]
@@ -201,6 +233,12 @@ pub enum JuizError {
     #[error("TopicManifest is invalid. (message={message})")]
     TopicManifestInvalidError{message: String},
 
+    #[error("SharedMemory Error (message={message})")]
+    SharedMemoryError{ message: String },
+
+    #[error("Record File Error (message={message})")]
+    RecordFileError{ message: String },
+
     #[error("Poison Error (error={error})")]
     PoisonError{ error: String},
 }
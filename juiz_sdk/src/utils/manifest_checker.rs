@@ -93,7 +93,7 @@ fn check_arguments(args_manifest: &Vec<ArgumentManifest>, argument: &CapsuleMap)
                     process_manifest: args_manifest.iter().map(|a|{a.clone().into()}).collect::<Vec<Value>>().into(), 
                     missing_arg_name: arg_manifest.name.clone()}));
                 },
-            Ok(_) => {}
+            Ok(value) => arg_manifest.check(&value)?,
         };
     }
     Ok(())
@@ -102,3 +102,11 @@ fn check_arguments(args_manifest: &Vec<ArgumentManifest>, argument: &CapsuleMap)
 pub fn check_manifest_before_call(manifest: &ProcessManifest, argument: &CapsuleMap) -> JuizResult<()> {
     check_arguments(&manifest.arguments, argument)
 }
+
+/// p_applyで1つの引数に値を与える前に、その引数のスキーマの制約を調べる
+pub fn check_argument_before_apply(manifest: &ProcessManifest, arg_name: &str, value: &CapsulePtr) -> JuizResult<()> {
+    match manifest.arguments.iter().find(|a| a.name == arg_name) {
+        Some(arg_manifest) => arg_manifest.check(value),
+        None => Ok(()),
+    }
+}
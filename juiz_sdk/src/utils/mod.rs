@@ -6,7 +6,7 @@ pub mod manifest_checker;
 pub mod sync_util;
 pub mod yaml_conf_load;
 
-pub use manifest_checker::{check_connection_manifest, check_corebroker_manifest, check_manifest_before_call};
+pub use manifest_checker::{check_argument_before_apply, check_connection_manifest, check_corebroker_manifest, check_manifest_before_call};
 pub use manifest_util::{get_value, get_str, get_array, get_array_mut, get_hashmap, get_hashmap_mut, when_contains_do, when_contains_do_mut};
 pub use sync_util::{juiz_lock, juiz_try_lock, juiz_borrow_mut, juiz_borrow};
 pub use yaml_conf_load::yaml_conf_load;
\ No newline at end of file
@@ -1,14 +1,20 @@
 
 
-use std::{collections::HashMap, fmt::Display, mem::swap};
+use std::{collections::HashMap, fmt::Display, mem::swap, time::{Duration, SystemTime, UNIX_EPOCH}};
 
 // #[cfg(feature="opencv4")]
 // use opencv::core::Mat;
 
+use base64::{engine::general_purpose::STANDARD, Engine};
 use image::DynamicImage;
 use serde_json::Map;
 use crate::prelude::*;
 
+/// JSONしか運べない経路でバイト列を送るときのキー。値はBase64の文字列
+pub const BYTES_VALUE_KEY: &str = "__bytes__";
+/// JSONしか運べない経路で数値の配列を送るときのキー。値は`{"dtype", "shape", "data"}`で、dataはリトルエンディアンのバイト列のBase64
+pub const NUMERIC_ARRAY_VALUE_KEY: &str = "__numeric_array__";
+
 #[derive(Clone, Debug)]
 pub enum CapsuleValue {
     Empty(()),
@@ -17,6 +23,9 @@ pub enum CapsuleValue {
     // #[cfg(feature="opencv4")]    
     // Mat(Mat),
     Image(DynamicImage),
+    SharedImage(SharedImage),
+    Bytes(Vec<u8>),
+    NumericArray(NumericArray),
 }
 
 impl Display for CapsuleValue {
@@ -29,6 +38,15 @@ impl Display for CapsuleValue {
             CapsuleValue::Image(_dynamic_image) => {
                 f.write_fmt(format_args!("DynamicImage"))
             }
+            CapsuleValue::SharedImage(shared_image) => {
+                f.write_fmt(format_args!("{:?}", shared_image))
+            }
+            CapsuleValue::Bytes(bytes) => {
+                f.write_fmt(format_args!("Bytes(len={})", bytes.len()))
+            }
+            CapsuleValue::NumericArray(array) => {
+                f.write_fmt(format_args!("{}", array))
+            }
         }
     }
 }
@@ -36,6 +54,30 @@ impl From<Value> for CapsuleValue {
     fn from(value: Value) -> Self { Self::Value( value ) }
 }
 
+fn tagged_value_to_binary(value: &Value) -> Option<JuizResult<CapsuleValue>> {
+    let obj = value.as_object()?;
+    if obj.len() != 1 {
+        return None;
+    }
+    if let Some(encoded) = obj.get(BYTES_VALUE_KEY) {
+        let encoded = encoded.as_str()?;
+        return Some(STANDARD.decode(encoded).map(CapsuleValue::Bytes).map_err(anyhow::Error::from));
+    }
+    let array = obj.get(NUMERIC_ARRAY_VALUE_KEY)?;
+    Some((|| {
+        let bytes = STANDARD.decode(obj_get_str(array, "data")?)?;
+        Ok(CapsuleValue::NumericArray(NumericArray::from_header_and_bytes(array, &bytes)?))
+    })())
+}
+
+impl From<Vec<u8>> for CapsuleValue {
+    fn from(value: Vec<u8>) -> Self { Self::Bytes( value ) }
+}
+
+impl From<NumericArray> for CapsuleValue {
+    fn from(value: NumericArray) -> Self { Self::NumericArray( value ) }
+}
+
 // #[cfg(feature="opencv4")]
 // impl From<Mat> for CapsuleValue {
 //     fn from(value: Mat) -> Self { Self::Mat( value ) }
@@ -45,6 +87,10 @@ impl From<DynamicImage> for CapsuleValue {
     fn from(value: DynamicImage) -> Self { Self::Image( value ) }
 }
 
+impl From<SharedImage> for CapsuleValue {
+    fn from(value: SharedImage) -> Self { Self::SharedImage( value ) }
+}
+
 impl CapsuleValue {
 
     pub fn is_empty(&self) -> bool {
@@ -100,10 +146,19 @@ impl CapsuleValue {
     //     }
     // }
 
+    /// 共有メモリ上の画像も画像として扱う
     pub fn is_image(&self) -> bool {
+        matches!(self, Self::Image(_) | Self::SharedImage(_))
+    }
+
+    pub fn is_shared_image(&self) -> bool {
+        matches!(self, Self::SharedImage(_))
+    }
+
+    pub fn as_shared_image(&self) -> Option<&SharedImage> {
         match self {
-            Self::Image(_) => return true, 
-            _ => return false
+            Self::SharedImage(v) => Some(v),
+            _ => None
         }
     }
 
@@ -117,9 +172,70 @@ impl CapsuleValue {
     pub fn to_image(self) -> Option<DynamicImage> {
         match self {
             Self::Image(v) => return Some(v), 
+            Self::SharedImage(v) => return v.to_image().ok(),
             _ => return None
         }
     }
+
+    pub fn is_bytes(&self) -> bool {
+        matches!(self, Self::Bytes(_))
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(v) => Some(v),
+            _ => None
+        }
+    }
+
+    pub fn to_bytes(self) -> Option<Vec<u8>> {
+        match self {
+            Self::Bytes(v) => Some(v),
+            _ => None
+        }
+    }
+
+    pub fn is_numeric_array(&self) -> bool {
+        matches!(self, Self::NumericArray(_))
+    }
+
+    pub fn as_numeric_array(&self) -> Option<&NumericArray> {
+        match self {
+            Self::NumericArray(v) => Some(v),
+            _ => None
+        }
+    }
+
+    pub fn to_numeric_array(self) -> Option<NumericArray> {
+        match self {
+            Self::NumericArray(v) => Some(v),
+            _ => None
+        }
+    }
+
+    /// JSONで表せる形にする。バイト列と数値の配列はBase64で包む。画像と空はNone
+    pub fn to_json_value(&self) -> Option<Value> {
+        match self {
+            Self::Value(v) => Some(v.clone()),
+            Self::Bytes(v) => Some(jvalue!({BYTES_VALUE_KEY: STANDARD.encode(v)})),
+            Self::NumericArray(v) => {
+                let mut header = v.header();
+                header.as_object_mut()?.insert("data".to_owned(), jvalue!(STANDARD.encode(v.to_le_bytes())));
+                Some(jvalue!({NUMERIC_ARRAY_VALUE_KEY: header}))
+            },
+            _ => None
+        }
+    }
+
+    /// to_json_value()の逆。Base64で包んだバイト列と数値の配列を元に戻す
+    ///
+    /// 利用者のJSONをバイナリと取り違えないよう、JSONしか運べない通信路で受け取った値にだけ使う
+    pub fn from_json_value(value: Value) -> JuizResult<Self> {
+        match tagged_value_to_binary(&value) {
+            Some(v) => v,
+            None => Ok(Self::Value( value )),
+        }
+    }
     /*
     pub fn to_mat(&self) -> Option<Mat> {
         match self {
@@ -130,6 +246,13 @@ impl CapsuleValue {
 }
 
 
+/// 出力を作った時刻 (UNIX時刻の秒) を入れるオプションのキー
+pub const TIMESTAMP_OPTION_KEY: &str = "timestamp";
+/// 出力側 (Outlet) ごとの通し番号を入れるオプションのキー
+pub const SEQUENCE_OPTION_KEY: &str = "seq";
+/// 出力を作ったプロセスのIdentifierを入れるオプションのキー
+pub const PRODUCER_OPTION_KEY: &str = "producer";
+
 #[derive(Clone, Debug)]
 pub struct Capsule {
     value: CapsuleValue,
@@ -191,6 +314,12 @@ impl TryFrom<Capsule> for Value {
                     "__option__": jvalue!(value.option)
                 }))
             }
+            CapsuleValue::Bytes(_) | CapsuleValue::NumericArray(_) => {
+                Ok(jvalue!({
+                    "__value__": value.value.to_json_value(),
+                    "__option__": jvalue!(value.option)
+                }))
+            }
             _ => Err(anyhow::Error::from(JuizError::CapsuleIsNotValueTypeError{}))
         }
     }
@@ -206,6 +335,13 @@ impl TryFrom<Capsule> for Value {
 //     }
 // }
 
+/// __value__と__option__の包みを解かずに、値をそのまま持たせる
+impl From<CapsuleValue> for Capsule {
+    fn from(value: CapsuleValue) -> Self {
+        Self{ value, option: HashMap::new() }
+    }
+}
+
 impl From<DynamicImage> for Capsule {
     fn from(img_value: DynamicImage) -> Self {
         Self{
@@ -215,6 +351,33 @@ impl From<DynamicImage> for Capsule {
     }
 }
 
+impl From<SharedImage> for Capsule {
+    fn from(img_value: SharedImage) -> Self {
+        Self{
+            value: CapsuleValue::from(img_value),
+            option: HashMap::new(),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Capsule {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self{
+            value: CapsuleValue::from(bytes),
+            option: HashMap::new(),
+        }
+    }
+}
+
+impl From<NumericArray> for Capsule {
+    fn from(array: NumericArray) -> Self {
+        Self{
+            value: CapsuleValue::from(array),
+            option: HashMap::new(),
+        }
+    }
+}
+
 /*
 impl TryInto<Mat> for Capsule {
     type Error = anyhow::Error;
@@ -253,6 +416,24 @@ impl Capsule {
 
     pub fn to_image(self) -> Option<DynamicImage> { self.value.to_image() }
 
+    pub fn is_shared_image(&self) -> bool { self.value.is_shared_image() }
+
+    pub fn as_shared_image(&self) -> Option<&SharedImage> { self.value.as_shared_image() }
+
+    pub fn is_bytes(&self) -> bool { self.value.is_bytes() }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> { self.value.as_bytes() }
+
+    pub fn to_bytes(self) -> Option<Vec<u8>> { self.value.to_bytes() }
+
+    pub fn is_numeric_array(&self) -> bool { self.value.is_numeric_array() }
+
+    pub fn as_numeric_array(&self) -> Option<&NumericArray> { self.value.as_numeric_array() }
+
+    pub fn to_numeric_array(self) -> Option<NumericArray> { self.value.to_numeric_array() }
+
+    pub fn to_json_value(&self) -> Option<Value> { self.value.to_json_value() }
+
     pub fn set_option(&mut self, key: &str, value: &str) -> &mut Self {
         self.option.insert(key.to_owned(), value.to_owned());
         self
@@ -265,6 +446,43 @@ impl Capsule {
     pub fn get_options(&self) -> &HashMap<String, String> {
         &self.option
     }
+
+    /// 時刻はマイクロ秒まで小数で持つ
+    pub fn set_timestamp(&mut self, time: SystemTime) -> &mut Self {
+        let secs = time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_secs_f64();
+        self.set_option(TIMESTAMP_OPTION_KEY, format!("{secs:.6}").as_str())
+    }
+
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        let secs = self.get_option(TIMESTAMP_OPTION_KEY)?.parse::<f64>().ok()?;
+        UNIX_EPOCH.checked_add(Duration::try_from_secs_f64(secs).ok()?)
+    }
+
+    pub fn set_sequence(&mut self, seq: u64) -> &mut Self {
+        self.set_option(SEQUENCE_OPTION_KEY, seq.to_string().as_str())
+    }
+
+    pub fn sequence(&self) -> Option<u64> {
+        self.get_option(SEQUENCE_OPTION_KEY)?.parse::<u64>().ok()
+    }
+
+    pub fn set_producer(&mut self, producer: &str) -> &mut Self {
+        self.set_option(PRODUCER_OPTION_KEY, producer)
+    }
+
+    pub fn producer(&self) -> Option<&String> {
+        self.get_option(PRODUCER_OPTION_KEY)
+    }
+
+    /// 作成したプロセスと作成時刻を書き込む
+    ///
+    /// 処理の中でtimestampを書いていれば (カメラの撮影時刻など) そのまま残し、無いときだけ現在時刻にする。
+    pub fn stamp(&mut self, producer: &str) -> &mut Self {
+        if self.timestamp().is_none() {
+            self.set_timestamp(SystemTime::now());
+        }
+        self.set_producer(producer)
+    }
     
     pub fn empty() -> Capsule {
         Self {
@@ -288,11 +506,24 @@ impl Capsule {
         self.value = value;
     }
 
+    pub(crate) fn replace_options(&mut self, option: HashMap<String, String>) {
+        self.option = option;
+    }
+
     pub(crate) fn take_value(&mut self) -> CapsuleValue {
         let mut emp = CapsuleValue::Empty(());
         swap(&mut self.value, &mut emp);
         emp
     }
+
+    /// 値がBase64で包んだバイト列か数値の配列なら元に戻す。CapsuleValue::from_json_value()
+    pub(crate) fn decode_json_value(&mut self) -> JuizResult<()> {
+        if self.is_value() {
+            let v = self.take_value().to_value().unwrap();
+            self.value = CapsuleValue::from_json_value(v)?;
+        }
+        Ok(())
+    }
 }
 
 impl From<bool> for Capsule {
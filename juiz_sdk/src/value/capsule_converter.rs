@@ -1,5 +1,5 @@
 
-use std::{sync::{Arc, Mutex}, ffi::CStr};
+use std::{sync::{Arc, Mutex}, ffi::{CStr, c_void}};
 
 use crate::prelude::*;
 use super::converter_error::*;
@@ -291,4 +291,88 @@ pub unsafe extern "C" fn capsule_set_empty_array(capsule: *mut Capsule) -> i64 {
     let cap = capsule.as_mut().unwrap();
     cap.replace_value(jvalue!([]).into());
     JUIZ_OK
-}
\ No newline at end of file
+}
+
+
+
+#[no_mangle]
+pub unsafe extern "C" fn capsule_is_bytes(capsule: *mut Capsule) -> bool {
+    let c = capsule.as_ref().unwrap();
+    c.is_bytes()
+}
+
+/// 返すポインタはcapsuleが変更されるまで有効
+#[no_mangle]
+pub unsafe extern "C" fn capsule_get_bytes(capsule: *mut Capsule, v: *mut *const u8, len: *mut usize) -> i64 {
+    match capsule.as_ref().unwrap().as_bytes() {
+        Some(bytes) => {
+            *v = bytes.as_ptr();
+            *len = bytes.len();
+            JUIZ_OK
+        },
+        None => JUIZ_CAPSULE_TYPE_ERROR
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn capsule_set_bytes(capsule: *mut Capsule, v: *const u8, len: usize) -> i64 {
+    let cap = capsule.as_mut().unwrap();
+    let bytes = if len == 0 { Vec::new() } else { std::slice::from_raw_parts(v, len).to_vec() };
+    cap.replace_value(bytes.into());
+    JUIZ_OK
+}
+
+
+
+#[no_mangle]
+pub unsafe extern "C" fn capsule_is_numeric_array(capsule: *mut Capsule) -> bool {
+    let c = capsule.as_ref().unwrap();
+    c.is_numeric_array()
+}
+
+/// 要素の型の番号はNumericType::code()。返すポインタはcapsuleが変更されるまで有効
+#[no_mangle]
+pub unsafe extern "C" fn capsule_get_numeric_array(capsule: *mut Capsule, numeric_type: *mut i64, data: *mut *const c_void, len: *mut usize, shape: *mut *const usize, ndim: *mut usize) -> i64 {
+    match capsule.as_ref().unwrap().as_numeric_array() {
+        Some(array) => {
+            write_numeric_array(array, numeric_type, data, len, shape, ndim);
+            JUIZ_OK
+        },
+        None => JUIZ_CAPSULE_TYPE_ERROR
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn capsule_set_numeric_array(capsule: *mut Capsule, numeric_type: i64, data: *const c_void, len: usize, shape: *const usize, ndim: usize) -> i64 {
+    let cap = capsule.as_mut().unwrap();
+    match read_numeric_array(numeric_type, data, len, shape, ndim) {
+        Some(array) => {
+            cap.replace_value(array.into());
+            JUIZ_OK
+        },
+        None => JUIZ_VALUE_TYPE_ERROR
+    }
+}
+
+pub(crate) unsafe fn write_numeric_array(array: &NumericArray, numeric_type: *mut i64, data: *mut *const c_void, len: *mut usize, shape: *mut *const usize, ndim: *mut usize) {
+    *numeric_type = array.numeric_type().code();
+    *data = array.data().as_ptr();
+    *len = array.len();
+    *shape = array.shape().as_ptr();
+    *ndim = array.shape().len();
+}
+
+/// 型の番号が不正か、形と要素の数が合わなければNone
+pub(crate) unsafe fn read_numeric_array(numeric_type: i64, data: *const c_void, len: usize, shape: *const usize, ndim: usize) -> Option<NumericArray> {
+    unsafe fn to_vec<T: Clone>(data: *const c_void, len: usize) -> Vec<T> {
+        if len == 0 { Vec::new() } else { std::slice::from_raw_parts(data as *const T, len).to_vec() }
+    }
+    let numeric_data = match NumericType::from_code(numeric_type)? {
+        NumericType::F32 => NumericData::F32(to_vec(data, len)),
+        NumericType::F64 => NumericData::F64(to_vec(data, len)),
+        NumericType::I32 => NumericData::I32(to_vec(data, len)),
+        NumericType::U8 => NumericData::U8(to_vec(data, len)),
+    };
+    let shape = if ndim == 0 { vec![len] } else { std::slice::from_raw_parts(shape, ndim).to_vec() };
+    NumericArray::new(shape, numeric_data).ok()
+}
@@ -1,10 +1,22 @@
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::time::Duration;
+use anyhow::anyhow;
 use serde_json::Map;
 
 use crate::prelude::*;
 use crate::utils::get_hashmap;
 
+/// 呼び出しのタイムアウト [sec] を載せるパラメータのキー
+pub const TIMEOUT_PARAM_KEY: &str = "timeout";
+
+/// タイムアウトのパラメータ (秒) を読む
+pub fn parse_timeout(value: &str) -> JuizResult<Duration> {
+    value.parse::<f64>().ok()
+        .and_then(|sec| Duration::try_from_secs_f64(sec).ok())
+        .ok_or_else(|| anyhow!(JuizError::InvalidValueError{message: format!("timeout parameter ({value}) must be non-negative seconds.")}))
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct CapsuleMap {
@@ -34,6 +46,15 @@ impl CapsuleMap {
         self.param.get(key)
     }
 
+    /// この呼び出しをtimeoutで打ち切るように頼む
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.set_param(TIMEOUT_PARAM_KEY, timeout.as_secs_f64().to_string().as_str())
+    }
+
+    pub fn timeout(&self) -> JuizResult<Option<Duration>> {
+        self.get_param(TIMEOUT_PARAM_KEY).map(|v| parse_timeout(v)).transpose()
+    }
+
     pub fn get(&self, key: &str) -> JuizResult<CapsulePtr> {
         match self.map.get(key) {
             Some(v) => Ok(v.clone()),
@@ -95,6 +116,15 @@ impl CapsuleMap {
         self.map.insert(key.to_owned(), value)
     }
 
+    /// JSONしか運べない通信路で受け取った値から作る。Base64で包んだバイト列と数値の配列は元に戻す
+    pub fn from_json_value(value: Value) -> JuizResult<Self> {
+        let map: CapsuleMap = value.try_into()?;
+        for (_k, v) in map.iter() {
+            v.decode_json_value()?;
+        }
+        Ok(map)
+    }
+
 }
 
 
@@ -147,7 +177,7 @@ impl From<CapsuleMap> for Value {
         
         return jvalue!({
             "__map__": map_map,
-            "__param___": capsule_map.param,
+            "__param__": capsule_map.param,
         })
     }
 }
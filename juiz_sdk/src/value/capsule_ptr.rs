@@ -1,7 +1,7 @@
 
 //pub type CapsulePtr = Arc<Mutex<Capsule>>;
 
-use std::{collections::HashMap, fmt::Display, sync::{Arc, Mutex}};
+use std::{collections::HashMap, fmt::Display, sync::{Arc, Mutex}, time::SystemTime};
 use anyhow::anyhow;
 use image::DynamicImage;
 // #[cfg(feature="opencv4")]
@@ -35,12 +35,31 @@ impl CapsulePtr {
     pub fn replace(&self, capsule: CapsulePtr) -> () {
         match self.value.lock() {
             Ok(mut c) => {
-                c.replace_value(capsule.value.lock().unwrap().take_value());
+                let mut src = capsule.value.lock().unwrap();
+                c.replace_value(src.take_value());
+                // 時刻などのメタデータも値と一緒に入れ替える
+                c.replace_options(src.get_options().clone());
             },
             Err(_) => todo!(),
         }
     }
 
+    /// JSONしか運べない通信路で受け取った値から作る。Base64で包んだバイト列と数値の配列は元に戻す
+    pub fn from_json_value(value: Value) -> JuizResult<Self> {
+        let capsule: CapsulePtr = value.into();
+        capsule.decode_json_value()?;
+        Ok(capsule)
+    }
+
+    pub(crate) fn decode_json_value(&self) -> JuizResult<()> {
+        self.lock_and("decode_json_value", |c| c.decode_json_value())?
+    }
+
+    /// JSONで表せる形にする。バイト列と数値の配列はBase64で包む。画像と空はNone
+    pub fn to_json_value(&self) -> JuizResult<Option<Value>> {
+        self.lock_and("to_json_value", |c| c.to_json_value())
+    }
+
     pub fn replace_with_value(&mut self, value: Value) -> () {
         self.value = Arc::new(Mutex::new(value.into()));
     }
@@ -75,6 +94,23 @@ impl CapsulePtr {
     }
 
 
+    pub fn is_shared_image(&self) -> JuizResult<bool> {
+        match self.value.lock() {
+            Ok(c) => {
+                Ok(c.is_shared_image())
+            },
+            Err(_e) => Err(anyhow::Error::from(JuizError::MutexLockFailedError { error: "CapsulePtr.is_shared_image() lock error.".to_owned() })),
+        }
+    }
+
+    pub fn is_bytes(&self) -> JuizResult<bool> {
+        self.lock_and("is_bytes", |c| c.is_bytes())
+    }
+
+    pub fn is_numeric_array(&self) -> JuizResult<bool> {
+        self.lock_and("is_numeric_array", |c| c.is_numeric_array())
+    }
+
     pub fn is_value(&self) -> JuizResult<bool> {
         match self.value.lock() {
             Ok(c) => {
@@ -107,7 +143,47 @@ impl CapsulePtr {
             Err(_e) => Err(anyhow::Error::from(JuizError::MutexLockFailedError { error: "CapsulePtr.get_potion() lock error.".to_owned() })),
         }
     }
-    
+
+    pub fn get_options(&self) -> JuizResult<HashMap<String, String>> {
+        match self.value.lock() {
+            Ok(c) => Ok(c.get_options().clone()),
+            Err(_e) => Err(anyhow::Error::from(JuizError::MutexLockFailedError { error: "CapsulePtr.get_options() lock error.".to_owned() })),
+        }
+    }
+
+    fn lock_and<T, F>(&self, name: &str, func: F) -> JuizResult<T> where F: FnOnce(&mut Capsule) -> T {
+        match self.value.lock() {
+            Ok(mut c) => Ok(func(&mut c)),
+            Err(_e) => Err(anyhow::Error::from(JuizError::MutexLockFailedError { error: format!("CapsulePtr.{name}() lock error.") })),
+        }
+    }
+
+    pub fn timestamp(&self) -> JuizResult<Option<SystemTime>> {
+        self.lock_and("timestamp", |c| c.timestamp())
+    }
+
+    pub fn sequence(&self) -> JuizResult<Option<u64>> {
+        self.lock_and("sequence", |c| c.sequence())
+    }
+
+    pub fn producer(&self) -> JuizResult<Option<String>> {
+        self.lock_and("producer", |c| c.producer().cloned())
+    }
+
+    /// 作成時刻 (現在時刻)、通し番号、作成したプロセスを書き込む
+    pub fn stamp(&self, producer: &str) -> JuizResult<()> {
+        self.lock_and("stamp", |c| { c.stamp(producer); })
+    }
+
+    pub fn set_sequence(&self, seq: u64) -> JuizResult<()> {
+        self.lock_and("set_sequence", |c| { c.set_sequence(seq); })
+    }
+
+    /// 中身のCapsuleを複製して取り出す
+    pub fn clone_capsule(&self) -> JuizResult<Capsule> {
+        self.lock_and("clone_capsule", |c| c.clone())
+    }
+
     pub fn lock_as_value_and_opt<T, F>(&self, func: F) -> JuizResult<T> where F: FnOnce(&Value, &HashMap<String, String>) -> T{
         match self.value.lock() {
             Ok(c) => {
@@ -137,6 +213,8 @@ impl CapsulePtr {
                 .and_then(|v| { 
                     if let Some(img) = v.as_image() {
                         Ok(img.clone())
+                    } else if let Some(shared_img) = v.as_shared_image() {
+                        shared_img.to_image()
                     } else {
                         Err(anyhow!(JuizError::ValueTypeError { message: format!("value must be image, but {:?}", v) }))
                     }
@@ -145,7 +223,28 @@ impl CapsulePtr {
         }
     }
 
+    pub fn extract_bytes(self) -> JuizResult<Vec<u8>> {
+        let bytes = match Arc::try_unwrap(self.value) {
+            Ok(v) => v.into_inner().ok().and_then(|c| c.to_bytes()),
+            Err(e) => e.lock().ok().and_then(|c| c.as_bytes().map(|b| b.to_vec())),
+        };
+        bytes.ok_or_else(|| anyhow!(JuizError::ValueTypeError { message: "CapsulePtr.extract_bytes() failed. Value is not bytes.".to_owned() }))
+    }
+
+    pub fn extract_numeric_array(self) -> JuizResult<NumericArray> {
+        let array = match Arc::try_unwrap(self.value) {
+            Ok(v) => v.into_inner().ok().and_then(|c| c.to_numeric_array()),
+            Err(e) => e.lock().ok().and_then(|c| c.as_numeric_array().cloned()),
+        };
+        array.ok_or_else(|| anyhow!(JuizError::ValueTypeError { message: "CapsulePtr.extract_numeric_array() failed. Value is not numeric array.".to_owned() }))
+    }
+
+    /// バイト列と数値の配列はBase64で包んだJSONにして取り出す
     pub fn extract_value(self) -> JuizResult<Value> {
+        if self.is_bytes()? || self.is_numeric_array()? {
+            return self.lock_and("extract_value", |c| c.to_json_value())?
+                .ok_or_else(|| anyhow!(JuizError::ValueTypeError { message: "CapsulePtr.extract_value() failed.".to_owned() }));
+        }
         match Arc::try_unwrap(self.value) {
             Ok(v) => {
                 match v.into_inner() {
@@ -243,15 +342,71 @@ impl CapsulePtr {
     // }
 
     // #[cfg(not(feature="opencv4"))]
+    /// 共有メモリ上の画像はここでDynamicImageに複製される。複製したくない場合はlock_as_image_viewを使う。
     pub fn lock_as_image<T, F>(&self, func: F) -> JuizResult<T> where F: FnOnce(&DynamicImage) -> T{
         match self.value.lock() {
             Ok(c) => {
-                match c.as_image() {
+                if let Some(v) = c.as_image() {
+                    Ok(func(v))
+                } else if let Some(v) = c.as_shared_image() {
+                    Ok(func(&v.to_image()?))
+                } else {
+                    Err(anyhow!(JuizError::ValueTypeError { message: format!("value must be image, but {:?}", c) }))
+                }
+            }
+            Err(_e) => Err(anyhow::Error::from(JuizError::MutexLockFailedError { error: "CapsulePtr.lock_as_image() lock error.".to_owned() })),
+        }
+    }
+
+    /// 共有メモリ上の画像は複製せずにmapした画素を参照する。DynamicImageが要るときはImageView::to_imageで複製する。
+    pub fn lock_as_image_view<T, F>(&self, func: F) -> JuizResult<T> where F: FnOnce(&ImageView) -> T{
+        match self.value.lock() {
+            Ok(c) => {
+                if let Some(v) = c.as_image() {
+                    Ok(func(&ImageView::Image(v)))
+                } else if let Some(v) = c.as_shared_image() {
+                    Ok(func(&v.view()?))
+                } else {
+                    Err(anyhow!(JuizError::ValueTypeError { message: format!("value must be image, but {:?}", c) }))
+                }
+            }
+            Err(_e) => Err(anyhow::Error::from(JuizError::MutexLockFailedError { error: "CapsulePtr.lock_as_image_view() lock error.".to_owned() })),
+        }
+    }
+    
+    pub fn lock_as_bytes<T, F>(&self, func: F) -> JuizResult<T> where F: FnOnce(&[u8]) -> T {
+        match self.value.lock() {
+            Ok(c) => {
+                match c.as_bytes() {
                     Some(v) => Ok(func(v)),
-                    None => todo!(),
+                    None => Err(anyhow!(JuizError::ValueTypeError { message: format!("value must be bytes, but {}", c) })),
                 }
             }
-            Err(_e) => Err(anyhow::Error::from(JuizError::MutexLockFailedError { error: "CapsulePtr.lock_as_value() lock error.".to_owned() })),
+            Err(_e) => Err(anyhow::Error::from(JuizError::MutexLockFailedError { error: "CapsulePtr.lock_as_bytes() lock error.".to_owned() })),
+        }
+    }
+
+    pub fn lock_as_numeric_array<T, F>(&self, func: F) -> JuizResult<T> where F: FnOnce(&NumericArray) -> T {
+        match self.value.lock() {
+            Ok(c) => {
+                match c.as_numeric_array() {
+                    Some(v) => Ok(func(v)),
+                    None => Err(anyhow!(JuizError::ValueTypeError { message: format!("value must be numeric array, but {}", c) })),
+                }
+            }
+            Err(_e) => Err(anyhow::Error::from(JuizError::MutexLockFailedError { error: "CapsulePtr.lock_as_numeric_array() lock error.".to_owned() })),
+        }
+    }
+
+    pub fn lock_as_shared_image<T, F>(&self, func: F) -> JuizResult<T> where F: FnOnce(&SharedImage) -> T{
+        match self.value.lock() {
+            Ok(c) => {
+                match c.as_shared_image() {
+                    Some(v) => Ok(func(v)),
+                    None => Err(anyhow!(JuizError::ValueTypeError { message: format!("value must be shared image, but {:?}", c) })),
+                }
+            }
+            Err(_e) => Err(anyhow::Error::from(JuizError::MutexLockFailedError { error: "CapsulePtr.lock_as_shared_image() lock error.".to_owned() })),
         }
     }
     
@@ -285,7 +440,16 @@ impl From<Value> for CapsulePtr {
                 match obj.remove_entry("__value__") {
                     None => Self{value: Arc::new(Mutex::new(value.into()))},
                     Some((_k, v)) => {
-                        Self{value: Arc::new(Mutex::new(v.into()))}
+                        let mut capsule: Capsule = v.into();
+                        // 一緒に送られてきたオプション (時刻などのメタデータ) を戻す
+                        if let Some(Value::Object(options)) = obj.get("__option__") {
+                            for (k, o) in options.iter() {
+                                if let Some(s) = o.as_str() {
+                                    capsule.set_option(k.as_str(), s);
+                                }
+                            }
+                        }
+                        Self{value: Arc::new(Mutex::new(capsule))}
                     },
                 }
             }
@@ -307,6 +471,24 @@ impl From<DynamicImage> for CapsulePtr {
     }
 }
 
+impl From<SharedImage> for CapsulePtr {
+    fn from(value: SharedImage) -> Self {
+        Self{value: Arc::new(Mutex::new(value.into()))}
+    }
+}
+
+impl From<Vec<u8>> for CapsulePtr {
+    fn from(value: Vec<u8>) -> Self {
+        Self{value: Arc::new(Mutex::new(value.into()))}
+    }
+}
+
+impl From<NumericArray> for CapsulePtr {
+    fn from(value: NumericArray) -> Self {
+        Self{value: Arc::new(Mutex::new(value.into()))}
+    }
+}
+
 impl From<Capsule> for CapsulePtr {
     fn from(value: Capsule) -> Self {
         Self{value: Arc::new(Mutex::new(value))}
@@ -333,6 +515,14 @@ impl Clone for CapsulePtr {
 
 pub fn capsule_to_value(capsule: CapsulePtr) -> JuizResult<Value> {
     log::trace!("capsule_to_value(capsule: {capsule:?}) called");
+    if capsule.is_bytes()? || capsule.is_numeric_array()? {
+        return capsule.lock_and("capsule_to_value", |c| {
+            jvalue!({
+                "__value__": c.to_json_value(),
+                "__option__": jvalue!(c.get_options())
+            })
+        });
+    }
     capsule.lock_as_value_and_opt(|v, opt| {
         jvalue!({
             "__value__": v,
@@ -345,6 +535,19 @@ pub fn value_to_capsule(value: Value) -> CapsulePtr {
     value.into()
 }
 
+/// serdeで読めるユーザー定義の型にする。#[juiz_process]の引数で使う
+pub fn capsule_ptr_to_deserialize<T: serde::de::DeserializeOwned>(capsule: CapsulePtr) -> JuizResult<T> {
+    let value = capsule.lock_as_value(|v| v.clone())?;
+    serde_json::from_value::<T>(value).map_err(|e| {
+        anyhow!(JuizError::ValueTypeError{message: format!("capsule_ptr_to_deserialize() failed. {e}")})
+    })
+}
+
+/// serdeで書けるユーザー定義の型をCapsuleにする。#[juiz_process]の戻り値で使う
+pub fn serialize_to_capsule<T: serde::Serialize>(value: &T) -> JuizResult<Capsule> {
+    Ok(serde_json::to_value(value)?.into())
+}
+
 impl TryInto<i64> for CapsulePtr {
     type Error = anyhow::Error;
 
@@ -427,6 +630,22 @@ impl TryInto<Vec<f64>> for CapsulePtr {
     }
 }
 
+impl TryInto<Vec<u8>> for CapsulePtr {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<Vec<u8>, Self::Error> {
+        self.extract_bytes()
+    }
+}
+
+impl TryInto<NumericArray> for CapsulePtr {
+    type Error = anyhow::Error;
+
+    fn try_into(self) -> Result<NumericArray, Self::Error> {
+        self.extract_numeric_array()
+    }
+}
+
 impl TryInto<DynamicImage> for CapsulePtr {
     type Error = anyhow::Error;
 
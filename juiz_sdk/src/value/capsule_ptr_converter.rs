@@ -3,6 +3,7 @@ use std::{ffi::CStr, sync::{Arc, Mutex}};
 
 use crate::prelude::*;
 use super::converter_error::*;
+use super::capsule_converter::write_numeric_array;
 
 #[no_mangle]
 pub unsafe extern "C" fn capsule_ptr_get_value(capsule_ptr: *mut CapsulePtr) -> *mut Arc<Mutex<Capsule>> {
@@ -45,6 +46,25 @@ pub unsafe extern "C" fn capsule_ptr_get_string(capsule_ptr: *mut CapsulePtr, v:
     } ).or::<i64>(Ok(JUIZ_CAPSULEPTR_LOCK_ERROR)).unwrap()
 }
 
+/// 返すポインタはcapsule_ptrが変更されるまで有効
+#[no_mangle]
+pub unsafe extern "C" fn capsule_ptr_get_bytes(capsule_ptr: *mut CapsulePtr, v: *mut *const u8, len: *mut usize) -> i64 {
+    capsule_ptr.as_ref().unwrap().lock_as_bytes(|bytes| {
+        *v = bytes.as_ptr();
+        *len = bytes.len();
+        JUIZ_OK
+    } ).or::<i64>(Ok(JUIZ_VALUE_TYPE_ERROR)).unwrap()
+}
+
+/// 要素の型の番号はNumericType::code()。返すポインタはcapsule_ptrが変更されるまで有効
+#[no_mangle]
+pub unsafe extern "C" fn capsule_ptr_get_numeric_array(capsule_ptr: *mut CapsulePtr, numeric_type: *mut i64, data: *mut *const std::ffi::c_void, len: *mut usize, shape: *mut *const usize, ndim: *mut usize) -> i64 {
+    capsule_ptr.as_ref().unwrap().lock_as_numeric_array(|array| {
+        write_numeric_array(array, numeric_type, data, len, shape, ndim);
+        JUIZ_OK
+    } ).or::<i64>(Ok(JUIZ_VALUE_TYPE_ERROR)).unwrap()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn capsule_ptr_lock_as_value(capsule_ptr: *mut CapsulePtr, callback: extern fn(*mut Value) -> ()) -> () {
     let _ = capsule_ptr.as_mut().unwrap().lock_modify_as_value(|v|->() {
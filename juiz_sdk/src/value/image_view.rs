@@ -0,0 +1,103 @@
+//! 画素を複製せずに参照する画像
+//!
+//! `CapsulePtr::lock_as_image_view`はこの型で画像を渡す。共有メモリ上の画像はmapした領域をそのまま参照する。
+
+use std::{borrow::Cow, io::{Seek, Write}};
+
+use image::{ColorType, DynamicImage, GenericImageView, ImageBuffer, ImageFormat, Luma, LumaA, Pixel, Rgb, Rgba};
+
+use crate::prelude::*;
+
+/// DynamicImageか、共有メモリの画素の上に置いたImageBufferを参照する
+pub enum ImageView<'a> {
+    Image(&'a DynamicImage),
+    L8(ImageBuffer<Luma<u8>, &'a [u8]>),
+    La8(ImageBuffer<LumaA<u8>, &'a [u8]>),
+    Rgb8(ImageBuffer<Rgb<u8>, &'a [u8]>),
+    Rgba8(ImageBuffer<Rgba<u8>, &'a [u8]>),
+}
+
+fn to_owned_buffer<P: Pixel<Subpixel = u8>>(buffer: &ImageBuffer<P, &[u8]>) -> ImageBuffer<P, Vec<u8>> {
+    // 元のImageBufferで大きさは確かめてあるので失敗しない
+    ImageBuffer::from_raw(buffer.width(), buffer.height(), buffer.to_vec()).unwrap()
+}
+
+impl<'a> ImageView<'a> {
+
+    pub fn width(&self) -> u32 {
+        self.dimensions().0
+    }
+
+    pub fn height(&self) -> u32 {
+        self.dimensions().1
+    }
+
+    pub fn color(&self) -> ColorType {
+        match self {
+            ImageView::Image(image) => image.color(),
+            ImageView::L8(_) => ColorType::L8,
+            ImageView::La8(_) => ColorType::La8,
+            ImageView::Rgb8(_) => ColorType::Rgb8,
+            ImageView::Rgba8(_) => ColorType::Rgba8,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            ImageView::Image(image) => image.as_bytes(),
+            ImageView::L8(buffer) => buffer.as_raw(),
+            ImageView::La8(buffer) => buffer.as_raw(),
+            ImageView::Rgb8(buffer) => buffer.as_raw(),
+            ImageView::Rgba8(buffer) => buffer.as_raw(),
+        }
+    }
+
+    /// DynamicImageとして扱う。共有メモリ上の画像はここで複製される
+    pub fn to_image(&self) -> Cow<'a, DynamicImage> {
+        match self {
+            ImageView::Image(image) => Cow::Borrowed(*image),
+            ImageView::L8(buffer) => Cow::Owned(DynamicImage::ImageLuma8(to_owned_buffer(buffer))),
+            ImageView::La8(buffer) => Cow::Owned(DynamicImage::ImageLumaA8(to_owned_buffer(buffer))),
+            ImageView::Rgb8(buffer) => Cow::Owned(DynamicImage::ImageRgb8(to_owned_buffer(buffer))),
+            ImageView::Rgba8(buffer) => Cow::Owned(DynamicImage::ImageRgba8(to_owned_buffer(buffer))),
+        }
+    }
+
+    /// 画素を複製せずに符号化して書き出す
+    pub fn write_to<W: Write + Seek>(&self, writer: &mut W, format: ImageFormat) -> JuizResult<()> {
+        match self {
+            ImageView::Image(image) => image.write_to(writer, format)?,
+            _ => image::write_buffer_with_format(writer, self.as_bytes(), self.width(), self.height(), self.color(), format)?,
+        }
+        Ok(())
+    }
+}
+
+impl GenericImageView for ImageView<'_> {
+    type Pixel = Rgba<u8>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        match self {
+            ImageView::Image(image) => image.dimensions(),
+            ImageView::L8(buffer) => buffer.dimensions(),
+            ImageView::La8(buffer) => buffer.dimensions(),
+            ImageView::Rgb8(buffer) => buffer.dimensions(),
+            ImageView::Rgba8(buffer) => buffer.dimensions(),
+        }
+    }
+
+    fn bounds(&self) -> (u32, u32, u32, u32) {
+        let (width, height) = self.dimensions();
+        (0, 0, width, height)
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Self::Pixel {
+        match self {
+            ImageView::Image(image) => image.get_pixel(x, y),
+            ImageView::L8(buffer) => buffer.get_pixel(x, y).to_rgba(),
+            ImageView::La8(buffer) => buffer.get_pixel(x, y).to_rgba(),
+            ImageView::Rgb8(buffer) => buffer.get_pixel(x, y).to_rgba(),
+            ImageView::Rgba8(buffer) => buffer.get_pixel(x, y).to_rgba(),
+        }
+    }
+}
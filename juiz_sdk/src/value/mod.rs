@@ -1,7 +1,7 @@
 //! juizで使うデータ型に関する機能パッケージ
 //! 
 //! 
-//! juiz内部ではデータはCapsuleというデータ型で表現されます。これはserde_json::ValueとImageの直積です。
+//! juiz内部ではデータはCapsuleというデータ型で表現されます。これはserde_json::Value、Image、バイト列、数値の配列の直和です。
 
 
 pub mod value;
@@ -18,8 +18,17 @@ pub mod capsule_map_converter;
 pub mod capsule_ptr;
 pub mod capsule_ptr_converter;
 
+pub mod shared_image;
+
+pub mod image_view;
+
+pub mod numeric_array;
+
 
 pub use value::*;
 pub use capsule::*;
 pub use capsule_ptr::*;
-pub use capsule_map::*;
\ No newline at end of file
+pub use capsule_map::*;
+pub use shared_image::{SharedImage, SharedImageColor, SharedImageHandle, SharedImageLease};
+pub use image_view::ImageView;
+pub use numeric_array::{NumericArray, NumericData, NumericType};
\ No newline at end of file
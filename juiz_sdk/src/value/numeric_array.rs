@@ -0,0 +1,262 @@
+//! 型と形を持つ数値の配列
+//!
+//! 点群、音声、レーザースキャンのように大量の数値を運ぶためのデータ型。
+//! JSONの数値の配列にはせず、要素の型(f32, f64, i32, u8)と形(各次元の長さ)と要素の列をそのまま持つ。
+//! 要素は行優先 (最後の次元が連続する) で並べる。
+//! ブローカーで送るときの要素のバイト列はリトルエンディアンに揃える。
+
+use std::fmt::Display;
+use anyhow::anyhow;
+
+use crate::prelude::*;
+
+/// 要素の型
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumericType {
+    F32,
+    F64,
+    I32,
+    U8,
+}
+
+impl NumericType {
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NumericType::F32 => "f32",
+            NumericType::F64 => "f64",
+            NumericType::I32 => "i32",
+            NumericType::U8 => "u8",
+        }
+    }
+
+    /// 1要素のバイト数
+    pub fn size(&self) -> usize {
+        match self {
+            NumericType::F32 => 4,
+            NumericType::F64 => 8,
+            NumericType::I32 => 4,
+            NumericType::U8 => 1,
+        }
+    }
+
+    /// C/C++のプラグインとやりとりするときの番号
+    pub fn code(&self) -> i64 {
+        match self {
+            NumericType::F32 => 0,
+            NumericType::F64 => 1,
+            NumericType::I32 => 2,
+            NumericType::U8 => 3,
+        }
+    }
+
+    pub fn from_code(code: i64) -> Option<Self> {
+        match code {
+            0 => Some(NumericType::F32),
+            1 => Some(NumericType::F64),
+            2 => Some(NumericType::I32),
+            3 => Some(NumericType::U8),
+            _ => None,
+        }
+    }
+}
+
+impl Display for NumericType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl TryFrom<&str> for NumericType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "f32" => Ok(NumericType::F32),
+            "f64" => Ok(NumericType::F64),
+            "i32" => Ok(NumericType::I32),
+            "u8" => Ok(NumericType::U8),
+            _ => Err(anyhow!(JuizError::ValueTypeError{message: format!("NumericType '{value}' is not supported. Use f32, f64, i32 or u8.")}))
+        }
+    }
+}
+
+/// 要素の列
+#[derive(Clone, Debug, PartialEq)]
+pub enum NumericData {
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+    I32(Vec<i32>),
+    U8(Vec<u8>),
+}
+
+impl NumericData {
+
+    pub fn numeric_type(&self) -> NumericType {
+        match self {
+            NumericData::F32(_) => NumericType::F32,
+            NumericData::F64(_) => NumericType::F64,
+            NumericData::I32(_) => NumericType::I32,
+            NumericData::U8(_) => NumericType::U8,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            NumericData::F32(v) => v.len(),
+            NumericData::F64(v) => v.len(),
+            NumericData::I32(v) => v.len(),
+            NumericData::U8(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 要素の先頭のポインタ。C/C++のプラグインに渡す
+    pub fn as_ptr(&self) -> *const std::ffi::c_void {
+        match self {
+            NumericData::F32(v) => v.as_ptr() as *const std::ffi::c_void,
+            NumericData::F64(v) => v.as_ptr() as *const std::ffi::c_void,
+            NumericData::I32(v) => v.as_ptr() as *const std::ffi::c_void,
+            NumericData::U8(v) => v.as_ptr() as *const std::ffi::c_void,
+        }
+    }
+
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        match self {
+            NumericData::F32(v) => v.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            NumericData::F64(v) => v.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            NumericData::I32(v) => v.iter().flat_map(|x| x.to_le_bytes()).collect(),
+            NumericData::U8(v) => v.clone(),
+        }
+    }
+
+    pub fn from_le_bytes(numeric_type: NumericType, bytes: &[u8]) -> JuizResult<Self> {
+        if bytes.len() % numeric_type.size() != 0 {
+            return Err(anyhow!(JuizError::ValueTypeError{message: format!("NumericData::from_le_bytes() failed. Byte length {} is not a multiple of {} ({numeric_type}).", bytes.len(), numeric_type.size())}));
+        }
+        Ok(match numeric_type {
+            NumericType::F32 => NumericData::F32(bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()),
+            NumericType::F64 => NumericData::F64(bytes.chunks_exact(8).map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])).collect()),
+            NumericType::I32 => NumericData::I32(bytes.chunks_exact(4).map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()),
+            NumericType::U8 => NumericData::U8(bytes.to_vec()),
+        })
+    }
+}
+
+impl From<Vec<f32>> for NumericData {
+    fn from(value: Vec<f32>) -> Self { NumericData::F32(value) }
+}
+
+impl From<Vec<f64>> for NumericData {
+    fn from(value: Vec<f64>) -> Self { NumericData::F64(value) }
+}
+
+impl From<Vec<i32>> for NumericData {
+    fn from(value: Vec<i32>) -> Self { NumericData::I32(value) }
+}
+
+impl From<Vec<u8>> for NumericData {
+    fn from(value: Vec<u8>) -> Self { NumericData::U8(value) }
+}
+
+/// 型と形を持つ数値の配列
+#[derive(Clone, Debug, PartialEq)]
+pub struct NumericArray {
+    shape: Vec<usize>,
+    data: NumericData,
+}
+
+impl Display for NumericArray {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("NumericArray({}, shape={:?})", self.numeric_type(), self.shape))
+    }
+}
+
+impl NumericArray {
+
+    /// 形の要素数の積と要素の数が合わなければエラー。通信で受け取った形も渡るので、積があふれてもエラーにする
+    pub fn new(shape: Vec<usize>, data: impl Into<NumericData>) -> JuizResult<Self> {
+        let data = data.into();
+        let size = shape.iter().try_fold(1usize, |size, d| size.checked_mul(*d)).ok_or_else(|| {
+            anyhow!(JuizError::ValueTypeError{message: format!("NumericArray::new() failed. Shape {shape:?} is too large.")})
+        })?;
+        if size != data.len() {
+            return Err(anyhow!(JuizError::ValueTypeError{message: format!("NumericArray::new() failed. Shape {shape:?} needs {size} elements, but data has {}.", data.len())}));
+        }
+        Ok(NumericArray{shape, data})
+    }
+
+    /// 1次元の配列
+    pub fn from_vec(data: impl Into<NumericData>) -> Self {
+        let data = data.into();
+        NumericArray{shape: vec![data.len()], data}
+    }
+
+    pub fn from_le_bytes(numeric_type: NumericType, shape: Vec<usize>, bytes: &[u8]) -> JuizResult<Self> {
+        Self::new(shape, NumericData::from_le_bytes(numeric_type, bytes)?)
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn data(&self) -> &NumericData {
+        &self.data
+    }
+
+    pub fn into_data(self) -> NumericData {
+        self.data
+    }
+
+    pub fn numeric_type(&self) -> NumericType {
+        self.data.numeric_type()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn as_f32(&self) -> Option<&[f32]> {
+        match &self.data { NumericData::F32(v) => Some(v), _ => None }
+    }
+
+    pub fn as_f64(&self) -> Option<&[f64]> {
+        match &self.data { NumericData::F64(v) => Some(v), _ => None }
+    }
+
+    pub fn as_i32(&self) -> Option<&[i32]> {
+        match &self.data { NumericData::I32(v) => Some(v), _ => None }
+    }
+
+    pub fn as_u8(&self) -> Option<&[u8]> {
+        match &self.data { NumericData::U8(v) => Some(v), _ => None }
+    }
+
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        self.data.to_le_bytes()
+    }
+
+    /// 要素の型と形。バイナリの本体と別に送るときに使う
+    pub fn header(&self) -> Value {
+        jvalue!({
+            "dtype": self.numeric_type().as_str(),
+            "shape": self.shape,
+        })
+    }
+
+    /// header()で作った型と形と、リトルエンディアンのバイト列から作る
+    pub fn from_header_and_bytes(header: &Value, bytes: &[u8]) -> JuizResult<Self> {
+        let numeric_type = NumericType::try_from(obj_get_str(header, "dtype")?)?;
+        let shape = get_array(obj_get(header, "shape")?)?.iter().map(|v| {
+            v.as_u64().map(|d| d as usize).ok_or_else(|| anyhow!(JuizError::ValueTypeError{message: format!("NumericArray shape must be array of unsigned integer. ({header})")}))
+        }).collect::<JuizResult<Vec<usize>>>()?;
+        Self::from_le_bytes(numeric_type, shape, bytes)
+    }
+}
@@ -0,0 +1,417 @@
+//! 共有メモリ上の画像
+//!
+//! 画素バッファをPOSIX共有メモリに置き、別のプロセスにはハンドル(セグメント名と画像の形)だけを渡す。
+//! 受け手はセグメントをmapしてそのまま画素を参照できる。
+//!
+//! セグメントの先頭には参照カウントがあり、セグメントをmapしているSharedMemoryがすべてdropされるとunlinkされる。
+//! 同じプロセスの中ではSharedImageのcloneはArcで同じmapを共有する。
+//!
+//! 別のプロセスに渡すときは受け手の分の参照を先に足しておき、送り手は受け手が開くまでSharedImageLeaseを持つ。
+//! 受け手が開く前に(接続が切れるなどして)leaseがdropされると、受け手の分の参照は取り消されてセグメントは残らない。
+//! leaseはセグメントをmapしたままにはしないので、受け手が開いたあとのセグメントの寿命には関わらない。
+
+use std::{fmt::Debug, sync::{atomic::{AtomicU32, Ordering}, Arc}};
+
+use image::{DynamicImage, ImageBuffer};
+use uuid::Uuid;
+
+use crate::prelude::*;
+
+/// 参照カウントと、渡したがまだ開かれていないハンドルの数を置くヘッダ。画素のアラインメントのために大きめにとる。
+const HEADER_SIZE: usize = 64;
+const UNCLAIMED_OFFSET: usize = 4;
+
+fn shm_error(message: String) -> anyhow::Error {
+    anyhow::Error::from(JuizError::SharedMemoryError{ message })
+}
+
+/// 共有メモリのセグメント。mapしている間はセグメントの参照を一つ持つ。
+pub struct SharedMemory {
+    name: String,
+    ptr: *mut u8,
+    size: usize,
+}
+
+// mapした領域はプロセス内のどのスレッドからも参照できる。書き込みは&mutを通してしか行わない。
+unsafe impl Send for SharedMemory {}
+unsafe impl Sync for SharedMemory {}
+
+impl Debug for SharedMemory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("SharedMemory(name={}, size={})", self.name, self.len()))
+    }
+}
+
+impl SharedMemory {
+
+    /// sizeバイトの領域を持つセグメントを新しく作る
+    pub fn create(size: usize) -> JuizResult<SharedMemory> {
+        // macOSではセグメント名は31文字まで
+        let name = format!("/juiz_{}", &Uuid::new_v4().simple().to_string()[..24]);
+        let ptr = map(name.as_str(), HEADER_SIZE + size, true)?;
+        let memory = SharedMemory{ name, ptr, size: HEADER_SIZE + size };
+        memory.refcount().store(1, Ordering::Release);
+        Ok(memory)
+    }
+
+    /// 別のプロセスが作ったセグメントを開く。
+    ///
+    /// 送り手が`lend`しておいた参照を引き取るので、参照カウントは増やさない。送り手が取り消したあとは開けない。
+    pub fn open(name: &str, size: usize) -> JuizResult<SharedMemory> {
+        let ptr = map(name, HEADER_SIZE + size, false)?;
+        let memory = SharedMemory{ name: name.to_owned(), ptr, size: HEADER_SIZE + size };
+        if !decrement_if_positive(memory.unclaimed()) {
+            // 引き取る参照が無いので、参照カウントを減らさずに閉じる
+            unmap(memory.ptr, memory.size);
+            std::mem::forget(memory);
+            return Err(shm_error(format!("SharedMemory({name}) was revoked by its owner.")));
+        }
+        Ok(memory)
+    }
+
+    /// 別のプロセスに渡すための参照を一つ増やす。渡した先で`open`されるか、`revoke`で取り消すまで残る。
+    pub fn lend(&self) {
+        self.refcount().fetch_add(1, Ordering::AcqRel);
+        self.unclaimed().fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// `lend`した参照がまだ`open`されていなければ取り消す。取り消したらtrue
+    ///
+    /// 受け手の代わりにヘッダだけを開いて参照を引き取り、すぐに閉じる。最後の参照だったらここでunlinkされる。
+    pub fn revoke(name: &str) -> bool {
+        SharedMemory::open(name, 0).is_ok()
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn len(&self) -> usize {
+        self.size - HEADER_SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.add(HEADER_SIZE), self.len()) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.add(HEADER_SIZE), self.len()) }
+    }
+
+    /// 他のプロセスや渡したハンドルがセグメントを参照していなければtrue
+    pub fn is_unique(&self) -> bool {
+        self.refcount().load(Ordering::Acquire) == 1
+    }
+
+    fn refcount(&self) -> &AtomicU32 {
+        unsafe { &*(self.ptr as *const AtomicU32) }
+    }
+
+    fn unclaimed(&self) -> &AtomicU32 {
+        unsafe { &*(self.ptr.add(UNCLAIMED_OFFSET) as *const AtomicU32) }
+    }
+}
+
+fn decrement_if_positive(counter: &AtomicU32) -> bool {
+    counter.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1)).is_ok()
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        let last = self.refcount().fetch_sub(1, Ordering::AcqRel) == 1;
+        unmap(self.ptr, self.size);
+        if last {
+            log::trace!("SharedMemory({}) is unlinked.", self.name);
+            unlink(self.name.as_str());
+        }
+    }
+}
+
+#[cfg(unix)]
+fn map(name: &str, size: usize, create: bool) -> JuizResult<*mut u8> {
+    let c_name = std::ffi::CString::new(name)?;
+    let os_error = |function: &str| shm_error(format!("{function}({name}) failed. Error({})", std::io::Error::last_os_error()));
+    unsafe {
+        let flags = if create { libc::O_CREAT | libc::O_EXCL | libc::O_RDWR } else { libc::O_RDWR };
+        let fd = libc::shm_open(c_name.as_ptr(), flags, 0o600 as libc::c_uint);
+        if fd < 0 {
+            return Err(os_error("shm_open"));
+        }
+        let prepared = if create {
+            libc::ftruncate(fd, size as libc::off_t) == 0 && reserve(fd, size)
+        } else {
+            let mut stat: libc::stat = std::mem::zeroed();
+            libc::fstat(fd, &mut stat) == 0 && stat.st_size as usize >= size
+        };
+        if !prepared {
+            let e = os_error(if create { "ftruncate" } else { "fstat" });
+            libc::close(fd);
+            if create {
+                libc::shm_unlink(c_name.as_ptr());
+            }
+            return Err(e);
+        }
+        let ptr = libc::mmap(std::ptr::null_mut(), size, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, fd, 0);
+        libc::close(fd);
+        if ptr == libc::MAP_FAILED {
+            let e = os_error("mmap");
+            if create {
+                libc::shm_unlink(c_name.as_ptr());
+            }
+            return Err(e);
+        }
+        Ok(ptr as *mut u8)
+    }
+}
+
+/// 書き込み時にSIGBUSにならないように、領域を先に確保しておく
+#[cfg(target_os = "linux")]
+unsafe fn reserve(fd: libc::c_int, size: usize) -> bool {
+    libc::posix_fallocate(fd, 0, size as libc::off_t) == 0
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+unsafe fn reserve(_fd: libc::c_int, _size: usize) -> bool {
+    true
+}
+
+#[cfg(unix)]
+fn unmap(ptr: *mut u8, size: usize) {
+    unsafe { libc::munmap(ptr as *mut libc::c_void, size); }
+}
+
+#[cfg(unix)]
+fn unlink(name: &str) {
+    if let Ok(c_name) = std::ffi::CString::new(name) {
+        unsafe { libc::shm_unlink(c_name.as_ptr()); }
+    }
+}
+
+#[cfg(not(unix))]
+fn map(name: &str, _size: usize, _create: bool) -> JuizResult<*mut u8> {
+    Err(shm_error(format!("SharedMemory({name}) is not supported on this platform.")))
+}
+
+#[cfg(not(unix))]
+fn unmap(_ptr: *mut u8, _size: usize) {}
+
+#[cfg(not(unix))]
+fn unlink(_name: &str) {}
+
+/// 共有メモリ上の画像の画素形式
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SharedImageColor {
+    L8,
+    La8,
+    Rgb8,
+    Rgba8,
+}
+
+impl SharedImageColor {
+
+    pub fn channels(&self) -> usize {
+        match self {
+            SharedImageColor::L8 => 1,
+            SharedImageColor::La8 => 2,
+            SharedImageColor::Rgb8 => 3,
+            SharedImageColor::Rgba8 => 4,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SharedImageColor::L8 => "l8",
+            SharedImageColor::La8 => "la8",
+            SharedImageColor::Rgb8 => "rgb8",
+            SharedImageColor::Rgba8 => "rgba8",
+        }
+    }
+}
+
+impl TryFrom<&str> for SharedImageColor {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "l8" => Ok(SharedImageColor::L8),
+            "la8" => Ok(SharedImageColor::La8),
+            "rgb8" => Ok(SharedImageColor::Rgb8),
+            "rgba8" => Ok(SharedImageColor::Rgba8),
+            _ => Err(shm_error(format!("unknown color type ({value})"))),
+        }
+    }
+}
+
+/// 別のプロセスにSharedImageを渡すためのハンドル
+#[derive(Clone, Debug, PartialEq)]
+pub struct SharedImageHandle {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub color: SharedImageColor,
+}
+
+/// 別のプロセスに渡したSharedImageを、受け手が開くまで送り手が持っておくためのもの。
+///
+/// 受け手が開く前にdropすると受け手の分の参照を取り消す。受け手が開いたあとにdropしても何も起きない。
+pub struct SharedImageLease {
+    name: String,
+}
+
+impl Debug for SharedImageLease {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("SharedImageLease(name={})", self.name))
+    }
+}
+
+impl Drop for SharedImageLease {
+    fn drop(&mut self) {
+        if SharedMemory::revoke(self.name.as_str()) {
+            log::debug!("SharedMemory({}) was not opened by the receiver. revoked.", self.name);
+        }
+    }
+}
+
+/// 画素を共有メモリに置いた画像
+#[derive(Clone)]
+pub struct SharedImage {
+    memory: Arc<SharedMemory>,
+    width: u32,
+    height: u32,
+    color: SharedImageColor,
+}
+
+impl Debug for SharedImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("SharedImage(name={}, {}x{}, {})", self.memory.name(), self.width, self.height, self.color.as_str()))
+    }
+}
+
+impl SharedImage {
+
+    /// 黒で塗られた画像を共有メモリ上に作る。画素は`as_bytes_mut`で書き込む。
+    pub fn new(width: u32, height: u32, color: SharedImageColor) -> JuizResult<SharedImage> {
+        let memory = SharedMemory::create(width as usize * height as usize * color.channels())?;
+        Ok(SharedImage{ memory: Arc::new(memory), width, height, color })
+    }
+
+    /// DynamicImageを共有メモリに複製する。8bit以外の画像はRGBA8に変換する。
+    pub fn from_image(image: &DynamicImage) -> JuizResult<SharedImage> {
+        let (color, converted) = match image {
+            DynamicImage::ImageLuma8(_) => (SharedImageColor::L8, None),
+            DynamicImage::ImageLumaA8(_) => (SharedImageColor::La8, None),
+            DynamicImage::ImageRgb8(_) => (SharedImageColor::Rgb8, None),
+            DynamicImage::ImageRgba8(_) => (SharedImageColor::Rgba8, None),
+            _ => (SharedImageColor::Rgba8, Some(DynamicImage::ImageRgba8(image.to_rgba8()))),
+        };
+        let source = converted.as_ref().unwrap_or(image);
+        let mut shared = SharedImage::new(source.width(), source.height(), color)?;
+        shared.as_bytes_mut()?.copy_from_slice(source.as_bytes());
+        Ok(shared)
+    }
+
+    /// 別のプロセスから渡されたハンドルの画像を開く
+    pub fn open(handle: &SharedImageHandle) -> JuizResult<SharedImage> {
+        let size = handle.width as usize * handle.height as usize * handle.color.channels();
+        let memory = SharedMemory::open(handle.name.as_str(), size)?;
+        Ok(SharedImage{ memory: Arc::new(memory), width: handle.width, height: handle.height, color: handle.color })
+    }
+
+    /// 別のプロセスに渡すハンドルを作る。受け手の分の参照を増やすので、ハンドルは一度だけ`open`すること。
+    ///
+    /// 受け手が開くまでは返したleaseを持っておく。渡せなかったときはleaseをdropすれば参照は取り消される。
+    pub fn share(&self) -> (SharedImageHandle, SharedImageLease) {
+        self.memory.lend();
+        let handle = SharedImageHandle{ name: self.memory.name().to_owned(), width: self.width, height: self.height, color: self.color };
+        (handle, SharedImageLease{ name: self.memory.name().to_owned() })
+    }
+
+    pub fn name(&self) -> &str {
+        self.memory.name()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn color(&self) -> SharedImageColor {
+        self.color
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.memory.as_slice()
+    }
+
+    /// 画素に書き込む。cloneされているか、`share`で別のプロセスに渡している間は書き込めない。
+    pub fn as_bytes_mut(&mut self) -> JuizResult<&mut [u8]> {
+        let name = self.memory.name().to_owned();
+        Arc::get_mut(&mut self.memory)
+            .filter(|memory| memory.is_unique())
+            .map(|memory| memory.as_mut_slice())
+            .ok_or_else(|| shm_error(format!("SharedImage({name}) is shared and can not be modified.")))
+    }
+
+    /// 画素を複製せずに、mapした領域の上にImageBufferを置いて参照する
+    pub fn view(&self) -> JuizResult<ImageView<'_>> {
+        let bytes = self.as_bytes();
+        let view = match self.color {
+            SharedImageColor::L8 => ImageBuffer::from_raw(self.width, self.height, bytes).map(ImageView::L8),
+            SharedImageColor::La8 => ImageBuffer::from_raw(self.width, self.height, bytes).map(ImageView::La8),
+            SharedImageColor::Rgb8 => ImageBuffer::from_raw(self.width, self.height, bytes).map(ImageView::Rgb8),
+            SharedImageColor::Rgba8 => ImageBuffer::from_raw(self.width, self.height, bytes).map(ImageView::Rgba8),
+        };
+        view.ok_or_else(|| shm_error(format!("SharedImage({}) size does not match pixel buffer.", self.memory.name())))
+    }
+
+    /// 画素を複製してDynamicImageにする
+    pub fn to_image(&self) -> JuizResult<DynamicImage> {
+        Ok(self.view()?.to_image().into_owned())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_image_is_unlinked_after_last_reference_test() -> JuizResult<()> {
+        let image = DynamicImage::ImageRgb8(image::RgbImage::from_fn(5, 4, |x, y| image::Rgb([x as u8, y as u8, 7])));
+        let shared = SharedImage::from_image(&image)?;
+        let path = format!("/dev/shm{}", shared.name());
+
+        let (handle, lease) = shared.share();
+        drop(shared);
+        assert!(std::path::Path::new(&path).exists());
+
+        let opened = SharedImage::open(&handle)?;
+        drop(lease);
+        assert_eq!(opened.to_image()?, image);
+        drop(opened);
+        assert!(!std::path::Path::new(&path).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn shared_image_lease_revokes_unopened_handle_test() -> JuizResult<()> {
+        let mut shared = SharedImage::new(4, 4, SharedImageColor::L8)?;
+        let path = format!("/dev/shm{}", shared.name());
+
+        // 渡している間は画素に書き込めない
+        let (handle, lease) = shared.share();
+        assert!(shared.as_bytes_mut().is_err());
+        drop(lease);
+        assert!(SharedImage::open(&handle).is_err());
+        assert!(shared.as_bytes_mut().is_ok());
+        drop(shared);
+        assert!(!std::path::Path::new(&path).exists());
+        Ok(())
+    }
+}
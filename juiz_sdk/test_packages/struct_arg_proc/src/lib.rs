@@ -0,0 +1,15 @@
+use juiz_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, JuizSchema)]
+struct Point {
+    x: f64,
+    y: f64,
+    label: Option<String>,
+}
+
+#[juiz_process]
+fn _arg_proc(arg1: Point, arg2: Vec<Point>) -> JuizResult<Point> {
+    log::trace!("process({arg1:?}, {arg2:?}) called");
+    return Ok(Point{x: arg1.x + arg2.len() as f64, y: arg1.y, label: arg1.label});
+}